@@ -0,0 +1,61 @@
+//! TVQ to Arrow conversion, from decoded series and from compact columns.
+
+use std::hint::black_box;
+use std::time::{Duration, SystemTime};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use prost::Message;
+
+use crowsong::canary::utility::protobuf_shared_types::GrpcTvq;
+use crowsong::canary::views::grpc::api::{GetRawDataResponse, RawTagData};
+use crowsong::interop::arrow::series_to_arrow;
+use crowsong::{Quality, RawDataColumns, TagSeries, Tvq, Value};
+
+const SAMPLES: usize = 100_000;
+
+fn series() -> TagSeries {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    TagSeries {
+        tag_name: "Bench.Tag".to_string(),
+        eng_units: None,
+        tvqs: (0..SAMPLES)
+            .map(|i| Tvq {
+                timestamp: start + Duration::from_secs(i as u64),
+                value: Value::Float((i as f64 * 0.01).sin()),
+                quality: Quality(192),
+            })
+            .collect(),
+    }
+}
+
+fn to_arrow(c: &mut Criterion) {
+    let series = series();
+    let response = GetRawDataResponse {
+        raw_data: vec![RawTagData {
+            tag_name: series.tag_name.clone(),
+            tvqs: series.tvqs.iter().map(GrpcTvq::from).collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let columns = RawDataColumns::decode(response.encode_to_vec().as_slice())
+        .unwrap()
+        .raw_data
+        .remove(0);
+
+    let mut group = c.benchmark_group("to_arrow");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("series", |b| {
+        b.iter(|| series_to_arrow(black_box(&series)).unwrap())
+    });
+    group.bench_function("grpc_via_series", |b| {
+        b.iter(|| series_to_arrow(&TagSeries::from(black_box(&response.raw_data[0]))).unwrap())
+    });
+    group.bench_function("columnar", |b| {
+        b.iter(|| black_box(&columns).to_arrow().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, to_arrow);
+criterion_main!(benches);
@@ -0,0 +1,119 @@
+//! Bulk raw-data decoding: generated protobuf messages against compact columns.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use prost::Message;
+use tokio::sync::Mutex;
+
+use crowsong::RawDataColumns;
+use crowsong::canary::utility::protobuf_shared_types::GrpcTvq;
+use crowsong::canary::views::grpc::api::*;
+use crowsong::testing::MockViewsServer;
+use crowsong::testing::generate::Plant;
+
+const SAMPLES: usize = 20_000;
+/// Fewer samples over the wire, to stay under tonic's 4 MiB default message limit.
+const ROUND_TRIP_SAMPLES: usize = 5_000;
+const INTERVAL: Duration = Duration::from_secs(1);
+
+fn start() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+fn request(plant: &Plant, samples: usize) -> GetRawDataRequest {
+    let end = start() + INTERVAL * samples as u32;
+    GetRawDataRequest {
+        view: "Bench".to_string(),
+        requests: plant
+            .tag_names()
+            .into_iter()
+            .map(|tag_name| RawTagRequest {
+                tag_name,
+                start_time: Some(start().into()),
+                end_time: Some(end.into()),
+                client_data: 0,
+                continuation_point: Vec::new(),
+            })
+            .collect(),
+        max_count_per_tag: samples as i32,
+        return_bounds: false,
+        return_annotations: false,
+        cci: 0,
+    }
+}
+
+fn decode(c: &mut Criterion) {
+    let plant = Plant::new(1, 2);
+    let response = GetRawDataResponse {
+        raw_data: plant
+            .series(start(), INTERVAL, SAMPLES)
+            .iter()
+            .map(|series| RawTagData {
+                tag_name: series.tag_name.clone(),
+                tvqs: series.tvqs.iter().map(GrpcTvq::from).collect(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let bytes = response.encode_to_vec();
+    let samples: usize = response.raw_data.iter().map(|d| d.tvqs.len()).sum();
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(samples as u64));
+    group.bench_function("generated", |b| {
+        b.iter(|| GetRawDataResponse::decode(black_box(bytes.as_slice())).unwrap())
+    });
+    group.bench_function("columnar", |b| {
+        b.iter(|| RawDataColumns::decode(black_box(bytes.as_slice())).unwrap())
+    });
+    group.finish();
+}
+
+fn mock_round_trip(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let plant = Plant::new(1, 2);
+    let mock = MockViewsServer::new();
+    plant.seed_mock(
+        &mock,
+        "Bench",
+        "Plant",
+        start(),
+        INTERVAL,
+        ROUND_TRIP_SAMPLES,
+    );
+    let client = Arc::new(Mutex::new(rt.block_on(mock.client()).unwrap()));
+    let request = request(&plant, ROUND_TRIP_SAMPLES);
+
+    let mut group = c.benchmark_group("get_raw_data");
+    group.throughput(Throughput::Elements(
+        (plant.tags().len() * ROUND_TRIP_SAMPLES) as u64,
+    ));
+    group.sample_size(20);
+    group.bench_function("generated", |b| {
+        b.to_async(&rt).iter(|| {
+            let (client, request) = (client.clone(), request.clone());
+            async move { client.lock().await.get_raw_data(request).await.unwrap() }
+        })
+    });
+    group.bench_function("columnar", |b| {
+        b.to_async(&rt).iter(|| {
+            let (client, request) = (client.clone(), request.clone());
+            async move {
+                client
+                    .lock()
+                    .await
+                    .get_raw_data_columnar(request)
+                    .await
+                    .unwrap()
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, mock_round_trip);
+criterion_main!(benches);
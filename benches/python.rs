@@ -0,0 +1,100 @@
+//! Conversions on the Python bindings' hot paths.
+
+use std::hint::black_box;
+use std::time::{Duration, SystemTime};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use prost::Message;
+use pyo3::Python;
+
+use crowsong::RawDataColumns;
+use crowsong::canary::utility::protobuf_shared_types::variant::Kind;
+use crowsong::canary::utility::protobuf_shared_types::{GrpcTvq, Variant};
+use crowsong::canary::views::grpc::api::{GetRawDataResponse, RawTagData};
+use crowsong::python::{
+    columns_row_to_py_dict, parse_iso_timestamp, tvq_to_py_dict, variant_to_py,
+};
+
+const SAMPLES: usize = 10_000;
+
+fn variants(c: &mut Criterion) {
+    Python::initialize();
+    let cases = [
+        ("double", Kind::Double(42.5)),
+        ("int32", Kind::Int32(42)),
+        ("bool", Kind::Bool(true)),
+        ("string", Kind::String("running".to_string())),
+    ];
+
+    let mut group = c.benchmark_group("variant_to_py");
+    Python::attach(|py| {
+        for (name, kind) in cases {
+            let variant = Variant { kind: Some(kind) };
+            group.bench_function(name, |b| b.iter(|| variant_to_py(py, black_box(&variant))));
+        }
+    });
+    group.finish();
+}
+
+fn raw_rows(c: &mut Criterion) {
+    Python::initialize();
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let tvqs: Vec<GrpcTvq> = (0..SAMPLES)
+        .map(|i| GrpcTvq {
+            timestamp: Some((start + Duration::from_secs(i as u64)).into()),
+            value: Some(Variant {
+                kind: Some(Kind::Double(i as f64)),
+            }),
+            quality: 192,
+        })
+        .collect();
+    let response = GetRawDataResponse {
+        raw_data: vec![RawTagData {
+            tag_name: "Bench.Tag".to_string(),
+            tvqs: tvqs.clone(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let columns = RawDataColumns::decode(response.encode_to_vec().as_slice())
+        .unwrap()
+        .raw_data
+        .remove(0);
+
+    let mut group = c.benchmark_group("raw_rows_to_py");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    Python::attach(|py| {
+        group.bench_function("generated", |b| {
+            b.iter(|| {
+                for tvq in &tvqs {
+                    black_box(tvq_to_py_dict(py, tvq).unwrap());
+                }
+            })
+        });
+        group.bench_function("columnar", |b| {
+            b.iter(|| {
+                for row in 0..columns.len() {
+                    black_box(columns_row_to_py_dict(py, &columns, row).unwrap());
+                }
+            })
+        });
+    });
+    group.finish();
+}
+
+fn timestamps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_iso_timestamp");
+    for (name, input) in [
+        ("seconds", "2024-03-10T02:30:00Z"),
+        ("nanos", "2024-03-10T02:30:00.123456789Z"),
+        ("space", "2024-03-10 02:30:00"),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| parse_iso_timestamp(black_box(input)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, variants, raw_rows, timestamps);
+criterion_main!(benches);
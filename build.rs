@@ -1,12 +1,50 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let feature = |name: &str| std::env::var_os(format!("CARGO_FEATURE_{name}")).is_some();
+
+    // Server traits are only needed to stand up fake services in tests; default
+    // stubs let fakes implement just the RPCs they care about.
+    let server_stubs = feature("SERVER_STUBS");
+
+    // The descriptor set is exposed as `crowsong::descriptors()`; `probe`
+    // compares it with what a server reports over reflection, and `dynamic`
+    // encodes calls and JSON against it.
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
+
+    // Only compile the services the enabled features need. The shared types
+    // are always built; the Views service imports the calculations types, so
+    // `views` enables `calculations`.
+    let mut protos = vec![
+        "proto/Utility.ProtobufSharedTypes/Protos/grpc_tvq.proto",
+        "proto/Utility.ProtobufSharedTypes/Protos/variant.proto",
+    ];
+    if feature("CALCULATIONS") {
+        protos.push("proto/Calculations.Grpc.Common/Protos/canary_calculations_common.proto");
+        protos.push(
+            "proto/Calculations.Batch.Grpc.Common/Protos/canary_calculations_batch_common.proto",
+        );
+    }
+    if feature("STORE_AND_FORWARD") {
+        protos.push("proto/StoreAndForward/canary_store_and_forward_api_service.proto");
+    }
+    if feature("VIEWS") {
+        protos.push("proto/Views/canary_views_api_service.proto");
+    }
+
+    // Parse the protos with protox rather than protoc, so building needs no
+    // protobuf compiler installed on the machine.
+    let mut compiler = protox::Compiler::new(["proto/"])?;
+    compiler.include_imports(true).include_source_info(true);
+    compiler.open_files(&protos)?;
+    std::fs::write(
+        out_dir.join("crowsong_descriptor.bin"),
+        compiler.encode_file_descriptor_set(),
+    )?;
+
     tonic_prost_build::configure()
-        .build_server(false)
-        .compile_protos(
-            &[
-                "proto/StoreAndForward/canary_store_and_forward_api_service.proto",
-                "proto/Views/canary_views_api_service.proto",
-            ],
-            &["proto/"],
-        )?;
+        .build_server(server_stubs)
+        .generate_default_stubs(true)
+        .compile_fds(compiler.file_descriptor_set())?;
+
+    println!("cargo:rerun-if-changed=proto");
     Ok(())
 }
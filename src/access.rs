@@ -0,0 +1,133 @@
+//! Checking which tags the current credentials can see and read.
+//!
+//! The Views service leaves out tags the caller may not access rather than
+//! failing, so a permission problem usually surfaces as a tag that is
+//! "missing" or has no data. [`ViewsClient::check_access`] tells the cases
+//! apart: tag info only describes tags the caller can see, and a current
+//! value read only returns tags the caller can read.
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! let tags = vec!["Plant.Line1.Speed".to_string(), "Plant.Line1.Temp".to_string()];
+//! let report = client.check_access("Plant", tags).await?;
+//! for (tag, access) in report.denied() {
+//!     eprintln!("{tag}: {access}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use tonic::Status;
+
+use crate::canary::views::grpc::api::{
+    GetTagCurrentValueRequest, get_tag_current_value_response, get_tag_info_response,
+};
+use crate::error::Error;
+use crate::views_client::ViewsClient;
+
+/// What the caller may do with one tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// Described and read.
+    Readable,
+    /// Described, but no current value came back: the caller may browse the
+    /// tag but not read it, or it has no data yet.
+    NotReadable,
+    /// Not described: the tag doesn't exist or the caller may not see it.
+    /// The server doesn't say which.
+    NotVisible,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Readable => "readable",
+            Self::NotReadable => "visible but not readable, or without data",
+            Self::NotVisible => "not found or not visible",
+        })
+    }
+}
+
+/// The access the caller has to each tag checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessReport {
+    pub view: String,
+    pub tags: BTreeMap<String, Access>,
+}
+
+impl AccessReport {
+    /// Whether every tag checked is readable.
+    pub fn all_readable(&self) -> bool {
+        self.tags.values().all(|access| *access == Access::Readable)
+    }
+
+    /// The tags that aren't readable, with why.
+    pub fn denied(&self) -> impl Iterator<Item = (&str, Access)> {
+        self.tags
+            .iter()
+            .filter(|(_, access)| **access != Access::Readable)
+            .map(|(tag, access)| (tag.as_str(), *access))
+    }
+}
+
+impl ViewsClient {
+    /// Check which of `tags` in `view` the current credentials can see and
+    /// read. Fails with `NOT_FOUND` if the view itself isn't visible. See
+    /// [`access`](crate::access).
+    pub async fn check_access(
+        &self,
+        view: impl Into<String>,
+        tags: Vec<String>,
+    ) -> Result<AccessReport, Error> {
+        let view = view.into();
+        let not_found = || Status::not_found(format!("view {view} not found"));
+
+        let info = self.get_tag_info(view.as_str(), tags.clone()).await?;
+        if info.extended_status() == get_tag_info_response::Status::ViewNotFound {
+            return Err(not_found().into());
+        }
+        let visible: HashSet<String> = info
+            .tag_infos
+            .into_iter()
+            .map(|info| info.tag_item_id)
+            .collect();
+
+        let mut readable = HashSet::new();
+        let probe: Vec<String> = tags
+            .iter()
+            .filter(|tag| visible.contains(*tag))
+            .cloned()
+            .collect();
+        if !probe.is_empty() {
+            let values = self
+                .get_tag_current_value(GetTagCurrentValueRequest {
+                    view: view.clone(),
+                    tag_names: probe,
+                    ..Default::default()
+                })
+                .await?;
+            if values.extended_status() == get_tag_current_value_response::Status::ViewNotFound {
+                return Err(not_found().into());
+            }
+            readable.extend(values.tag_values.into_iter().map(|value| value.tag_item_id));
+        }
+
+        let tags = tags
+            .into_iter()
+            .map(|tag| {
+                let access = if readable.contains(&tag) {
+                    Access::Readable
+                } else if visible.contains(&tag) {
+                    Access::NotReadable
+                } else {
+                    Access::NotVisible
+                };
+                (tag, access)
+            })
+            .collect();
+        Ok(AccessReport { view, tags })
+    }
+}
@@ -0,0 +1,107 @@
+//! Provisioning operations for automation scripts.
+//!
+//! Canary's Admin/management API is not part of the public API protos that
+//! crowsong vendors, so [`AdminClient`] covers only what those protos expose:
+//! listing the historian's datasets through the Store & Forward service.
+//! To create a dataset, open a session and use
+//! [`StoreAndForwardClient::create_dataset`]. Managing retention and listing
+//! active client connections need the Admin proto and are not available.
+//!
+//! [`StoreAndForwardClient::create_dataset`]: crate::StoreAndForwardClient::create_dataset
+
+use std::fmt;
+
+use tonic::Status;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::canary::store_and_forward2::grpc::api::canary_store_and_forward_api_service_client::CanaryStoreAndForwardApiServiceClient;
+use crate::canary::store_and_forward2::grpc::api::{
+    ApiAccessTokenContext, GetDatasetsRequest, ResponseStatus,
+};
+use crate::error::Error;
+#[cfg(feature = "tls")]
+use crate::tls::{TlsPolicy, tls_channel};
+use crate::transport::Redacted;
+#[cfg(not(feature = "tls"))]
+use crate::transport::plain_channel;
+
+/// A client for provisioning calls against a Canary Store & Forward service.
+pub struct AdminClient {
+    inner: CanaryStoreAndForwardApiServiceClient<Channel>,
+    api_key: String,
+}
+
+impl fmt::Debug for AdminClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminClient")
+            .field("api_key", &Redacted)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AdminClient {
+    /// Connect to a Store & Forward service. The API key is sent with each call.
+    pub async fn connect(
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let endpoint = Endpoint::from_shared(endpoint.into())?;
+        #[cfg(feature = "tls")]
+        let channel = tls_channel(endpoint, &TlsPolicy::default(), None)?;
+        #[cfg(not(feature = "tls"))]
+        let channel = plain_channel(endpoint)?;
+        Ok(Self::from_channel(channel, api_key))
+    }
+
+    /// Connect as [`connect`](Self::connect) does, verifying `https`
+    /// endpoints as `policy` says. See [`tls`](crate::tls).
+    #[cfg(feature = "tls")]
+    pub async fn connect_with_tls(
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+        policy: &TlsPolicy,
+    ) -> Result<Self, Error> {
+        let endpoint = Endpoint::from_shared(endpoint.into())?;
+        let channel = tls_channel(endpoint, policy, None)?;
+        Ok(Self::from_channel(channel, api_key))
+    }
+
+    fn from_channel(channel: Channel, api_key: impl Into<String>) -> Self {
+        Self {
+            inner: CanaryStoreAndForwardApiServiceClient::new(channel),
+            api_key: api_key.into(),
+        }
+    }
+
+    /// Test the gRPC connection.
+    pub async fn test(&mut self) -> Result<(), Error> {
+        self.inner.test(()).await?;
+        Ok(())
+    }
+
+    /// The names of every dataset in the historian, including datasets no
+    /// view exposes.
+    pub async fn list_datasets(&mut self) -> Result<Vec<String>, Error> {
+        let resp = self
+            .inner
+            .get_datasets(GetDatasetsRequest {
+                api_access_token_context: Some(ApiAccessTokenContext {
+                    nullable_api_access_token: Some(self.api_key.clone()),
+                }),
+            })
+            .await?
+            .into_inner();
+        match resp.status() {
+            ResponseStatus::Good => Ok(resp.datasets),
+            ResponseStatus::BadAccessDenied => {
+                Err(Status::permission_denied(resp.nullable_error).into())
+            }
+            status => Err(Status::unknown(format!(
+                "GetDatasets failed ({}): {}",
+                status.as_str_name(),
+                resp.nullable_error
+            ))
+            .into()),
+        }
+    }
+}
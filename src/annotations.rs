@@ -0,0 +1,150 @@
+//! Annotations: notes pinned to a tag at a point in its history.
+//!
+//! [`ViewsClient::annotate`] stores one through the Views API's
+//! `StoreAnnotation` RPC, and [`ViewsClient::get_annotations`] lists a time
+//! range's through `GetAnnotations`. Both need a server with
+//! [`Capability::Annotations`].
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use std::time::{Duration, SystemTime};
+//!
+//! let now = SystemTime::now();
+//! let tag = "Site.Pump1.Flow";
+//! client.annotate("Plant", tag, now, "pump swapped").await?;
+//! let hour_ago = now - Duration::from_secs(3600);
+//! for note in client.get_annotations("Plant", vec![tag.into()], hour_ago, now).await? {
+//!     println!("{}: {}", note.tag, note.message);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::SystemTime;
+
+use tonic::Status;
+
+use crate::canary::views::grpc::api::*;
+use crate::capabilities::Capability;
+use crate::error::{CanaryError, Error};
+use crate::views_client::ViewsClient;
+
+/// One note on a tag.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Annotation {
+    pub tag: String,
+    /// The point in the tag's history the note is about.
+    pub time: Option<SystemTime>,
+    /// When the note was written, if the server says.
+    pub created: Option<SystemTime>,
+    pub user: String,
+    pub message: String,
+}
+
+fn system_time(ts: Option<prost_types::Timestamp>) -> Option<SystemTime> {
+    ts.and_then(|ts| SystemTime::try_from(ts).ok())
+}
+
+impl Annotation {
+    /// The notes in one tag's entry of a `GetAnnotations` response.
+    fn from_tag(tag: &AnnotationTag) -> impl Iterator<Item = Self> + '_ {
+        tag.entries.iter().map(|entry| Self {
+            tag: tag.tag_id.clone(),
+            time: system_time(tag.timestamp),
+            created: system_time(entry.entry_time),
+            user: entry.user.clone(),
+            message: entry.message.clone(),
+        })
+    }
+}
+
+/// Split a tag path into the prefix and name `StoreAnnotation` takes.
+fn split_tag(tag: &str) -> (&str, &str) {
+    tag.rsplit_once('.').unwrap_or(("", tag))
+}
+
+impl ViewsClient {
+    /// Store `message` as a note on `tag` at `time`, as the client's user.
+    ///
+    /// Each call adds a note, so unlike reads it is sent once, whatever the
+    /// client's [`RetryPolicy`](crate::RetryPolicy).
+    pub async fn annotate(
+        &self,
+        view: impl Into<String>,
+        tag: &str,
+        time: SystemTime,
+        message: impl Into<String>,
+    ) -> Result<(), Error> {
+        self.capabilities().require(Capability::Annotations)?;
+        let view = view.into();
+        let (tag_prefix, tag_name) = split_tag(tag);
+        let annotation = StoreAnnotationRequestItem {
+            tag_prefix: tag_prefix.to_string(),
+            tag_name: tag_name.to_string(),
+            annotation_time_stamp: Some(time.into()),
+            annotation_message: message.into(),
+            created_at: None,
+            user_id: self.user_id().to_string(),
+            visible: true,
+        };
+        // Every call stores a new note, so a failed one isn't resent.
+        let resp = self
+            .call_with_cci_once(|mut inner, cci| {
+                let request = StoreAnnotationRequest {
+                    view_name: view.clone(),
+                    cci,
+                    annotation: Some(annotation),
+                };
+                async move { inner.store_annotation(request).await }
+            })
+            .await?
+            .into_inner();
+        match resp.extended_status() {
+            store_annotation_response::Status::Unspecified => Ok(()),
+            store_annotation_response::Status::ViewNotFound => {
+                Err(CanaryError::UnknownView { view: Some(view) }.into())
+            }
+            store_annotation_response::Status::FailedToStoreAnnotation => {
+                Err(Status::internal(format!("failed to store annotation on {tag}")).into())
+            }
+        }
+    }
+
+    /// The notes on `tags` between `start` and `end`, oldest first.
+    pub async fn get_annotations(
+        &self,
+        view: impl Into<String>,
+        tags: Vec<String>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<Vec<Annotation>, Error> {
+        self.capabilities().require(Capability::Annotations)?;
+        let view = view.into();
+        // The request names the view `server`.
+        let resp = self
+            .call(|mut inner| {
+                let request = GetAnnotationsRequest {
+                    server: view.clone(),
+                    tag_ids: tags.clone(),
+                    start_time: Some(start.into()),
+                    end_time: Some(end.into()),
+                };
+                async move { inner.get_annotations(request).await }
+            })
+            .await?
+            .into_inner();
+        match resp.extended_status() {
+            get_annotations_response::Status::Unspecified => {}
+            get_annotations_response::Status::ViewNotFound => {
+                return Err(CanaryError::UnknownView { view: Some(view) }.into());
+            }
+        }
+        let mut annotations: Vec<Annotation> = resp
+            .annotations
+            .iter()
+            .flat_map(Annotation::from_tag)
+            .collect();
+        annotations.sort_by_key(|a| a.time);
+        Ok(annotations)
+    }
+}
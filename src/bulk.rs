@@ -0,0 +1,335 @@
+//! Paged bulk extraction of raw data.
+//!
+//! [`BulkFetch`] plans a raw-data extract as a series of pages, following each
+//! tag's continuation point until its range is exhausted. Completed pages are
+//! collected into [`Pages`]. With the `spill` feature and a
+//! [`memory_cap`](BulkFetch::memory_cap), the oldest pages are written to
+//! temporary Arrow IPC files whenever the resident pages exceed the cap, and
+//! read back one at a time when the pages are consumed.
+
+#[cfg(feature = "spill")]
+mod spill;
+
+use std::collections::VecDeque;
+use std::io;
+#[cfg(feature = "spill")]
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::canary::views::grpc::api::{GetRawDataRequest, RawTagRequest};
+use crate::cci_pool::CciPool;
+use crate::columnar::RawColumns;
+use crate::error::Error;
+use crate::request_id;
+use crate::views_client::{ViewsClient, check_progress};
+
+/// A planned raw-data extract over many tags and a long time range.
+#[derive(Debug, Clone)]
+pub struct BulkFetch {
+    view: String,
+    tags: Vec<String>,
+    start: SystemTime,
+    end: SystemTime,
+    page_size: i32,
+    tags_per_request: usize,
+    bounds: bool,
+    #[cfg(feature = "spill")]
+    memory_cap: Option<usize>,
+    #[cfg(feature = "spill")]
+    spill_dir: Option<PathBuf>,
+}
+
+impl BulkFetch {
+    pub fn new(
+        view: impl Into<String>,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Self {
+        Self {
+            view: view.into(),
+            tags: tags.into_iter().map(Into::into).collect(),
+            start,
+            end,
+            page_size: 10_000,
+            tags_per_request: 100,
+            bounds: false,
+            #[cfg(feature = "spill")]
+            memory_cap: None,
+            #[cfg(feature = "spill")]
+            spill_dir: None,
+        }
+    }
+
+    /// The maximum number of samples per tag in one page. Defaults to 10,000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// How many tags to request per call. Defaults to 100.
+    pub fn tags_per_request(mut self, tags_per_request: usize) -> Self {
+        self.tags_per_request = tags_per_request.max(1);
+        self
+    }
+
+    /// Also return each tag's last sample before the range and first
+    /// sample after it, in its first and last pages.
+    pub fn bounds(mut self, bounds: bool) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    /// Keep at most roughly `bytes` of pages in memory, spilling the rest to disk.
+    #[cfg(feature = "spill")]
+    pub fn memory_cap(mut self, bytes: usize) -> Self {
+        self.memory_cap = Some(bytes);
+        self
+    }
+
+    /// Where to create spill files. Defaults to the system temp directory.
+    #[cfg(feature = "spill")]
+    pub fn spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    /// Fetch every page, in tag order and then time order.
+    pub async fn run(&self, client: &ViewsClient) -> Result<Pages, Error> {
+        request_id::operation(async {
+            let mut pages = self.pages()?;
+            for chunk in self.tags.chunks(self.tags_per_request) {
+                let mut pending = self.chunk_requests(chunk);
+                while !pending.is_empty() {
+                    for page in self.fetch_step(client, &mut pending).await? {
+                        pages.push(page)?;
+                    }
+                }
+            }
+            Ok(pages)
+        })
+        .await
+    }
+
+    /// Fetch every page like [`run`](Self::run), with the tag chunks spread
+    /// across the CCIs of `pool`, one chunk per CCI at a time.
+    pub async fn run_pooled(&self, pool: &CciPool) -> Result<Pages, Error> {
+        request_id::operation(async {
+            let mut pages = self.pages()?;
+            let chunks: Vec<&[String]> = self.tags.chunks(self.tags_per_request).collect();
+            for wave in chunks.chunks(pool.len().max(1)) {
+                let results = pool
+                    .fan_out(wave.iter().copied(), |client, chunk| {
+                        let fetch = self.clone();
+                        let chunk = chunk.to_vec();
+                        async move { fetch.fetch_chunk(&client, &chunk).await }
+                    })
+                    .await;
+                for result in results {
+                    for page in result? {
+                        pages.push(page)?;
+                    }
+                }
+            }
+            Ok(pages)
+        })
+        .await
+    }
+
+    /// Fetch every page of one chunk of tags.
+    pub(crate) async fn fetch_chunk(
+        &self,
+        client: &ViewsClient,
+        chunk: &[String],
+    ) -> Result<Vec<RawColumns>, Error> {
+        request_id::operation(async {
+            let mut pending = self.chunk_requests(chunk);
+            let mut pages = Vec::new();
+            while !pending.is_empty() {
+                pages.extend(self.fetch_step(client, &mut pending).await?);
+            }
+            Ok(pages)
+        })
+        .await
+    }
+
+    fn chunk_requests(&self, chunk: &[String]) -> Vec<RawTagRequest> {
+        chunk
+            .iter()
+            .map(|tag| self.tag_request(tag.clone(), Vec::new()))
+            .collect()
+    }
+
+    /// Request the next page of every pending tag, replacing `pending` with
+    /// the tags that have more pages to come.
+    async fn fetch_step(
+        &self,
+        client: &ViewsClient,
+        pending: &mut Vec<RawTagRequest>,
+    ) -> Result<Vec<RawColumns>, Error> {
+        let sent = std::mem::take(pending);
+        let resp = client
+            .raw_data_columnar_page(GetRawDataRequest {
+                view: self.view.clone(),
+                requests: sent.clone(),
+                max_count_per_tag: self.page_size,
+                return_bounds: self.bounds,
+                return_annotations: false,
+                cci: 0,
+            })
+            .await?;
+        for page in &resp.raw_data {
+            let sent = sent
+                .iter()
+                .find(|r| r.tag_name == page.tag_name)
+                .map_or(&[][..], |r| &r.continuation_point);
+            check_progress(&page.tag_name, sent, &page.continuation_point, page.len())?;
+            if !page.continuation_point.is_empty() {
+                pending
+                    .push(self.tag_request(page.tag_name.clone(), page.continuation_point.clone()));
+            }
+        }
+        Ok(resp.raw_data)
+    }
+
+    fn tag_request(&self, tag_name: String, continuation_point: Vec<u8>) -> RawTagRequest {
+        RawTagRequest {
+            tag_name,
+            start_time: Some(self.start.into()),
+            end_time: Some(self.end.into()),
+            client_data: 0,
+            continuation_point,
+        }
+    }
+
+    #[cfg(feature = "spill")]
+    fn pages(&self) -> io::Result<Pages> {
+        match self.memory_cap {
+            Some(cap) => Pages::with_memory_cap(cap, self.spill_dir.as_deref()),
+            None => Ok(Pages::new()),
+        }
+    }
+
+    #[cfg(not(feature = "spill"))]
+    fn pages(&self) -> io::Result<Pages> {
+        Ok(Pages::new())
+    }
+}
+
+enum Page {
+    Resident(Box<RawColumns>),
+    #[cfg(feature = "spill")]
+    Spilled(PathBuf),
+}
+
+/// The pages of a bulk extract, in fetch order, some possibly spilled to disk.
+///
+/// Consume them with `into_iter()`; spilled pages are read back one at a
+/// time, so iterating never holds more than the resident pages plus one.
+#[derive(Default)]
+pub struct Pages {
+    pages: VecDeque<Page>,
+    resident_bytes: usize,
+    #[cfg(feature = "spill")]
+    spill: Option<spill::Spill>,
+}
+
+impl Pages {
+    /// An in-memory page buffer with no cap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A page buffer that spills its oldest pages to Arrow IPC files under
+    /// `dir` (or the system temp directory) once resident pages exceed `cap` bytes.
+    #[cfg(feature = "spill")]
+    pub fn with_memory_cap(cap: usize, dir: Option<&Path>) -> io::Result<Self> {
+        Ok(Self {
+            spill: Some(spill::Spill::new(cap, dir)?),
+            ..Self::default()
+        })
+    }
+
+    /// Append a page, spilling older pages if the memory cap is exceeded.
+    pub fn push(&mut self, page: RawColumns) -> io::Result<()> {
+        self.resident_bytes += page.heap_size();
+        self.pages.push_back(Page::Resident(Box::new(page)));
+        #[cfg(feature = "spill")]
+        self.enforce_cap()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "spill")]
+    fn enforce_cap(&mut self) -> io::Result<()> {
+        let Some(spill) = &mut self.spill else {
+            return Ok(());
+        };
+        let mut pages = self.pages.iter_mut();
+        while self.resident_bytes > spill.cap() {
+            let Some(slot) = pages.next() else {
+                break;
+            };
+            if let Page::Resident(page) = slot {
+                let path = spill.write(page)?;
+                self.resident_bytes -= page.heap_size();
+                *slot = Page::Spilled(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of pages.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// The approximate heap size of the pages held in memory.
+    pub fn resident_bytes(&self) -> usize {
+        self.resident_bytes
+    }
+
+    /// The number of pages currently spilled to disk.
+    pub fn spilled(&self) -> usize {
+        #[cfg(feature = "spill")]
+        return self
+            .pages
+            .iter()
+            .filter(|p| matches!(p, Page::Spilled(_)))
+            .count();
+        #[cfg(not(feature = "spill"))]
+        0
+    }
+}
+
+impl IntoIterator for Pages {
+    type Item = io::Result<RawColumns>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter { pages: self }
+    }
+}
+
+/// Iterator over the pages of a [`Pages`] buffer, reading spilled pages back from disk.
+pub struct IntoIter {
+    pages: Pages,
+}
+
+impl Iterator for IntoIter {
+    type Item = io::Result<RawColumns>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.pages.pages.pop_front()? {
+            Page::Resident(page) => {
+                self.pages.resident_bytes -= page.heap_size();
+                Some(Ok(*page))
+            }
+            #[cfg(feature = "spill")]
+            Page::Spilled(path) => Some(spill::read(&path)),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+use prost::Message;
+use tempfile::TempDir;
+
+use crate::canary::views::grpc::api::RawTagData;
+use crate::columnar::RawColumns;
+
+/// Schema metadata key holding the page's encoded `RawTagData`, minus its samples.
+const HEADER_KEY: &str = "crowsong.raw_tag_data";
+
+/// A temporary directory of spilled pages, removed when dropped.
+pub(super) struct Spill {
+    dir: TempDir,
+    cap: usize,
+    next: usize,
+}
+
+impl Spill {
+    pub(super) fn new(cap: usize, parent: Option<&Path>) -> io::Result<Self> {
+        let dir = match parent {
+            Some(parent) => tempfile::Builder::new()
+                .prefix("crowsong-spill-")
+                .tempdir_in(parent)?,
+            None => tempfile::Builder::new()
+                .prefix("crowsong-spill-")
+                .tempdir()?,
+        };
+        Ok(Self { dir, cap, next: 0 })
+    }
+
+    pub(super) fn cap(&self) -> usize {
+        self.cap
+    }
+
+    /// Write a page to a new Arrow IPC file and return its path.
+    pub(super) fn write(&mut self, page: &RawColumns) -> io::Result<PathBuf> {
+        let path = self.dir.path().join(format!("page-{:06}.arrow", self.next));
+        self.next += 1;
+
+        let batch = page.to_arrow().map_err(io::Error::other)?;
+        let metadata = HashMap::from([(
+            HEADER_KEY.to_string(),
+            encode_hex(&header(page).encode_to_vec()),
+        )]);
+        let schema = Arc::new(batch.schema().as_ref().clone().with_metadata(metadata));
+        let batch = batch.with_schema(schema).map_err(io::Error::other)?;
+        let mut writer =
+            FileWriter::try_new(File::create(&path)?, &batch.schema()).map_err(io::Error::other)?;
+        writer.write(&batch).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)?;
+        Ok(path)
+    }
+}
+
+/// Read a spilled page back and delete its file.
+pub(super) fn read(path: &Path) -> io::Result<RawColumns> {
+    let reader = FileReader::try_new(File::open(path)?, None).map_err(io::Error::other)?;
+    let header = reader
+        .schema()
+        .metadata()
+        .get(HEADER_KEY)
+        .map(|hex| decode_hex(hex))
+        .transpose()?
+        .unwrap_or_default();
+    let header = RawTagData::decode(header.as_slice()).map_err(io::Error::other)?;
+
+    let mut page = RawColumns {
+        tag_name: header.tag_name,
+        client_data: header.client_data,
+        result_flags: header.result_flags,
+        error_message: header.error_message,
+        error_code: header.error_code,
+        annotations: header.annotations,
+        continuation_point: header.continuation_point,
+        ..RawColumns::default()
+    };
+    for batch in reader {
+        page.extend_from_arrow(&batch.map_err(io::Error::other)?)
+            .map_err(io::Error::other)?;
+    }
+    std::fs::remove_file(path)?;
+    Ok(page)
+}
+
+fn header(page: &RawColumns) -> RawTagData {
+    RawTagData {
+        tag_name: page.tag_name.clone(),
+        client_data: page.client_data,
+        result_flags: page.result_flags,
+        error_message: page.error_message.clone(),
+        error_code: page.error_code,
+        tvqs: Vec::new(),
+        annotations: page.annotations.clone(),
+        continuation_point: page.continuation_point.clone(),
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> io::Result<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad spill header"))
+        })
+        .collect()
+}
@@ -0,0 +1,467 @@
+//! Portable snapshot bundles, for moving history between air-gapped sites.
+//!
+//! A bundle is a directory holding a view's tag metadata and raw history
+//! over a time range:
+//!
+//! ```text
+//! manifest.json    what the bundle holds, with every file's size and SHA-256
+//! inventory.json   the tags' properties and data contexts, as an [`Inventory`]
+//! data/00000.jsonl one tag's samples, one JSON object per line
+//! ```
+//!
+//! [`BundleExport`] writes one from a live view; [`Bundle::import`] checks
+//! every file against the manifest, then writes the samples into another
+//! historian through a [`StoreAndForwardClient`], renaming tags with a
+//! [`TagMap`]. The manifest is written last, so a bundle whose export was
+//! interrupted has none and can't be imported. `crowsong export-bundle` and
+//! `crowsong import-bundle` do the same from the command line.
+//!
+//! Each sample line holds the timestamp in nanoseconds since the Unix epoch,
+//! the quality, and the value tagged with its type, so values come back as
+//! the type they were read as:
+//!
+//! ```text
+//! {"t":1714564800000000000,"q":192,"v":{"float":21.5}}
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::bulk::BulkFetch;
+use crate::error::Error;
+use crate::inventory::{Inventory, InventoryScan};
+use crate::quality::Quality;
+use crate::saf_client::StoreAndForwardClient;
+use crate::sync::TagMap;
+use crate::timestamps::{nanos_to_system_time, system_time_to_nanos};
+use crate::types::{Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// The name of a bundle's manifest file.
+pub const MANIFEST: &str = "manifest.json";
+
+const FORMAT: &str = "crowsong-bundle";
+const VERSION: u32 = 1;
+
+/// How many samples to write to the target at a time.
+const IMPORT_BATCH: usize = 10_000;
+
+/// A file in a bundle, as the manifest records it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// The path relative to the bundle directory.
+    pub path: String,
+    pub bytes: u64,
+    /// The SHA-256 of the contents, in lowercase hex.
+    pub sha256: String,
+}
+
+/// A tag's samples in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataFile {
+    pub tag_name: String,
+    pub samples: u64,
+    #[serde(flatten)]
+    pub file: BundleFile,
+}
+
+/// What a bundle holds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    #[serde(with = "rfc3339")]
+    pub created: SystemTime,
+    pub view: String,
+    /// The time range exported; samples fall in `start..end`.
+    #[serde(with = "rfc3339")]
+    pub start: SystemTime,
+    #[serde(with = "rfc3339")]
+    pub end: SystemTime,
+    pub inventory: BundleFile,
+    pub data: Vec<DataFile>,
+}
+
+impl Manifest {
+    /// Samples across every data file.
+    pub fn samples(&self) -> u64 {
+        self.data.iter().map(|data| data.samples).sum()
+    }
+}
+
+/// A planned export of a view's metadata and history into a bundle.
+#[derive(Debug, Clone)]
+pub struct BundleExport {
+    view: String,
+    datasets: Vec<String>,
+    start: SystemTime,
+    end: SystemTime,
+    page_size: i32,
+}
+
+impl BundleExport {
+    pub fn new(view: impl Into<String>, start: SystemTime, end: SystemTime) -> Self {
+        Self {
+            view: view.into(),
+            datasets: Vec::new(),
+            start,
+            end,
+            page_size: 10_000,
+        }
+    }
+
+    /// Export `dataset`; call again to add more. Defaults to every visible
+    /// dataset of the view.
+    pub fn dataset(mut self, dataset: impl Into<String>) -> Self {
+        self.datasets.push(dataset.into());
+        self
+    }
+
+    /// The maximum number of samples in one read. Defaults to 10,000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Write the bundle to `dir`, creating it if needed.
+    pub async fn run(
+        &self,
+        client: &ViewsClient,
+        dir: impl AsRef<Path>,
+    ) -> Result<Manifest, Error> {
+        self.run_with_progress(client, dir, |_, _| {}).await
+    }
+
+    /// Write the bundle to `dir`, calling `progress` with each tag and its
+    /// sample count once its data file is written.
+    pub async fn run_with_progress(
+        &self,
+        client: &ViewsClient,
+        dir: impl AsRef<Path>,
+        mut progress: impl FnMut(&str, u64),
+    ) -> Result<Manifest, Error> {
+        let dir = dir.as_ref();
+        if dir.join(MANIFEST).exists() {
+            return Err(format!("{} already holds a bundle", dir.display()).into());
+        }
+        fs::create_dir_all(dir.join("data"))?;
+        let created = SystemTime::now();
+
+        let mut inventory = InventoryScan::new().view(&self.view).run(client).await?;
+        if !self.datasets.is_empty() {
+            inventory
+                .tags
+                .retain(|entry| self.datasets.contains(&entry.dataset));
+        }
+
+        let mut data = Vec::with_capacity(inventory.tags.len());
+        for (index, entry) in inventory.tags.iter().enumerate() {
+            let path = format!("data/{index:05}.jsonl");
+            let mut out = HashingWriter::create(&dir.join(&path))?;
+            let mut samples = 0;
+            let pages = BulkFetch::new(&self.view, [&entry.tag_name], self.start, self.end)
+                .page_size(self.page_size)
+                .run(client)
+                .await?;
+            for page in pages {
+                let page = page?;
+                if page.error_code != 0 {
+                    return Err(format!(
+                        "reading {} failed ({}): {}",
+                        entry.tag_name, page.error_code, page.error_message
+                    )
+                    .into());
+                }
+                for row in 0..page.len() {
+                    serde_json::to_writer(&mut out, &Sample::from(&page.tvq(row)))
+                        .map_err(Error::other)?;
+                    out.write_all(b"\n")?;
+                }
+                samples += page.len() as u64;
+            }
+            progress(&entry.tag_name, samples);
+            data.push(DataFile {
+                tag_name: entry.tag_name.clone(),
+                samples,
+                file: out.finish(path)?,
+            });
+        }
+
+        let mut out = HashingWriter::create(&dir.join("inventory.json"))?;
+        inventory.write_json(&mut out)?;
+        let manifest = Manifest {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            created,
+            view: self.view.clone(),
+            start: self.start,
+            end: self.end,
+            inventory: out.finish("inventory.json".to_string())?,
+            data,
+        };
+        // Write beside the target and rename, so the manifest only appears
+        // once it's whole.
+        let partial = dir.join(format!("{MANIFEST}.partial"));
+        fs::write(
+            &partial,
+            serde_json::to_vec_pretty(&manifest).map_err(Error::other)?,
+        )?;
+        fs::rename(partial, dir.join(MANIFEST))?;
+        Ok(manifest)
+    }
+}
+
+/// What a finished import wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportSummary {
+    pub tags: usize,
+    pub samples: u64,
+}
+
+/// A bundle on disk.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl Bundle {
+    /// Open the bundle in `dir`, reading its manifest.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let dir = dir.into();
+        let path = dir.join(MANIFEST);
+        let manifest: Manifest = serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| format!("bad manifest in {}: {e}", path.display()))?;
+        if manifest.format != FORMAT || manifest.version != VERSION {
+            return Err(format!(
+                "{} is a {} v{} bundle; this crowsong reads {FORMAT} v{VERSION}",
+                dir.display(),
+                manifest.format,
+                manifest.version
+            )
+            .into());
+        }
+        Ok(Self { dir, manifest })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Check every file's size and checksum against the manifest.
+    pub fn verify(&self) -> Result<(), Error> {
+        let files = std::iter::once(&self.manifest.inventory)
+            .chain(self.manifest.data.iter().map(|data| &data.file));
+        for file in files {
+            let (bytes, sha256) = hash_file(&self.dir.join(&file.path))?;
+            if bytes != file.bytes || sha256 != file.sha256 {
+                return Err(format!(
+                    "{} in {} doesn't match the manifest: {bytes} bytes with SHA-256 {sha256}, \
+                     expected {} bytes with SHA-256 {}",
+                    file.path,
+                    self.dir.display(),
+                    file.bytes,
+                    file.sha256
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The tags' metadata as exported.
+    pub fn inventory(&self) -> io::Result<Inventory> {
+        Inventory::load(self.dir.join(&self.manifest.inventory.path))
+    }
+
+    /// Read a data file's samples.
+    pub fn samples(&self, data: &DataFile) -> Result<Vec<Tvq>, Error> {
+        let path = self.dir.join(&data.file.path);
+        let mut tvqs = Vec::with_capacity(data.samples as usize);
+        for (number, line) in BufReader::new(File::open(&path)?).lines().enumerate() {
+            let sample: Sample = serde_json::from_str(&line?)
+                .map_err(|e| format!("bad sample at {}:{}: {e}", path.display(), number + 1))?;
+            tvqs.push(sample.into());
+        }
+        Ok(tvqs)
+    }
+
+    /// Verify the bundle, then write every tag's samples to `target`, which
+    /// must have an open session, naming tags by `map`.
+    pub async fn import(
+        &self,
+        target: &mut StoreAndForwardClient,
+        map: &TagMap,
+    ) -> Result<ImportSummary, Error> {
+        self.import_with_progress(target, map, |_, _| {}).await
+    }
+
+    /// Like [`import`](Self::import), calling `progress` with each target
+    /// tag and its sample count once it's written.
+    pub async fn import_with_progress(
+        &self,
+        target: &mut StoreAndForwardClient,
+        map: &TagMap,
+        mut progress: impl FnMut(&str, u64),
+    ) -> Result<ImportSummary, Error> {
+        self.verify()?;
+        let mut summary = ImportSummary::default();
+        for data in &self.manifest.data {
+            let target_tag = map.map(&data.tag_name);
+            let tvqs = self.samples(data)?;
+            for batch in tvqs.chunks(IMPORT_BATCH) {
+                target.store_tvqs(&target_tag, batch).await?;
+            }
+            summary.tags += 1;
+            summary.samples += tvqs.len() as u64;
+            progress(&target_tag, tvqs.len() as u64);
+        }
+        Ok(summary)
+    }
+}
+
+/// One sample line in a data file.
+#[derive(Serialize, Deserialize)]
+struct Sample {
+    t: i64,
+    q: u32,
+    v: SampleValue,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SampleValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    #[serde(rename = "uint")]
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Decimal(Vec<u8>),
+}
+
+impl From<&Tvq> for Sample {
+    fn from(tvq: &Tvq) -> Self {
+        let v = match &tvq.value {
+            Value::Null => SampleValue::Null,
+            Value::Bool(b) => SampleValue::Bool(*b),
+            Value::Int(i) => SampleValue::Int(*i),
+            Value::UInt(u) => SampleValue::UInt(*u),
+            Value::Float(f) => SampleValue::Float(*f),
+            Value::String(s) => SampleValue::String(s.clone()),
+            Value::Decimal(d) => SampleValue::Decimal(d.clone()),
+        };
+        Self {
+            t: system_time_to_nanos(tvq.timestamp),
+            q: tvq.quality.0,
+            v,
+        }
+    }
+}
+
+impl From<Sample> for Tvq {
+    fn from(sample: Sample) -> Self {
+        let value = match sample.v {
+            SampleValue::Null => Value::Null,
+            SampleValue::Bool(b) => Value::Bool(b),
+            SampleValue::Int(i) => Value::Int(i),
+            SampleValue::UInt(u) => Value::UInt(u),
+            SampleValue::Float(f) => Value::Float(f),
+            SampleValue::String(s) => Value::String(s),
+            SampleValue::Decimal(d) => Value::Decimal(d),
+        };
+        Self {
+            timestamp: nanos_to_system_time(sample.t),
+            value,
+            quality: Quality(sample.q),
+        }
+    }
+}
+
+/// A file being written, counting and hashing what goes into it.
+struct HashingWriter {
+    out: BufWriter<File>,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl HashingWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            hasher: Sha256::new(),
+            bytes: 0,
+        })
+    }
+
+    /// Flush the file and describe it as `path`.
+    fn finish(mut self, path: String) -> io::Result<BundleFile> {
+        self.out.flush()?;
+        Ok(BundleFile {
+            path,
+            bytes: self.bytes,
+            sha256: hex(&self.hasher.finalize()),
+        })
+    }
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.out.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// A file's size and SHA-256.
+fn hash_file(path: &Path) -> io::Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok((bytes, hex(&hasher.finalize())));
+        }
+        hasher.update(&buf[..read]);
+        bytes += read as u64;
+    }
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+mod rfc3339 {
+    use std::time::SystemTime;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::timestamps;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&timestamps::format_rfc3339(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        timestamps::parse_rfc3339(&s).map_err(D::Error::custom)
+    }
+}
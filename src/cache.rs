@@ -0,0 +1,593 @@
+//! A persistent on-disk cache of raw history.
+//!
+//! [`ReadCache`] keeps each tag's previously fetched raw data as Arrow IPC
+//! segment files, each covering a time range that was read in full. A read
+//! is served from the segments it overlaps, and only the gaps between them
+//! are requested from the server. Newly fetched gaps are merged with the
+//! segments they touch, so repeated adjacent queries coalesce into one file.
+//!
+//! Samples newer than the [mutable window](ReadCache::mutable_window) may
+//! still change on the server, so that part of a read is always fetched live
+//! and never cached. Ranges are start-inclusive and end-exclusive.
+//!
+//! By default a read fails when the historian can't be reached. With an
+//! [`OfflinePolicy`] of `ServeStale` or `ServeStaleWithWarning`, it is served
+//! from whatever the cache holds instead, so a trend display keeps rendering
+//! through a network outage. [`ReadCache::read`] marks such tags as stale:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient, start: std::time::SystemTime, end: std::time::SystemTime) -> Result<(), crowsong::Error> {
+//! use crowsong::cache::{OfflinePolicy, ReadCache};
+//!
+//! let cache = ReadCache::open("cache")?.offline(OfflinePolicy::ServeStale);
+//! let read = cache.read(client, "Plant", &["Site.Pump1.Flow"], start, end).await?;
+//! for stale in &read.stale {
+//!     println!("showing cached data: {stale}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+
+use crate::bulk::BulkFetch;
+use crate::columnar::RawColumns;
+use crate::connection::is_unreachable;
+use crate::error::Error;
+use crate::timestamps::{self, nanos_to_system_time, system_time_to_nanos};
+use crate::views_client::ViewsClient;
+
+/// A time range of one tag that is fully present on disk, in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Segment {
+    start: i64,
+    end: i64,
+}
+
+impl Segment {
+    fn file_name(self) -> String {
+        format!("{}_{}.arrow", self.start, self.end)
+    }
+
+    fn parse(file_name: &str) -> Option<Self> {
+        let (start, end) = file_name.strip_suffix(".arrow")?.split_once('_')?;
+        Some(Self {
+            start: start.parse().ok()?,
+            end: end.parse().ok()?,
+        })
+    }
+}
+
+/// What a read does when the historian can't be reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OfflinePolicy {
+    /// Fail the read.
+    #[default]
+    Fail,
+    /// Serve what the cache holds, marked stale in the [`CachedRead`].
+    ServeStale,
+    /// Serve what the cache holds, and also report each stale tag to the
+    /// [stale handler](ReadCache::on_stale).
+    ServeStaleWithWarning,
+}
+
+/// A tag served from the cache alone because the historian was unreachable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleRead {
+    pub view: String,
+    pub tag_name: String,
+    /// The end of the newest cached sample range that overlaps the read, if
+    /// any; nothing after it was served.
+    pub cached_until: Option<SystemTime>,
+    /// Why the historian couldn't be read.
+    pub error: String,
+}
+
+impl fmt::Display for StaleRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} served from the cache",
+            self.tag_name, self.view
+        )?;
+        match self.cached_until {
+            Some(until) => write!(f, " up to {}", timestamps::format_rfc3339(until))?,
+            None => f.write_str(", which holds nothing for the range")?,
+        }
+        write!(f, " (historian unreachable: {})", self.error)
+    }
+}
+
+/// The result of [`ReadCache::read`].
+#[derive(Debug, Clone, Default)]
+pub struct CachedRead {
+    /// Each tag's samples, in the order asked for.
+    pub tags: Vec<RawColumns>,
+    /// The tags served from the cache alone, if any.
+    pub stale: Vec<StaleRead>,
+}
+
+impl CachedRead {
+    /// Whether any tag was served from the cache alone.
+    pub fn is_stale(&self) -> bool {
+        !self.stale.is_empty()
+    }
+}
+
+type StaleHandler = Arc<dyn Fn(&StaleRead) + Send + Sync>;
+
+/// A directory of cached raw history, keyed by view, tag, and time range.
+#[derive(Clone)]
+pub struct ReadCache {
+    dir: PathBuf,
+    mutable_window: Duration,
+    page_size: i32,
+    offline: OfflinePolicy,
+    on_stale: StaleHandler,
+}
+
+impl fmt::Debug for ReadCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadCache")
+            .field("dir", &self.dir)
+            .field("mutable_window", &self.mutable_window)
+            .field("page_size", &self.page_size)
+            .field("offline", &self.offline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReadCache {
+    /// Open (creating if needed) a cache rooted at `dir`.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            mutable_window: Duration::from_secs(3600),
+            page_size: 10_000,
+            offline: OfflinePolicy::Fail,
+            on_stale: Arc::new(|stale| eprintln!("crowsong: {stale}")),
+        })
+    }
+
+    /// How far back from now data is treated as mutable and always fetched
+    /// live. Defaults to one hour.
+    pub fn mutable_window(mut self, window: Duration) -> Self {
+        self.mutable_window = window;
+        self
+    }
+
+    /// The page size used when fetching missing ranges. Defaults to 10,000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// What a read does when the historian can't be reached. Defaults to
+    /// [`OfflinePolicy::Fail`].
+    pub fn offline(mut self, policy: OfflinePolicy) -> Self {
+        self.offline = policy;
+        self
+    }
+
+    /// Pass tags served stale under
+    /// [`OfflinePolicy::ServeStaleWithWarning`] to `handler` instead of
+    /// printing them to stderr.
+    pub fn on_stale(mut self, handler: impl Fn(&StaleRead) + Send + Sync + 'static) -> Self {
+        self.on_stale = Arc::new(handler);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The time ranges of a tag that are cached, in order.
+    pub fn cached_ranges(
+        &self,
+        view: &str,
+        tag: &str,
+    ) -> io::Result<Vec<(SystemTime, SystemTime)>> {
+        Ok(segments(&self.tag_dir(view, tag))?
+            .into_iter()
+            .map(|s| (nanos_to_system_time(s.start), nanos_to_system_time(s.end)))
+            .collect())
+    }
+
+    /// Drop everything cached for a tag.
+    pub fn invalidate(&self, view: &str, tag: &str) -> io::Result<()> {
+        match fs::remove_dir_all(self.tag_dir(view, tag)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop everything in the cache.
+    pub fn clear(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the raw data of each tag over `start..end`, from the cache where
+    /// possible and from the server otherwise. See [`read`](Self::read) to
+    /// tell which tags, if any, were served stale.
+    pub async fn get_raw_data(
+        &self,
+        client: &ViewsClient,
+        view: &str,
+        tags: &[impl AsRef<str>],
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<Vec<RawColumns>, Error> {
+        Ok(self.read(client, view, tags, start, end).await?.tags)
+    }
+
+    /// Like [`get_raw_data`](Self::get_raw_data), also saying which tags
+    /// were served from the cache alone under the
+    /// [offline policy](Self::offline).
+    pub async fn read(
+        &self,
+        client: &ViewsClient,
+        view: &str,
+        tags: &[impl AsRef<str>],
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<CachedRead, Error> {
+        let mut read = CachedRead::default();
+        for tag in tags {
+            let tag = tag.as_ref();
+            match self.get_tag(client, view, tag, start, end).await {
+                Ok(data) => read.tags.push(data),
+                Err(e) if self.offline != OfflinePolicy::Fail && historian_unreachable(&e) => {
+                    let (data, cached_until) = self.get_cached(view, tag, start, end)?;
+                    let stale = StaleRead {
+                        view: view.to_string(),
+                        tag_name: tag.to_string(),
+                        cached_until,
+                        error: e.to_string(),
+                    };
+                    if self.offline == OfflinePolicy::ServeStaleWithWarning {
+                        (self.on_stale)(&stale);
+                    }
+                    read.tags.push(data);
+                    read.stale.push(stale);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(read)
+    }
+
+    /// Whatever the cache holds of a tag over `start..end`, and the end of
+    /// the newest segment that overlaps it.
+    fn get_cached(
+        &self,
+        view: &str,
+        tag: &str,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> io::Result<(RawColumns, Option<SystemTime>)> {
+        let start_ns = system_time_to_nanos(start);
+        let end_ns = system_time_to_nanos(end).max(start_ns);
+        let dir = self.tag_dir(view, tag);
+        let mut result = RawColumns {
+            tag_name: tag.to_string(),
+            ..RawColumns::default()
+        };
+        let mut cached_until = None;
+        for segment in segments(&dir)?
+            .into_iter()
+            .filter(|s| s.end > start_ns && s.start < end_ns)
+        {
+            let data = read_segment(&dir.join(segment.file_name()))?;
+            append_range(&mut result, &data, start_ns, end_ns);
+            cached_until = Some(nanos_to_system_time(segment.end.min(end_ns)));
+        }
+        Ok((result, cached_until))
+    }
+
+    async fn get_tag(
+        &self,
+        client: &ViewsClient,
+        view: &str,
+        tag: &str,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<RawColumns, Error> {
+        let start_ns = system_time_to_nanos(start);
+        let end_ns = system_time_to_nanos(end).max(start_ns);
+        let horizon = SystemTime::now()
+            .checked_sub(self.mutable_window)
+            .map_or(i64::MIN, system_time_to_nanos)
+            .clamp(start_ns, end_ns);
+
+        let dir = self.tag_dir(view, tag);
+        let mut cached = segments(&dir)?;
+        for gap in gaps(&cached, start_ns, horizon) {
+            let fetched = self.fetch(client, view, tag, gap).await?;
+            cached = merge(&dir, &cached, gap, fetched)?;
+        }
+
+        let mut result = RawColumns {
+            tag_name: tag.to_string(),
+            ..RawColumns::default()
+        };
+        for segment in cached
+            .iter()
+            .filter(|s| s.end > start_ns && s.start < horizon)
+        {
+            let data = read_segment(&dir.join(segment.file_name()))?;
+            append_range(&mut result, &data, start_ns, horizon);
+        }
+        if horizon < end_ns {
+            let live = Segment {
+                start: horizon,
+                end: end_ns,
+            };
+            let data = self.fetch(client, view, tag, live).await?;
+            append_range(&mut result, &data, horizon, end_ns);
+        }
+        Ok(result)
+    }
+
+    async fn fetch(
+        &self,
+        client: &ViewsClient,
+        view: &str,
+        tag: &str,
+        range: Segment,
+    ) -> Result<RawColumns, Error> {
+        let pages = BulkFetch::new(
+            view,
+            [tag],
+            nanos_to_system_time(range.start),
+            nanos_to_system_time(range.end),
+        )
+        .page_size(self.page_size)
+        .run(client)
+        .await?;
+
+        let mut data = RawColumns::default();
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {tag} failed ({}): {}",
+                    page.error_code, page.error_message
+                )
+                .into());
+            }
+            data.extend_from_rows(&page, 0..page.len());
+        }
+        Ok(data)
+    }
+
+    fn tag_dir(&self, view: &str, tag: &str) -> PathBuf {
+        self.dir.join(escape(view)).join(escape(tag))
+    }
+}
+
+/// Whether a read failed because the historian couldn't be reached.
+fn historian_unreachable(e: &Error) -> bool {
+    e.status().is_some_and(is_unreachable)
+}
+
+/// Make a view or tag name safe to use as a single path component.
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric()
+            || matches!(b, b'-' | b'_')
+            || (b == b'.' && !escaped.is_empty())
+        {
+            escaped.push(b as char);
+        } else {
+            escaped.push_str(&format!("%{b:02X}"));
+        }
+    }
+    escaped
+}
+
+/// The cached segments in a tag directory, sorted by start.
+fn segments(dir: &Path) -> io::Result<Vec<Segment>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut segments = Vec::new();
+    for entry in entries {
+        if let Some(segment) = entry?.file_name().to_str().and_then(Segment::parse) {
+            segments.push(segment);
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+/// The parts of `start..end` not covered by `segments`.
+fn gaps(segments: &[Segment], start: i64, end: i64) -> Vec<Segment> {
+    let mut gaps = Vec::new();
+    let mut cursor = start;
+    for segment in segments {
+        if segment.start >= end {
+            break;
+        }
+        if segment.start > cursor {
+            gaps.push(Segment {
+                start: cursor,
+                end: segment.start,
+            });
+        }
+        cursor = cursor.max(segment.end);
+    }
+    if cursor < end {
+        gaps.push(Segment { start: cursor, end });
+    }
+    gaps
+}
+
+/// Store a newly fetched range, merging it with every segment it overlaps or
+/// touches, and return the updated segment list.
+fn merge(
+    dir: &Path,
+    cached: &[Segment],
+    range: Segment,
+    data: RawColumns,
+) -> io::Result<Vec<Segment>> {
+    let (touching, mut kept): (Vec<Segment>, Vec<Segment>) = cached
+        .iter()
+        .partition(|s| s.end >= range.start && s.start <= range.end);
+
+    let mut parts = vec![(range, data)];
+    for segment in &touching {
+        parts.push((*segment, read_segment(&dir.join(segment.file_name()))?));
+    }
+    parts.sort_by_key(|(segment, _)| *segment);
+
+    let merged = Segment {
+        start: parts[0].0.start,
+        end: parts.iter().map(|(s, _)| s.end).max().unwrap_or(range.end),
+    };
+    let mut columns = RawColumns::default();
+    let mut cursor = merged.start;
+    for (segment, data) in &parts {
+        append_range(&mut columns, data, cursor.max(segment.start), segment.end);
+        cursor = cursor.max(segment.end);
+    }
+
+    fs::create_dir_all(dir)?;
+    write_segment(&dir.join(merged.file_name()), &columns)?;
+    for segment in touching {
+        if segment != merged {
+            fs::remove_file(dir.join(segment.file_name()))?;
+        }
+    }
+    kept.push(merged);
+    kept.sort();
+    Ok(kept)
+}
+
+/// Append the samples of `data` with timestamps in `start..end`.
+fn append_range(into: &mut RawColumns, data: &RawColumns, start: i64, end: i64) {
+    let first = data.timestamps.partition_point(|t| *t < start);
+    let last = data.timestamps.partition_point(|t| *t < end);
+    if first < last {
+        into.extend_from_rows(data, first..last);
+    }
+}
+
+fn write_segment(path: &Path, data: &RawColumns) -> io::Result<()> {
+    let batch = data.to_arrow().map_err(io::Error::other)?;
+    // Write beside the target and rename, so readers never see a partial file.
+    let partial = path.with_extension("partial");
+    let mut writer =
+        FileWriter::try_new(File::create(&partial)?, &batch.schema()).map_err(io::Error::other)?;
+    writer.write(&batch).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+    fs::rename(partial, path)
+}
+
+fn read_segment(path: &Path) -> io::Result<RawColumns> {
+    let reader = FileReader::try_new(File::open(path)?, None).map_err(io::Error::other)?;
+    let mut data = RawColumns::default();
+    for batch in reader {
+        data.extend_from_arrow(&batch.map_err(io::Error::other)?)
+            .map_err(io::Error::other)?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columnar::ValueKind;
+
+    fn segment(start: i64, end: i64) -> Segment {
+        Segment { start, end }
+    }
+
+    fn samples(timestamps: impl IntoIterator<Item = i64>) -> RawColumns {
+        let timestamps: Vec<i64> = timestamps.into_iter().collect();
+        RawColumns {
+            tag_name: "Tank.Level".into(),
+            values: timestamps.iter().map(|t| *t as f64).collect(),
+            kinds: vec![ValueKind::Float; timestamps.len()],
+            qualities: vec![0xC0; timestamps.len()],
+            timestamps,
+            ..Default::default()
+        }
+    }
+
+    /// A fresh directory for one test's segments.
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("crowsong-cache-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn segment_names_round_trip() {
+        let segment = segment(-5_000, 1_714_564_800_000_000_000);
+        assert_eq!(Segment::parse(&segment.file_name()), Some(segment));
+        assert_eq!(Segment::parse("10_20.partial"), None);
+        assert_eq!(Segment::parse("10-20.arrow"), None);
+        assert_eq!(Segment::parse("ten_20.arrow"), None);
+    }
+
+    #[test]
+    fn finds_the_uncovered_parts_of_a_range() {
+        assert_eq!(gaps(&[], 0, 100), vec![segment(0, 100)]);
+        assert_eq!(gaps(&[segment(0, 100)], 10, 90), vec![]);
+        assert_eq!(
+            gaps(&[segment(10, 20), segment(40, 50)], 0, 60),
+            vec![segment(0, 10), segment(20, 40), segment(50, 60)]
+        );
+        assert_eq!(
+            gaps(&[segment(0, 30), segment(20, 40)], 10, 50),
+            vec![segment(40, 50)]
+        );
+        assert_eq!(gaps(&[segment(60, 70)], 0, 50), vec![segment(0, 50)]);
+    }
+
+    #[test]
+    fn merges_touching_segments_into_one() {
+        let dir = scratch("merge");
+        let cached = merge(&dir, &[], segment(0, 10), samples((0..10).step_by(2))).unwrap();
+        let cached = merge(&dir, &cached, segment(100, 110), samples([100, 105])).unwrap();
+        assert_eq!(cached, vec![segment(0, 10), segment(100, 110)]);
+
+        let merged = merge(&dir, &cached, segment(5, 20), samples(5..20)).unwrap();
+        assert_eq!(merged, vec![segment(0, 20), segment(100, 110)]);
+        assert_eq!(segments(&dir).unwrap(), merged);
+
+        let data = read_segment(&dir.join(segment(0, 20).file_name())).unwrap();
+        assert!(data.timestamps.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(data.timestamps.first(), Some(&0));
+        assert_eq!(data.timestamps.last(), Some(&19));
+        assert_eq!(gaps(&merged, 0, 110), vec![segment(20, 100)]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn escapes_path_components() {
+        assert_eq!(escape("Plant.Line1.Temp"), "Plant.Line1.Temp");
+        assert_eq!(escape("../etc"), "%2E.%2Fetc");
+        assert_eq!(escape("a b/c"), "a%20b%2Fc");
+    }
+}
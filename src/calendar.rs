@@ -0,0 +1,276 @@
+//! Calendar-aligned aggregate intervals.
+//!
+//! A fixed 86,400-second interval stops lining up with local days at the
+//! first daylight-saving change, and no fixed interval fits a month. A
+//! [`CalendarInterval`] instead splits a range at local midnights (or week
+//! and month starts) in a time zone, so a day bucket across a DST change is
+//! 23 or 25 hours long.
+//!
+//! [`Query::aggregate_calendar`](crate::query::Query::aggregate_calendar)
+//! reads aggregates over these buckets. Canary only takes a single interval
+//! per request, so consecutive buckets of the same length are requested
+//! together and each change of length starts a new request: daily buckets
+//! over a year take a handful of requests, monthly buckets one per month.
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use crowsong::calendar::{CalendarInterval, Tz};
+//!
+//! let daily = client
+//!     .query()
+//!     .view("Plant")
+//!     .tag("Plant.Line1.Flow")
+//!     .range(
+//!         crowsong::timestamps::parse_rfc3339("2024-03-01")?,
+//!         crowsong::timestamps::parse_rfc3339("2024-04-01")?,
+//!     )
+//!     .aggregate_calendar("Total", CalendarInterval::days(1).timezone(Tz::Europe__Berlin))
+//!     .fetch()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+
+pub use chrono::Weekday;
+pub use chrono_tz::Tz;
+
+/// The calendar unit a [`CalendarInterval`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalendarUnit {
+    Day,
+    Week,
+    Month,
+}
+
+/// Buckets of whole days, weeks, or months in a time zone.
+///
+/// Buckets start at local midnight, on the week's first day for weeks and
+/// on the first of the month for months. Where a DST change skips
+/// midnight, the bucket starts at the first local time that exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarInterval {
+    unit: CalendarUnit,
+    count: u32,
+    timezone: Tz,
+    week_start: Weekday,
+}
+
+impl CalendarInterval {
+    /// Buckets of `count` units, in UTC until [`timezone`](Self::timezone)
+    /// says otherwise.
+    pub fn new(unit: CalendarUnit, count: u32) -> Self {
+        Self {
+            unit,
+            count: count.max(1),
+            timezone: Tz::UTC,
+            week_start: Weekday::Mon,
+        }
+    }
+
+    pub fn days(count: u32) -> Self {
+        Self::new(CalendarUnit::Day, count)
+    }
+
+    pub fn weeks(count: u32) -> Self {
+        Self::new(CalendarUnit::Week, count)
+    }
+
+    pub fn months(count: u32) -> Self {
+        Self::new(CalendarUnit::Month, count)
+    }
+
+    /// The time zone whose calendar the buckets follow. Defaults to UTC.
+    pub fn timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// The day weeks start on. Defaults to Monday.
+    pub fn week_start(mut self, day: Weekday) -> Self {
+        self.week_start = day;
+        self
+    }
+
+    /// The buckets covering `start..end`, as `(start, end)` pairs. The first
+    /// starts at the boundary at or before `start` and the last ends at the
+    /// boundary at or after `end`.
+    pub fn buckets(&self, start: SystemTime, end: SystemTime) -> Vec<(SystemTime, SystemTime)> {
+        let mut buckets = Vec::new();
+        let mut date = self.first_date(start);
+        let mut bucket_start = self.midnight(date);
+        while bucket_start < end {
+            let Some(next) = self.next_date(date) else {
+                break;
+            };
+            let bucket_end = self.midnight(next);
+            buckets.push((bucket_start, bucket_end));
+            date = next;
+            bucket_start = bucket_end;
+        }
+        buckets
+    }
+
+    /// The buckets covering `start..end`, merged into runs of equal-length
+    /// buckets as `(start, end, bucket length)`.
+    pub(crate) fn runs(
+        &self,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Vec<(SystemTime, SystemTime, Duration)> {
+        let mut runs: Vec<(SystemTime, SystemTime, Duration)> = Vec::new();
+        for (bucket_start, bucket_end) in self.buckets(start, end) {
+            let length = bucket_end.duration_since(bucket_start).unwrap_or_default();
+            match runs.last_mut() {
+                Some((_, run_end, run_length)) if *run_length == length => *run_end = bucket_end,
+                _ => runs.push((bucket_start, bucket_end, length)),
+            }
+        }
+        runs
+    }
+
+    /// The local date the bucket holding `time` starts on.
+    fn first_date(&self, time: SystemTime) -> NaiveDate {
+        let date = DateTime::<Utc>::from(time)
+            .with_timezone(&self.timezone)
+            .date_naive();
+        match self.unit {
+            CalendarUnit::Day => date,
+            CalendarUnit::Week => {
+                let days = (7 + date.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                date - Days::new(days.into())
+            }
+            CalendarUnit::Month => date.with_day(1).expect("every month has a first"),
+        }
+    }
+
+    fn next_date(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self.unit {
+            CalendarUnit::Day => date.checked_add_days(Days::new(self.count.into())),
+            CalendarUnit::Week => date.checked_add_days(Days::new(7 * u64::from(self.count))),
+            CalendarUnit::Month => date.checked_add_months(Months::new(self.count)),
+        }
+    }
+
+    /// Local midnight at the start of `date`, or the first local time after
+    /// it that exists.
+    fn midnight(&self, date: NaiveDate) -> SystemTime {
+        let midnight = date.and_time(NaiveTime::MIN);
+        (0..=96)
+            .find_map(|quarter| {
+                let local = midnight + TimeDelta::minutes(15 * quarter);
+                self.timezone.from_local_datetime(&local).earliest()
+            })
+            .map_or_else(|| midnight.and_utc().into(), SystemTime::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamps::parse_rfc3339;
+
+    const HOUR: Duration = Duration::from_secs(3_600);
+
+    fn utc(s: &str) -> SystemTime {
+        parse_rfc3339(s).unwrap()
+    }
+
+    fn lengths(buckets: &[(SystemTime, SystemTime)]) -> Vec<Duration> {
+        buckets
+            .iter()
+            .map(|(start, end)| end.duration_since(*start).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn days_shrink_at_spring_forward() {
+        // Berlin moves from +01:00 to +02:00 at 02:00 on 2024-03-31.
+        let days = CalendarInterval::days(1).timezone(Tz::Europe__Berlin);
+        let buckets = days.buckets(utc("2024-03-30T12:00:00Z"), utc("2024-04-01T12:00:00Z"));
+        assert_eq!(buckets[0].0, utc("2024-03-29T23:00:00Z"));
+        assert_eq!(buckets[1].0, utc("2024-03-30T23:00:00Z"));
+        assert_eq!(buckets[2].0, utc("2024-03-31T22:00:00Z"));
+        assert_eq!(lengths(&buckets), [24 * HOUR, 23 * HOUR, 24 * HOUR]);
+    }
+
+    #[test]
+    fn days_grow_at_fall_back() {
+        // Berlin moves from +02:00 back to +01:00 at 03:00 on 2024-10-27.
+        let days = CalendarInterval::days(1).timezone(Tz::Europe__Berlin);
+        let buckets = days.buckets(utc("2024-10-26T00:00:00Z"), utc("2024-10-28T00:00:00Z"));
+        assert_eq!(buckets[1].0, utc("2024-10-26T22:00:00Z"));
+        assert_eq!(buckets[2].0, utc("2024-10-27T23:00:00Z"));
+        assert_eq!(lengths(&buckets), [24 * HOUR, 25 * HOUR, 24 * HOUR]);
+    }
+
+    #[test]
+    fn days_start_after_a_skipped_midnight() {
+        // Santiago skips from 00:00 to 01:00 on 2024-09-08.
+        let days = CalendarInterval::days(1).timezone(Tz::America__Santiago);
+        let buckets = days.buckets(utc("2024-09-07T12:00:00Z"), utc("2024-09-09T12:00:00Z"));
+        assert_eq!(buckets[0].0, utc("2024-09-07T04:00:00Z"));
+        assert_eq!(buckets[1].0, utc("2024-09-08T04:00:00Z"));
+        assert_eq!(buckets[2].0, utc("2024-09-09T03:00:00Z"));
+        assert_eq!(lengths(&buckets), [24 * HOUR, 23 * HOUR, 24 * HOUR]);
+    }
+
+    #[test]
+    fn weeks_and_months_span_dst_changes() {
+        let berlin = Tz::Europe__Berlin;
+        let weeks = CalendarInterval::weeks(1).timezone(berlin);
+        let buckets = weeks.buckets(utc("2024-10-23T00:00:00Z"), utc("2024-10-24T00:00:00Z"));
+        assert_eq!(
+            buckets,
+            [(utc("2024-10-20T22:00:00Z"), utc("2024-10-27T23:00:00Z"))]
+        );
+
+        let sundays = weeks.week_start(Weekday::Sun);
+        let buckets = sundays.buckets(utc("2024-10-23T00:00:00Z"), utc("2024-10-24T00:00:00Z"));
+        assert_eq!(buckets[0].0, utc("2024-10-19T22:00:00Z"));
+        assert_eq!(lengths(&buckets), [7 * 24 * HOUR]);
+
+        let months = CalendarInterval::months(1).timezone(berlin);
+        let buckets = months.buckets(utc("2024-10-15T00:00:00Z"), utc("2024-10-16T00:00:00Z"));
+        assert_eq!(
+            buckets,
+            [(utc("2024-09-30T22:00:00Z"), utc("2024-10-31T23:00:00Z"))]
+        );
+        assert_eq!(lengths(&buckets), [(31 * 24 + 1) * HOUR]);
+    }
+
+    #[test]
+    fn runs_break_at_each_change_of_length() {
+        let days = CalendarInterval::days(1).timezone(Tz::Europe__Berlin);
+        let runs = days.runs(utc("2024-03-01T00:00:00Z"), utc("2024-04-02T00:00:00Z"));
+        assert_eq!(
+            runs,
+            [
+                (
+                    utc("2024-02-29T23:00:00Z"),
+                    utc("2024-03-30T23:00:00Z"),
+                    24 * HOUR
+                ),
+                (
+                    utc("2024-03-30T23:00:00Z"),
+                    utc("2024-03-31T22:00:00Z"),
+                    23 * HOUR
+                ),
+                (
+                    utc("2024-03-31T22:00:00Z"),
+                    utc("2024-04-02T22:00:00Z"),
+                    24 * HOUR
+                ),
+            ]
+        );
+        assert!(
+            days.buckets(utc("2024-02-29T23:00:00Z"), utc("2024-02-29T23:00:00Z"))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Server version parsing and feature capabilities.
+//!
+//! [`ViewsClient`] reads the service version when it connects. Helpers that
+//! depend on a newer server check [`Capabilities::require`] first, so an old
+//! server produces "server 21.2.0 is too old for live subscriptions" rather
+//! than an opaque `UNIMPLEMENTED`. A version that can't be parsed (or a
+//! server that won't report one) is assumed to support everything.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use tonic::Status;
+
+use crate::error::Error;
+use crate::views_client::ViewsClient;
+
+/// A Canary service version, such as `23.1.0`.
+#[derive(Debug, Clone)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The version string as the server reported it.
+    pub raw: String,
+}
+
+impl ServerVersion {
+    /// Parse the first dotted number in a version string, e.g. the `22.2.1`
+    /// of `"Canary Views 22.2.1.23155"`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let start = version.find(|c: char| c.is_ascii_digit())?;
+        let mut parts = version[start..]
+            .split(|c: char| !c.is_ascii_digit())
+            .map_while(|part| part.parse::<u32>().ok());
+        let major = parts.next()?;
+        let minor = parts.next()?;
+        let patch = parts.next().unwrap_or(0);
+        Some(Self {
+            major,
+            minor,
+            patch,
+            raw: version.to_string(),
+        })
+    }
+
+    fn triple(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl PartialEq for ServerVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.triple() == other.triple()
+    }
+}
+
+impl Eq for ServerVersion {}
+
+impl PartialOrd for ServerVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ServerVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.triple().cmp(&other.triple())
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A server feature that only exists from some version on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `SubscribeToLiveData`.
+    LiveSubscriptions,
+    /// Returning and storing annotations.
+    Annotations,
+}
+
+impl Capability {
+    /// The first release with the feature, as `(major, minor, patch)`.
+    pub fn since(self) -> (u32, u32, u32) {
+        match self {
+            Capability::LiveSubscriptions => (22, 0, 0),
+            Capability::Annotations => (23, 0, 0),
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Capability::LiveSubscriptions => "live subscriptions",
+            Capability::Annotations => "annotations",
+        }
+    }
+}
+
+/// The features a connected server supports.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    version: Option<ServerVersion>,
+}
+
+impl Capabilities {
+    pub fn new(version: Option<ServerVersion>) -> Self {
+        Self { version }
+    }
+
+    /// The parsed server version, if the server reported a recognizable one.
+    pub fn version(&self) -> Option<&ServerVersion> {
+        self.version.as_ref()
+    }
+
+    /// Whether the server supports a feature. True when the version is unknown.
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.version
+            .as_ref()
+            .is_none_or(|v| v.triple() >= capability.since())
+    }
+
+    /// Fail with `UNIMPLEMENTED` and a readable message if the server is too
+    /// old for a feature.
+    pub fn require(&self, capability: Capability) -> Result<(), Error> {
+        match &self.version {
+            Some(version) if !self.supports(capability) => {
+                let (major, minor, patch) = capability.since();
+                Err(Status::unimplemented(format!(
+                    "server {version} is too old for {} (requires {major}.{minor}.{patch} or newer)",
+                    capability.description()
+                ))
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ViewsClient {
+    /// The version of the connected server, if it reported a recognizable one.
+    pub fn server_version(&self) -> Option<&ServerVersion> {
+        self.capabilities().version()
+    }
+
+    /// Whether the connected server supports a feature.
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities().supports(capability)
+    }
+}
@@ -0,0 +1,247 @@
+//! A pool of client connection IDs for parallel fan-out.
+//!
+//! Some Canary servers throttle each client connection ID (CCI) separately.
+//! A [`CciPool`] acquires several CCIs under the same credentials and channel
+//! as an existing [`ViewsClient`] and spreads concurrent reads across them
+//! round-robin.
+//!
+//! One channel is one HTTP/2 connection, so every pooled read shares its
+//! TCP window and the server's per-connection limits.
+//! [`ViewsClientBuilder::connect_pool`] connects several times instead,
+//! each over its own channel with its own CCI, for parallel historical
+//! pulls that a single connection would serialize:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), crowsong::Error> {
+//! use crowsong::ViewsClient;
+//!
+//! let pool = ViewsClient::builder("https://historian:55321", "key")
+//!     .connect_pool(4)
+//!     .await?;
+//! let client = pool.client();
+//! # let _ = client.get_views().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The pool keeps its CCIs alive with
+//! [`spawn_keepalive`](CciPool::spawn_keepalive) and releases them together
+//! with [`release`](CciPool::release); CCIs are not released on drop.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::canary::views::grpc::api::*;
+use crate::error::Error;
+use crate::request_id::{self, RequestId};
+use crate::views_client::{ViewsClient, ViewsClientBuilder};
+
+/// Client connection IDs, on one channel or several, handed out round-robin.
+pub struct CciPool {
+    clients: Vec<ViewsClient>,
+    next: AtomicUsize,
+}
+
+impl ViewsClient {
+    /// Acquire `size` additional client connection IDs over this client's
+    /// channel, at least 1, so a `size` of 0 acquires one. This client's
+    /// own CCI is not part of the pool.
+    pub async fn cci_pool(&self, size: usize) -> Result<CciPool, Error> {
+        let mut clients = Vec::with_capacity(size.max(1));
+        for _ in 0..size.max(1) {
+            match self.with_new_cci().await {
+                Ok(client) => clients.push(client),
+                Err(status) => {
+                    let _ = release_all(clients).await;
+                    return Err(status.into());
+                }
+            }
+        }
+        Ok(CciPool {
+            clients,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl ViewsClientBuilder {
+    /// Connect `channels` times, each over its own channel with its own
+    /// client connection ID, and pool the connections. At least 1, so
+    /// `channels` of 0 connects once. Every connection gets the builder's
+    /// options.
+    pub async fn connect_pool(self, channels: usize) -> Result<CciPool, Error> {
+        let mut clients = Vec::with_capacity(channels.max(1));
+        for _ in 0..channels.max(1) {
+            match self.clone().connect().await {
+                Ok(client) => clients.push(client),
+                Err(error) => {
+                    let _ = release_all(clients).await;
+                    return Err(error);
+                }
+            }
+        }
+        Ok(CciPool {
+            clients,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl CciPool {
+    /// The number of CCIs in the pool.
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// The pooled client connection IDs.
+    pub fn ccis(&self) -> Vec<i32> {
+        self.clients.iter().map(ViewsClient::cci).collect()
+    }
+
+    /// A handle on the next CCI in round-robin order.
+    pub fn client(&self) -> ViewsClient {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        self.clients[i].handle()
+    }
+
+    /// Send a keepalive for every CCI in the pool.
+    pub async fn keepalive(&self) -> Result<(), Error> {
+        let mut tasks = JoinSet::new();
+        for client in &self.clients {
+            let client = client.handle();
+            tasks.spawn(async move { client.keepalive().await });
+        }
+        let mut result = Ok(());
+        while let Some(joined) = tasks.join_next().await {
+            let outcome = joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+
+    /// Send keepalives for every CCI in the pool every `period` until the
+    /// returned task is dropped. Stop it before releasing the pool.
+    pub fn spawn_keepalive(&self, period: Duration) -> KeepaliveTask {
+        let clients: Vec<ViewsClient> = self.clients.iter().map(ViewsClient::handle).collect();
+        let handle = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                for client in &clients {
+                    let _ = client.keepalive().await;
+                }
+            }
+        });
+        KeepaliveTask { handle }
+    }
+
+    /// Release every CCI in the pool, reporting the first failure.
+    pub async fn release(self) -> Result<(), Error> {
+        release_all(self.clients).await
+    }
+
+    /// Run `read` once per item, concurrently, spreading the items across the
+    /// pool's CCIs. Results are returned in item order.
+    pub async fn fan_out<I, T, F, Fut>(
+        &self,
+        items: impl IntoIterator<Item = I>,
+        read: F,
+    ) -> Vec<Result<T, Error>>
+    where
+        F: Fn(ViewsClient, I) -> Fut,
+        Fut: Future<Output = Result<T, Error>> + Send + 'static,
+        T: Send + 'static,
+    {
+        // The reads are one operation, sent under one request ID.
+        let id = RequestId::current().unwrap_or_default();
+        let mut tasks = JoinSet::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let call = read(self.client(), item);
+            tasks.spawn(request_id::scope(id, async move { (i, call.await) }));
+        }
+
+        let mut results: Vec<Option<Result<T, Error>>> = Vec::new();
+        results.resize_with(tasks.len(), || None);
+        while let Some(joined) = tasks.join_next().await {
+            // Tasks are never aborted, so a join error is always a panic.
+            let (i, result) = joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            results[i] = Some(result);
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    /// Get raw data, splitting the tag requests into batches of
+    /// `tags_per_request` that are fetched concurrently across the pool.
+    pub async fn get_raw_data(
+        &self,
+        request: GetRawDataRequest,
+        tags_per_request: usize,
+    ) -> Result<GetRawDataResponse, Error> {
+        let batches: Vec<Vec<RawTagRequest>> = request
+            .requests
+            .chunks(tags_per_request.max(1))
+            .map(<[_]>::to_vec)
+            .collect();
+        let template = GetRawDataRequest {
+            requests: Vec::new(),
+            ..request
+        };
+        let responses = self
+            .fan_out(batches, |client, requests| {
+                let request = GetRawDataRequest {
+                    requests,
+                    ..template.clone()
+                };
+                async move { client.get_raw_data(request).await }
+            })
+            .await;
+
+        let mut merged = GetRawDataResponse::default();
+        for response in responses {
+            let response = response?;
+            if merged.status.is_none() {
+                merged.status = response.status;
+                merged.extended_status = response.extended_status;
+            }
+            merged.raw_data.extend(response.raw_data);
+        }
+        Ok(merged)
+    }
+}
+
+async fn release_all(clients: Vec<ViewsClient>) -> Result<(), Error> {
+    let mut result = Ok(());
+    for client in clients {
+        let outcome = client.disconnect().await;
+        if result.is_ok() {
+            result = outcome;
+        }
+    }
+    result
+}
+
+/// A background keepalive loop for a [`CciPool`], stopped when dropped.
+pub struct KeepaliveTask {
+    handle: JoinHandle<()>,
+}
+
+impl KeepaliveTask {
+    /// Stop sending keepalives.
+    pub fn stop(self) {}
+}
+
+impl Drop for KeepaliveTask {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
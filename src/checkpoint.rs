@@ -0,0 +1,73 @@
+//! Per-tag progress through a long copy or export, kept in a file so an
+//! interrupted run resumes where it stopped.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::timestamps::{nanos_to_system_time, system_time_to_nanos};
+
+/// Per-tag high-water marks, persisted to a file.
+///
+/// Each line holds a tag's mark in nanoseconds since the Unix epoch and the
+/// tag name, separated by a tab.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    path: PathBuf,
+    marks: BTreeMap<String, i64>,
+}
+
+impl Checkpoint {
+    /// Load a checkpoint file, or start an empty one if it doesn't exist.
+    pub fn load(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut marks = BTreeMap::new();
+        for line in contents.lines().filter(|l| !l.is_empty()) {
+            let parsed = line
+                .split_once('\t')
+                .and_then(|(mark, tag)| Some((tag.to_string(), mark.parse().ok()?)));
+            let Some((tag, mark)) = parsed else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bad checkpoint line in {}: {line:?}", path.display()),
+                ));
+            };
+            marks.insert(tag, mark);
+        }
+        Ok(Self { path, marks })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How far a tag has been completed.
+    pub fn get(&self, tag: &str) -> Option<SystemTime> {
+        self.marks.get(tag).copied().map(nanos_to_system_time)
+    }
+
+    /// Record that a tag is complete up to `through`. Call
+    /// [`save`](Self::save) to persist it.
+    pub fn set(&mut self, tag: impl Into<String>, through: SystemTime) {
+        self.marks.insert(tag.into(), system_time_to_nanos(through));
+    }
+
+    /// Write the checkpoint file.
+    pub fn save(&self) -> io::Result<()> {
+        let mut contents = String::new();
+        for (tag, mark) in &self.marks {
+            contents.push_str(&format!("{mark}\t{tag}\n"));
+        }
+        // Write beside the target and rename, so a crash never leaves a torn file.
+        let partial = self.path.with_extension("partial");
+        fs::write(&partial, contents)?;
+        fs::rename(partial, &self.path)
+    }
+}
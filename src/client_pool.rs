@@ -0,0 +1,230 @@
+//! Connections to many historians, keyed by site.
+//!
+//! A service that reads from several Canary servers registers each one with
+//! a [`ClientPool`] under a site name. The pool connects a site the first
+//! time a client is asked for, hands out handles on that connection, and
+//! replaces connections that stop answering:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), crowsong::Error> {
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use crowsong::{ClientPool, ViewsClient};
+//!
+//! let pool = Arc::new(
+//!     ClientPool::new()
+//!         .site("north", ViewsClient::builder("https://north:55321", "key-n"))
+//!         .site("south", ViewsClient::builder("https://south:55321", "key-s")),
+//! );
+//! let _checks = pool.spawn_health_checks(Duration::from_secs(60));
+//!
+//! let north = pool.client("north").await?;
+//! println!("{:?}", north.get_views().await?.views);
+//!
+//! pool.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::error::Error;
+use crate::views_client::{ViewsClient, ViewsClientBuilder};
+
+/// Lazily connected clients for a fixed set of sites.
+#[derive(Default)]
+pub struct ClientPool {
+    sites: BTreeMap<String, Arc<Site>>,
+    closed: AtomicBool,
+}
+
+struct Site {
+    builder: ViewsClientBuilder,
+    /// The site's connection, once made. Held across connecting, so
+    /// concurrent callers wait for one connection rather than racing.
+    client: Mutex<Option<ViewsClient>>,
+}
+
+/// The outcome of a health check for one site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SiteHealth {
+    /// Not connected yet; nothing to check.
+    Idle,
+    /// The connection answered.
+    Healthy,
+    /// The connection failed and was replaced with a new one.
+    Reconnected { error: String },
+    /// The connection failed, and so did reconnecting, with `error`. The
+    /// next [`ClientPool::client`] call for the site tries again.
+    Unavailable { error: String },
+}
+
+impl ClientPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a site, connecting with `builder` when first used.
+    /// Registering a name again replaces the earlier site.
+    pub fn site(mut self, name: impl Into<String>, builder: ViewsClientBuilder) -> Self {
+        self.sites.insert(
+            name.into(),
+            Arc::new(Site {
+                builder,
+                client: Mutex::new(None),
+            }),
+        );
+        self
+    }
+
+    /// The registered site names, sorted.
+    pub fn sites(&self) -> impl Iterator<Item = &str> {
+        self.sites.keys().map(String::as_str)
+    }
+
+    /// A handle on `site`'s connection, connecting first if needed.
+    /// [`Error::Disconnected`] once the pool is shut down.
+    pub async fn client(&self, site: &str) -> Result<ViewsClient, Error> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(Error::Disconnected);
+        }
+        let entry = self
+            .sites
+            .get(site)
+            .ok_or_else(|| Error::Other(format!("unknown site {site}").into()))?;
+        let mut client = entry.client.lock().await;
+        if let Some(connected) = client.as_ref() {
+            return Ok(connected.handle());
+        }
+        let connected = entry.builder.clone().connect().await?;
+        Ok(client.insert(connected).handle())
+    }
+
+    /// Drop `site`'s connection, so the next [`client`](Self::client) call
+    /// reconnects. Use it after a call fails in a way that suggests the
+    /// connection is gone. Handles already given out keep the old one.
+    pub async fn invalidate(&self, site: &str) {
+        if let Some(entry) = self.sites.get(site) {
+            entry.client.lock().await.take();
+        }
+    }
+
+    /// Send a keepalive on every connected site's connection, concurrently,
+    /// and reconnect those that fail.
+    pub async fn health_check(&self) -> BTreeMap<String, SiteHealth> {
+        let mut tasks = JoinSet::new();
+        for (name, site) in &self.sites {
+            let (name, site) = (name.clone(), site.clone());
+            tasks.spawn(async move { (name, site.check().await) });
+        }
+        let mut report = BTreeMap::new();
+        while let Some(joined) = tasks.join_next().await {
+            // Tasks are never aborted, so a join error is always a panic.
+            let (name, health) =
+                joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            report.insert(name, health);
+        }
+        report
+    }
+
+    /// Check every site's health every `period` until the returned task is
+    /// dropped.
+    pub fn spawn_health_checks(self: &Arc<Self>, period: Duration) -> HealthCheckTask {
+        let pool = Arc::clone(self);
+        let handle = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            ticks.tick().await;
+            while !pool.closed.load(Ordering::Acquire) {
+                ticks.tick().await;
+                pool.health_check().await;
+            }
+        });
+        HealthCheckTask { handle }
+    }
+
+    /// Release every site's connection, reporting the first failure. Later
+    /// [`client`](Self::client) calls fail.
+    pub async fn shutdown(&self) -> Result<(), Error> {
+        self.closed.store(true, Ordering::Release);
+        let mut tasks = JoinSet::new();
+        for site in self.sites.values() {
+            let site = site.clone();
+            tasks.spawn(async move {
+                match site.client.lock().await.take() {
+                    Some(client) => client.disconnect().await,
+                    None => Ok(()),
+                }
+            });
+        }
+        let mut result = Ok(());
+        while let Some(joined) = tasks.join_next().await {
+            let outcome = joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+}
+
+impl Site {
+    async fn check(&self) -> SiteHealth {
+        let mut client = self.client.lock().await;
+        let Some(connected) = client.as_ref() else {
+            return SiteHealth::Idle;
+        };
+        let error = match connected.keepalive().await {
+            Ok(()) => return SiteHealth::Healthy,
+            Err(error) => error.to_string(),
+        };
+        // Best effort: the old client connection ID may already be gone.
+        let _ = connected.disconnect().await;
+        *client = None;
+        let reconnected = self
+            .builder
+            .clone()
+            .connect()
+            .await
+            .map_err(|e| e.to_string());
+        match reconnected {
+            Ok(new) => {
+                *client = Some(new);
+                SiteHealth::Reconnected { error }
+            }
+            Err(error) => SiteHealth::Unavailable { error },
+        }
+    }
+}
+
+impl fmt::Debug for ClientPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientPool")
+            .field("sites", &self.sites.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A background health-check loop for a [`ClientPool`], stopped when
+/// dropped or when the pool shuts down.
+pub struct HealthCheckTask {
+    handle: JoinHandle<()>,
+}
+
+impl HealthCheckTask {
+    /// Stop checking.
+    pub fn stop(self) {}
+}
+
+impl Drop for HealthCheckTask {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
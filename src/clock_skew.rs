@@ -0,0 +1,150 @@
+//! Detecting a skewed clock between the client and the historian.
+//!
+//! Relative times such as `Now-1h` are resolved on the server, while
+//! staleness checks compare sample timestamps with the local clock, so a
+//! skewed clock on either side quietly shifts query windows and makes fresh
+//! data look stale (or stale data fresh). [`ViewsClient::clock_skew`] asks
+//! the server what time it is and compares the answer with the local clock
+//! at the midpoint of the round trip.
+//!
+//! [`ViewsClientBuilder::clock_skew_check`] measures the skew once at
+//! connect and, by default, warns on stderr when it is 5 seconds or more:
+//!
+//! ```text
+//! crowsong: server clock is 42.318s ahead of the local clock (round trip 0.012s)
+//! ```
+//!
+//! [`ViewsClientBuilder::clock_skew_check`]: crate::views_client::ViewsClientBuilder::clock_skew_check
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use tonic::Status;
+
+use crate::canary::views::grpc::api::ParseTimestampRequest;
+use crate::canary::views::grpc::api::parse_timestamp_response::Status as ParseStatus;
+use crate::error::Error;
+use crate::request_id;
+use crate::timestamps;
+use crate::views_client::ViewsClient;
+
+/// The skew at or above which [`SkewCheck::default`] warns.
+pub const DEFAULT_SKEW_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// One measurement of the server's clock against the local clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSkew {
+    /// The time the server reported.
+    pub server_time: SystemTime,
+    /// The local time halfway through the round trip.
+    pub local_time: SystemTime,
+    /// How long the call took; the measurement is good to about half of it.
+    pub round_trip: Duration,
+}
+
+impl ClockSkew {
+    /// How far apart the clocks are, in either direction.
+    pub fn skew(&self) -> Duration {
+        match self.server_time.duration_since(self.local_time) {
+            Ok(ahead) => ahead,
+            Err(behind) => behind.duration(),
+        }
+    }
+
+    /// Whether the server's clock is ahead of the local one.
+    pub fn server_ahead(&self) -> bool {
+        self.server_time > self.local_time
+    }
+}
+
+impl fmt::Display for ClockSkew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "server clock is {:.3}s {} the local clock (round trip {:.3}s)",
+            self.skew().as_secs_f64(),
+            if self.server_ahead() {
+                "ahead of"
+            } else {
+                "behind"
+            },
+            self.round_trip.as_secs_f64()
+        )
+    }
+}
+
+/// How much skew to tolerate at connect, and where to report more.
+#[derive(Clone)]
+pub struct SkewCheck {
+    threshold: Duration,
+    handler: Arc<dyn Fn(&ClockSkew) + Send + Sync>,
+}
+
+impl SkewCheck {
+    /// Warn on stderr when the skew is at least `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            handler: Arc::new(|skew| eprintln!("crowsong: {skew}")),
+        }
+    }
+
+    /// Pass excessive skew to `handler` instead, e.g. to log it elsewhere or
+    /// refuse to run.
+    pub fn handler(mut self, handler: impl Fn(&ClockSkew) + Send + Sync + 'static) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Report `skew` if it is at least the threshold.
+    pub(crate) fn check(&self, skew: &ClockSkew) {
+        if skew.skew() >= self.threshold {
+            (self.handler)(skew);
+        }
+    }
+}
+
+impl Default for SkewCheck {
+    fn default() -> Self {
+        Self::new(DEFAULT_SKEW_THRESHOLD)
+    }
+}
+
+impl fmt::Debug for SkewCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SkewCheck")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ViewsClient {
+    /// Measure the server's clock against the local clock by having the
+    /// server resolve `Now`.
+    pub async fn clock_skew(&self) -> Result<ClockSkew, Error> {
+        let mut inner = self.inner();
+        let sent = SystemTime::now();
+        let started = Instant::now();
+        let resp = request_id::call(inner.parse_timestamp(ParseTimestampRequest {
+            timestamp: "Now".to_string(),
+            time_zone_id: None,
+        }))
+        .await?
+        .into_inner();
+        let round_trip = started.elapsed();
+        let server_time = match (resp.extended_status(), &resp.timestamp) {
+            (ParseStatus::Unspecified, Some(ts)) => timestamps::from_proto(ts),
+            _ => return Err(Status::unimplemented("the server did not report its time").into()),
+        };
+        Ok(ClockSkew {
+            server_time,
+            local_time: sent + round_trip / 2,
+            round_trip,
+        })
+    }
+}
@@ -0,0 +1,346 @@
+//! Compact, column-oriented decoding of raw data responses.
+//!
+//! [`RawDataColumns`] decodes a `GetRawDataResponse` straight off the wire
+//! into per-tag timestamp, value, and quality vectors, without materializing a
+//! `GrpcTvq` (and its boxed `Variant`) for every sample. A numeric sample
+//! costs 21 bytes instead of the ~64 of the generated message, which matters
+//! for bulk extracts of months of high-rate data.
+
+use std::ops::Range;
+use std::time::SystemTime;
+
+use prost::bytes::{Buf, BufMut};
+use prost::encoding::{self, DecodeContext, WireType};
+use prost::{DecodeError, Message};
+
+use crate::canary::utility::protobuf_shared_types::variant::Kind;
+use crate::canary::utility::protobuf_shared_types::{GrpcTvq, Variant};
+use crate::canary::views::grpc::api::{Annotation, GetRawDataResponse, RawTagData};
+use crate::canary::views::grpc::common::ApiCallStatus;
+use crate::quality::Quality;
+use crate::types::{TagSeries, Tvq, Value};
+
+pub use crate::timestamps::{nanos_to_system_time, system_time_to_nanos};
+
+/// The type a sample had on the wire, kept so values can be rebuilt exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u8)]
+pub enum ValueKind {
+    #[default]
+    Null,
+    Bool,
+    Int,
+    UInt,
+    Float,
+    String,
+    Decimal,
+}
+
+impl ValueKind {
+    /// Whether samples of this kind carry a meaningful float in the value column.
+    pub fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            ValueKind::Bool | ValueKind::Int | ValueKind::UInt | ValueKind::Float
+        )
+    }
+}
+
+/// The raw samples of one tag, stored column-wise.
+///
+/// All sample vectors have the same length. Numeric and boolean values live in
+/// `values` (booleans as `0.0`/`1.0`); rows that are null, strings, or decimals
+/// hold `NaN` there, with strings and decimals kept in the sparse
+/// `non_numeric` list. 64-bit integers beyond 2^53 lose precision.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RawColumns {
+    pub tag_name: String,
+    pub client_data: i32,
+    pub result_flags: i32,
+    pub error_message: String,
+    pub error_code: i32,
+    /// Sample timestamps, in nanoseconds since the Unix epoch.
+    pub timestamps: Vec<i64>,
+    pub values: Vec<f64>,
+    pub kinds: Vec<ValueKind>,
+    pub qualities: Vec<u32>,
+    /// String and decimal samples, as `(row, value)` in row order.
+    pub non_numeric: Vec<(usize, Value)>,
+    pub annotations: Vec<Annotation>,
+    pub continuation_point: Vec<u8>,
+}
+
+impl RawColumns {
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// The approximate heap memory held by the samples.
+    pub fn heap_size(&self) -> usize {
+        let non_numeric: usize = self
+            .non_numeric
+            .iter()
+            .map(|(_, v)| match v {
+                Value::String(s) => s.capacity(),
+                Value::Decimal(b) => b.capacity(),
+                _ => 0,
+            })
+            .sum();
+        self.timestamps.capacity() * size_of::<i64>()
+            + self.values.capacity() * size_of::<f64>()
+            + self.kinds.capacity() * size_of::<ValueKind>()
+            + self.qualities.capacity() * size_of::<u32>()
+            + self.non_numeric.capacity() * size_of::<(usize, Value)>()
+            + non_numeric
+    }
+
+    /// The timestamp of a sample.
+    pub fn timestamp(&self, row: usize) -> SystemTime {
+        nanos_to_system_time(self.timestamps[row])
+    }
+
+    /// Rebuild the value of a sample.
+    pub fn value(&self, row: usize) -> Value {
+        let v = self.values[row];
+        match self.kinds[row] {
+            ValueKind::Null => Value::Null,
+            ValueKind::Bool => Value::Bool(v != 0.0),
+            ValueKind::Int => Value::Int(v as i64),
+            ValueKind::UInt => Value::UInt(v as u64),
+            ValueKind::Float => Value::Float(v),
+            ValueKind::String | ValueKind::Decimal => self
+                .non_numeric
+                .binary_search_by_key(&row, |(r, _)| *r)
+                .map(|i| self.non_numeric[i].1.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The quality of a sample.
+    pub fn quality(&self, row: usize) -> Quality {
+        Quality(self.qualities[row])
+    }
+
+    /// Rebuild a single sample.
+    pub fn tvq(&self, row: usize) -> Tvq {
+        Tvq {
+            timestamp: self.timestamp(row),
+            value: self.value(row),
+            quality: self.quality(row),
+        }
+    }
+
+    /// Append copies of another tag's samples in `rows`.
+    pub fn extend_from_rows(&mut self, other: &RawColumns, rows: Range<usize>) {
+        let offset = self.len();
+        let first = other.non_numeric.partition_point(|(r, _)| *r < rows.start);
+        let last = other.non_numeric.partition_point(|(r, _)| *r < rows.end);
+        self.non_numeric.extend(
+            other.non_numeric[first..last]
+                .iter()
+                .map(|(r, v)| (r - rows.start + offset, v.clone())),
+        );
+        self.timestamps
+            .extend_from_slice(&other.timestamps[rows.clone()]);
+        self.values.extend_from_slice(&other.values[rows.clone()]);
+        self.kinds.extend_from_slice(&other.kinds[rows.clone()]);
+        self.qualities.extend_from_slice(&other.qualities[rows]);
+    }
+
+    fn push(&mut self, timestamp: i64, value: Option<Variant>, quality: u32) {
+        let row = self.timestamps.len();
+        let (kind, v) = match value.and_then(|v| v.kind) {
+            None => (ValueKind::Null, f64::NAN),
+            Some(Kind::Bool(b)) => (ValueKind::Bool, if b { 1.0 } else { 0.0 }),
+            Some(Kind::Int8(i)) | Some(Kind::Int16(i)) | Some(Kind::Int32(i)) => {
+                (ValueKind::Int, i as f64)
+            }
+            Some(Kind::Int64(i)) => (ValueKind::Int, i as f64),
+            Some(Kind::UInt8(u)) | Some(Kind::UInt16(u)) | Some(Kind::UInt32(u)) => {
+                (ValueKind::UInt, u as f64)
+            }
+            Some(Kind::UInt64(u)) => (ValueKind::UInt, u as f64),
+            Some(Kind::Float(f)) => (ValueKind::Float, f as f64),
+            Some(Kind::Double(d)) => (ValueKind::Float, d),
+            Some(Kind::String(s)) => {
+                self.non_numeric.push((row, Value::String(s)));
+                (ValueKind::String, f64::NAN)
+            }
+            Some(Kind::Decimal(b)) => {
+                self.non_numeric.push((row, Value::Decimal(b)));
+                (ValueKind::Decimal, f64::NAN)
+            }
+        };
+        self.timestamps.push(timestamp);
+        self.values.push(v);
+        self.kinds.push(kind);
+        self.qualities.push(quality);
+    }
+
+    /// Decode one length-delimited `GrpcTvq` directly into the columns.
+    fn merge_tvq(
+        &mut self,
+        wire_type: WireType,
+        buf: &mut impl Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        encoding::check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let mut sample = (None::<prost_types::Timestamp>, None::<Variant>, 0u32);
+        encoding::merge_loop(&mut sample, buf, ctx, |sample, buf, ctx| {
+            let (tag, wire_type) = encoding::decode_key(buf)?;
+            match tag {
+                1 => encoding::message::merge(
+                    wire_type,
+                    sample.0.get_or_insert_with(Default::default),
+                    buf,
+                    ctx,
+                ),
+                2 => encoding::message::merge(
+                    wire_type,
+                    sample.1.get_or_insert_with(Default::default),
+                    buf,
+                    ctx,
+                ),
+                3 => encoding::uint32::merge(wire_type, &mut sample.2, buf, ctx),
+                _ => encoding::skip_field(wire_type, tag, buf, ctx),
+            }
+        })?;
+        let (timestamp, value, quality) = sample;
+        let nanos = timestamp
+            .map(|ts| {
+                ts.seconds
+                    .saturating_mul(1_000_000_000)
+                    .saturating_add(ts.nanos as i64)
+            })
+            .unwrap_or(0);
+        self.push(nanos, value, quality);
+        Ok(())
+    }
+
+    /// Convert back into the generated protobuf message.
+    pub fn to_raw_tag_data(&self) -> RawTagData {
+        RawTagData {
+            tag_name: self.tag_name.clone(),
+            client_data: self.client_data,
+            result_flags: self.result_flags,
+            error_message: self.error_message.clone(),
+            error_code: self.error_code,
+            tvqs: (0..self.len())
+                .map(|row| GrpcTvq::from(&self.tvq(row)))
+                .collect(),
+            annotations: self.annotations.clone(),
+            continuation_point: self.continuation_point.clone(),
+        }
+    }
+}
+
+impl From<&RawColumns> for TagSeries {
+    fn from(columns: &RawColumns) -> Self {
+        Self {
+            tag_name: columns.tag_name.clone(),
+            eng_units: None,
+            tvqs: (0..columns.len()).map(|row| columns.tvq(row)).collect(),
+        }
+    }
+}
+
+impl Message for RawColumns {
+    fn encode_raw(&self, buf: &mut impl BufMut) {
+        self.to_raw_tag_data().encode_raw(buf);
+    }
+
+    fn merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut impl Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        match tag {
+            1 => encoding::string::merge(wire_type, &mut self.tag_name, buf, ctx),
+            2 => encoding::int32::merge(wire_type, &mut self.client_data, buf, ctx),
+            3 => encoding::int32::merge(wire_type, &mut self.result_flags, buf, ctx),
+            4 => encoding::string::merge(wire_type, &mut self.error_message, buf, ctx),
+            5 => encoding::int32::merge(wire_type, &mut self.error_code, buf, ctx),
+            6 => self.merge_tvq(wire_type, buf, ctx),
+            7 => encoding::message::merge_repeated(wire_type, &mut self.annotations, buf, ctx),
+            8 => encoding::bytes::merge(wire_type, &mut self.continuation_point, buf, ctx),
+            _ => encoding::skip_field(wire_type, tag, buf, ctx),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.to_raw_tag_data().encoded_len()
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// A `GetRawDataResponse` decoded into [`RawColumns`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RawDataColumns {
+    pub status: Option<ApiCallStatus>,
+    pub extended_status: i32,
+    pub raw_data: Vec<RawColumns>,
+}
+
+impl RawDataColumns {
+    /// Convert back into the generated protobuf message.
+    pub fn to_response(&self) -> GetRawDataResponse {
+        GetRawDataResponse {
+            status: self.status.clone(),
+            extended_status: self.extended_status,
+            raw_data: self
+                .raw_data
+                .iter()
+                .map(RawColumns::to_raw_tag_data)
+                .collect(),
+        }
+    }
+
+    /// The total number of samples across all tags.
+    pub fn sample_count(&self) -> usize {
+        self.raw_data.iter().map(RawColumns::len).sum()
+    }
+}
+
+impl Message for RawDataColumns {
+    fn encode_raw(&self, buf: &mut impl BufMut) {
+        self.to_response().encode_raw(buf);
+    }
+
+    fn merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut impl Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        match tag {
+            1 => encoding::message::merge(
+                wire_type,
+                self.status.get_or_insert_with(Default::default),
+                buf,
+                ctx,
+            ),
+            2 => encoding::int32::merge(wire_type, &mut self.extended_status, buf, ctx),
+            4 => encoding::message::merge_repeated(wire_type, &mut self.raw_data, buf, ctx),
+            _ => encoding::skip_field(wire_type, tag, buf, ctx),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.to_response().encoded_len()
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,302 @@
+//! Gap detection and data-completeness reporting.
+//!
+//! A [`GapScan`] checks tags against an expected sample interval over a time
+//! range and produces a [`CompletenessReport`] per tag: the gaps where
+//! samples are missing, the share of the range that is covered, and the
+//! largest missing windows.
+//!
+//! By default the scan reads raw samples and reports a gap wherever two
+//! consecutive samples (or a range boundary and the nearest sample) are more
+//! than the interval plus a tolerance apart. For long ranges,
+//! [`GapScan::count_buckets`] instead asks the server for `Count` aggregates
+//! and reports buckets without samples, which transfers far less data at a
+//! coarser resolution.
+
+use std::time::{Duration, SystemTime};
+
+use crate::bulk::BulkFetch;
+use crate::canary::views::grpc::api::{AggregateTagRequest, GetAggregateDataRequest};
+use crate::error::Error;
+use crate::quality::QualityFilter;
+use crate::types::{Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// A window in which samples were expected but none arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+impl Gap {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+/// The completeness of one tag over a time range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletenessReport {
+    pub tag_name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    /// The number of samples the expected interval implies.
+    pub expected_samples: u64,
+    /// The number of samples found (that passed the quality filter).
+    pub samples: u64,
+    /// The missing windows, in time order.
+    pub gaps: Vec<Gap>,
+}
+
+impl CompletenessReport {
+    /// Build a report from a tag's sample times, which must be sorted.
+    pub fn from_timestamps(
+        tag_name: impl Into<String>,
+        timestamps: &[SystemTime],
+        start: SystemTime,
+        end: SystemTime,
+        interval: Duration,
+        tolerance: Duration,
+    ) -> Self {
+        let mut gaps = Vec::new();
+        // The range start behaves like a sample one interval before it, so a
+        // series that begins on time has no leading gap.
+        let mut expected = start;
+        let mut samples = 0;
+        for &time in timestamps.iter().filter(|t| **t >= start && **t < end) {
+            samples += 1;
+            let late = time.duration_since(expected).unwrap_or_default();
+            if late > tolerance {
+                gaps.push(Gap {
+                    start: expected,
+                    end: time,
+                });
+            }
+            expected = time + interval;
+        }
+        if end.duration_since(expected).unwrap_or_default() > tolerance {
+            gaps.push(Gap {
+                start: expected,
+                end,
+            });
+        }
+
+        Self {
+            tag_name: tag_name.into(),
+            start,
+            end,
+            expected_samples: expected_samples(start, end, interval),
+            samples,
+            gaps,
+        }
+    }
+
+    /// The total time covered by gaps.
+    pub fn missing(&self) -> Duration {
+        self.gaps.iter().map(Gap::duration).sum()
+    }
+
+    /// The share of the range not covered by gaps, from 0 to 100.
+    pub fn coverage_percent(&self) -> f64 {
+        let range = self.end.duration_since(self.start).unwrap_or_default();
+        if range.is_zero() {
+            return 100.0;
+        }
+        let missing = self.missing().min(range);
+        100.0 * (1.0 - missing.as_secs_f64() / range.as_secs_f64())
+    }
+
+    /// The `n` longest gaps, longest first.
+    pub fn largest_gaps(&self, n: usize) -> Vec<Gap> {
+        let mut gaps = self.gaps.clone();
+        gaps.sort_by_key(|g| std::cmp::Reverse(g.duration()));
+        gaps.truncate(n);
+        gaps
+    }
+}
+
+fn expected_samples(start: SystemTime, end: SystemTime, interval: Duration) -> u64 {
+    let range = end.duration_since(start).unwrap_or_default();
+    (range.as_nanos() / interval.as_nanos().max(1)) as u64
+}
+
+/// A planned completeness check of tags against an expected sample interval.
+#[derive(Debug, Clone)]
+pub struct GapScan {
+    view: String,
+    tags: Vec<String>,
+    start: SystemTime,
+    end: SystemTime,
+    interval: Duration,
+    tolerance: Duration,
+    quality: QualityFilter,
+    count_bucket: Option<Duration>,
+}
+
+impl GapScan {
+    /// Check `tags` for samples at least every `interval` over `start..end`.
+    pub fn new(
+        view: impl Into<String>,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+        interval: Duration,
+    ) -> Self {
+        let interval = interval.max(Duration::from_nanos(1));
+        Self {
+            view: view.into(),
+            tags: tags.into_iter().map(Into::into).collect(),
+            start,
+            end,
+            interval,
+            tolerance: interval / 2,
+            quality: QualityFilter::Any,
+            count_bucket: None,
+        }
+    }
+
+    /// How late a sample may be before the wait counts as a gap. Defaults to
+    /// half the interval.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Treat samples that fail the filter as missing. Ignored with
+    /// [`count_buckets`](Self::count_buckets), since counts include every
+    /// sample.
+    pub fn quality(mut self, quality: QualityFilter) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Use `Count` aggregates over buckets of this width instead of raw
+    /// samples. A bucket without samples is a gap.
+    pub fn count_buckets(mut self, bucket: Duration) -> Self {
+        self.count_bucket = Some(bucket.max(Duration::from_secs(1)));
+        self
+    }
+
+    /// Run the scan and report on each tag, in order.
+    pub async fn run(&self, client: &ViewsClient) -> Result<Vec<CompletenessReport>, Error> {
+        match self.count_bucket {
+            Some(bucket) => self.run_counts(client, bucket).await,
+            None => self.run_raw(client).await,
+        }
+    }
+
+    async fn run_raw(&self, client: &ViewsClient) -> Result<Vec<CompletenessReport>, Error> {
+        let mut times: Vec<Vec<SystemTime>> = vec![Vec::new(); self.tags.len()];
+        let pages = BulkFetch::new(&self.view, &self.tags, self.start, self.end)
+            .run(client)
+            .await?;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            let Some(index) = self.tags.iter().position(|t| *t == page.tag_name) else {
+                continue;
+            };
+            times[index].extend(
+                (0..page.len())
+                    .filter(|row| self.quality.accepts(page.quality(*row)))
+                    .map(|row| page.timestamp(row)),
+            );
+        }
+
+        Ok(self
+            .tags
+            .iter()
+            .zip(times)
+            .map(|(tag, mut times)| {
+                times.sort();
+                CompletenessReport::from_timestamps(
+                    tag.as_str(),
+                    &times,
+                    self.start,
+                    self.end,
+                    self.interval,
+                    self.tolerance,
+                )
+            })
+            .collect())
+    }
+
+    async fn run_counts(
+        &self,
+        client: &ViewsClient,
+        bucket: Duration,
+    ) -> Result<Vec<CompletenessReport>, Error> {
+        let resp = client
+            .get_aggregate_data(GetAggregateDataRequest {
+                view: self.view.clone(),
+                requests: self
+                    .tags
+                    .iter()
+                    .map(|tag| AggregateTagRequest {
+                        tag_name: tag.clone(),
+                        aggregate_name: "Count".to_string(),
+                        aggregate_configuration: None,
+                        sloped: false,
+                        client_data: 0,
+                    })
+                    .collect(),
+                start_time: Some(self.start.into()),
+                end_time: Some(self.end.into()),
+                interval: Some(bucket.try_into().map_err(Error::other)?),
+                return_annotations: false,
+                cci: 0,
+            })
+            .await?;
+
+        let mut reports = Vec::with_capacity(self.tags.len());
+        for tag in &self.tags {
+            let data = resp
+                .aggregated_data
+                .iter()
+                .find(|d| d.tag_name == *tag)
+                .ok_or_else(|| format!("no counts returned for {tag}"))?;
+            if data.error_code != 0 {
+                return Err(format!(
+                    "counting {tag} failed ({}): {}",
+                    data.error_code, data.error_message
+                )
+                .into());
+            }
+
+            let mut report = CompletenessReport {
+                tag_name: tag.clone(),
+                start: self.start,
+                end: self.end,
+                expected_samples: expected_samples(self.start, self.end, self.interval),
+                samples: 0,
+                gaps: Vec::new(),
+            };
+            for tvq in data.tvqs.iter().map(Tvq::from) {
+                let count = match tvq.value {
+                    Value::Null => 0,
+                    value => value.as_f64().unwrap_or(0.0) as u64,
+                };
+                report.samples += count;
+                if count > 0 {
+                    continue;
+                }
+                let bucket_end = (tvq.timestamp + bucket).min(self.end);
+                match report.gaps.last_mut() {
+                    Some(gap) if gap.end == tvq.timestamp => gap.end = bucket_end,
+                    _ => report.gaps.push(Gap {
+                        start: tvq.timestamp,
+                        end: bucket_end,
+                    }),
+                }
+            }
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+}
@@ -0,0 +1,155 @@
+//! The state of a client's connection, for health endpoints and UIs.
+//!
+//! The channel under a [`ViewsClient`] dials lazily and redials on its own
+//! after a failure, so the connection's state shows in how calls turn out.
+//! Every call on the client, its handles, and subscriptions resubscribing
+//! through it moves the state:
+//!
+//! - a call that succeeds: [`Connected`](ConnectionState::Connected);
+//! - a call that fails because the server couldn't be reached or the
+//!   connection dropped: [`Reconnecting`](ConnectionState::Reconnecting),
+//!   since the next call dials again;
+//! - a call that times out or is turned away as overloaded:
+//!   [`Degraded`](ConnectionState::Degraded);
+//! - a call to [`ViewsClient::disconnect`]:
+//!   [`Disconnected`](ConnectionState::Disconnected).
+//!
+//! Other failures, such as an unknown tag, say nothing about the connection
+//! and leave the state as it is.
+//!
+//! ```no_run
+//! # fn example(client: &crowsong::ViewsClient) {
+//! client.on_state_change(|state| eprintln!("historian connection: {state}"));
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::sync::watch;
+use tonic::{Code, Status};
+
+use crate::error::Classify;
+use crate::views_client::ViewsClient;
+
+/// How a client's connection is doing, judged by its latest calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// The latest call got through.
+    Connected,
+    /// The server couldn't be reached; the next call dials again.
+    Reconnecting,
+    /// Calls are timing out or being turned away as overloaded.
+    Degraded,
+    /// The client released its connection.
+    Disconnected,
+}
+
+impl ConnectionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::Reconnecting => "reconnecting",
+            Self::Degraded => "degraded",
+            Self::Disconnected => "disconnected",
+        }
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+type StateHandler = Arc<dyn Fn(ConnectionState) + Send + Sync>;
+
+/// The state of one connection, shared by every handle on it.
+pub(crate) struct StateMonitor {
+    state: watch::Sender<ConnectionState>,
+    handlers: Mutex<Vec<StateHandler>>,
+}
+
+impl StateMonitor {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: watch::Sender::new(ConnectionState::Connected),
+            handlers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Move to `state`, telling the handlers if it changed.
+    pub(crate) fn set(&self, state: ConnectionState) {
+        if !self.state.send_if_modified(|current| {
+            let changed = *current != state;
+            *current = state;
+            changed
+        }) {
+            return;
+        }
+        let handlers = self
+            .handlers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for handler in handlers {
+            handler(state);
+        }
+    }
+
+    /// Record how a call turned out.
+    pub(crate) fn observe<T>(&self, result: &Result<T, Status>) {
+        match result {
+            Ok(_) => self.set(ConnectionState::Connected),
+            Err(status) => {
+                if let Some(state) = state_after(status) {
+                    self.set(state);
+                }
+            }
+        }
+    }
+}
+
+/// The state a failed call points to, if it says anything about the
+/// connection.
+fn state_after(status: &Status) -> Option<ConnectionState> {
+    match status.code() {
+        Code::DeadlineExceeded | Code::ResourceExhausted => Some(ConnectionState::Degraded),
+        Code::Unavailable => Some(ConnectionState::Reconnecting),
+        // Transport failures arrive as UNKNOWN or INTERNAL.
+        Code::Unknown | Code::Internal if status.is_retryable() && !status.is_invalid_cci() => {
+            Some(ConnectionState::Reconnecting)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a call failed because the server couldn't be reached or answer
+/// in time, rather than for anything about the call itself.
+#[cfg(feature = "cache")]
+pub(crate) fn is_unreachable(status: &Status) -> bool {
+    state_after(status).is_some()
+}
+
+impl ViewsClient {
+    /// The connection's current state. See [`connection`](crate::connection).
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.state_monitor().state.borrow()
+    }
+
+    /// A receiver that sees every later state of the connection (or at
+    /// least the latest, if it falls behind).
+    pub fn watch_connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state_monitor().state.subscribe()
+    }
+
+    /// Call `handler` with the new state each time the connection's state
+    /// changes, on the task whose call changed it. Keep it quick.
+    pub fn on_state_change(&self, handler: impl Fn(ConnectionState) + Send + Sync + 'static) {
+        self.state_monitor()
+            .handlers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(handler));
+    }
+}
@@ -0,0 +1,35 @@
+//! API keys kept in the platform credential store.
+//!
+//! Keys are stored in the macOS Keychain, the Windows Credential Manager, or
+//! the Secret Service on Linux, under the service name `crowsong` and the
+//! endpoint they belong to. `crowsong login` stores one;
+//! [`ViewsClientBuilder::api_key_from_keyring`](crate::views_client::ViewsClientBuilder::api_key_from_keyring)
+//! and the CLI read it back, so keys don't have to live in `.env` files.
+
+use keyring::Entry;
+
+/// The keyring service name crowsong stores API keys under.
+pub const KEYRING_SERVICE: &str = "crowsong";
+
+/// The API key stored for `endpoint`, or `None` if there isn't one.
+pub fn load_api_key(endpoint: &str) -> Result<Option<String>, keyring::Error> {
+    match Entry::new(KEYRING_SERVICE, endpoint)?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Store `api_key` for `endpoint`, replacing any key stored before.
+pub fn store_api_key(endpoint: &str, api_key: &str) -> Result<(), keyring::Error> {
+    Entry::new(KEYRING_SERVICE, endpoint)?.set_password(api_key)
+}
+
+/// Remove the API key stored for `endpoint`. Returns whether there was one.
+pub fn delete_api_key(endpoint: &str) -> Result<bool, keyring::Error> {
+    match Entry::new(KEYRING_SERVICE, endpoint)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
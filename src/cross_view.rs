@@ -0,0 +1,169 @@
+//! Issuing the same read against several views at once.
+//!
+//! Services that federate many views behind one endpoint can be queried with
+//! [`ViewsClient::for_each_view`] and its typed shorthands. Each view's call
+//! runs concurrently over the shared channel, and a failure in one view is
+//! reported alongside the others' results instead of aborting them.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use tokio::task::JoinSet;
+
+use crate::canary::views::grpc::api::*;
+use crate::error::Error;
+use crate::request_id::{self, RequestId};
+use crate::types::TagSeries;
+use crate::views_client::ViewsClient;
+
+/// The per-view outcomes of a cross-view read, keyed by view name.
+#[derive(Debug)]
+pub struct PerView<T> {
+    results: BTreeMap<String, Result<T, Error>>,
+}
+
+impl<T> PerView<T> {
+    /// The outcome for one view.
+    pub fn get(&self, view: &str) -> Option<&Result<T, Error>> {
+        self.results.get(view)
+    }
+
+    /// The views that succeeded, with their results.
+    pub fn ok(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.results
+            .iter()
+            .filter_map(|(view, r)| Some((view.as_str(), r.as_ref().ok()?)))
+    }
+
+    /// The views that failed, with their errors.
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &Error)> {
+        self.results
+            .iter()
+            .filter_map(|(view, r)| Some((view.as_str(), r.as_ref().err()?)))
+    }
+
+    /// Whether every view succeeded.
+    pub fn all_ok(&self) -> bool {
+        self.results.values().all(Result::is_ok)
+    }
+
+    pub fn into_inner(self) -> BTreeMap<String, Result<T, Error>> {
+        self.results
+    }
+}
+
+impl<T> IntoIterator for PerView<T> {
+    type Item = (String, Result<T, Error>);
+    type IntoIter = std::collections::btree_map::IntoIter<String, Result<T, Error>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.into_iter()
+    }
+}
+
+impl PerView<GetRawDataResponse> {
+    /// Merge the successful views' series, labelled with their view.
+    pub fn series(&self) -> Vec<(String, TagSeries)> {
+        self.ok()
+            .flat_map(|(view, resp)| {
+                resp.raw_data
+                    .iter()
+                    .map(move |data| (view.to_string(), TagSeries::from(data)))
+            })
+            .collect()
+    }
+}
+
+impl PerView<GetAggregateDataResponse> {
+    /// Merge the successful views' series, labelled with their view.
+    pub fn series(&self) -> Vec<(String, TagSeries)> {
+        self.ok()
+            .flat_map(|(view, resp)| {
+                resp.aggregated_data
+                    .iter()
+                    .map(move |data| (view.to_string(), TagSeries::from(data)))
+            })
+            .collect()
+    }
+}
+
+impl ViewsClient {
+    /// Run `read` once per view, concurrently, each with its own handle on
+    /// this client's connection.
+    pub async fn for_each_view<T, F, Fut>(
+        &self,
+        views: impl IntoIterator<Item = impl Into<String>>,
+        read: F,
+    ) -> PerView<T>
+    where
+        F: Fn(ViewsClient, String) -> Fut,
+        Fut: Future<Output = Result<T, Error>> + Send + 'static,
+        T: Send + 'static,
+    {
+        // The reads are one operation, sent under one request ID.
+        let id = RequestId::current().unwrap_or_default();
+        let mut tasks = JoinSet::new();
+        for view in views {
+            let view = view.into();
+            let call = read(self.handle(), view.clone());
+            tasks.spawn(request_id::scope(id, async move { (view, call.await) }));
+        }
+
+        let mut results = BTreeMap::new();
+        while let Some(joined) = tasks.join_next().await {
+            // Tasks are never aborted, so a join error is always a panic.
+            let (view, result) =
+                joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            results.insert(view, result);
+        }
+        PerView { results }
+    }
+
+    /// Get raw data for the same tags from each view. `request.view` is ignored.
+    pub async fn get_raw_data_across_views(
+        &self,
+        views: impl IntoIterator<Item = impl Into<String>>,
+        request: GetRawDataRequest,
+    ) -> PerView<GetRawDataResponse> {
+        self.for_each_view(views, |client, view| {
+            let request = GetRawDataRequest {
+                view,
+                ..request.clone()
+            };
+            async move { client.get_raw_data(request).await }
+        })
+        .await
+    }
+
+    /// Get aggregate data for the same tags from each view. `request.view` is ignored.
+    pub async fn get_aggregate_data_across_views(
+        &self,
+        views: impl IntoIterator<Item = impl Into<String>>,
+        request: GetAggregateDataRequest,
+    ) -> PerView<GetAggregateDataResponse> {
+        self.for_each_view(views, |client, view| {
+            let request = GetAggregateDataRequest {
+                view,
+                ..request.clone()
+            };
+            async move { client.get_aggregate_data(request).await }
+        })
+        .await
+    }
+
+    /// Get the current values of the same tags from each view. `request.view` is ignored.
+    pub async fn get_tag_current_value_across_views(
+        &self,
+        views: impl IntoIterator<Item = impl Into<String>>,
+        request: GetTagCurrentValueRequest,
+    ) -> PerView<GetTagCurrentValueResponse> {
+        self.for_each_view(views, |client, view| {
+            let request = GetTagCurrentValueRequest {
+                view,
+                ..request.clone()
+            };
+            async move { client.get_tag_current_value(request).await }
+        })
+        .await
+    }
+}
@@ -0,0 +1,21 @@
+//! The compiled descriptors of the vendored protos.
+
+use std::sync::OnceLock;
+
+use prost::Message;
+use prost_types::FileDescriptorSet;
+
+/// The vendored protos of the enabled services, with their imports and
+/// source info, encoded as a `FileDescriptorSet`. This is the form gRPC
+/// reflection servers and descriptor pools load.
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/crowsong_descriptor.bin"));
+
+/// The vendored protos of the enabled services, decoded once.
+pub fn descriptors() -> &'static FileDescriptorSet {
+    static SET: OnceLock<FileDescriptorSet> = OnceLock::new();
+    SET.get_or_init(|| {
+        FileDescriptorSet::decode(FILE_DESCRIPTOR_SET)
+            .expect("the build script writes a valid descriptor set")
+    })
+}
@@ -0,0 +1,170 @@
+//! Calling RPCs by name, with JSON requests and responses.
+//!
+//! The typed wrappers on [`ViewsClient`] cover the RPCs crowsong knows how
+//! to use well. [`ViewsClient::call_dynamic`] reaches any other unary RPC in
+//! the vendored protos, or one added by a newer server to the same
+//! messages, by encoding a JSON request against the compiled descriptors
+//! and decoding the answer back to JSON:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! let json = client
+//!     .call_dynamic("CanaryViewsApiService", "GetServerVersion", "{}")
+//!     .await?;
+//! println!("{json}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! JSON follows the protobuf mapping: fields in `lowerCamelCase`,
+//! timestamps as RFC 3339 strings, and 64-bit integers as strings. A
+//! request with a `cci` field that is left out gets the client's CCI.
+//! [`to_json`] renders typed messages the same way.
+
+use std::sync::OnceLock;
+
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor, ReflectMessage,
+    SerializeOptions, Value,
+};
+use tonic::Status;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+
+use crate::error::Error;
+use crate::request_id;
+use crate::views_client::ViewsClient;
+
+/// The vendored protos as a descriptor pool, for looking up message types
+/// by name.
+pub fn descriptor_pool() -> &'static DescriptorPool {
+    static POOL: OnceLock<DescriptorPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        DescriptorPool::decode(crate::FILE_DESCRIPTOR_SET)
+            .expect("the build script writes a valid descriptor set")
+    })
+}
+
+/// Encode `message`, of the protobuf type `type_name` (such as
+/// `canary.views.grpc.api.GetRawDataRequest`), as JSON, e.g. for logging.
+pub fn to_json(type_name: &str, message: &impl Message) -> Result<String, Error> {
+    let descriptor = descriptor_pool()
+        .get_message_by_name(type_name)
+        .ok_or_else(|| format!("unknown message type {type_name:?}"))?;
+    let message = DynamicMessage::decode(descriptor, message.encode_to_vec().as_slice())
+        .map_err(Error::other)?;
+    json_string(&message)
+}
+
+fn json_string(message: &DynamicMessage) -> Result<String, Error> {
+    let mut json = serde_json::Serializer::new(Vec::new());
+    message
+        .serialize_with_options(
+            &mut json,
+            &SerializeOptions::new().skip_default_fields(false),
+        )
+        .map_err(Error::other)?;
+    String::from_utf8(json.into_inner()).map_err(Error::other)
+}
+
+/// Find `method` of `service`, which may be the service's full name or just
+/// its last segment.
+fn find_method(service: &str, method: &str) -> Result<MethodDescriptor, Error> {
+    let found = descriptor_pool()
+        .services()
+        .find(|s| s.full_name() == service || s.name() == service)
+        .ok_or_else(|| format!("unknown service {service:?}"))?;
+    found
+        .methods()
+        .find(|m| m.name() == method)
+        .ok_or_else(|| format!("{} has no method {method:?}", found.full_name()).into())
+}
+
+impl ViewsClient {
+    /// Call the unary RPC `method` of `service` with a JSON request,
+    /// returning the response as JSON.
+    pub async fn call_dynamic(
+        &self,
+        service: &str,
+        method: &str,
+        json_request: &str,
+    ) -> Result<String, Error> {
+        let method = find_method(service, method)?;
+        if method.is_client_streaming() || method.is_server_streaming() {
+            return Err(format!("{} is a streaming method", method.full_name()).into());
+        }
+
+        let mut deserializer = serde_json::Deserializer::from_str(json_request);
+        let mut request =
+            DynamicMessage::deserialize(method.input(), &mut deserializer).map_err(Error::other)?;
+        deserializer.end().map_err(Error::other)?;
+        if let Some(field) = request.descriptor().get_field_by_name("cci")
+            && !request.has_field(&field)
+        {
+            request.set_field(&field, Value::I32(self.cci()));
+        }
+
+        let path = format!("/{}/{}", method.parent_service().full_name(), method.name())
+            .parse::<http::uri::PathAndQuery>()
+            .map_err(Error::other)?;
+        let mut grpc = tonic::client::Grpc::new(self.service());
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unknown(format!("Service was not ready: {e}")))?;
+        let codec = DynamicCodec(method.output());
+        let response = self
+            .lease_clock()
+            .track(request_id::call(grpc.unary(
+                tonic::Request::new(request),
+                path,
+                codec,
+            )))
+            .await?
+            .into_inner();
+        json_string(&response)
+    }
+}
+
+/// Encodes dynamic requests and decodes responses of one message type.
+#[derive(Clone)]
+struct DynamicCodec(MessageDescriptor);
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.0.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: DynamicMessage, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        item.encode(dst)
+            .map_err(|e| Status::internal(format!("encoding request: {e}")))
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<DynamicMessage>, Status> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::internal(format!("decoding response: {e}")))
+    }
+}
@@ -0,0 +1,733 @@
+//! Classifying failed calls.
+//!
+//! Everything that can fail returns an [`Error`]; a call the server rejects
+//! is an [`Error::Grpc`] holding the [`tonic::Status`] it sent. [`Classify`]
+//! sorts an error into the categories callers act on: retry it, fix the
+//! credentials, or give up because the thing asked for doesn't exist. For a
+//! status the decision comes from the gRPC code first and, for the codes
+//! Canary overloads, from the message the server sent with it:
+//!
+//! | Code                  | Retryable | Auth | Not found |
+//! |-----------------------|-----------|------|-----------|
+//! | `UNAVAILABLE`         | yes       |      |           |
+//! | `DEADLINE_EXCEEDED`   | yes       |      |           |
+//! | `RESOURCE_EXHAUSTED`  | yes       |      |           |
+//! | `ABORTED`             | yes       |      |           |
+//! | `UNKNOWN`, `INTERNAL` | if the message names a transport failure | | |
+//! | `FAILED_PRECONDITION` | if the message names an invalid or expired CCI | | |
+//! | `UNAUTHENTICATED`     |           | yes  |           |
+//! | `PERMISSION_DENIED`   |           | yes  |           |
+//! | `NOT_FOUND`           |           |      | yes       |
+//!
+//! An invalid or expired client connection ID is retryable because the
+//! client can acquire a new one and repeat the call.
+//!
+//! A `NOT_FOUND` alone doesn't say whether the view, the dataset, or the tag
+//! was missing. [`CanaryError::from_status`] reads the message Canary sent
+//! with a status into the failure it describes, so an application can tell
+//! `UnknownTag { tag }` from `UnknownView` without matching strings itself.
+//!
+//! Connecting can fail before any call is made: a malformed endpoint, an API
+//! key that can't be read or sent, a TLS policy that can't be met, or a
+//! connect timeout. The other variants of [`Error`] name those categories,
+//! along with a time that doesn't parse and a [`ClientPool`] that has been
+//! shut down, so code mixing connects and calls can match on all of them:
+//!
+//! ```no_run
+//! # async fn example(pool: &crowsong::ClientPool) -> Result<(), crowsong::Error> {
+//! use crowsong::{Error, timestamps};
+//!
+//! let start = timestamps::parse_rfc3339("2024-05-01T00:00:00Z")?;
+//! match pool.client("north").await {
+//!     Ok(client) => println!("{:?}", client.get_views().await?.views),
+//!     Err(Error::Disconnected) => println!("shutting down"),
+//!     Err(e) => return Err(e),
+//! }
+//! # let _ = start;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ClientPool`]: crate::ClientPool
+
+use std::fmt;
+use std::pin::Pin;
+use std::time::Duration;
+
+use tonic::codegen::tokio_stream::Stream;
+use tonic::{Code, Status};
+
+use crate::timestamps::ParseTimestampError;
+
+/// Any other error, as [`Error::Other`] holds it: a failed bearer token
+/// fetch, a malformed configuration file, an export format's writer.
+///
+/// It is `Send + Sync + 'static`, so an [`Error`] holding one can cross
+/// `tokio::spawn` and convert into `anyhow::Error` with `?`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A stream of results that ends after its first error.
+pub type ResultStream<T> = Pin<Box<dyn Stream<Item = Result<T, Error>> + Send + 'static>>;
+
+/// The error of everything in this crate that can fail.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The endpoint isn't a valid URL, or no channel could be built to it.
+    Transport(tonic::transport::Error),
+    /// TLS couldn't be set up: the TLS policy can't be met,
+    /// or this build has no TLS for an `https` endpoint.
+    Tls(String),
+    /// The API key or bearer token can't be sent as a header value.
+    InvalidApiKey,
+    /// The API key couldn't be read from its file or the OS keyring.
+    Credentials(String),
+    /// Connecting took longer than the connect timeout.
+    Timeout(Duration),
+    /// A call to the server failed.
+    Grpc(Status),
+    /// A time couldn't be parsed.
+    InvalidTimestamp(ParseTimestampError),
+    /// Reading or writing a file or socket failed.
+    Io(std::io::Error),
+    /// The client pool has been shut down.
+    Disconnected,
+    /// Anything else, such as failing to fetch a bearer token.
+    Other(BoxError),
+}
+
+impl Error {
+    /// Wrap an error of another kind, or a message, as [`Error::Other`].
+    pub fn other(error: impl Into<BoxError>) -> Self {
+        Error::Other(error.into())
+    }
+
+    /// The status of a failed call, if that's what this is.
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            Error::Grpc(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(e) => match std::error::Error::source(e) {
+                Some(source) => write!(f, "{e}: {source}"),
+                None => write!(f, "{e}"),
+            },
+            Error::Tls(message) => write!(f, "TLS: {message}"),
+            Error::InvalidApiKey => f.write_str("the API key is not a valid header value"),
+            Error::Credentials(message) => f.write_str(message),
+            Error::Timeout(timeout) => {
+                write!(f, "connecting timed out after {}s", timeout.as_secs_f64())
+            }
+            Error::Grpc(status) => write!(f, "{} ({:?})", status.message(), status.code()),
+            Error::InvalidTimestamp(e) => write!(f, "{e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Disconnected => f.write_str("disconnected"),
+            Error::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Grpc(status) => Some(status),
+            Error::InvalidTimestamp(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Other(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        Error::Grpc(status)
+    }
+}
+
+/// The status of a failed call as is, and the closest code for the rest,
+/// for relaying an error from a gRPC service of one's own.
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Grpc(status) => status,
+            Error::Timeout(_) => Status::deadline_exceeded(error.to_string()),
+            Error::InvalidApiKey | Error::Credentials(_) => {
+                Status::unauthenticated(error.to_string())
+            }
+            Error::InvalidTimestamp(_) => Status::invalid_argument(error.to_string()),
+            Error::Transport(_) | Error::Disconnected => Status::unavailable(error.to_string()),
+            Error::Tls(_) | Error::Io(_) | Error::Other(_) => Status::unknown(error.to_string()),
+        }
+    }
+}
+
+impl From<CanaryError> for Error {
+    fn from(error: CanaryError) -> Self {
+        Error::Grpc(error.into())
+    }
+}
+
+impl From<ParseTimestampError> for Error {
+    fn from(error: ParseTimestampError) -> Self {
+        Error::InvalidTimestamp(error)
+    }
+}
+
+impl From<tonic::transport::Error> for Error {
+    fn from(error: tonic::transport::Error) -> Self {
+        Error::Transport(error)
+    }
+}
+
+impl From<tonic::metadata::errors::InvalidMetadataValue> for Error {
+    fn from(_: tonic::metadata::errors::InvalidMetadataValue) -> Self {
+        Error::InvalidApiKey
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<BoxError> for Error {
+    fn from(error: BoxError) -> Self {
+        Error::Other(error)
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message.into())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Other(message.into())
+    }
+}
+
+impl Classify for Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::Grpc(status) => status.is_retryable(),
+            Error::Timeout(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_auth(&self) -> bool {
+        match self {
+            Error::Grpc(status) => status.is_auth(),
+            Error::InvalidApiKey | Error::Credentials(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        self.status().is_some_and(Status::is_not_found)
+    }
+
+    fn is_invalid_cci(&self) -> bool {
+        self.status().is_some_and(Status::is_invalid_cci)
+    }
+}
+
+/// Message fragments Canary sends when a client connection ID is no longer
+/// valid, whatever code it arrives with.
+const CCI_PATTERNS: &[&str] = &[
+    "invalid client connection id",
+    "invalid connection id",
+    "client connection id expired",
+    "cci expired",
+];
+
+/// Message fragments of transport failures tonic reports as `UNKNOWN` or
+/// `INTERNAL`.
+const TRANSPORT_PATTERNS: &[&str] = &[
+    "transport error",
+    "connection reset",
+    "connection refused",
+    "broken pipe",
+    "h2 protocol error",
+];
+
+/// Failure categories of a call, shared by callers and the retrying helpers.
+pub trait Classify {
+    /// Whether repeating the call may succeed: the server was unavailable,
+    /// overloaded, or too slow, the connection dropped, or the client
+    /// connection ID needs to be re-acquired.
+    fn is_retryable(&self) -> bool;
+
+    /// Whether the call failed because the API key is missing, wrong, or
+    /// lacks access.
+    fn is_auth(&self) -> bool;
+
+    /// Whether the view, dataset, or tag asked for doesn't exist.
+    fn is_not_found(&self) -> bool;
+
+    /// Whether the server rejected the client connection ID.
+    fn is_invalid_cci(&self) -> bool;
+}
+
+impl Classify for Status {
+    fn is_retryable(&self) -> bool {
+        match self.code() {
+            Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted => true,
+            Code::Unknown | Code::Internal => {
+                message_matches(self, TRANSPORT_PATTERNS) || self.is_invalid_cci()
+            }
+            Code::FailedPrecondition => self.is_invalid_cci(),
+            _ => false,
+        }
+    }
+
+    fn is_auth(&self) -> bool {
+        matches!(self.code(), Code::Unauthenticated | Code::PermissionDenied)
+    }
+
+    fn is_not_found(&self) -> bool {
+        self.code() == Code::NotFound
+    }
+
+    fn is_invalid_cci(&self) -> bool {
+        message_matches(self, CCI_PATTERNS)
+    }
+}
+
+fn message_matches(status: &Status, patterns: &[&str]) -> bool {
+    let message = status.message().to_ascii_lowercase();
+    patterns.iter().any(|pattern| message.contains(pattern))
+}
+
+/// A failure Canary reported, in terms an application can react to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CanaryError {
+    /// The view doesn't exist, or the API key can't see it.
+    UnknownView { view: Option<String> },
+    /// The view has no such dataset.
+    UnknownDataset { dataset: Option<String> },
+    /// The tag doesn't exist in the view.
+    UnknownTag { tag: Option<String> },
+    /// The server doesn't know the aggregate, or rejected its configuration.
+    InvalidAggregate { aggregate: Option<String> },
+    /// The client connection ID is invalid or has expired; connect again.
+    CciExpired,
+}
+
+impl CanaryError {
+    /// Read the failure a status describes, or `None` if it isn't one of the
+    /// failures above.
+    pub fn from_status(status: &Status) -> Option<Self> {
+        let message = status.message();
+        if message_matches(status, CCI_PATTERNS) {
+            return Some(CanaryError::CciExpired);
+        }
+        if let Some(aggregate) = invalid_aggregate(message) {
+            return Some(CanaryError::InvalidAggregate { aggregate });
+        }
+        if status.code() != Code::NotFound && !message.to_ascii_lowercase().contains("not found") {
+            return None;
+        }
+        // A tag's message may go on to name its view ("tag X not found in
+        // V"), so look for the most specific subject first.
+        if let Some(tag) = not_found_subject(message, "tag") {
+            Some(CanaryError::UnknownTag { tag })
+        } else if let Some(dataset) = not_found_subject(message, "dataset") {
+            Some(CanaryError::UnknownDataset { dataset })
+        } else {
+            not_found_subject(message, "view").map(|view| CanaryError::UnknownView { view })
+        }
+    }
+
+    fn code(&self) -> Code {
+        match self {
+            CanaryError::UnknownView { .. }
+            | CanaryError::UnknownDataset { .. }
+            | CanaryError::UnknownTag { .. } => Code::NotFound,
+            CanaryError::InvalidAggregate { .. } => Code::InvalidArgument,
+            CanaryError::CciExpired => Code::FailedPrecondition,
+        }
+    }
+}
+
+impl fmt::Display for CanaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (subject, name) = match self {
+            CanaryError::UnknownView { view } => ("view", view),
+            CanaryError::UnknownDataset { dataset } => ("dataset", dataset),
+            CanaryError::UnknownTag { tag } => ("tag", tag),
+            CanaryError::InvalidAggregate { aggregate } => {
+                return match aggregate {
+                    Some(aggregate) => write!(f, "invalid aggregate {aggregate}"),
+                    None => f.write_str("invalid aggregate"),
+                };
+            }
+            CanaryError::CciExpired => return f.write_str("client connection id expired"),
+        };
+        match name {
+            Some(name) => write!(f, "{subject} {name} not found"),
+            None => write!(f, "{subject} not found"),
+        }
+    }
+}
+
+impl std::error::Error for CanaryError {}
+
+impl From<CanaryError> for Status {
+    fn from(error: CanaryError) -> Self {
+        Status::new(error.code(), error.to_string())
+    }
+}
+
+/// Find `subject` as a whole word followed by `"not found"`, as in
+/// `"tag X not found in V"`, `"view not found"`, or `"dataset not found: X"`,
+/// returning the name the message gives, if any.
+fn not_found_subject(message: &str, subject: &str) -> Option<Option<String>> {
+    let lower = message.to_ascii_lowercase();
+    let start = word(&lower, subject)? + subject.len();
+    let end = start + lower[start..].find("not found")?;
+    let before = message[start..end].trim();
+    let after = message[end + "not found".len()..].trim_start();
+    let name = if !before.is_empty() {
+        Some(before)
+    } else {
+        after
+            .strip_prefix(':')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    };
+    Some(name.map(|name| name.trim_matches(['\'', '"']).to_string()))
+}
+
+/// The aggregate named by an "invalid aggregate" or "unknown aggregate"
+/// message, if the message is one.
+fn invalid_aggregate(message: &str) -> Option<Option<String>> {
+    let lower = message.to_ascii_lowercase();
+    let start = word(&lower, "aggregate")?;
+    let prefix = lower[..start].trim_end();
+    if !(prefix.ends_with("invalid") || prefix.ends_with("unknown")) {
+        return None;
+    }
+    let name = message[start + "aggregate".len()..]
+        .trim_start_matches([':', ' '])
+        .split_whitespace()
+        .next()
+        .map(|name| name.trim_matches(['\'', '"']).to_string())
+        .filter(|name| !name.is_empty());
+    Some(name)
+}
+
+/// The byte offset of `word` in `text`, where it isn't part of a longer word.
+fn word(text: &str, word: &str) -> Option<usize> {
+    text.match_indices(word).map(|(i, _)| i).find(|&i| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_statuses_by_code() {
+        // (code, retryable, auth, not found)
+        let table = [
+            (Code::Unavailable, true, false, false),
+            (Code::DeadlineExceeded, true, false, false),
+            (Code::ResourceExhausted, true, false, false),
+            (Code::Aborted, true, false, false),
+            (Code::Unknown, false, false, false),
+            (Code::Internal, false, false, false),
+            (Code::FailedPrecondition, false, false, false),
+            (Code::Unauthenticated, false, true, false),
+            (Code::PermissionDenied, false, true, false),
+            (Code::NotFound, false, false, true),
+            (Code::InvalidArgument, false, false, false),
+            (Code::Cancelled, false, false, false),
+        ];
+        for (code, retryable, auth, not_found) in table {
+            let status = Status::new(code, "something went wrong");
+            assert_eq!(status.is_retryable(), retryable, "{code:?}");
+            assert_eq!(status.is_auth(), auth, "{code:?}");
+            assert_eq!(status.is_not_found(), not_found, "{code:?}");
+            assert!(!status.is_invalid_cci(), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn retries_transport_failures_by_message() {
+        for message in [
+            "transport error",
+            "error trying to connect: Connection refused (os error 111)",
+            "Connection reset by peer",
+            "Broken pipe",
+            "h2 protocol error: stream no longer needed",
+        ] {
+            for code in [Code::Unknown, Code::Internal] {
+                assert!(
+                    Status::new(code, message).is_retryable(),
+                    "{code:?} {message:?}"
+                );
+            }
+            assert!(
+                !Status::new(Code::InvalidArgument, message).is_retryable(),
+                "{message:?}"
+            );
+        }
+        for message in [
+            "transport",
+            "connection closed",
+            "pipe broken",
+            "protocol error",
+        ] {
+            assert!(
+                !Status::new(Code::Unknown, message).is_retryable(),
+                "{message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn retries_rejected_ccis_by_message() {
+        for message in [
+            "Invalid client connection id",
+            "invalid connection ID 42",
+            "Client connection ID expired",
+            "CCI expired",
+        ] {
+            for code in [Code::FailedPrecondition, Code::Unknown, Code::Internal] {
+                let status = Status::new(code, message);
+                assert!(status.is_invalid_cci(), "{code:?} {message:?}");
+                assert!(status.is_retryable(), "{code:?} {message:?}");
+            }
+            let status = Status::new(Code::InvalidArgument, message);
+            assert!(
+                status.is_invalid_cci() && !status.is_retryable(),
+                "{message:?}"
+            );
+        }
+        for message in [
+            "invalid client",
+            "connection id missing",
+            "cci",
+            "expired token",
+        ] {
+            let status = Status::new(Code::FailedPrecondition, message);
+            assert!(!status.is_invalid_cci(), "{message:?}");
+            assert!(!status.is_retryable(), "{message:?}");
+        }
+    }
+
+    #[test]
+    fn reads_canary_failures_from_messages() {
+        let some = |s: &str| Some(s.to_string());
+        let table = [
+            (
+                Code::NotFound,
+                "Tag Plant.Line1.Flow not found in Plant",
+                Some(CanaryError::UnknownTag {
+                    tag: some("Plant.Line1.Flow"),
+                }),
+            ),
+            (
+                Code::NotFound,
+                "tag not found: Plant.Line1.Flow",
+                Some(CanaryError::UnknownTag {
+                    tag: some("Plant.Line1.Flow"),
+                }),
+            ),
+            (
+                Code::NotFound,
+                "Tag not found",
+                Some(CanaryError::UnknownTag { tag: None }),
+            ),
+            (
+                Code::NotFound,
+                "dataset Plant not found",
+                Some(CanaryError::UnknownDataset {
+                    dataset: some("Plant"),
+                }),
+            ),
+            (
+                Code::NotFound,
+                "Dataset not found: Plant",
+                Some(CanaryError::UnknownDataset {
+                    dataset: some("Plant"),
+                }),
+            ),
+            (
+                Code::NotFound,
+                "View 'North' not found",
+                Some(CanaryError::UnknownView {
+                    view: some("North"),
+                }),
+            ),
+            (
+                Code::NotFound,
+                "view not found",
+                Some(CanaryError::UnknownView { view: None }),
+            ),
+            // Canary sometimes sends a not-found message with another code.
+            (
+                Code::Unknown,
+                "view North not found",
+                Some(CanaryError::UnknownView {
+                    view: some("North"),
+                }),
+            ),
+            (
+                Code::InvalidArgument,
+                "Invalid aggregate: TimeAvg",
+                Some(CanaryError::InvalidAggregate {
+                    aggregate: some("TimeAvg"),
+                }),
+            ),
+            (
+                Code::InvalidArgument,
+                "unknown aggregate 'Foo' requested",
+                Some(CanaryError::InvalidAggregate {
+                    aggregate: some("Foo"),
+                }),
+            ),
+            (
+                Code::InvalidArgument,
+                "invalid aggregate",
+                Some(CanaryError::InvalidAggregate { aggregate: None }),
+            ),
+            (
+                Code::FailedPrecondition,
+                "Client connection ID expired",
+                Some(CanaryError::CciExpired),
+            ),
+            (
+                Code::Unknown,
+                "invalid client connection id 7",
+                Some(CanaryError::CciExpired),
+            ),
+            // Near misses.
+            (Code::NotFound, "not found", None),
+            (Code::NotFound, "tagged item missing", None),
+            (Code::NotFound, "the tags were not found", None),
+            (Code::NotFound, "preview not found", None),
+            (Code::InvalidArgument, "tag Plant.Flow is invalid", None),
+            (Code::InvalidArgument, "aggregate interval is invalid", None),
+            (Code::InvalidArgument, "invalid aggregated data", None),
+            (Code::Unavailable, "server unavailable", None),
+        ];
+        for (code, message, expected) in table {
+            let status = Status::new(code, message);
+            assert_eq!(
+                CanaryError::from_status(&status),
+                expected,
+                "{code:?} {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn turns_canary_failures_into_statuses() {
+        let status = Status::from(CanaryError::UnknownTag {
+            tag: Some("Plant.Flow".into()),
+        });
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "tag Plant.Flow not found");
+        let status = Status::from(CanaryError::UnknownView { view: None });
+        assert_eq!(status.message(), "view not found");
+        let status = Status::from(CanaryError::InvalidAggregate {
+            aggregate: Some("Foo".into()),
+        });
+        assert_eq!(
+            (status.code(), status.message()),
+            (Code::InvalidArgument, "invalid aggregate Foo")
+        );
+        let status = Status::from(CanaryError::CciExpired);
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert!(status.is_invalid_cci() && status.is_retryable());
+
+        // Each reads back as itself.
+        for error in [
+            CanaryError::UnknownView {
+                view: Some("North".into()),
+            },
+            CanaryError::UnknownDataset {
+                dataset: Some("Plant".into()),
+            },
+            CanaryError::UnknownTag {
+                tag: Some("Plant.Flow".into()),
+            },
+            CanaryError::InvalidAggregate {
+                aggregate: Some("Foo".into()),
+            },
+            CanaryError::CciExpired,
+        ] {
+            assert_eq!(
+                CanaryError::from_status(&Status::from(error.clone())),
+                Some(error)
+            );
+        }
+    }
+
+    #[test]
+    fn finds_whole_words() {
+        assert_eq!(word("the tag x", "tag"), Some(4));
+        assert_eq!(word("tagged tag", "tag"), Some(7));
+        assert_eq!(word("tags", "tag"), None);
+        assert_eq!(word("preview", "view"), None);
+    }
+
+    #[test]
+    fn classifies_errors() {
+        assert!(Error::Timeout(Duration::from_secs(5)).is_retryable());
+        assert!(Error::InvalidApiKey.is_auth());
+        assert!(Error::Credentials("no key".into()).is_auth());
+        assert!(Error::from(Status::unavailable("down")).is_retryable());
+        assert!(Error::from(Status::not_found("tag X not found")).is_not_found());
+        assert!(Error::from(Status::unknown("cci expired")).is_invalid_cci());
+        assert!(!Error::Tls("no TLS".into()).is_retryable());
+        assert!(!Error::Disconnected.is_retryable());
+        assert!(!Error::Other("boom".into()).is_auth());
+    }
+
+    #[test]
+    fn relays_errors_as_statuses() {
+        let status = Status::from(Error::from(Status::not_found("tag X not found")));
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "tag X not found");
+
+        let cases = [
+            (
+                Error::Timeout(Duration::from_secs(5)),
+                Code::DeadlineExceeded,
+            ),
+            (Error::InvalidApiKey, Code::Unauthenticated),
+            (Error::Credentials("no key".into()), Code::Unauthenticated),
+            (Error::Disconnected, Code::Unavailable),
+            (Error::Tls("no TLS".into()), Code::Unknown),
+            (Error::Io(std::io::Error::other("full")), Code::Unknown),
+            (Error::Other("boom".into()), Code::Unknown),
+        ];
+        for (error, code) in cases {
+            let message = error.to_string();
+            let status = Status::from(error);
+            assert_eq!(status.code(), code, "{message}");
+            assert_eq!(status.message(), message);
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Events and alarms recorded by Canary's event calculations.
+//!
+//! Canary stores events (alarms, batches, downtime, and other calculated
+//! intervals) alongside tag history and serves them through the Views API's
+//! `FindEvents` RPC. [`ViewsClient::get_events`] follows the continuation
+//! points and returns typed [`Event`]s filtered by an [`EventQuery`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+use tonic::Status;
+
+use crate::canary::calculations::grpc::common::{self as calc, BatchEventStatus};
+use crate::canary::views::grpc::api::*;
+use crate::error::Error;
+use crate::request_id;
+use crate::types::Value;
+use crate::views_client::ViewsClient;
+
+/// The lifecycle state of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EventStatus {
+    #[default]
+    Unspecified,
+    Active,
+    Completed,
+    Aborted,
+}
+
+impl EventStatus {
+    /// The status as a lowercase name, such as `"active"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventStatus::Unspecified => "unspecified",
+            EventStatus::Active => "active",
+            EventStatus::Completed => "completed",
+            EventStatus::Aborted => "aborted",
+        }
+    }
+}
+
+impl From<BatchEventStatus> for EventStatus {
+    fn from(status: BatchEventStatus) -> Self {
+        match status {
+            BatchEventStatus::Unspecified => EventStatus::Unspecified,
+            BatchEventStatus::Active => EventStatus::Active,
+            BatchEventStatus::Completed => EventStatus::Completed,
+            BatchEventStatus::Aborted => EventStatus::Aborted,
+        }
+    }
+}
+
+/// One event, such as an alarm or a batch.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Event {
+    /// The server's event ID, or 0 for ad hoc search results.
+    pub id: i64,
+    pub name: String,
+    /// The event calculation that produced the event.
+    pub calculation: String,
+    /// The asset path or tag the event belongs to.
+    pub source: String,
+    pub start: Option<SystemTime>,
+    /// When the event ended; `None` while it is still active.
+    pub end: Option<SystemTime>,
+    pub status: EventStatus,
+    /// Event properties by name.
+    pub properties: BTreeMap<String, Value>,
+}
+
+impl Event {
+    /// How long the event lasted, if it has ended.
+    pub fn duration(&self) -> Option<Duration> {
+        self.end?.duration_since(self.start?).ok()
+    }
+}
+
+fn system_time(ts: Option<prost_types::Timestamp>) -> Option<SystemTime> {
+    ts.and_then(|ts| SystemTime::try_from(ts).ok())
+}
+
+impl From<&calc::Event> for Event {
+    fn from(event: &calc::Event) -> Self {
+        Self {
+            id: event.id,
+            name: event.name.clone(),
+            calculation: event.calculation_name.clone(),
+            source: event.asset_base_path.clone(),
+            start: system_time(event.starts_at),
+            end: system_time(event.ends_at),
+            status: event.status().into(),
+            properties: event
+                .properties
+                .iter()
+                .map(|p| {
+                    (
+                        p.name.clone(),
+                        p.value.as_ref().map(Value::from).unwrap_or_default(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&AdHocEvent> for Event {
+    fn from(event: &AdHocEvent) -> Self {
+        Self {
+            source: event.source.clone(),
+            start: system_time(event.starts_at),
+            end: system_time(event.ends_at),
+            properties: event
+                .properties
+                .iter()
+                .map(|p| {
+                    let value = p
+                        .property_value
+                        .as_ref()
+                        .map(Value::from)
+                        .unwrap_or_default();
+                    (p.property_name.clone(), value)
+                })
+                .collect(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Which events to return from [`ViewsClient::get_events`].
+///
+/// An event matches when it overlaps `start..end` and, if any sources or
+/// calculations are given, belongs to one of them. Server-side search
+/// criteria narrow the search before these filters are applied.
+#[derive(Debug, Clone)]
+pub struct EventQuery {
+    start: SystemTime,
+    end: SystemTime,
+    sources: Vec<String>,
+    calculations: Vec<String>,
+    criteria: HashMap<String, String>,
+}
+
+impl EventQuery {
+    pub fn new(start: SystemTime, end: SystemTime) -> Self {
+        Self {
+            start,
+            end,
+            sources: Vec::new(),
+            calculations: Vec::new(),
+            criteria: HashMap::new(),
+        }
+    }
+
+    /// Keep events whose source starts with `prefix`, e.g. an asset path.
+    pub fn source(mut self, prefix: impl Into<String>) -> Self {
+        self.sources.push(prefix.into());
+        self
+    }
+
+    /// Keep events produced by the named event calculation.
+    pub fn calculation(mut self, name: impl Into<String>) -> Self {
+        self.calculations.push(name.into());
+        self
+    }
+
+    /// Pass a search criterion through to the server's `FindEvents`.
+    pub fn criterion(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.criteria.insert(key.into(), value.into());
+        self
+    }
+
+    /// Whether an event satisfies the time range, source, and calculation filters.
+    pub fn matches(&self, event: &Event) -> bool {
+        let starts_before_end = event.start.is_none_or(|start| start < self.end);
+        let ends_after_start = event.end.is_none_or(|end| end > self.start);
+        starts_before_end
+            && ends_after_start
+            && (self.sources.is_empty() || self.sources.iter().any(|s| event.source.starts_with(s)))
+            && (self.calculations.is_empty() || self.calculations.contains(&event.calculation))
+    }
+}
+
+impl ViewsClient {
+    /// Find events matching raw search criteria. One page; see
+    /// [`get_events`](Self::get_events) for a typed, paged search.
+    pub async fn find_events(
+        &self,
+        request: FindEventsRequest,
+    ) -> Result<FindEventsResponse, Error> {
+        let resp = self
+            .call(|mut inner| {
+                let request = request.clone();
+                async move { inner.find_events(request).await }
+            })
+            .await?
+            .into_inner();
+        match resp.extended_status() {
+            find_events_response::Status::Unspecified => Ok(resp),
+            find_events_response::Status::CalculationsRequestFailed => {
+                Err(Status::unavailable("calculations request failed").into())
+            }
+        }
+    }
+
+    /// Get every event matching a query, following continuation points.
+    pub async fn get_events(&self, query: &EventQuery) -> Result<Vec<Event>, Error> {
+        request_id::operation(self.get_event_pages(query)).await
+    }
+
+    async fn get_event_pages(&self, query: &EventQuery) -> Result<Vec<Event>, Error> {
+        let mut events = Vec::new();
+        let mut continuation_point = Vec::new();
+        loop {
+            let resp = self
+                .find_events(FindEventsRequest {
+                    search_criteria: query.criteria.clone(),
+                    continuation_point,
+                })
+                .await?;
+            events.extend(
+                resp.events
+                    .iter()
+                    .map(Event::from)
+                    .filter(|e| query.matches(e)),
+            );
+            if resp.continuation_point.is_empty() {
+                return Ok(events);
+            }
+            continuation_point = resp.continuation_point;
+        }
+    }
+
+    /// Get the names of the event calculations.
+    pub async fn get_event_calculation_names(&self) -> Result<Vec<String>, Error> {
+        let resp = self
+            .call(|mut inner| async move {
+                inner
+                    .get_event_calculation_names(GetEventCalculationNamesRequest {})
+                    .await
+            })
+            .await?
+            .into_inner();
+        match resp.extended_status() {
+            get_event_calculation_names_response::Status::Unspecified => {
+                Ok(resp.event_calculation_names)
+            }
+            get_event_calculation_names_response::Status::CalculationsRequestFailed => {
+                Err(Status::unavailable("calculations request failed").into())
+            }
+        }
+    }
+
+    /// Get the names of the event properties.
+    pub async fn get_event_property_names(&self) -> Result<Vec<String>, Error> {
+        let resp = self
+            .call(|mut inner| async move {
+                inner
+                    .get_event_property_names(GetEventPropertyNamesRequest {})
+                    .await
+            })
+            .await?
+            .into_inner();
+        match resp.extended_status() {
+            get_event_property_names_response::Status::Unspecified => Ok(resp.event_property_names),
+            get_event_property_names_response::Status::CalculationsRequestFailed => {
+                Err(Status::unavailable("calculations request failed").into())
+            }
+        }
+    }
+
+    /// Run an ad hoc event search over a view's history. One page.
+    pub async fn search_for_events(
+        &self,
+        request: SearchForEventsRequest,
+    ) -> Result<SearchForEventsResponse, Error> {
+        let resp = self
+            .call_with_cci(|mut inner, cci| {
+                let request = SearchForEventsRequest {
+                    cci,
+                    ..request.clone()
+                };
+                async move { inner.search_for_events(request).await }
+            })
+            .await?
+            .into_inner();
+        match resp.extended_status() {
+            search_for_events_response::Status::Unspecified => Ok(resp),
+            search_for_events_response::Status::UnhandledException => {
+                Err(Status::internal("unhandled exception in event search").into())
+            }
+            search_for_events_response::Status::ViewsError => {
+                Err(Status::internal("views error").into())
+            }
+        }
+    }
+}
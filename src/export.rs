@@ -0,0 +1,398 @@
+//! Exporting a whole dataset's history to files.
+//!
+//! An [`Exporter`] walks every tag in a dataset and reads each one's raw
+//! history in fixed windows, writing every window that has samples to its
+//! own CSV or Parquet file. By default a tag's history runs from its oldest
+//! sample to when the export started. With a [`Checkpoint`] file, each tag's
+//! high-water mark is saved after every window is written, so an export
+//! interrupted partway through a multi-day run picks up at the next window
+//! instead of starting over:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use std::time::Duration;
+//! use crowsong::export::Exporter;
+//!
+//! let summary = Exporter::new("Plant", "Line1", "/data/line1/{tag}/{date}.parquet")
+//!     .window(Duration::from_secs(24 * 3600))
+//!     .checkpoint("/data/line1/checkpoint.tsv")
+//!     .run(client)
+//!     .await?;
+//! println!("wrote {} samples to {} files", summary.samples, summary.files);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Output paths must contain `{tag}` and `{start}` or `{end}`, and may
+//! contain `{dataset}`, and `{date}` and `{hour}` of the window's start.
+//! Path separators in tag names become `_`. Files are written as scheduled
+//! exports write them: Parquet (with the `parquet` feature) for paths ending
+//! in `.parquet`, CSV otherwise, and `s3://` paths with the `s3` feature.
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+
+use crate::bulk::BulkFetch;
+use crate::checkpoint::Checkpoint;
+use crate::error::Error;
+use crate::timestamps::{self, TimestampFormat};
+use crate::views_client::ViewsClient;
+
+/// Where a running export is, reported after every completed window.
+#[derive(Debug, Clone)]
+pub struct ExportProgress {
+    pub tag: String,
+    /// The position of the tag in the dataset, from 0.
+    pub tag_index: usize,
+    pub tag_count: usize,
+    /// How far the tag has been exported.
+    pub through: SystemTime,
+    /// The file the window was written to, or `None` if it had no samples.
+    pub path: Option<String>,
+    /// Samples written so far by the whole export.
+    pub samples: u64,
+}
+
+/// What a finished export did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExportSummary {
+    pub tags: usize,
+    pub files: usize,
+    pub samples: u64,
+}
+
+/// A planned export of a dataset's raw history.
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    view: String,
+    dataset: String,
+    output: String,
+    start: Option<SystemTime>,
+    end: Option<SystemTime>,
+    checkpoint: Option<PathBuf>,
+    window: Duration,
+    page_size: i32,
+    timestamp_format: TimestampFormat,
+}
+
+impl Exporter {
+    pub fn new(
+        view: impl Into<String>,
+        dataset: impl Into<String>,
+        output: impl Into<String>,
+    ) -> Self {
+        Self {
+            view: view.into(),
+            dataset: dataset.into(),
+            output: output.into(),
+            start: None,
+            end: None,
+            checkpoint: None,
+            window: Duration::from_secs(24 * 3600),
+            page_size: 10_000,
+            timestamp_format: TimestampFormat::default(),
+        }
+    }
+
+    /// Export nothing before `start`. Defaults to each tag's oldest sample.
+    pub fn start(mut self, start: SystemTime) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Export nothing from `end` on. Defaults to when the export starts.
+    pub fn end(mut self, end: SystemTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Record progress in a checkpoint file and resume from it.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    /// How much history goes in one file. Progress is checkpointed after
+    /// each. Defaults to one day.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window.max(Duration::from_secs(1));
+        self
+    }
+
+    /// The maximum number of samples in one read. Defaults to 10,000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// How CSV files render timestamps. Defaults to RFC 3339 in UTC.
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = format;
+        self
+    }
+
+    /// The output path for one tag's window `start..end`.
+    pub fn output_path(&self, tag: &str, start: SystemTime, end: SystemTime) -> String {
+        let date = DateTime::<Utc>::from(start);
+        self.output
+            .replace("{tag}", &tag.replace(['/', '\\'], "_"))
+            .replace("{dataset}", &self.dataset.replace(['/', '\\'], "_"))
+            .replace("{start}", &compact_time(start))
+            .replace("{end}", &compact_time(end))
+            .replace("{date}", &date.format("%Y-%m-%d").to_string())
+            .replace("{hour}", &date.format("%H").to_string())
+    }
+
+    /// Export every tag.
+    pub async fn run(&self, client: &ViewsClient) -> Result<ExportSummary, Error> {
+        self.run_with_progress(client, |_| {}).await
+    }
+
+    /// Export every tag, calling `progress` after each completed window.
+    pub async fn run_with_progress(
+        &self,
+        client: &ViewsClient,
+        mut progress: impl FnMut(&ExportProgress),
+    ) -> Result<ExportSummary, Error> {
+        if !self.output.contains("{tag}")
+            || !(self.output.contains("{start}") || self.output.contains("{end}"))
+        {
+            return Err(format!(
+                "export path {} must contain {{tag}} and {{start}} or {{end}}",
+                self.output
+            )
+            .into());
+        }
+        Output::new(&self.output, self.timestamp_format)?;
+        let end = self.end.unwrap_or_else(SystemTime::now);
+        let mut checkpoint = self.checkpoint.clone().map(Checkpoint::load).transpose()?;
+        let tags = client.get_all_tags(&self.view, &self.dataset).await?;
+        let mut summary = ExportSummary::default();
+
+        for (tag_index, tag) in tags.iter().enumerate() {
+            summary.tags += 1;
+            let mark = checkpoint.as_ref().and_then(|c| c.get(tag));
+            if mark.is_some_and(|mark| mark >= end) {
+                continue;
+            }
+            let Some(start) = self.tag_start(client, tag).await? else {
+                // No samples to export.
+                continue;
+            };
+            let mut cursor = mark.map_or(start, |mark| mark.max(start));
+
+            while cursor < end {
+                let window_end = cursor.checked_add(self.window).unwrap_or(end).min(end);
+                let pages = BulkFetch::new(&self.view, [tag], cursor, window_end)
+                    .page_size(self.page_size)
+                    .run(client)
+                    .await?;
+                let mut output = Output::new(&self.output, self.timestamp_format)?;
+                let mut samples = 0;
+                for page in pages {
+                    let page = page?;
+                    if page.error_code != 0 {
+                        return Err(format!(
+                            "reading {tag} failed ({}): {}",
+                            page.error_code, page.error_message
+                        )
+                        .into());
+                    }
+                    output.push(&page)?;
+                    samples += page.len() as u64;
+                }
+                let path = if samples > 0 {
+                    let path = self.output_path(tag, cursor, window_end);
+                    write_output(&path, output.finish()?).await?;
+                    summary.files += 1;
+                    summary.samples += samples;
+                    Some(path)
+                } else {
+                    None
+                };
+
+                cursor = window_end;
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.set(tag.as_str(), cursor);
+                    checkpoint.save()?;
+                }
+                progress(&ExportProgress {
+                    tag: tag.clone(),
+                    tag_index,
+                    tag_count: tags.len(),
+                    through: cursor,
+                    path,
+                    samples: summary.samples,
+                });
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Where a tag's export starts: the configured start, or its oldest
+    /// sample. `None` if it has no samples.
+    async fn tag_start(
+        &self,
+        client: &ViewsClient,
+        tag: &str,
+    ) -> Result<Option<SystemTime>, Error> {
+        if let Some(start) = self.start {
+            return Ok(Some(start));
+        }
+        let oldest = client
+            .get_tag_data_context(self.view.as_str(), vec![tag.to_string()])
+            .await?
+            .contexts
+            .into_iter()
+            .find(|context| context.tag_item_id == tag)
+            .and_then(|context| context.oldest_timestamp)
+            .map(|ts| timestamps::from_proto(&ts));
+        Ok(oldest)
+    }
+}
+
+/// `2024-05-01T12:00:00Z` as `20240501T120000Z`.
+pub(crate) fn compact_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// An output file being built in memory.
+pub(crate) enum Output {
+    Csv(String, TimestampFormat),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<parquet::arrow::ArrowWriter<Vec<u8>>>),
+}
+
+impl Output {
+    pub(crate) fn new(path: &str, timestamps: TimestampFormat) -> Result<Self, Error> {
+        if !path.ends_with(".parquet") {
+            return Ok(Self::Csv(
+                "tag,timestamp,value,quality\n".to_string(),
+                timestamps,
+            ));
+        }
+        #[cfg(feature = "parquet")]
+        {
+            let schema = Arc::new(parquet_schema());
+            Ok(Self::Parquet(Box::new(
+                parquet::arrow::ArrowWriter::try_new(Vec::new(), schema, None)
+                    .map_err(Error::other)?,
+            )))
+        }
+        #[cfg(not(feature = "parquet"))]
+        Err(
+            format!("{path} is Parquet, but crowsong was built without the `parquet` feature")
+                .into(),
+        )
+    }
+
+    pub(crate) fn push(&mut self, page: &crate::columnar::RawColumns) -> Result<(), Error> {
+        match self {
+            Self::Csv(out, timestamps) => {
+                let tag = csv_field(&page.tag_name);
+                for row in 0..page.len() {
+                    let tvq = page.tvq(row);
+                    out.push_str(&format!(
+                        "{tag},{},{},{}\n",
+                        timestamps.format(tvq.timestamp),
+                        csv_field(&tvq.value.to_string()),
+                        tvq.quality.0
+                    ));
+                }
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(writer) => {
+                use arrow_array::{ArrayRef, RecordBatch, StringArray};
+
+                let batch = page.to_arrow().map_err(Error::other)?;
+                let tag: ArrayRef = Arc::new(StringArray::from(vec![
+                    page.tag_name.as_str();
+                    batch.num_rows()
+                ]));
+                let mut columns = vec![tag];
+                columns.extend(batch.columns().iter().cloned());
+                writer
+                    .write(
+                        &RecordBatch::try_new(Arc::new(parquet_schema()), columns)
+                            .map_err(Error::other)?,
+                    )
+                    .map_err(Error::other)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Csv(out, _) => Ok(out.into_bytes()),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(writer) => writer.into_inner().map_err(Error::other),
+        }
+    }
+}
+
+/// A `tag` column followed by the Arrow interop columns.
+#[cfg(feature = "parquet")]
+fn parquet_schema() -> arrow_schema::Schema {
+    use arrow_schema::{DataType, Field, Schema};
+
+    let mut fields = vec![Arc::new(Field::new("tag", DataType::Utf8, false))];
+    fields.extend(crate::interop::arrow::schema().fields().iter().cloned());
+    Schema::new(fields)
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Write `contents` to a local file, through a temporary name, or to
+/// `s3://bucket/key`.
+pub(crate) async fn write_output(path: &str, contents: Vec<u8>) -> Result<(), Error> {
+    if let Some(location) = path.strip_prefix("s3://") {
+        return write_s3(location, contents).await;
+    }
+    let path = Path::new(path);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    std::fs::write(&partial, contents)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[cfg(feature = "s3")]
+async fn write_s3(location: &str, contents: Vec<u8>) -> Result<(), Error> {
+    use object_store::ObjectStore;
+    use object_store::aws::AmazonS3Builder;
+
+    let (bucket, key) = location
+        .split_once('/')
+        .ok_or_else(|| format!("s3://{location} has no object key"))?;
+    let store = AmazonS3Builder::from_env()
+        .with_bucket_name(bucket)
+        .build()
+        .map_err(Error::other)?;
+    store
+        .put(&object_store::path::Path::from(key), contents.into())
+        .await
+        .map_err(Error::other)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "s3"))]
+async fn write_s3(location: &str, _contents: Vec<u8>) -> Result<(), Error> {
+    Err(format!("cannot write s3://{location}: crowsong was built without the `s3` feature").into())
+}
@@ -0,0 +1,201 @@
+//! Wide, columnar layout for multi-tag results.
+
+use std::collections::BTreeSet;
+use std::time::SystemTime;
+
+use crate::canary::views::grpc::api::{GetAggregateDataResponse, GetRawDataResponse};
+use crate::join::AsOf;
+use crate::quality::Quality;
+use crate::types::{TagSeries, Value};
+
+/// The values and qualities of one tag, aligned to a frame's timestamp index.
+///
+/// Rows where the tag had no sample hold `Value::Null` and a `None` quality.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Column {
+    pub tag_name: String,
+    pub eng_units: Option<String>,
+    pub values: Vec<Value>,
+    pub qualities: Vec<Option<Quality>>,
+}
+
+impl Column {
+    /// The values as floats, with `None` for missing or non-numeric samples.
+    pub fn as_f64(&self) -> Vec<Option<f64>> {
+        self.values.iter().map(Value::as_f64).collect()
+    }
+}
+
+/// Multi-tag results pivoted onto a shared, sorted timestamp index.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimeSeriesFrame {
+    timestamps: Vec<SystemTime>,
+    columns: Vec<Column>,
+}
+
+impl TimeSeriesFrame {
+    /// Pivot a set of series into a frame. The index is the union of all
+    /// timestamps; when a series repeats a timestamp the last sample wins.
+    pub fn from_series(series: &[TagSeries]) -> Self {
+        let timestamps: Vec<SystemTime> = series
+            .iter()
+            .flat_map(|s| s.tvqs.iter().map(|tvq| tvq.timestamp))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let columns = series
+            .iter()
+            .map(|s| {
+                let mut values = vec![Value::Null; timestamps.len()];
+                let mut qualities = vec![None; timestamps.len()];
+                for tvq in &s.tvqs {
+                    if let Ok(row) = timestamps.binary_search(&tvq.timestamp) {
+                        values[row] = tvq.value.clone();
+                        qualities[row] = Some(tvq.quality);
+                    }
+                }
+                Column {
+                    tag_name: s.tag_name.clone(),
+                    eng_units: s.eng_units.clone(),
+                    values,
+                    qualities,
+                }
+            })
+            .collect();
+
+        Self {
+            timestamps,
+            columns,
+        }
+    }
+
+    /// Join `others` onto the timestamps of `left`, each column holding the
+    /// sample `join` matches at that row. `left` is the first column, and
+    /// all series must be in time order.
+    pub fn as_of(left: &TagSeries, others: &[TagSeries], join: &AsOf) -> Self {
+        let timestamps: Vec<SystemTime> = left.tvqs.iter().map(|tvq| tvq.timestamp).collect();
+        let mut columns = vec![Column {
+            tag_name: left.tag_name.clone(),
+            eng_units: left.eng_units.clone(),
+            values: left.tvqs.iter().map(|tvq| tvq.value.clone()).collect(),
+            qualities: left.tvqs.iter().map(|tvq| Some(tvq.quality)).collect(),
+        }];
+        for other in others {
+            let matches = join.join(&left.tvqs, &other.tvqs);
+            columns.push(Column {
+                tag_name: other.tag_name.clone(),
+                eng_units: other.eng_units.clone(),
+                values: matches
+                    .iter()
+                    .map(|m| m.map_or(Value::Null, |tvq| tvq.value.clone()))
+                    .collect(),
+                qualities: matches.iter().map(|m| m.map(|tvq| tvq.quality)).collect(),
+            });
+        }
+
+        Self {
+            timestamps,
+            columns,
+        }
+    }
+
+    /// The shared timestamp index.
+    pub fn timestamps(&self) -> &[SystemTime] {
+        &self.timestamps
+    }
+
+    /// All columns, in the order the series were given.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The tag names, in column order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|c| c.tag_name.as_str())
+    }
+
+    /// The column for a tag.
+    pub fn column(&self, tag_name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.tag_name == tag_name)
+    }
+
+    /// The number of rows (timestamps).
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// The number of columns (tags).
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The value of a tag at a row.
+    pub fn value(&self, row: usize, tag_name: &str) -> Option<&Value> {
+        self.column(tag_name)?.values.get(row)
+    }
+
+    /// A single row.
+    pub fn row(&self, index: usize) -> Option<Row<'_>> {
+        (index < self.len()).then_some(Row { frame: self, index })
+    }
+
+    /// Iterate rows in timestamp order.
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> {
+        (0..self.len()).map(move |index| Row { frame: self, index })
+    }
+}
+
+impl From<&GetRawDataResponse> for TimeSeriesFrame {
+    fn from(resp: &GetRawDataResponse) -> Self {
+        let series: Vec<TagSeries> = resp.raw_data.iter().map(TagSeries::from).collect();
+        Self::from_series(&series)
+    }
+}
+
+impl From<&GetAggregateDataResponse> for TimeSeriesFrame {
+    fn from(resp: &GetAggregateDataResponse) -> Self {
+        let series: Vec<TagSeries> = resp.aggregated_data.iter().map(TagSeries::from).collect();
+        Self::from_series(&series)
+    }
+}
+
+/// A borrowed view of one frame row.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    frame: &'a TimeSeriesFrame,
+    index: usize,
+}
+
+impl<'a> Row<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        self.frame.timestamps[self.index]
+    }
+
+    /// The value of a tag in this row.
+    pub fn value(&self, tag_name: &str) -> Option<&'a Value> {
+        self.frame.value(self.index, tag_name)
+    }
+
+    /// The quality of a tag in this row, if it had a sample.
+    pub fn quality(&self, tag_name: &str) -> Option<Quality> {
+        self.frame.column(tag_name)?.qualities[self.index]
+    }
+
+    /// Iterate `(tag_name, value, quality)` across the row, in column order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a Value, Option<Quality>)> + 'a {
+        let index = self.index;
+        self.frame
+            .columns
+            .iter()
+            .map(move |c| (c.tag_name.as_str(), &c.values[index], c.qualities[index]))
+    }
+}
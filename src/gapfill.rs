@@ -0,0 +1,497 @@
+//! Resampling series onto a regular grid, filling or flagging the gaps.
+//!
+//! Historian samples arrive whenever a value changes, while most analysis
+//! and ML pipelines want one row per fixed step. [`GapFill`] puts each
+//! series on a grid of `start`, `start + interval`, ... before `end`. A grid
+//! point with a sample in the step leading up to it takes the latest such
+//! sample; any other point is a gap, which the tag's [`FillMethod`] either
+//! fills or marks as NaN:
+//!
+//! ```
+//! use std::time::{Duration, SystemTime};
+//! use crowsong::gapfill::{FillMethod, GapFill};
+//! use crowsong::{Quality, TagSeries, Tvq, Value};
+//!
+//! let t0 = SystemTime::UNIX_EPOCH;
+//! let tvq = |secs, value| Tvq {
+//!     timestamp: t0 + Duration::from_secs(secs),
+//!     value: Value::Float(value),
+//!     quality: Quality::GOOD,
+//! };
+//! let series = TagSeries {
+//!     tag_name: "Flow".into(),
+//!     eng_units: None,
+//!     tvqs: vec![tvq(0, 1.0), tvq(30, 4.0)],
+//! };
+//! let filled = GapFill::new(Duration::from_secs(10))
+//!     .method(FillMethod::Linear)
+//!     .fill(&series, t0, t0 + Duration::from_secs(40));
+//! assert_eq!(filled.values, [1.0, 2.0, 3.0, 4.0]);
+//! assert_eq!(filled.filled, [false, true, true, false]);
+//! ```
+//!
+//! Filled points keep the quality of the samples they came from (the worse
+//! of the two for linear fills), so a value held from an uncertain sample
+//! stays uncertain. A gap that follows a sample the quality filter rejects
+//! is always NaN and carries that sample's quality; other NaN points are
+//! [`Quality::BAD`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::quality::{Quality, QualityFilter};
+use crate::types::{TagSeries, Tvq, Value};
+
+/// How a gap in a series is filled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FillMethod {
+    /// Repeat the last sample before the gap.
+    #[default]
+    Hold,
+    /// Interpolate between the samples either side of the gap.
+    Linear,
+    /// Leave the gap as NaN.
+    Nan,
+}
+
+impl fmt::Display for FillMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FillMethod::Hold => "hold",
+            FillMethod::Linear => "linear",
+            FillMethod::Nan => "nan",
+        })
+    }
+}
+
+/// Error returned when a fill method name is not recognized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFillMethodError(String);
+
+impl fmt::Display for ParseFillMethodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown fill method {:?}; expected hold, linear, or nan",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseFillMethodError {}
+
+impl FromStr for FillMethod {
+    type Err = ParseFillMethodError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "hold" | "last" | "ffill" | "previous" => Ok(FillMethod::Hold),
+            "linear" | "interpolate" => Ok(FillMethod::Linear),
+            "nan" | "none" => Ok(FillMethod::Nan),
+            _ => Err(ParseFillMethodError(s.to_string())),
+        }
+    }
+}
+
+/// One series on a regular grid, as parallel columns.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FilledSeries {
+    pub tag_name: String,
+    pub eng_units: Option<String>,
+    pub timestamps: Vec<SystemTime>,
+    /// Values, NaN where a gap was left unfilled.
+    pub values: Vec<f64>,
+    pub qualities: Vec<Quality>,
+    /// Whether each point was filled or marked, rather than sampled.
+    pub filled: Vec<bool>,
+}
+
+impl FilledSeries {
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// The grid as a plain series, for the frame and export paths.
+    pub fn to_series(&self) -> TagSeries {
+        TagSeries {
+            tag_name: self.tag_name.clone(),
+            eng_units: self.eng_units.clone(),
+            tvqs: self
+                .timestamps
+                .iter()
+                .zip(&self.values)
+                .zip(&self.qualities)
+                .map(|((&timestamp, &value), &quality)| Tvq {
+                    timestamp,
+                    value: Value::Float(value),
+                    quality,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Settings for putting series on a regular grid.
+#[derive(Debug, Clone)]
+pub struct GapFill {
+    interval: Duration,
+    method: FillMethod,
+    tag_methods: HashMap<String, FillMethod>,
+    max_gap: Option<Duration>,
+    quality: QualityFilter,
+}
+
+impl GapFill {
+    /// A grid with points every `interval`, holding the last value across
+    /// gaps.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.max(Duration::from_nanos(1)),
+            method: FillMethod::Hold,
+            tag_methods: HashMap::new(),
+            max_gap: None,
+            quality: QualityFilter::NonBad,
+        }
+    }
+
+    /// How to fill gaps in tags without a method of their own. Defaults to
+    /// [`FillMethod::Hold`].
+    pub fn method(mut self, method: FillMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// How to fill gaps in `tag_name`.
+    pub fn tag_method(mut self, tag_name: impl Into<String>, method: FillMethod) -> Self {
+        self.tag_methods.insert(tag_name.into(), method);
+        self
+    }
+
+    /// Leave gaps as NaN when the samples either side are further apart than
+    /// `max_gap`, instead of filling across an outage.
+    pub fn max_gap(mut self, max_gap: Duration) -> Self {
+        self.max_gap = Some(max_gap);
+        self
+    }
+
+    /// Which samples count; the rest end the value before them. Defaults to
+    /// [`QualityFilter::NonBad`].
+    pub fn quality(mut self, quality: QualityFilter) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// The method used for `tag_name`.
+    pub fn method_for(&self, tag_name: &str) -> FillMethod {
+        self.tag_methods
+            .get(tag_name)
+            .copied()
+            .unwrap_or(self.method)
+    }
+
+    /// The grid points in `start..end`.
+    pub fn grid(&self, start: SystemTime, end: SystemTime) -> Vec<SystemTime> {
+        std::iter::successors(Some(start), |t| t.checked_add(self.interval))
+            .take_while(|t| *t < end)
+            .collect()
+    }
+
+    /// Put one series, in time order, on the grid over `start..end`.
+    /// Include the samples either side of the range to fill its edges.
+    pub fn fill(&self, series: &TagSeries, start: SystemTime, end: SystemTime) -> FilledSeries {
+        let method = self.method_for(&series.tag_name);
+        let tvqs = &series.tvqs;
+        let mut filled = FilledSeries {
+            tag_name: series.tag_name.clone(),
+            eng_units: series.eng_units.clone(),
+            ..Default::default()
+        };
+        // Index of the first sample after the grid point.
+        let mut next = 0;
+        for t in self.grid(start, end) {
+            while next < tvqs.len() && tvqs[next].timestamp <= t {
+                next += 1;
+            }
+            let prev = next.checked_sub(1).map(|i| &tvqs[i]);
+            let (value, quality, was_filled) = self.point(method, t, prev, tvqs.get(next));
+            filled.timestamps.push(t);
+            filled.values.push(value);
+            filled.qualities.push(quality);
+            filled.filled.push(was_filled);
+        }
+        filled
+    }
+
+    /// Put each series on the grid over `start..end`.
+    pub fn fill_all(
+        &self,
+        series: &[TagSeries],
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Vec<FilledSeries> {
+        series.iter().map(|s| self.fill(s, start, end)).collect()
+    }
+
+    /// Read raw samples for `tags` over `start..end`, with the bounding
+    /// samples, and put each on the grid, in order.
+    #[cfg(feature = "views")]
+    pub async fn fetch(
+        &self,
+        client: &crate::views_client::ViewsClient,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<Vec<FilledSeries>, crate::error::Error> {
+        let mut series: Vec<TagSeries> = tags
+            .into_iter()
+            .map(|tag| TagSeries {
+                tag_name: tag.into(),
+                ..Default::default()
+            })
+            .collect();
+        let names: Vec<String> = series.iter().map(|s| s.tag_name.clone()).collect();
+        let pages = crate::bulk::BulkFetch::new(view, &names, start, end)
+            .bounds(true)
+            .run(client)
+            .await?;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            if let Some(i) = names.iter().position(|t| *t == page.tag_name) {
+                series[i]
+                    .tvqs
+                    .extend((0..page.len()).map(|row| page.tvq(row)));
+            }
+        }
+        Ok(self.fill_all(&series, start, end))
+    }
+
+    /// The value, quality, and filled flag at grid point `t`, given the last
+    /// sample at or before it and the first sample after it.
+    fn point(
+        &self,
+        method: FillMethod,
+        t: SystemTime,
+        prev: Option<&Tvq>,
+        next: Option<&Tvq>,
+    ) -> (f64, Quality, bool) {
+        let Some(prev) = prev else {
+            return (f64::NAN, Quality::BAD, true);
+        };
+        let Some(value) = self.usable(prev) else {
+            return (f64::NAN, prev.quality, true);
+        };
+        let age = t.duration_since(prev.timestamp).unwrap_or_default();
+        if age < self.interval {
+            return (value, prev.quality, false);
+        }
+        let within = |span: Duration| self.max_gap.is_none_or(|max| span <= max);
+        match method {
+            FillMethod::Hold if within(age) => (value, prev.quality, true),
+            FillMethod::Linear => {
+                let Some((next, next_value)) = next.and_then(|n| Some((n, self.usable(n)?))) else {
+                    return (f64::NAN, Quality::BAD, true);
+                };
+                let span = next
+                    .timestamp
+                    .duration_since(prev.timestamp)
+                    .unwrap_or_default();
+                if !within(span) || span.is_zero() {
+                    return (f64::NAN, Quality::BAD, true);
+                }
+                let share = age.as_secs_f64() / span.as_secs_f64();
+                let quality = worse(prev.quality, next.quality);
+                (value + (next_value - value) * share, quality, true)
+            }
+            _ => (f64::NAN, Quality::BAD, true),
+        }
+    }
+
+    fn usable(&self, tvq: &Tvq) -> Option<f64> {
+        if !self.quality.accepts(tvq.quality) {
+            return None;
+        }
+        tvq.value.as_f64().filter(|v| v.is_finite())
+    }
+}
+
+/// The lower of two qualities by major status, bad below uncertain below
+/// good.
+fn worse(a: Quality, b: Quality) -> Quality {
+    let rank = |q: Quality| {
+        if q.is_good() {
+            2
+        } else if q.is_uncertain() {
+            1
+        } else {
+            0
+        }
+    };
+    if rank(b) < rank(a) { b } else { a }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn tvq(secs: u64, value: f64, quality: Quality) -> Tvq {
+        Tvq {
+            timestamp: at(secs),
+            value: Value::Float(value),
+            quality,
+        }
+    }
+
+    fn series(tvqs: Vec<Tvq>) -> TagSeries {
+        TagSeries {
+            tag_name: "Flow".into(),
+            eng_units: None,
+            tvqs,
+        }
+    }
+
+    /// Values with NaN as `None`, which compare equal.
+    fn values(filled: &FilledSeries) -> Vec<Option<f64>> {
+        filled
+            .values
+            .iter()
+            .map(|v| (!v.is_nan()).then_some(*v))
+            .collect()
+    }
+
+    #[test]
+    fn hold_stops_past_max_gap() {
+        let outage = series(vec![
+            tvq(0, 1.0, Quality::GOOD),
+            tvq(60, 2.0, Quality::GOOD),
+        ]);
+        let fill = GapFill::new(Duration::from_secs(10));
+        let held = fill.fill(&outage, at(0), at(70));
+        assert_eq!(held.values, [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0]);
+
+        let held = fill
+            .max_gap(Duration::from_secs(30))
+            .fill(&outage, at(0), at(70));
+        let nan = None;
+        let one = Some(1.0);
+        assert_eq!(values(&held), [one, one, one, one, nan, nan, Some(2.0)]);
+        assert_eq!(held.filled, [false, true, true, true, true, true, false]);
+        assert_eq!(held.qualities[4], Quality::BAD);
+    }
+
+    #[test]
+    fn linear_stops_past_max_gap() {
+        let outage = series(vec![
+            tvq(0, 0.0, Quality::GOOD),
+            tvq(60, 6.0, Quality::GOOD),
+        ]);
+        let fill = GapFill::new(Duration::from_secs(20)).method(FillMethod::Linear);
+        let within = fill.clone().max_gap(Duration::from_secs(60));
+        assert_eq!(
+            within.fill(&outage, at(0), at(80)).values,
+            [0.0, 2.0, 4.0, 6.0]
+        );
+
+        let beyond = fill
+            .max_gap(Duration::from_secs(59))
+            .fill(&outage, at(0), at(80));
+        assert_eq!(values(&beyond), [Some(0.0), None, None, Some(6.0)]);
+        assert_eq!(
+            beyond.qualities,
+            [Quality::GOOD, Quality::BAD, Quality::BAD, Quality::GOOD]
+        );
+    }
+
+    #[test]
+    fn linear_needs_a_usable_sample_after_the_gap() {
+        let trailing = series(vec![tvq(0, 1.0, Quality::GOOD), tvq(20, 9.0, Quality::BAD)]);
+        let filled = GapFill::new(Duration::from_secs(10))
+            .method(FillMethod::Linear)
+            .fill(&trailing, at(0), at(20));
+        assert_eq!(values(&filled), [Some(1.0), None]);
+        assert_eq!(filled.qualities[1], Quality::BAD);
+    }
+
+    #[test]
+    fn nan_points_carry_the_quality_of_their_cause() {
+        let uncertain = Quality(0x44);
+        let comm_failure = Quality(0x18);
+        let tvqs = vec![
+            tvq(10, 1.0, uncertain),
+            tvq(30, 5.0, comm_failure),
+            tvq(50, f64::NAN, Quality::GOOD),
+            tvq(70, 3.0, Quality::GOOD),
+        ];
+        let filled = GapFill::new(Duration::from_secs(10)).fill(&series(tvqs), at(0), at(80));
+        let nan = None;
+        assert_eq!(
+            values(&filled),
+            [nan, Some(1.0), Some(1.0), nan, nan, nan, nan, Some(3.0)]
+        );
+        assert_eq!(
+            filled.qualities,
+            [
+                // Before the first sample.
+                Quality::BAD,
+                uncertain,
+                uncertain,
+                // Rejected by the quality filter.
+                comm_failure,
+                comm_failure,
+                // Good but not a number.
+                Quality::GOOD,
+                Quality::GOOD,
+                Quality::GOOD,
+            ]
+        );
+        assert_eq!(
+            filled.filled,
+            [true, false, true, true, true, true, true, false]
+        );
+
+        let any = GapFill::new(Duration::from_secs(10)).quality(QualityFilter::Any);
+        let filled = any.fill(&series(vec![tvq(0, 5.0, comm_failure)]), at(0), at(20));
+        assert_eq!(filled.values, [5.0, 5.0]);
+        assert_eq!(filled.qualities, [comm_failure, comm_failure]);
+    }
+
+    #[test]
+    fn linear_fills_take_the_worse_quality() {
+        let uncertain = Quality(0x40);
+        let tvqs = vec![tvq(0, 0.0, Quality::GOOD), tvq(20, 2.0, uncertain)];
+        let filled = GapFill::new(Duration::from_secs(10))
+            .tag_method("Flow", FillMethod::Linear)
+            .fill(&series(tvqs), at(0), at(20));
+        assert_eq!(filled.values, [0.0, 1.0]);
+        assert_eq!(filled.qualities, [Quality::GOOD, uncertain]);
+    }
+
+    #[test]
+    fn nan_method_leaves_every_gap() {
+        let tvqs = vec![tvq(0, 1.0, Quality::GOOD), tvq(20, 2.0, Quality::GOOD)];
+        let filled = GapFill::new(Duration::from_secs(10))
+            .method(FillMethod::Nan)
+            .fill(&series(tvqs), at(0), at(30));
+        assert_eq!(values(&filled), [Some(1.0), None, Some(2.0)]);
+        assert_eq!("ffill".parse(), Ok(FillMethod::Hold));
+        assert_eq!(" Interpolate ".parse(), Ok(FillMethod::Linear));
+        assert!("zero".parse::<FillMethod>().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Hedged reads for latency-sensitive callers such as dashboards.
+//!
+//! A [`HedgedClient`] sends a read, and if it hasn't completed within a delay
+//! derived from recent latencies (by default the 95th percentile), sends a
+//! second attempt, optionally to a secondary endpoint. Whichever attempt
+//! succeeds first wins and the other is cancelled.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::canary::views::grpc::api::*;
+use crate::error::Error;
+use crate::request_id;
+use crate::views_client::ViewsClient;
+
+/// When to fire the second attempt of a hedged read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HedgePolicy {
+    percentile: Option<f64>,
+    initial_delay: Duration,
+    min_delay: Duration,
+    window: usize,
+    min_samples: usize,
+}
+
+impl Default for HedgePolicy {
+    fn default() -> Self {
+        Self {
+            percentile: Some(0.95),
+            initial_delay: Duration::from_millis(100),
+            min_delay: Duration::from_millis(10),
+            window: 100,
+            min_samples: 10,
+        }
+    }
+}
+
+impl HedgePolicy {
+    /// Hedge after the 95th percentile of the last 100 read latencies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always hedge after a fixed delay.
+    pub fn fixed(delay: Duration) -> Self {
+        Self {
+            percentile: None,
+            initial_delay: delay,
+            min_delay: Duration::ZERO,
+            ..Self::default()
+        }
+    }
+
+    /// The latency percentile, in `0.0..=1.0`, after which to hedge.
+    pub fn percentile(mut self, percentile: f64) -> Self {
+        self.percentile = Some(percentile.clamp(0.0, 1.0));
+        self
+    }
+
+    /// The delay used until enough latencies have been observed. Defaults to 100 ms.
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// A floor on the hedge delay, so a fast service isn't hit with a
+    /// duplicate of every read. Defaults to 10 ms.
+    pub fn min_delay(mut self, delay: Duration) -> Self {
+        self.min_delay = delay;
+        self
+    }
+
+    /// How many recent latencies the percentile is computed over. Defaults to 100.
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    fn delay(&self, latencies: &VecDeque<Duration>) -> Duration {
+        let Some(percentile) = self.percentile else {
+            return self.initial_delay;
+        };
+        if latencies.len() < self.min_samples.min(self.window) {
+            return self.initial_delay.max(self.min_delay);
+        }
+        let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = ((sorted.len() - 1) as f64 * percentile).round() as usize;
+        sorted[rank].max(self.min_delay)
+    }
+}
+
+/// Counters describing how often hedging kicked in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HedgeStats {
+    /// Reads issued through the hedged client.
+    pub reads: u64,
+    /// Reads for which a second attempt was sent.
+    pub hedged: u64,
+    /// Hedged reads answered by the second attempt.
+    pub hedge_wins: u64,
+}
+
+/// A [`ViewsClient`] whose reads are hedged according to a [`HedgePolicy`].
+pub struct HedgedClient {
+    primary: ViewsClient,
+    secondary: Option<ViewsClient>,
+    policy: HedgePolicy,
+    latencies: VecDeque<Duration>,
+    stats: HedgeStats,
+}
+
+impl HedgedClient {
+    /// Hedge reads against `primary` itself.
+    pub fn new(primary: ViewsClient, policy: HedgePolicy) -> Self {
+        Self {
+            primary,
+            secondary: None,
+            policy,
+            latencies: VecDeque::new(),
+            stats: HedgeStats::default(),
+        }
+    }
+
+    /// Send second attempts to another connection, e.g. a replica endpoint.
+    pub fn with_secondary(mut self, secondary: ViewsClient) -> Self {
+        self.secondary = Some(secondary);
+        self
+    }
+
+    /// The delay after which the next read will be hedged.
+    pub fn current_delay(&self) -> Duration {
+        self.policy.delay(&self.latencies)
+    }
+
+    pub fn stats(&self) -> HedgeStats {
+        self.stats
+    }
+
+    /// The primary client, for calls that shouldn't be hedged.
+    pub fn primary_mut(&mut self) -> &mut ViewsClient {
+        &mut self.primary
+    }
+
+    pub fn into_inner(self) -> (ViewsClient, Option<ViewsClient>) {
+        (self.primary, self.secondary)
+    }
+
+    /// Run `read` with hedging. It is called once per attempt with a handle on
+    /// the connection that attempt should use.
+    /// Both attempts are sent under the same request ID.
+    pub async fn hedge<T, F, Fut>(&mut self, read: F) -> Result<T, Error>
+    where
+        F: Fn(ViewsClient) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        request_id::operation(self.race(read)).await
+    }
+
+    async fn race<T, F, Fut>(&mut self, read: F) -> Result<T, Error>
+    where
+        F: Fn(ViewsClient) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.stats.reads += 1;
+        let delay = self.current_delay();
+        let started = Instant::now();
+        let first = read(self.primary.handle());
+        tokio::pin!(first);
+
+        let result = tokio::select! {
+            result = &mut first => result,
+            _ = tokio::time::sleep(delay) => {
+                self.stats.hedged += 1;
+                let second_client = self.secondary.as_ref().unwrap_or(&self.primary).handle();
+                let second_started = Instant::now();
+                let second = read(second_client);
+                tokio::pin!(second);
+                tokio::select! {
+                    result = &mut first => match result {
+                        Ok(value) => Ok(value),
+                        Err(_) => {
+                            self.stats.hedge_wins += 1;
+                            let result = second.await;
+                            self.record(second_started.elapsed());
+                            return result;
+                        }
+                    },
+                    result = &mut second => match result {
+                        Ok(value) => {
+                            self.stats.hedge_wins += 1;
+                            self.record(second_started.elapsed());
+                            return Ok(value);
+                        }
+                        Err(_) => first.await,
+                    },
+                }
+            }
+        };
+        self.record(started.elapsed());
+        result
+    }
+
+    fn record(&mut self, latency: Duration) {
+        if self.latencies.len() == self.policy.window {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+    }
+
+    /// Get raw data, hedged.
+    pub async fn get_raw_data(
+        &mut self,
+        request: GetRawDataRequest,
+    ) -> Result<GetRawDataResponse, Error> {
+        self.hedge(|client| {
+            let request = request.clone();
+            async move { client.get_raw_data(request).await }
+        })
+        .await
+    }
+
+    /// Get aggregate data, hedged.
+    pub async fn get_aggregate_data(
+        &mut self,
+        request: GetAggregateDataRequest,
+    ) -> Result<GetAggregateDataResponse, Error> {
+        self.hedge(|client| {
+            let request = request.clone();
+            async move { client.get_aggregate_data(request).await }
+        })
+        .await
+    }
+
+    /// Get the current values of tags, hedged.
+    pub async fn get_tag_current_value(
+        &mut self,
+        request: GetTagCurrentValueRequest,
+    ) -> Result<GetTagCurrentValueResponse, Error> {
+        self.hedge(|client| {
+            let request = request.clone();
+            async move { client.get_tag_current_value(request).await }
+        })
+        .await
+    }
+}
@@ -0,0 +1,232 @@
+//! A task-oriented facade over the Views and Store & Forward clients.
+//!
+//! [`CanaryHistorian`] is a single entry point for applications that want
+//! to find tags, read them aligned on a common time index, write samples
+//! back, and check that the writes landed, without assembling requests for
+//! the individual RPCs. The underlying clients stay reachable through
+//! [`views`](CanaryHistorian::views) and
+//! [`store_and_forward`](CanaryHistorian::store_and_forward) for anything
+//! the facade doesn't cover.
+
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+use tonic::Status;
+
+use crate::error::Error;
+use crate::frame::TimeSeriesFrame;
+use crate::saf_client::StoreAndForwardClient;
+use crate::types::{TagSeries, Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// Historian access for application code.
+pub struct CanaryHistorian {
+    views: ViewsClient,
+    saf: Option<StoreAndForwardClient>,
+    session: String,
+}
+
+impl CanaryHistorian {
+    /// Read through `views`. Writing needs a Store & Forward client too; see
+    /// [`with_store_and_forward`](Self::with_store_and_forward).
+    pub fn new(views: ViewsClient) -> Self {
+        Self {
+            views,
+            saf: None,
+            session: "crowsong".to_string(),
+        }
+    }
+
+    /// Write through `saf`, opening a session the first time samples are
+    /// written unless one is already open.
+    pub fn with_store_and_forward(mut self, saf: StoreAndForwardClient) -> Self {
+        self.saf = Some(saf);
+        self
+    }
+
+    /// The name of the Store & Forward session opened for writes. Defaults
+    /// to `crowsong`.
+    pub fn session_name(mut self, name: impl Into<String>) -> Self {
+        self.session = name.into();
+        self
+    }
+
+    /// The Views client, for calls the facade doesn't cover.
+    pub fn views(&mut self) -> &mut ViewsClient {
+        &mut self.views
+    }
+
+    /// The Store & Forward client, if there is one.
+    pub fn store_and_forward(&mut self) -> Option<&mut StoreAndForwardClient> {
+        self.saf.as_mut()
+    }
+
+    /// The names of the tags in `view` matching `pattern`, sorted. `*`
+    /// matches any run of characters and `?` any one character, ignoring
+    /// ASCII case, so `Plant.Line?.*Temp*` finds every line's temperatures.
+    pub async fn resolve_tags(&mut self, view: &str, pattern: &str) -> Result<Vec<String>, Error> {
+        let datasets = self.views.get_dataset_list(view, false).await?;
+        let mut tags = Vec::new();
+        for dataset in &datasets.datasets {
+            let names = self.views.get_all_tags(view, dataset).await?;
+            tags.extend(names.into_iter().filter(|tag| glob_match(pattern, tag)));
+        }
+        tags.sort_unstable();
+        tags.dedup();
+        Ok(tags)
+    }
+
+    /// Read the raw samples of `tags` in `range`, one series per tag with
+    /// its engineering units.
+    pub async fn read_raw(
+        &mut self,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        range: Range<SystemTime>,
+    ) -> Result<Vec<TagSeries>, Error> {
+        self.views
+            .query()
+            .view(view)
+            .tags(tags)
+            .range(range.start, range.end)
+            .with_eng_units()
+            .fetch()
+            .await
+    }
+
+    /// Read `aggregate` (e.g. `"TimeAverage"`) of `tags` over each
+    /// `interval` of `range`, aligned on one time index.
+    pub async fn read_aligned(
+        &mut self,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        range: Range<SystemTime>,
+        interval: Duration,
+        aggregate: &str,
+    ) -> Result<TimeSeriesFrame, Error> {
+        self.views
+            .query()
+            .view(view)
+            .tags(tags)
+            .range(range.start, range.end)
+            .aggregate(aggregate, interval)
+            .with_eng_units()
+            .fetch_frame()
+            .await
+    }
+
+    /// Write samples to the tag at `tag_path` (`Dataset.Tag`) through Store
+    /// & Forward.
+    pub async fn write(&mut self, tag_path: &str, tvqs: &[Tvq]) -> Result<(), Error> {
+        let saf = self
+            .saf
+            .as_mut()
+            .ok_or_else(|| Status::failed_precondition("writing needs a Store & Forward client"))?;
+        if saf.session_token().is_none() {
+            saf.open_session(self.session.clone()).await?;
+        }
+        saf.store_tvqs(tag_path, tvqs).await
+    }
+
+    /// Read `tag` back from `view` over the span of `expected` and compare.
+    /// Written samples can take a moment to become readable, so verify a
+    /// little after writing.
+    pub async fn verify(
+        &mut self,
+        view: &str,
+        tag: &str,
+        expected: &[Tvq],
+    ) -> Result<Verification, Error> {
+        let (Some(first), Some(last)) = (
+            expected.iter().map(|tvq| tvq.timestamp).min(),
+            expected.iter().map(|tvq| tvq.timestamp).max(),
+        ) else {
+            return Ok(Verification::default());
+        };
+        // The end of a raw read is exclusive.
+        let end = last + Duration::from_nanos(1);
+        let mut actual = self.views.tag(view, tag).raw(first..end).await?;
+        actual.sort_by_key(|tvq| tvq.timestamp);
+
+        let mut verification = Verification::default();
+        for tvq in expected {
+            let found = actual
+                .binary_search_by_key(&tvq.timestamp, |a| a.timestamp)
+                .map(|i| &actual[i]);
+            match found.ok() {
+                None => verification.missing.push(tvq.clone()),
+                Some(a) if !same_value(&a.value, &tvq.value) || a.quality != tvq.quality => {
+                    verification.mismatched.push((tvq.clone(), a.clone()))
+                }
+                Some(_) => verification.matched += 1,
+            }
+        }
+        Ok(verification)
+    }
+
+    /// Close any Store & Forward session, then shut the Views connection down.
+    pub async fn close(mut self) -> Result<(), Error> {
+        if let Some(saf) = &mut self.saf
+            && saf.session_token().is_some()
+        {
+            saf.close_session().await?;
+        }
+        self.views.shutdown().await
+    }
+}
+
+/// The outcome of [`CanaryHistorian::verify`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Verification {
+    /// How many samples were read back as written.
+    pub matched: usize,
+    /// Samples with no stored sample at their timestamp.
+    pub missing: Vec<Tvq>,
+    /// Samples stored with another value or quality, as (expected, stored).
+    pub mismatched: Vec<(Tvq, Tvq)>,
+}
+
+impl Verification {
+    /// Whether every sample was read back as written.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// Whether a stored value matches a written one. Numbers compare by value,
+/// and at single precision, since a tag may store a narrower type than the
+/// one written.
+fn same_value(stored: &Value, written: &Value) -> bool {
+    match (stored.as_f64(), written.as_f64()) {
+        (Some(a), Some(b)) => a == b || a as f32 == b as f32,
+        _ => stored == written,
+    }
+}
+
+/// Match `text` against a `*`/`?` wildcard pattern, ignoring ASCII case.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`: its pattern index and the text
+    // index it currently stretches to.
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len()
+            && (pattern[p] == '?' || pattern[p].eq_ignore_ascii_case(&text[t]))
+        {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,247 @@
+//! History reads with plain arguments.
+//!
+//! [`ViewsClient::get_raw`] reads raw history for a few tags without building
+//! a `GetRawDataRequest` by hand, following continuation points so no tag is
+//! cut short, and returns each tag's samples keyed by name:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use std::time::{Duration, SystemTime};
+//! use crowsong::RawOptions;
+//!
+//! let end = SystemTime::now();
+//! let start = end - Duration::from_secs(3600);
+//! let tags = ["Plant.Line1.Temperature", "Plant.Line1.Pressure"];
+//! let history = client
+//!     .get_raw("Plant", &tags, start, end, RawOptions::new().bounds(true))
+//!     .await?;
+//! for (tag, tvqs) in &history {
+//!     println!("{tag}: {} samples", tvqs.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ViewsClient::get_aggregates`] does the same for aggregated history, with
+//! an [`Aggregate`] naming the function, optionally a different one for some
+//! tags, and whether to use sloped extrapolation:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient, start: std::time::SystemTime, end: std::time::SystemTime) -> Result<(), crowsong::Error> {
+//! use std::time::Duration;
+//! use crowsong::Aggregate;
+//!
+//! let tags = ["Plant.Line1.Temperature", "Plant.Line1.Runtime"];
+//! let aggregate = Aggregate::new("TimeAverage").tag("Plant.Line1.Runtime", "Delta");
+//! let history = client
+//!     .get_aggregates("Plant", &tags, start, end, Duration::from_secs(900), aggregate)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `start` and `end` take anything that converts into a `SystemTime`,
+//! including a `chrono::DateTime`.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use tonic::Status;
+
+use crate::canary::views::grpc::api::get_aggregate_data_response::Status as AggregateStatus;
+use crate::canary::views::grpc::api::{
+    AggregateTagRequest, GetAggregateDataRequest, GetAggregateDataResponse, GetRawDataRequest,
+    RawTagRequest,
+};
+use crate::error::{CanaryError, Error};
+use crate::types::Tvq;
+use crate::views_client::ViewsClient;
+
+/// Options for [`ViewsClient::get_raw`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawOptions {
+    page_size: i32,
+    bounds: bool,
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        Self {
+            page_size: 10_000,
+            bounds: false,
+        }
+    }
+}
+
+impl RawOptions {
+    /// Pages of 10,000 samples per tag, without bounding values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many samples per tag to ask for in each page. At least 1.
+    pub fn page_size(mut self, samples: i32) -> Self {
+        self.page_size = samples.max(1);
+        self
+    }
+
+    /// Include the last sample before the range and the first after it.
+    pub fn bounds(mut self, bounds: bool) -> Self {
+        self.bounds = bounds;
+        self
+    }
+}
+
+/// The aggregate function for a [`ViewsClient::get_aggregates`] call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregate {
+    name: String,
+    per_tag: HashMap<String, String>,
+    sloped: bool,
+}
+
+impl Aggregate {
+    /// Apply the aggregate `name`, such as `TimeAverage`, to every tag.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            per_tag: HashMap::new(),
+            sloped: false,
+        }
+    }
+
+    /// Apply the aggregate `name` to `tag` instead.
+    pub fn tag(mut self, tag: impl Into<String>, name: impl Into<String>) -> Self {
+        self.per_tag.insert(tag.into(), name.into());
+        self
+    }
+
+    /// Interpolate between samples with sloped rather than stepped
+    /// extrapolation. Off by default.
+    pub fn sloped(mut self, sloped: bool) -> Self {
+        self.sloped = sloped;
+        self
+    }
+
+    fn name_for(&self, tag: &str) -> &str {
+        self.per_tag.get(tag).unwrap_or(&self.name)
+    }
+}
+
+impl From<&str> for Aggregate {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Aggregate {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl ViewsClient {
+    /// Every raw sample of `tags` between `start` and `end`, keyed by tag
+    /// name, oldest first. A tag the server returned nothing for is absent.
+    pub async fn get_raw(
+        &self,
+        view: impl Into<String>,
+        tags: &[impl AsRef<str>],
+        start: impl Into<SystemTime>,
+        end: impl Into<SystemTime>,
+        options: RawOptions,
+    ) -> Result<HashMap<String, Vec<Tvq>>, Error> {
+        let (start, end): (SystemTime, SystemTime) = (start.into(), end.into());
+        let request = GetRawDataRequest {
+            view: view.into(),
+            requests: tags
+                .iter()
+                .map(|tag| RawTagRequest {
+                    tag_name: tag.as_ref().to_string(),
+                    start_time: Some(start.into()),
+                    end_time: Some(end.into()),
+                    client_data: 0,
+                    continuation_point: Vec::new(),
+                })
+                .collect(),
+            max_count_per_tag: options.page_size,
+            return_bounds: options.bounds,
+            return_annotations: false,
+            cci: 0,
+        };
+        let resp = self.get_raw_data_complete(request).await?;
+        Ok(resp
+            .raw_data
+            .into_iter()
+            .map(|data| (data.tag_name, data.tvqs.iter().map(Tvq::from).collect()))
+            .collect())
+    }
+
+    /// `tags` aggregated over each `interval` between `start` and `end`,
+    /// keyed by tag name, oldest first. `InvalidArgument` if `interval` is
+    /// too long to send, and an error if the server couldn't aggregate a tag.
+    pub async fn get_aggregates(
+        &self,
+        view: impl Into<String>,
+        tags: &[impl AsRef<str>],
+        start: impl Into<SystemTime>,
+        end: impl Into<SystemTime>,
+        interval: Duration,
+        aggregate: impl Into<Aggregate>,
+    ) -> Result<HashMap<String, Vec<Tvq>>, Error> {
+        let (view, aggregate) = (view.into(), aggregate.into());
+        let (start, end): (SystemTime, SystemTime) = (start.into(), end.into());
+        let interval = prost_types::Duration::try_from(interval)
+            .map_err(|_| Status::invalid_argument(format!("interval {interval:?} is too long")))?;
+        let request = GetAggregateDataRequest {
+            view: view.clone(),
+            requests: tags
+                .iter()
+                .map(|tag| AggregateTagRequest {
+                    tag_name: tag.as_ref().to_string(),
+                    aggregate_name: aggregate.name_for(tag.as_ref()).to_string(),
+                    aggregate_configuration: None,
+                    sloped: aggregate.sloped,
+                    client_data: 0,
+                })
+                .collect(),
+            start_time: Some(start.into()),
+            end_time: Some(end.into()),
+            interval: Some(interval),
+            return_annotations: false,
+            cci: 0,
+        };
+        let resp = check_aggregate_status(self.get_aggregate_data(request).await?, view)?;
+        resp.aggregated_data
+            .into_iter()
+            .map(|data| {
+                if data.error_code != 0 {
+                    return Err(Status::internal(format!(
+                        "aggregating {} failed ({}): {}",
+                        data.tag_name, data.error_code, data.error_message
+                    ))
+                    .into());
+                }
+                Ok((data.tag_name, data.tvqs.iter().map(Tvq::from).collect()))
+            })
+            .collect()
+    }
+}
+
+/// `resp`, or the error its extended status describes.
+pub(crate) fn check_aggregate_status(
+    resp: GetAggregateDataResponse,
+    view: String,
+) -> Result<GetAggregateDataResponse, Error> {
+    match resp.extended_status() {
+        AggregateStatus::Unspecified => Ok(resp),
+        AggregateStatus::ViewNotFound => {
+            Err(Status::from(CanaryError::UnknownView { view: Some(view) }).into())
+        }
+        AggregateStatus::NoTagsInRequest => {
+            Err(Status::invalid_argument("no tags in request").into())
+        }
+        AggregateStatus::TooManyTags => Err(Status::resource_exhausted("too many tags").into()),
+        AggregateStatus::TooManyValues => Err(Status::resource_exhausted("too many values").into()),
+    }
+}
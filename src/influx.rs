@@ -0,0 +1,323 @@
+//! Exporting samples as InfluxDB line protocol.
+//!
+//! [`LineProtocol`] renders samples as lines of the form
+//! `measurement,tag=<tag name>[,<static tags>] value=<value>,quality=<q>i <ns>`,
+//! with the measurement, the name of the tag key, and extra tags
+//! configurable. [`InfluxWriter`] POSTs rendered lines to an InfluxDB 1.x or
+//! 2.x write endpoint, and can mirror a bulk extract in batches.
+//!
+//! Values keep their type: floats as floats, integers with the `i` suffix,
+//! booleans, and strings as string fields. Null values are written with only
+//! the quality field.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write as _};
+use std::time::SystemTime;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+
+use crate::bulk::BulkFetch;
+use crate::error::Error;
+use crate::timestamps::system_time_to_nanos;
+use crate::transport::Redacted;
+use crate::types::{TagSeries, Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// How samples map to InfluxDB measurements, tags, and fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineProtocol {
+    measurement: String,
+    measurements: HashMap<String, String>,
+    tag_key: String,
+    static_tags: BTreeMap<String, String>,
+    field: String,
+}
+
+impl Default for LineProtocol {
+    fn default() -> Self {
+        Self {
+            measurement: "canary".to_string(),
+            measurements: HashMap::new(),
+            tag_key: "tag".to_string(),
+            static_tags: BTreeMap::new(),
+            field: "value".to_string(),
+        }
+    }
+}
+
+impl LineProtocol {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The measurement of tags without their own. Defaults to `canary`.
+    pub fn measurement(mut self, measurement: impl Into<String>) -> Self {
+        self.measurement = measurement.into();
+        self
+    }
+
+    /// Write one tag's samples to another measurement.
+    pub fn measurement_for(
+        mut self,
+        tag: impl Into<String>,
+        measurement: impl Into<String>,
+    ) -> Self {
+        self.measurements.insert(tag.into(), measurement.into());
+        self
+    }
+
+    /// The Influx tag key holding the Canary tag name. Defaults to `tag`.
+    pub fn tag_key(mut self, key: impl Into<String>) -> Self {
+        self.tag_key = key.into();
+        self
+    }
+
+    /// Add an Influx tag to every line, e.g. `site=plant1`.
+    pub fn static_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.static_tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// The field holding the value. Defaults to `value`.
+    pub fn field(mut self, field: impl Into<String>) -> Self {
+        self.field = field.into();
+        self
+    }
+
+    /// Render one tag's samples, one line each.
+    pub fn render(&self, tag: &str, tvqs: &[Tvq]) -> String {
+        let mut out = String::new();
+        for tvq in tvqs {
+            self.write_line(&mut out, tag, None, tvq);
+        }
+        out
+    }
+
+    /// Render several tags' samples, adding a `units` tag where the
+    /// engineering units are known.
+    pub fn render_series(&self, series: &[TagSeries]) -> String {
+        let mut out = String::new();
+        for s in series {
+            for tvq in &s.tvqs {
+                self.write_line(&mut out, &s.tag_name, s.eng_units.as_deref(), tvq);
+            }
+        }
+        out
+    }
+
+    fn write_line(&self, out: &mut String, tag: &str, units: Option<&str>, tvq: &Tvq) {
+        let measurement = self.measurements.get(tag).unwrap_or(&self.measurement);
+        out.push_str(&escape(measurement, &[',', ' ']));
+        let mut tags: Vec<(&str, &str)> = vec![(&self.tag_key, tag)];
+        if let Some(units) = units.filter(|u| !u.is_empty()) {
+            tags.push(("units", units));
+        }
+        tags.extend(
+            self.static_tags
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        );
+        // Influx expects tags sorted by key for the best write performance.
+        tags.sort_by_key(|(k, _)| *k);
+        for (key, value) in tags {
+            let _ = write!(
+                out,
+                ",{}={}",
+                escape(key, &[',', '=', ' ']),
+                escape(value, &[',', '=', ' '])
+            );
+        }
+
+        out.push(' ');
+        let field = escape(&self.field, &[',', '=', ' ']);
+        let value = match &tvq.value {
+            Value::Null => None,
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Int(i) => Some(format!("{i}i")),
+            Value::UInt(u) => Some(match i64::try_from(*u) {
+                Ok(i) => format!("{i}i"),
+                Err(_) => format!("{}", *u as f64),
+            }),
+            Value::String(s) => Some(format!("\"{}\"", escape(s, &['"', '\\']))),
+            value => value
+                .as_f64()
+                .filter(|f| f.is_finite())
+                .map(|f| format!("{f:?}")),
+        };
+        if let Some(value) = value {
+            let _ = write!(out, "{field}={value},");
+        }
+        let _ = writeln!(
+            out,
+            "quality={}i {}",
+            tvq.quality.0,
+            system_time_to_nanos(tvq.timestamp)
+        );
+    }
+}
+
+/// Backslash-escape the `special` characters.
+fn escape(s: &str, special: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Writes line protocol to an InfluxDB write endpoint over HTTP or HTTPS.
+#[derive(Clone)]
+pub struct InfluxWriter {
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    url: http::Uri,
+    token: Option<String>,
+    batch_lines: usize,
+}
+
+impl fmt::Debug for InfluxWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InfluxWriter")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| Redacted))
+            .field("batch_lines", &self.batch_lines)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InfluxWriter {
+    /// Write to the full write URL, e.g.
+    /// `http://localhost:8086/write?db=plant&precision=ns`. Timestamps are in
+    /// nanoseconds, so the URL must not set another precision.
+    pub fn new(url: impl AsRef<str>) -> Result<Self, Error> {
+        let url: http::Uri = url.as_ref().parse().map_err(Error::other)?;
+        let connector = HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            url,
+            token: None,
+            batch_lines: 5_000,
+        })
+    }
+
+    /// Write to an InfluxDB 1.x database.
+    pub fn v1(base_url: &str, database: &str) -> Result<Self, Error> {
+        Self::new(format!(
+            "{}/write?db={}&precision=ns",
+            base_url.trim_end_matches('/'),
+            query_escape(database)
+        ))
+    }
+
+    /// Write to an InfluxDB 2.x bucket, authenticating with an API token.
+    pub fn v2(base_url: &str, org: &str, bucket: &str, token: &str) -> Result<Self, Error> {
+        Ok(Self::new(format!(
+            "{}/api/v2/write?org={}&bucket={}&precision=ns",
+            base_url.trim_end_matches('/'),
+            query_escape(org),
+            query_escape(bucket)
+        ))?
+        .token(token))
+    }
+
+    /// Send `Authorization: Token <token>` with each write.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The number of lines per request when writing history. Defaults to
+    /// 5,000.
+    pub fn batch_lines(mut self, lines: usize) -> Self {
+        self.batch_lines = lines.max(1);
+        self
+    }
+
+    /// POST rendered lines in one request.
+    pub async fn write(&self, lines: impl Into<String>) -> Result<(), Error> {
+        let mut request = http::Request::post(self.url.clone())
+            .header(http::header::CONTENT_TYPE, "text/plain; charset=utf-8");
+        if let Some(token) = &self.token {
+            request = request.header(http::header::AUTHORIZATION, format!("Token {token}"));
+        }
+        let resp = self
+            .client
+            .request(
+                request
+                    .body(Full::new(Bytes::from(lines.into())))
+                    .map_err(Error::other)?,
+            )
+            .await
+            .map_err(Error::other)?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = resp
+            .into_body()
+            .collect()
+            .await
+            .map_err(Error::other)?
+            .to_bytes();
+        Err(format!(
+            "InfluxDB write failed ({status}): {}",
+            String::from_utf8_lossy(&body).trim()
+        )
+        .into())
+    }
+
+    /// Read raw history for `tags` over `start..end` and write it as
+    /// `protocol` renders it. Returns the number of samples written.
+    pub async fn write_history(
+        &self,
+        client: &ViewsClient,
+        protocol: &LineProtocol,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<u64, Error> {
+        let pages = BulkFetch::new(view, tags, start, end).run(client).await?;
+        let mut written = 0;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            let tvqs: Vec<Tvq> = (0..page.len()).map(|row| page.tvq(row)).collect();
+            for batch in tvqs.chunks(self.batch_lines) {
+                self.write(protocol.render(&page.tag_name, batch)).await?;
+                written += batch.len() as u64;
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Percent-encode a query parameter value.
+fn query_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+    }
+    out
+}
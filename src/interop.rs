@@ -0,0 +1,8 @@
+//! Conversions into third-party data structures, each behind its own feature.
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "polars")]
+pub mod polars;
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
@@ -0,0 +1,170 @@
+//! Conversions from Canary results into Arrow record batches.
+//!
+//! One batch holds one tag's samples with the [`schema`] columns:
+//! `timestamp` as `Timestamp(ns, UTC)`, `value` as `Float64` (null unless the
+//! sample is numeric or boolean), `quality` as `UInt32`, `kind` as the
+//! [`ValueKind`] discriminant, and nullable `text`/`decimal` columns for
+//! string and decimal samples. The layout is lossless for [`RawColumns`].
+
+use std::sync::Arc;
+
+use arrow_array::{
+    Array, ArrayRef, BinaryArray, Float64Array, RecordBatch, StringArray, TimestampNanosecondArray,
+    UInt8Array, UInt32Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+
+use crate::columnar::{RawColumns, ValueKind};
+use crate::timestamps::system_time_to_nanos;
+use crate::types::{TagSeries, Value};
+
+/// The schema of every batch produced here.
+pub fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("value", DataType::Float64, true),
+        Field::new("quality", DataType::UInt32, false),
+        Field::new("kind", DataType::UInt8, false),
+        Field::new("text", DataType::Utf8, true),
+        Field::new("decimal", DataType::Binary, true),
+    ])
+}
+
+fn value_kind(value: &Value) -> ValueKind {
+    match value {
+        Value::Null => ValueKind::Null,
+        Value::Bool(_) => ValueKind::Bool,
+        Value::Int(_) => ValueKind::Int,
+        Value::UInt(_) => ValueKind::UInt,
+        Value::Float(_) => ValueKind::Float,
+        Value::String(_) => ValueKind::String,
+        Value::Decimal(_) => ValueKind::Decimal,
+    }
+}
+
+fn kind_from_u8(kind: u8) -> ValueKind {
+    match kind {
+        1 => ValueKind::Bool,
+        2 => ValueKind::Int,
+        3 => ValueKind::UInt,
+        4 => ValueKind::Float,
+        5 => ValueKind::String,
+        6 => ValueKind::Decimal,
+        _ => ValueKind::Null,
+    }
+}
+
+impl RawColumns {
+    /// Convert the samples to a record batch.
+    pub fn to_arrow(&self) -> Result<RecordBatch, ArrowError> {
+        let mut text = vec![None; self.len()];
+        let mut decimal = vec![None; self.len()];
+        for (row, value) in &self.non_numeric {
+            match value {
+                Value::String(s) => text[*row] = Some(s.as_str()),
+                Value::Decimal(b) => decimal[*row] = Some(b.as_slice()),
+                _ => {}
+            }
+        }
+        let values = self
+            .values
+            .iter()
+            .zip(&self.kinds)
+            .map(|(v, kind)| kind.is_numeric().then_some(*v));
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampNanosecondArray::from(self.timestamps.clone()).with_timezone("UTC")),
+            Arc::new(values.collect::<Float64Array>()),
+            Arc::new(UInt32Array::from(self.qualities.clone())),
+            Arc::new(UInt8Array::from_iter_values(
+                self.kinds.iter().map(|k| *k as u8),
+            )),
+            Arc::new(StringArray::from(text)),
+            Arc::new(BinaryArray::from(decimal)),
+        ];
+        RecordBatch::try_new(Arc::new(schema()), columns)
+    }
+
+    /// Append the samples of a record batch with the [`schema`] columns.
+    pub fn extend_from_arrow(&mut self, batch: &RecordBatch) -> Result<(), ArrowError> {
+        fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T, ArrowError> {
+            batch
+                .column_by_name(name)
+                .and_then(|c| c.as_any().downcast_ref::<T>())
+                .ok_or_else(|| ArrowError::SchemaError(format!("missing or mistyped `{name}`")))
+        }
+
+        let timestamps = column::<TimestampNanosecondArray>(batch, "timestamp")?;
+        let values = column::<Float64Array>(batch, "value")?;
+        let qualities = column::<UInt32Array>(batch, "quality")?;
+        let kinds = column::<UInt8Array>(batch, "kind")?;
+        let text = column::<StringArray>(batch, "text")?;
+        let decimal = column::<BinaryArray>(batch, "decimal")?;
+
+        let offset = self.len();
+        for row in 0..batch.num_rows() {
+            if text.is_valid(row) {
+                self.non_numeric
+                    .push((offset + row, Value::String(text.value(row).to_string())));
+            } else if decimal.is_valid(row) {
+                self.non_numeric
+                    .push((offset + row, Value::Decimal(decimal.value(row).to_vec())));
+            }
+            self.values.push(if values.is_valid(row) {
+                values.value(row)
+            } else {
+                f64::NAN
+            });
+        }
+        self.timestamps.extend(timestamps.values().iter());
+        self.qualities.extend(qualities.values().iter());
+        self.kinds
+            .extend(kinds.values().iter().map(|k| kind_from_u8(*k)));
+        Ok(())
+    }
+}
+
+/// Convert one tag's series to a record batch.
+pub fn series_to_arrow(series: &TagSeries) -> Result<RecordBatch, ArrowError> {
+    let tvqs = &series.tvqs;
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            TimestampNanosecondArray::from_iter_values(
+                tvqs.iter().map(|tvq| system_time_to_nanos(tvq.timestamp)),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(
+            tvqs.iter()
+                .map(|tvq| tvq.value.as_f64())
+                .collect::<Float64Array>(),
+        ),
+        Arc::new(UInt32Array::from_iter_values(
+            tvqs.iter().map(|tvq| tvq.quality.0),
+        )),
+        Arc::new(UInt8Array::from_iter_values(
+            tvqs.iter().map(|tvq| value_kind(&tvq.value) as u8),
+        )),
+        Arc::new(
+            tvqs.iter()
+                .map(|tvq| match &tvq.value {
+                    Value::String(s) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect::<StringArray>(),
+        ),
+        Arc::new(
+            tvqs.iter()
+                .map(|tvq| match &tvq.value {
+                    Value::Decimal(b) => Some(b.as_slice()),
+                    _ => None,
+                })
+                .collect::<BinaryArray>(),
+        ),
+    ];
+    RecordBatch::try_new(Arc::new(schema()), columns)
+}
@@ -0,0 +1,30 @@
+//! Conversions from Canary results into `ndarray` matrices for numeric analysis.
+
+use std::time::SystemTime;
+
+use ::ndarray::Array2;
+
+use crate::canary::views::grpc::api::{GetAggregateDataResponse, GetRawDataResponse};
+use crate::frame::TimeSeriesFrame;
+
+impl TimeSeriesFrame {
+    /// Convert to a `rows = timestamps, columns = tags` matrix, returned with
+    /// the timestamp index. Missing and non-numeric samples become `NaN`;
+    /// columns follow [`TimeSeriesFrame::tags`] order.
+    pub fn to_ndarray(&self) -> (Vec<SystemTime>, Array2<f64>) {
+        let matrix = Array2::from_shape_fn((self.len(), self.width()), |(row, col)| {
+            self.columns()[col].values[row].as_f64().unwrap_or(f64::NAN)
+        });
+        (self.timestamps().to_vec(), matrix)
+    }
+}
+
+/// Align a raw data response and convert it to a matrix (see [`TimeSeriesFrame::to_ndarray`]).
+pub fn raw_to_ndarray(resp: &GetRawDataResponse) -> (Vec<SystemTime>, Array2<f64>) {
+    TimeSeriesFrame::from(resp).to_ndarray()
+}
+
+/// Align an aggregate data response and convert it to a matrix (see [`TimeSeriesFrame::to_ndarray`]).
+pub fn aggregate_to_ndarray(resp: &GetAggregateDataResponse) -> (Vec<SystemTime>, Array2<f64>) {
+    TimeSeriesFrame::from(resp).to_ndarray()
+}
@@ -0,0 +1,81 @@
+//! Conversions from Canary results into Polars dataframes.
+//!
+//! Timestamps become a `timestamp` column of `Datetime(ns)` values in UTC.
+//! Value columns are `Float64` when every sample is numeric or boolean, and
+//! `String` otherwise.
+
+use std::time::SystemTime;
+
+use ::polars::prelude::*;
+
+use crate::canary::views::grpc::api::{GetAggregateDataResponse, GetRawDataResponse};
+use crate::frame::TimeSeriesFrame;
+use crate::timestamps::system_time_to_nanos;
+use crate::types::{TagSeries, Value};
+
+fn timestamp_column(name: &str, timestamps: impl Iterator<Item = SystemTime>) -> PolarsResult<Column> {
+    let nanos: Vec<i64> = timestamps.map(system_time_to_nanos).collect();
+    Series::new(name.into(), nanos)
+        .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))
+        .map(Column::from)
+}
+
+fn value_column(name: &str, values: &[Value]) -> Column {
+    let numeric = values
+        .iter()
+        .all(|v| v.is_null() || v.as_f64().is_some());
+    if numeric {
+        let floats: Vec<Option<f64>> = values.iter().map(Value::as_f64).collect();
+        Column::new(name.into(), floats)
+    } else {
+        let strings: Vec<Option<String>> = values
+            .iter()
+            .map(|v| (!v.is_null()).then(|| v.to_string()))
+            .collect();
+        Column::new(name.into(), strings)
+    }
+}
+
+impl TimeSeriesFrame {
+    /// Convert to a wide dataframe: `timestamp`, then `<tag>` and `<tag>.quality`
+    /// columns for each tag.
+    pub fn to_polars(&self) -> PolarsResult<DataFrame> {
+        let mut columns = vec![timestamp_column("timestamp", self.timestamps().iter().copied())?];
+        for column in self.columns() {
+            columns.push(value_column(&column.tag_name, &column.values));
+            let qualities: Vec<Option<u32>> =
+                column.qualities.iter().map(|q| q.map(|q| q.0)).collect();
+            columns.push(Column::new(
+                format!("{}.quality", column.tag_name).into(),
+                qualities,
+            ));
+        }
+        DataFrame::new(columns)
+    }
+}
+
+/// Convert series to a long dataframe with `tag`, `timestamp`, `value`, and `quality` columns.
+pub fn series_to_polars(series: &[TagSeries]) -> PolarsResult<DataFrame> {
+    let rows = series.iter().flat_map(|s| s.tvqs.iter().map(move |tvq| (s, tvq)));
+    let tags: Vec<&str> = rows.clone().map(|(s, _)| s.tag_name.as_str()).collect();
+    let values: Vec<Value> = rows.clone().map(|(_, tvq)| tvq.value.clone()).collect();
+    let qualities: Vec<u32> = rows.clone().map(|(_, tvq)| tvq.quality.0).collect();
+    DataFrame::new(vec![
+        Column::new("tag".into(), tags),
+        timestamp_column("timestamp", rows.map(|(_, tvq)| tvq.timestamp))?,
+        value_column("value", &values),
+        Column::new("quality".into(), qualities),
+    ])
+}
+
+/// Convert a raw data response to a long dataframe (see [`series_to_polars`]).
+pub fn raw_to_polars(resp: &GetRawDataResponse) -> PolarsResult<DataFrame> {
+    let series: Vec<TagSeries> = resp.raw_data.iter().map(TagSeries::from).collect();
+    series_to_polars(&series)
+}
+
+/// Convert an aggregate data response to a long dataframe (see [`series_to_polars`]).
+pub fn aggregate_to_polars(resp: &GetAggregateDataResponse) -> PolarsResult<DataFrame> {
+    let series: Vec<TagSeries> = resp.aggregated_data.iter().map(TagSeries::from).collect();
+    series_to_polars(&series)
+}
@@ -0,0 +1,187 @@
+//! Human-friendly interval parsing.
+//!
+//! Builders take aggregation intervals and polling periods as
+//! [`Duration`]s; [`parse_interval`] turns specifications such as `15m`,
+//! `1h30m`, `1.5h`, or `1d` into one, so the CLI, the Python bindings, and
+//! configuration files accept the same forms:
+//!
+//! ```
+//! use std::time::Duration;
+//! use crowsong::interval::parse_interval;
+//!
+//! assert_eq!(parse_interval("1h30m")?, Duration::from_secs(5_400));
+//! assert_eq!(parse_interval("90")?, Duration::from_secs(90));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+/// Unit names and their length in nanoseconds. Months and years are left
+/// out because their length depends on the calendar.
+const UNITS: &[(&[&str], u128)] = &[
+    (&["ns", "nanosecond", "nanoseconds"], 1),
+    (&["us", "µs", "microsecond", "microseconds"], 1_000),
+    (&["ms", "millisecond", "milliseconds"], 1_000_000),
+    (&["s", "sec", "secs", "second", "seconds"], NANOS_PER_SEC),
+    (
+        &["m", "min", "mins", "minute", "minutes"],
+        60 * NANOS_PER_SEC,
+    ),
+    (&["h", "hr", "hrs", "hour", "hours"], 3_600 * NANOS_PER_SEC),
+    (&["d", "day", "days"], 86_400 * NANOS_PER_SEC),
+    (&["w", "week", "weeks"], 604_800 * NANOS_PER_SEC),
+];
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Error returned when an interval specification cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIntervalError(String);
+
+impl fmt::Display for ParseIntervalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid interval {:?}; expected e.g. 30s, 15m, 1h30m, or 1d",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseIntervalError {}
+
+/// Parse an interval such as `15m`, `1h30m`, `1.5 hours`, or `1d`.
+///
+/// An interval is one or more numbers, each followed by a unit: `ns`, `us`,
+/// `ms`, `s`, `m`, `h`, `d`, or `w`, or their long names (`min`, `hours`,
+/// and so on). A bare number is seconds. The parts are added up in whole
+/// nanoseconds, dropping any fraction of one.
+pub fn parse_interval(s: &str) -> Result<Duration, ParseIntervalError> {
+    let error = || ParseIntervalError(s.to_string());
+    let is_number = |c: char| c.is_ascii_digit() || c == '.';
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(error());
+    }
+    if rest.chars().all(is_number) {
+        return scaled(rest, NANOS_PER_SEC)
+            .and_then(to_duration)
+            .ok_or_else(error);
+    }
+
+    let mut nanos: u128 = 0;
+    while !rest.is_empty() {
+        let split = rest.find(|c| !is_number(c)).unwrap_or(rest.len());
+        let number = &rest[..split];
+        rest = rest[split..].trim_start();
+        let end = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = rest[..end].to_lowercase();
+        let (_, length) = UNITS
+            .iter()
+            .find(|(names, _)| names.contains(&unit.as_str()))
+            .ok_or_else(error)?;
+        nanos = scaled(number, *length)
+            .and_then(|part| nanos.checked_add(part))
+            .ok_or_else(error)?;
+        rest = rest[end..].trim_start();
+    }
+    to_duration(nanos).ok_or_else(error)
+}
+
+/// The decimal `number` times `length`, in whole nanoseconds, or `None` if
+/// it isn't a number or overflows.
+fn scaled(number: &str, length: u128) -> Option<u128> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let parse = |digits: &str| match digits {
+        "" => Some(0),
+        digits if digits.bytes().all(|b| b.is_ascii_digit()) => digits.parse::<u128>().ok(),
+        _ => None,
+    };
+    // Digits past the 18th are below a nanosecond even for weeks.
+    let fraction = &fraction[..fraction.len().min(18)];
+    let nanos = parse(whole)?.checked_mul(length)?;
+    let part = parse(fraction)? * length / 10u128.pow(fraction.len() as u32);
+    nanos.checked_add(part)
+}
+
+fn to_duration(nanos: u128) -> Option<Duration> {
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+    Some(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_units() {
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_interval("1d"), Ok(Duration::from_secs(86_400)));
+        assert_eq!(
+            parse_interval("2 weeks"),
+            Ok(Duration::from_secs(1_209_600))
+        );
+        assert_eq!(parse_interval("250us"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_interval("1.5h"), Ok(Duration::from_secs(5_400)));
+        assert_eq!(parse_interval("0.1s"), Ok(Duration::from_millis(100)));
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("2.5"), Ok(Duration::from_millis(2_500)));
+    }
+
+    #[test]
+    fn adds_mixed_units_exactly() {
+        assert_eq!(
+            parse_interval("1s500ms250ns"),
+            Ok(Duration::new(1, 500_000_250))
+        );
+        assert_eq!(parse_interval("1h30m"), Ok(Duration::from_secs(5_400)));
+        assert_eq!(
+            parse_interval("1w1d1h1m1s1ms1us1ns"),
+            Ok(Duration::new(694_861, 1_001_001))
+        );
+        assert_eq!(parse_interval("0.000000001s"), Ok(Duration::from_nanos(1)));
+        assert_eq!(parse_interval("0.0000000005s"), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn allows_whitespace_and_long_names() {
+        assert_eq!(parse_interval("  1h 30m  "), Ok(Duration::from_secs(5_400)));
+        assert_eq!(
+            parse_interval("1 hour 30 Minutes"),
+            Ok(Duration::from_secs(5_400))
+        );
+        assert_eq!(parse_interval(" 45 "), Ok(Duration::from_secs(45)));
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert!(parse_interval("18446744073709551616").is_err());
+        assert!(parse_interval("18446744073709551616s").is_err());
+        assert!(parse_interval("30500568904944w").is_err());
+        assert!(parse_interval("99999999999999999999999999999999999999999w").is_err());
+        assert_eq!(
+            parse_interval("18446744073709551615s"),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_intervals() {
+        for s in [
+            "", "   ", "h", "1x", "1h30", "1.2.3s", ".s", ".", "-5s", "-5", "+5s", "1e3", "inf",
+            "NaN", "1h-30m", "1,5h",
+        ] {
+            assert_eq!(
+                parse_interval(s),
+                Err(ParseIntervalError(s.to_string())),
+                "{s:?}"
+            );
+        }
+    }
+}
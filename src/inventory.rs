@@ -0,0 +1,420 @@
+//! Tag metadata inventories.
+//!
+//! An [`InventoryScan`] walks every dataset of the chosen views (all of
+//! them by default), and for each tag collects its properties and data
+//! context: the oldest and latest sample times and the latest value's data
+//! type. The resulting [`Inventory`] is written as CSV, one row per tag and
+//! one column per property name, or as JSON:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use crowsong::inventory::InventoryScan;
+//!
+//! let inventory = InventoryScan::new().view("Plant").run(client).await?;
+//! inventory.write_csv(std::fs::File::create("inventory.csv")?)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `crowsong inventory` command does the same from the command line.
+//!
+//! [`Inventory::diff`] compares two inventories, e.g. two views, a snapshot
+//! of the old server and a scan of the new one after a migration, or a
+//! saved snapshot and the live system, reporting tags added, removed, or
+//! with changed properties. Tags are matched by dataset and name, so the
+//! views compared may have different names. `crowsong inventory-diff`
+//! prints the differences.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::timestamps;
+use crate::types::{self, TagInfo};
+use crate::views_client::ViewsClient;
+
+/// The metadata of one tag.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InventoryEntry {
+    pub view: String,
+    pub dataset: String,
+    pub tag_name: String,
+    /// The time of the tag's oldest sample.
+    #[serde(with = "optional_time")]
+    pub oldest: Option<SystemTime>,
+    /// The time of the tag's latest sample.
+    #[serde(with = "optional_time")]
+    pub latest: Option<SystemTime>,
+    /// The data type of the latest value, as the server names it.
+    pub data_type: Option<String>,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl InventoryEntry {
+    /// The engineering units property, if the tag has a non-empty one.
+    pub fn eng_units(&self) -> Option<&str> {
+        types::eng_units(&self.properties)
+    }
+
+    /// The description property, if present.
+    pub fn description(&self) -> Option<&str> {
+        self.properties.get("Description").map(String::as_str)
+    }
+}
+
+/// The metadata of every tag a scan found, in view, dataset, and tag order.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    /// When the scan started.
+    #[serde(with = "optional_time")]
+    pub taken: Option<SystemTime>,
+    pub tags: Vec<InventoryEntry>,
+}
+
+impl Inventory {
+    /// Every property name used by any tag, sorted.
+    pub fn property_names(&self) -> BTreeSet<&str> {
+        self.tags
+            .iter()
+            .flat_map(|entry| entry.properties.keys().map(String::as_str))
+            .collect()
+    }
+
+    /// Write one CSV row per tag: `view`, `dataset`, `tag`, `oldest`,
+    /// `latest`, and `data_type`, then one column per property name.
+    pub fn write_csv(&self, mut out: impl Write) -> io::Result<()> {
+        let properties = self.property_names();
+        let mut header = vec!["view", "dataset", "tag", "oldest", "latest", "data_type"];
+        header.extend(properties.iter().copied());
+        writeln!(out, "{}", csv_row(header.into_iter().map(str::to_string)))?;
+
+        let time = |t: Option<SystemTime>| t.map(timestamps::format_rfc3339).unwrap_or_default();
+        for entry in &self.tags {
+            let mut row = vec![
+                entry.view.clone(),
+                entry.dataset.clone(),
+                entry.tag_name.clone(),
+                time(entry.oldest),
+                time(entry.latest),
+                entry.data_type.clone().unwrap_or_default(),
+            ];
+            row.extend(
+                properties
+                    .iter()
+                    .map(|name| entry.properties.get(*name).cloned().unwrap_or_default()),
+            );
+            writeln!(out, "{}", csv_row(row))?;
+        }
+        Ok(())
+    }
+
+    /// Write the inventory as pretty-printed JSON.
+    pub fn write_json(&self, out: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(out, self)?;
+        Ok(())
+    }
+
+    /// Load an inventory written by [`write_json`](Self::write_json).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad inventory in {}: {e}", path.display()),
+            )
+        })
+    }
+
+    /// How `newer` differs from this inventory. Data contexts are left out,
+    /// since they move with every sample written.
+    pub fn diff(&self, newer: &Inventory) -> InventoryDiff {
+        let key = |entry: &InventoryEntry| (entry.dataset.clone(), entry.tag_name.clone());
+        let old: BTreeMap<_, _> = self.tags.iter().map(|e| (key(e), e)).collect();
+        let new: BTreeMap<_, _> = newer.tags.iter().map(|e| (key(e), e)).collect();
+
+        let mut diff = InventoryDiff::default();
+        for (k, entry) in &old {
+            match new.get(k) {
+                None => diff.removed.push((*entry).clone()),
+                Some(newer) => {
+                    let changes = property_changes(&entry.properties, &newer.properties);
+                    if !changes.is_empty() {
+                        diff.changed.push(TagChange {
+                            dataset: entry.dataset.clone(),
+                            tag_name: entry.tag_name.clone(),
+                            properties: changes,
+                        });
+                    }
+                }
+            }
+        }
+        diff.added = new
+            .iter()
+            .filter(|(k, _)| !old.contains_key(*k))
+            .map(|(_, entry)| (*entry).clone())
+            .collect();
+        diff
+    }
+}
+
+/// One property whose value differs between two inventories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyChange {
+    pub name: String,
+    /// The old value, or `None` if the property was added.
+    pub old: Option<String>,
+    /// The new value, or `None` if the property was removed.
+    pub new: Option<String>,
+}
+
+/// A tag in both inventories whose properties differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagChange {
+    pub dataset: String,
+    pub tag_name: String,
+    pub properties: Vec<PropertyChange>,
+}
+
+/// The tags added, removed, and changed between two inventories, each in
+/// dataset and tag order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InventoryDiff {
+    pub added: Vec<InventoryEntry>,
+    pub removed: Vec<InventoryEntry>,
+    pub changed: Vec<TagChange>,
+}
+
+impl InventoryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// One line per difference: `+` for added tags, `-` for removed ones, and
+/// `~` for each changed property.
+impl fmt::Display for InventoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.added {
+            writeln!(f, "+ {} / {}", entry.dataset, entry.tag_name)?;
+        }
+        for entry in &self.removed {
+            writeln!(f, "- {} / {}", entry.dataset, entry.tag_name)?;
+        }
+        let show = |value: &Option<String>| match value {
+            Some(value) => format!("{value:?}"),
+            None => "(none)".to_string(),
+        };
+        for change in &self.changed {
+            for property in &change.properties {
+                writeln!(
+                    f,
+                    "~ {} / {}: {}: {} -> {}",
+                    change.dataset,
+                    change.tag_name,
+                    property.name,
+                    show(&property.old),
+                    show(&property.new)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn property_changes(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Vec<PropertyChange> {
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| PropertyChange {
+            name: name.clone(),
+            old: old.get(name).cloned(),
+            new: new.get(name).cloned(),
+        })
+        .collect()
+}
+
+/// Which part of the namespace an [`InventoryScan`] walks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryScan {
+    views: Vec<String>,
+    include_hidden: bool,
+    batch_size: usize,
+}
+
+impl Default for InventoryScan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InventoryScan {
+    /// Scan every view's visible datasets.
+    pub fn new() -> Self {
+        Self {
+            views: Vec::new(),
+            include_hidden: false,
+            batch_size: 500,
+        }
+    }
+
+    /// Scan `view`; call again to add more. Defaults to every view.
+    pub fn view(mut self, view: impl Into<String>) -> Self {
+        self.views.push(view.into());
+        self
+    }
+
+    /// Also scan hidden datasets.
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    /// How many tags to describe per call. Defaults to 500.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub async fn run(&self, client: &ViewsClient) -> Result<Inventory, Error> {
+        let mut inventory = Inventory {
+            taken: Some(SystemTime::now()),
+            tags: Vec::new(),
+        };
+        let views = if self.views.is_empty() {
+            client.get_views().await?.views
+        } else {
+            self.views.clone()
+        };
+        for view in views {
+            let datasets = client
+                .get_dataset_list(&view, self.include_hidden)
+                .await?
+                .datasets;
+            for dataset in datasets {
+                let names = client.get_all_tags(&view, &dataset).await?;
+                for chunk in names.chunks(self.batch_size) {
+                    inventory
+                        .tags
+                        .extend(entries(client, &view, &dataset, chunk).await?);
+                }
+            }
+        }
+        Ok(inventory)
+    }
+}
+
+/// The metadata of `tags` in `view`, named by path, in the order given. A
+/// tag's dataset is the first part of its path. Tags the server doesn't know
+/// come back with no data context or properties.
+pub async fn describe_tags(
+    client: &ViewsClient,
+    view: &str,
+    tags: &[String],
+) -> Result<Vec<InventoryEntry>, Error> {
+    let mut described = entries(client, view, "", tags).await?;
+    for entry in &mut described {
+        let dataset = entry.tag_name.split('.').next().unwrap_or_default();
+        entry.dataset = dataset.to_string();
+    }
+    Ok(described)
+}
+
+/// Describe `tags` of `dataset` in two calls.
+async fn entries(
+    client: &ViewsClient,
+    view: &str,
+    dataset: &str,
+    tags: &[String],
+) -> Result<Vec<InventoryEntry>, Error> {
+    let infos: HashMap<String, TagInfo> = client
+        .get_tag_info(view, tags.to_vec())
+        .await?
+        .tag_infos
+        .iter()
+        .map(|info| (info.tag_item_id.clone(), TagInfo::from(info)))
+        .collect();
+    let contexts: HashMap<_, _> = client
+        .get_tag_data_context(view, tags.to_vec())
+        .await?
+        .contexts
+        .into_iter()
+        .map(|context| (context.tag_item_id.clone(), context))
+        .collect();
+    Ok(tags
+        .iter()
+        .map(|tag_name| {
+            let context = contexts.get(tag_name);
+            InventoryEntry {
+                view: view.to_string(),
+                dataset: dataset.to_string(),
+                tag_name: tag_name.clone(),
+                oldest: context
+                    .and_then(|c| c.oldest_timestamp)
+                    .map(|ts| timestamps::from_proto(&ts)),
+                latest: context
+                    .and_then(|c| c.latest_timestamp)
+                    .map(|ts| timestamps::from_proto(&ts)),
+                data_type: context
+                    .map(|c| c.latest_value_data_type.clone())
+                    .filter(|t| !t.is_empty()),
+                properties: infos
+                    .get(tag_name)
+                    .map(|info| info.properties.clone())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+fn csv_row(fields: impl IntoIterator<Item = String>) -> String {
+    fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Optional times as RFC 3339 strings.
+mod optional_time {
+    use std::time::SystemTime;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::timestamps;
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.serialize_str(&timestamps::format_rfc3339(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| timestamps::parse_rfc3339(&s).map_err(D::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,218 @@
+//! As-of joins and merges over sample series.
+//!
+//! Tags are sampled at their own times, so lining one tag up against
+//! another means asking, for each sample of the first, what the second read
+//! at that moment. [`AsOf`] answers that: by default each lookup takes the
+//! last sample at or before the time, optionally only within a tolerance.
+//!
+//! ```
+//! use std::time::{Duration, SystemTime};
+//! use crowsong::join::AsOf;
+//! use crowsong::{Quality, Tvq, Value};
+//!
+//! let t0 = SystemTime::UNIX_EPOCH;
+//! let tvq = |secs, value| Tvq {
+//!     timestamp: t0 + Duration::from_secs(secs),
+//!     value: Value::Float(value),
+//!     quality: Quality::GOOD,
+//! };
+//! let speed = [tvq(0, 10.0), tvq(60, 20.0), tvq(600, 30.0)];
+//! let temperature = [tvq(5, 70.0), tvq(550, 75.0)];
+//! let joined = AsOf::new()
+//!     .tolerance(Duration::from_secs(120))
+//!     .join(&speed, &temperature);
+//! let values: Vec<_> = joined.iter().map(|m| m.map(|t| t.value.clone())).collect();
+//! assert_eq!(values, [None, Some(Value::Float(70.0)), Some(Value::Float(75.0))]);
+//! ```
+//!
+//! With the `views` feature, [`TimeSeriesFrame::as_of`] builds a table of
+//! several tags joined onto one tag's timestamps.
+//!
+//! [`TimeSeriesFrame::as_of`]: crate::frame::TimeSeriesFrame::as_of
+
+use std::time::{Duration, SystemTime};
+
+use crate::types::Tvq;
+
+/// Which samples an as-of lookup may match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The last sample at or before the time.
+    #[default]
+    Backward,
+    /// The first sample at or after the time.
+    Forward,
+    /// Whichever sample is closest, preferring the earlier on a tie.
+    Nearest,
+}
+
+/// Settings for as-of lookups and joins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AsOf {
+    direction: Direction,
+    tolerance: Option<Duration>,
+}
+
+impl AsOf {
+    /// Backward lookups with no tolerance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Which samples to match. Defaults to [`Direction::Backward`].
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Only match samples at most `tolerance` from the time.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = Some(tolerance);
+        self
+    }
+
+    /// The sample of `series`, in time order, matching time `at`.
+    pub fn lookup<'a>(&self, series: &'a [Tvq], at: SystemTime) -> Option<&'a Tvq> {
+        // Index of the first sample after `at`, and of the first at or after it.
+        let after = series.partition_point(|tvq| tvq.timestamp <= at);
+        let from = series.partition_point(|tvq| tvq.timestamp < at);
+        let backward = after.checked_sub(1).map(|i| &series[i]);
+        let forward = series.get(from);
+        let found = match self.direction {
+            Direction::Backward => backward,
+            Direction::Forward => forward,
+            Direction::Nearest => match (backward, forward) {
+                (Some(b), Some(f)) if distance(f, at) < distance(b, at) => Some(f),
+                (Some(b), _) => Some(b),
+                (None, f) => f,
+            },
+        }?;
+        self.tolerance
+            .is_none_or(|tolerance| distance(found, at) <= tolerance)
+            .then_some(found)
+    }
+
+    /// For each sample of `left`, the sample of `right` matching its time.
+    /// Both must be in time order.
+    pub fn join<'a>(&self, left: &[Tvq], right: &'a [Tvq]) -> Vec<Option<&'a Tvq>> {
+        left.iter()
+            .map(|tvq| self.lookup(right, tvq.timestamp))
+            .collect()
+    }
+}
+
+fn distance(tvq: &Tvq, at: SystemTime) -> Duration {
+    match tvq.timestamp.duration_since(at) {
+        Ok(d) => d,
+        Err(e) => e.duration(),
+    }
+}
+
+/// Merge two series of the same tag, each in time order, into one. Where
+/// both have a sample at the same time, `primary`'s is kept, so a corrected
+/// or backfilled source can be laid over the original.
+pub fn merge(primary: &[Tvq], secondary: &[Tvq]) -> Vec<Tvq> {
+    let mut merged = Vec::with_capacity(primary.len() + secondary.len());
+    let (mut p, mut s) = (primary.iter().peekable(), secondary.iter().peekable());
+    loop {
+        let next = match (p.peek(), s.peek()) {
+            (Some(a), Some(b)) if b.timestamp < a.timestamp => s.next(),
+            (Some(a), Some(b)) => {
+                if a.timestamp == b.timestamp {
+                    s.next();
+                }
+                p.next()
+            }
+            (Some(_), None) => p.next(),
+            (None, Some(_)) => s.next(),
+            (None, None) => break,
+        };
+        merged.extend(next.cloned());
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quality::Quality;
+    use crate::types::Value;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn tvq(secs: u64, value: f64) -> Tvq {
+        Tvq {
+            timestamp: at(secs),
+            value: Value::Float(value),
+            quality: Quality::GOOD,
+        }
+    }
+
+    fn value(found: Option<&Tvq>) -> Option<f64> {
+        found.and_then(|tvq| tvq.value.as_f64())
+    }
+
+    #[test]
+    fn nearest_prefers_the_earlier_sample_on_a_tie() {
+        let series = [tvq(10, 1.0), tvq(20, 2.0)];
+        let nearest = AsOf::new().direction(Direction::Nearest);
+        assert_eq!(value(nearest.lookup(&series, at(15))), Some(1.0));
+        assert_eq!(value(nearest.lookup(&series, at(16))), Some(2.0));
+        assert_eq!(value(nearest.lookup(&series, at(14))), Some(1.0));
+        assert_eq!(value(nearest.lookup(&series, at(20))), Some(2.0));
+        assert_eq!(value(nearest.lookup(&series, at(0))), Some(1.0));
+        assert_eq!(value(nearest.lookup(&series, at(99))), Some(2.0));
+        assert_eq!(value(nearest.lookup(&[], at(0))), None);
+    }
+
+    #[test]
+    fn tolerance_includes_its_boundary() {
+        let series = [tvq(10, 1.0), tvq(20, 2.0)];
+        let nearest = AsOf::new()
+            .direction(Direction::Nearest)
+            .tolerance(Duration::from_secs(5));
+        assert_eq!(value(nearest.lookup(&series, at(5))), Some(1.0));
+        assert_eq!(value(nearest.lookup(&series, at(4))), None);
+        assert_eq!(value(nearest.lookup(&series, at(25))), Some(2.0));
+        assert_eq!(value(nearest.lookup(&series, at(26))), None);
+        // A tie exactly at the tolerance still takes the earlier sample.
+        assert_eq!(value(nearest.lookup(&series, at(15))), Some(1.0));
+
+        let tight = nearest.tolerance(Duration::from_secs(4));
+        assert_eq!(value(tight.lookup(&series, at(15))), None);
+        assert_eq!(value(tight.lookup(&series, at(14))), Some(1.0));
+        let exact = nearest.tolerance(Duration::ZERO);
+        assert_eq!(value(exact.lookup(&series, at(20))), Some(2.0));
+        assert_eq!(value(exact.lookup(&series, at(19))), None);
+    }
+
+    #[test]
+    fn backward_and_forward_match_samples_at_the_time() {
+        let series = [tvq(10, 1.0), tvq(20, 2.0)];
+        let backward = AsOf::new();
+        let forward = AsOf::new().direction(Direction::Forward);
+        assert_eq!(value(backward.lookup(&series, at(20))), Some(2.0));
+        assert_eq!(value(backward.lookup(&series, at(19))), Some(1.0));
+        assert_eq!(value(backward.lookup(&series, at(9))), None);
+        assert_eq!(value(forward.lookup(&series, at(10))), Some(1.0));
+        assert_eq!(value(forward.lookup(&series, at(11))), Some(2.0));
+        assert_eq!(value(forward.lookup(&series, at(21))), None);
+        let within = forward.tolerance(Duration::from_secs(9));
+        assert_eq!(value(within.lookup(&series, at(11))), Some(2.0));
+        assert_eq!(value(within.lookup(&series, at(0))), None);
+    }
+
+    #[test]
+    fn merge_keeps_primary_samples_at_shared_times() {
+        let primary = [tvq(10, 1.0), tvq(30, 3.0)];
+        let secondary = [tvq(0, 0.0), tvq(10, -1.0), tvq(20, 2.0), tvq(40, 4.0)];
+        let merged: Vec<_> = merge(&primary, &secondary)
+            .iter()
+            .map(|tvq| tvq.value.as_f64().unwrap())
+            .collect();
+        assert_eq!(merged, [0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(merge(&[], &primary), primary);
+    }
+}
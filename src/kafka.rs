@@ -0,0 +1,385 @@
+//! Streaming live and historical data to Kafka.
+//!
+//! A [`KafkaSink`] produces one record per sample to a topic, keyed by tag
+//! name so each tag's samples land in one partition in order. Records are
+//! JSON objects or Avro datums (optionally in the Confluent wire format)
+//! with the view, tag, timestamp, value, and quality; see [`AVRO_SCHEMA`].
+//!
+//! [`KafkaSink::stream_live`] forwards a live subscription until it ends, and
+//! [`KafkaSink::export_history`] forwards a bulk extract. Samples are sent in
+//! batches; records the brokers fail to acknowledge are retried with
+//! exponential backoff, which can reorder a tag's samples around a failure.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use rdkafka::ClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde_json::json;
+
+use crate::bulk::BulkFetch;
+use crate::canary::views::grpc::api::SubscribeToLiveDataRequest;
+use crate::error::Error;
+use crate::timestamps::{TimestampFormat, system_time_to_nanos};
+use crate::transport::Redacted;
+use crate::types::{Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// The Avro schema of records produced with [`KafkaFormat::Avro`].
+pub const AVRO_SCHEMA: &str = r#"{"type":"record","name":"TagValue","namespace":"crowsong","fields":[{"name":"view","type":"string"},{"name":"tag","type":"string"},{"name":"timestamp","type":{"type":"long","logicalType":"timestamp-micros"}},{"name":"value","type":["null","boolean","long","double","string"]},{"name":"quality","type":"int"}]}"#;
+
+/// How samples are encoded as record values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KafkaFormat {
+    /// A JSON object with the timestamp rendered by the sink's
+    /// [`TimestampFormat`], RFC 3339 by default.
+    #[default]
+    Json,
+    /// A bare Avro datum of [`AVRO_SCHEMA`].
+    Avro,
+    /// An Avro datum prefixed with the Confluent schema registry header for
+    /// the given schema ID.
+    ConfluentAvro { schema_id: u32 },
+}
+
+/// Builder for a [`KafkaSink`].
+#[derive(Clone)]
+pub struct KafkaSinkBuilder {
+    config: ClientConfig,
+    topic: String,
+    format: KafkaFormat,
+    timestamp_format: TimestampFormat,
+    batch_size: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+    max_retries: u32,
+}
+
+impl fmt::Debug for KafkaSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // librdkafka properties include SASL passwords and TLS keys.
+        let config: BTreeMap<&str, &dyn fmt::Debug> = self
+            .config
+            .config_map()
+            .iter()
+            .map(|(key, value)| {
+                let value: &dyn fmt::Debug = if is_secret_property(key) {
+                    &Redacted
+                } else {
+                    value
+                };
+                (key.as_str(), value)
+            })
+            .collect();
+        f.debug_struct("KafkaSinkBuilder")
+            .field("config", &config)
+            .field("topic", &self.topic)
+            .field("format", &self.format)
+            .field("timestamp_format", &self.timestamp_format)
+            .field("batch_size", &self.batch_size)
+            .field("backoff", &self.backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("max_retries", &self.max_retries)
+            .finish()
+    }
+}
+
+/// Whether a librdkafka property holds a credential.
+fn is_secret_property(key: &str) -> bool {
+    key.contains("password")
+        || key.contains("secret")
+        || key.starts_with("sasl.oauthbearer.config")
+        || key == "ssl.key.pem"
+        || key == "ssl_key"
+}
+
+impl KafkaSinkBuilder {
+    /// Set a librdkafka producer property, e.g. `"compression.type"`.
+    pub fn config(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.set(key, value);
+        self
+    }
+
+    /// The record encoding. Defaults to JSON.
+    pub fn format(mut self, format: KafkaFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// How JSON records render timestamps. Defaults to RFC 3339 in UTC;
+    /// Avro records always carry epoch microseconds.
+    pub fn timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// How many records to send before waiting for their acknowledgements.
+    /// Defaults to 1,000.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// The wait before retrying failed deliveries, doubling up to `max`.
+    /// Defaults to 100 ms doubling up to 10 s.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// How many times to retry failed deliveries before giving up. Defaults
+    /// to 5.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Create the producer. Brokers are contacted when the first batch is
+    /// sent.
+    pub fn build(self) -> Result<KafkaSink, KafkaError> {
+        Ok(KafkaSink {
+            producer: self.config.create()?,
+            topic: self.topic,
+            format: self.format,
+            timestamp_format: self.timestamp_format,
+            batch_size: self.batch_size,
+            backoff: self.backoff,
+            max_backoff: self.max_backoff,
+            max_retries: self.max_retries,
+        })
+    }
+}
+
+/// A Kafka producer for samples.
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    format: KafkaFormat,
+    timestamp_format: TimestampFormat,
+    batch_size: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+    max_retries: u32,
+}
+
+impl KafkaSink {
+    /// Start configuring a sink producing to `topic` on the comma-separated
+    /// `brokers`.
+    pub fn builder(brokers: impl Into<String>, topic: impl Into<String>) -> KafkaSinkBuilder {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        KafkaSinkBuilder {
+            config,
+            topic: topic.into(),
+            format: KafkaFormat::default(),
+            timestamp_format: TimestampFormat::default(),
+            batch_size: 1_000,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            max_retries: 5,
+        }
+    }
+
+    /// Send one tag's samples.
+    pub async fn send(&self, view: &str, tag: &str, tvqs: &[Tvq]) -> Result<(), Error> {
+        for batch in tvqs.chunks(self.batch_size) {
+            let records: Vec<Vec<u8>> = batch
+                .iter()
+                .map(|tvq| self.encode(view, tag, tvq))
+                .collect();
+            self.deliver(tag, &records).await?;
+        }
+        Ok(())
+    }
+
+    /// Subscribe to live data for `tags` and send every update until the
+    /// subscription ends. Returns the number of samples sent.
+    pub async fn stream_live(
+        &self,
+        client: &ViewsClient,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<u64, Error> {
+        let mut live = client
+            .subscribe_to_live_data(SubscribeToLiveDataRequest {
+                tags: tags.into_iter().map(Into::into).collect(),
+                ..Default::default()
+            })
+            .await?;
+        let mut sent = 0;
+        while let Some(resp) = live.message().await? {
+            for (tag, data) in &resp.tags_and_data {
+                let tvqs: Vec<Tvq> = data.tvqs.iter().map(Tvq::from).collect();
+                self.send(view, tag, &tvqs).await?;
+                sent += tvqs.len() as u64;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Read raw history for `tags` over `start..end` and send it. Returns the
+    /// number of samples sent.
+    pub async fn export_history(
+        &self,
+        client: &ViewsClient,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<u64, Error> {
+        let pages = BulkFetch::new(view, tags, start, end).run(client).await?;
+        let mut sent = 0;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            let tvqs: Vec<Tvq> = (0..page.len()).map(|row| page.tvq(row)).collect();
+            self.send(view, &page.tag_name, &tvqs).await?;
+            sent += tvqs.len() as u64;
+        }
+        Ok(sent)
+    }
+
+    /// Produce records keyed by `key`, retrying failed ones with backoff
+    /// until all are acknowledged.
+    async fn deliver(&self, key: &str, records: &[Vec<u8>]) -> Result<(), Error> {
+        let mut pending: Vec<usize> = (0..records.len()).collect();
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let mut failed = Vec::new();
+            let mut last_error = None;
+            let mut deliveries = Vec::with_capacity(pending.len());
+            for &i in &pending {
+                let record = FutureRecord::to(&self.topic).key(key).payload(&records[i]);
+                match self.producer.send_result(record) {
+                    Ok(delivery) => deliveries.push((i, delivery)),
+                    Err((e, _)) => {
+                        failed.push(i);
+                        last_error = Some(e);
+                    }
+                }
+            }
+            for (i, delivery) in deliveries {
+                let error = match delivery.await {
+                    Ok(Ok(_)) => continue,
+                    Ok(Err((e, _))) => e,
+                    Err(_) => KafkaError::Canceled,
+                };
+                failed.push(i);
+                last_error = Some(error);
+            }
+
+            let Some(error) = last_error else {
+                return Ok(());
+            };
+            attempt += 1;
+            if attempt > self.max_retries {
+                return Err(format!(
+                    "delivering {} records for {key} to {} failed after {attempt} attempts: {error}",
+                    failed.len(),
+                    self.topic
+                )
+                .into());
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+            failed.sort_unstable();
+            pending = failed;
+        }
+    }
+
+    fn encode(&self, view: &str, tag: &str, tvq: &Tvq) -> Vec<u8> {
+        match self.format {
+            KafkaFormat::Json => json_record(view, tag, tvq, &self.timestamp_format),
+            KafkaFormat::Avro => avro_record(view, tag, tvq, Vec::new()),
+            KafkaFormat::ConfluentAvro { schema_id } => {
+                let mut header = vec![0];
+                header.extend_from_slice(&schema_id.to_be_bytes());
+                avro_record(view, tag, tvq, header)
+            }
+        }
+    }
+}
+
+fn json_record(view: &str, tag: &str, tvq: &Tvq, timestamps: &TimestampFormat) -> Vec<u8> {
+    let value = match &tvq.value {
+        Value::Null => serde_json::Value::Null,
+        Value::Bool(b) => json!(b),
+        Value::Int(i) => json!(i),
+        Value::UInt(u) => json!(u),
+        Value::String(s) => json!(s),
+        value => value
+            .as_f64()
+            .filter(|f| f.is_finite())
+            .map_or(serde_json::Value::Null, |f| json!(f)),
+    };
+    json!({
+        "view": view,
+        "tag": tag,
+        "timestamp": timestamps.format(tvq.timestamp),
+        "value": value,
+        "quality": tvq.quality.0,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+/// Append an Avro datum of [`AVRO_SCHEMA`] to `out`.
+fn avro_record(view: &str, tag: &str, tvq: &Tvq, mut out: Vec<u8>) -> Vec<u8> {
+    avro_string(&mut out, view);
+    avro_string(&mut out, tag);
+    avro_long(&mut out, system_time_to_nanos(tvq.timestamp) / 1_000);
+    // The union branches of `value`, in schema order.
+    match &tvq.value {
+        Value::Null => avro_long(&mut out, 0),
+        Value::Bool(b) => {
+            avro_long(&mut out, 1);
+            out.push(u8::from(*b));
+        }
+        Value::Int(i) => {
+            avro_long(&mut out, 2);
+            avro_long(&mut out, *i);
+        }
+        Value::UInt(u) => {
+            avro_long(&mut out, 2);
+            avro_long(&mut out, i64::try_from(*u).unwrap_or(i64::MAX));
+        }
+        Value::String(s) => {
+            avro_long(&mut out, 4);
+            avro_string(&mut out, s);
+        }
+        value => match value.as_f64() {
+            Some(f) => {
+                avro_long(&mut out, 3);
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+            None => avro_long(&mut out, 0),
+        },
+    }
+    avro_long(&mut out, i64::from(tvq.quality.0 as i32));
+    out
+}
+
+/// A zigzag varint, the Avro encoding of `int` and `long`.
+fn avro_long(out: &mut Vec<u8>, n: i64) {
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn avro_string(out: &mut Vec<u8>, s: &str) {
+    avro_long(out, s.len() as i64);
+    out.extend_from_slice(s.as_bytes());
+}
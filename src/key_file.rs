@@ -0,0 +1,85 @@
+//! API keys read from a file and reloaded when it changes.
+//!
+//! Kubernetes mounts secrets as files and updates them in place when they
+//! are rotated. A client built with
+//! [`ViewsClientBuilder::api_key_file`](crate::views_client::ViewsClientBuilder::api_key_file)
+//! reads its key from such a file and re-reads it periodically, sending the
+//! new key from the next call on once the file changes. Clients connected
+//! some other way can do the same with [`ViewsClient::watch_api_key_file`],
+//! or swap keys by hand with [`ViewsClient::set_api_key`].
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::views_client::{SharedApiKey, ViewsClient};
+
+/// How often a client built with an API key file re-reads it.
+pub(crate) const KEY_FILE_POLL: Duration = Duration::from_secs(30);
+
+/// The API key in the file at `path`, without surrounding whitespace.
+pub(crate) fn read_key_file(path: &Path) -> io::Result<String> {
+    let key = std::fs::read_to_string(path)?.trim().to_string();
+    if key.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the file is empty",
+        ));
+    }
+    Ok(key)
+}
+
+/// Re-read `path` every `period`, swapping its contents into `api_key` when
+/// they change. The task holds the key rather than a client, so it doesn't
+/// keep the connection alive.
+pub(crate) fn spawn_watch(api_key: SharedApiKey, path: PathBuf, period: Duration) -> KeyFileWatch {
+    let handle = tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(period);
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            // A secret being swapped may briefly be missing or empty; keep
+            // the current key and look again next time.
+            let Ok(key) = read_key_file(&path) else {
+                continue;
+            };
+            let Ok(key) = key.parse() else {
+                continue;
+            };
+            *api_key.write().unwrap_or_else(PoisonError::into_inner) = key;
+        }
+    });
+    KeyFileWatch { handle }
+}
+
+impl ViewsClient {
+    /// Re-read the API key from the file at `path` every `period` until the
+    /// returned watch is dropped, sending the new key on this client and
+    /// every handle on its connection when the file changes.
+    pub fn watch_api_key_file(&self, path: impl Into<PathBuf>, period: Duration) -> KeyFileWatch {
+        spawn_watch(self.api_key(), path.into(), period)
+    }
+}
+
+/// A background reload of an API key file, stopped when dropped.
+pub struct KeyFileWatch {
+    handle: JoinHandle<()>,
+}
+
+impl KeyFileWatch {
+    /// Stop watching the file. The current key stays in use.
+    pub fn stop(self) {}
+
+    pub(crate) fn abort_handle(&self) -> AbortHandle {
+        self.handle.abort_handle()
+    }
+}
+
+impl Drop for KeyFileWatch {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -0,0 +1,240 @@
+//! Client connection ID leases.
+//!
+//! Canary forgets a client connection ID (CCI) that goes unused for longer
+//! than its connection timeout, and the next call fails with "invalid
+//! client connection id". Long-idle services, such as a dashboard backend
+//! that only reads when someone opens a page, hit this hours after
+//! connecting.
+//!
+//! The Views API doesn't report the timeout, so a client is told it with
+//! [`ViewsClientBuilder::cci_lease`]. The client then tracks when its CCI
+//! last answered a call and, as the lease nears expiry, either warns or
+//! sends a keepalive:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), crowsong::Error> {
+//! use std::time::Duration;
+//! use crowsong::{CciLease, ViewsClient};
+//!
+//! let client = ViewsClient::builder("https://historian:55321", "api-key")
+//!     .cci_lease(CciLease::new(Duration::from_secs(300)).keepalive())
+//!     .connect()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ViewsClientBuilder::cci_lease`]: crate::views_client::ViewsClientBuilder::cci_lease
+
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use tokio::task::{AbortHandle, JoinHandle};
+use tonic::Status;
+
+use crate::connection::StateMonitor;
+use crate::views_client::ViewsClient;
+
+/// What a client does when its CCI lease is about to expire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LeaseAction {
+    /// Report a [`LeaseWarning`] once per idle stretch.
+    #[default]
+    Warn,
+    /// Send a keepalive, renewing the lease.
+    Keepalive,
+}
+
+/// A CCI that is close to, or past, its server's connection timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaseWarning {
+    pub cci: i32,
+    /// How long since the CCI last answered a call.
+    pub idle: Duration,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for LeaseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "client connection {} has been idle {}s of its {}s lease; calls after that may fail with an invalid CCI",
+            self.cci,
+            self.idle.as_secs(),
+            self.timeout.as_secs()
+        )
+    }
+}
+
+/// The server's connection timeout, and what to do as it nears.
+#[derive(Clone)]
+pub struct CciLease {
+    timeout: Duration,
+    margin: Duration,
+    action: LeaseAction,
+    handler: Arc<dyn Fn(&LeaseWarning) + Send + Sync>,
+}
+
+impl CciLease {
+    /// A lease that expires after `timeout` without a call, warning on
+    /// stderr when a fifth of it is left.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            margin: timeout / 5,
+            action: LeaseAction::Warn,
+            handler: Arc::new(|warning| eprintln!("crowsong: {warning}")),
+        }
+    }
+
+    /// Act when this much of the lease is left. Defaults to a fifth of the
+    /// timeout.
+    pub fn margin(mut self, margin: Duration) -> Self {
+        self.margin = margin.min(self.timeout);
+        self
+    }
+
+    /// Send a keepalive instead of warning.
+    pub fn keepalive(mut self) -> Self {
+        self.action = LeaseAction::Keepalive;
+        self
+    }
+
+    /// Pass warnings to `handler` instead of stderr.
+    pub fn handler(mut self, handler: impl Fn(&LeaseWarning) + Send + Sync + 'static) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn action(&self) -> LeaseAction {
+        self.action
+    }
+}
+
+impl fmt::Debug for CciLease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CciLease")
+            .field("timeout", &self.timeout)
+            .field("margin", &self.margin)
+            .field("action", &self.action)
+            .finish_non_exhaustive()
+    }
+}
+
+/// When a CCI last answered a call, shared by every handle on it, along
+/// with the state of the connection under it.
+pub(crate) struct LeaseClock {
+    last: Mutex<Instant>,
+    state: Arc<StateMonitor>,
+}
+
+impl LeaseClock {
+    pub(crate) fn new(state: Arc<StateMonitor>) -> Arc<Self> {
+        Arc::new(Self {
+            last: Mutex::new(Instant::now()),
+            state,
+        })
+    }
+
+    pub(crate) fn idle(&self) -> Duration {
+        self.last
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed()
+    }
+
+    pub(crate) fn state(&self) -> &Arc<StateMonitor> {
+        &self.state
+    }
+
+    fn touch(&self) {
+        *self.last.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    /// Run `call`, renewing the lease if it succeeds and recording how it
+    /// went in the connection's state.
+    pub(crate) async fn track<T>(
+        &self,
+        call: impl Future<Output = Result<T, Status>>,
+    ) -> Result<T, Status> {
+        let result = call.await;
+        if result.is_ok() {
+            self.touch();
+        }
+        self.state.observe(&result);
+        result
+    }
+}
+
+impl fmt::Debug for LeaseClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeaseClock")
+            .field("idle", &self.idle())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Watch `client`'s lease until the returned task is dropped. The task
+/// holds a data handle whose own watch is cleared, so it doesn't keep
+/// itself alive.
+pub(crate) fn spawn_watch(
+    client: &ViewsClient,
+    clock: Arc<LeaseClock>,
+    lease: CciLease,
+) -> LeaseWatch {
+    let mut client = client.data_handle();
+    client.clear_lease_watch();
+    let period = (lease.margin / 2).clamp(Duration::from_secs(1), Duration::from_secs(30));
+    let handle = tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(period);
+        let mut warned = false;
+        loop {
+            ticks.tick().await;
+            let idle = clock.idle();
+            if idle + lease.margin < lease.timeout {
+                warned = false;
+                continue;
+            }
+            match lease.action {
+                // A failed keepalive is retried next tick; the next call
+                // will report the error if the CCI really is gone.
+                LeaseAction::Keepalive => {
+                    let _ = client.keepalive().await;
+                }
+                LeaseAction::Warn if !warned => {
+                    warned = true;
+                    (lease.handler)(&LeaseWarning {
+                        cci: client.cci(),
+                        idle,
+                        timeout: lease.timeout,
+                    });
+                }
+                LeaseAction::Warn => {}
+            }
+        }
+    });
+    LeaseWatch { handle }
+}
+
+/// A background watch over a CCI lease, stopped when dropped.
+pub(crate) struct LeaseWatch {
+    handle: JoinHandle<()>,
+}
+
+impl LeaseWatch {
+    pub(crate) fn abort_handle(&self) -> AbortHandle {
+        self.handle.abort_handle()
+    }
+}
+
+impl Drop for LeaseWatch {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -3,6 +3,7 @@
 //! This crate provides a Rust client for interacting with the Canary Views API service.
 
 pub mod canary {
+    #[cfg(feature = "views")]
     pub mod views {
         pub mod grpc {
             pub mod common {
@@ -13,6 +14,7 @@ pub mod canary {
             }
         }
     }
+    #[cfg(feature = "store-and-forward")]
     pub mod store_and_forward2 {
         pub mod grpc {
             pub mod api {
@@ -25,6 +27,7 @@ pub mod canary {
             tonic::include_proto!("canary.utility.protobuf_shared_types");
         }
     }
+    #[cfg(feature = "calculations")]
     pub mod calculations {
         pub mod grpc {
             pub mod common {
@@ -41,8 +44,230 @@ pub mod canary {
     }
 }
 
-pub mod views_client;
-#[cfg(feature = "extension-module")]
+#[cfg(feature = "views")]
+pub mod access;
+#[cfg(feature = "store-and-forward")]
+pub mod admin;
+#[cfg(feature = "views")]
+pub mod annotations;
+#[cfg(feature = "views")]
+pub mod bulk;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "calendar")]
+pub mod calendar;
+#[cfg(feature = "views")]
+pub mod capabilities;
+#[cfg(feature = "views")]
+pub mod cci_pool;
+#[cfg(feature = "views")]
+pub mod checkpoint;
+#[cfg(feature = "views")]
+pub mod client_pool;
+#[cfg(feature = "views")]
+pub mod columnar;
+#[cfg(feature = "views")]
+pub mod clock_skew;
+#[cfg(feature = "views")]
+pub mod completeness;
+#[cfg(feature = "views")]
+pub mod connection;
+#[cfg(feature = "keyring")]
+pub mod credentials;
+mod descriptor;
+#[cfg(feature = "views")]
+pub mod cross_view;
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
+pub mod error;
+#[cfg(feature = "views")]
+pub mod events;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "views")]
+pub mod frame;
+pub mod gapfill;
+#[cfg(feature = "views")]
+pub mod hedge;
+#[cfg(all(feature = "views", feature = "store-and-forward"))]
+pub mod historian;
+#[cfg(feature = "views")]
+pub mod history;
+#[cfg(feature = "influx")]
+pub mod influx;
+#[cfg(feature = "views")]
+pub mod interop;
+pub mod interval;
+#[cfg(feature = "inventory")]
+pub mod inventory;
+pub mod join;
+#[cfg(feature = "views")]
+pub mod key_file;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "views")]
+pub mod lease;
+#[cfg(feature = "views")]
+pub mod live;
+#[cfg(feature = "live-state")]
+pub mod live_state;
+#[cfg(feature = "oauth")]
+pub mod oauth;
+#[cfg(feature = "views")]
+pub mod probe;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "python")]
 pub mod python;
+pub mod quality;
+#[cfg(feature = "views")]
+pub mod query;
+#[cfg(feature = "views")]
+pub mod quality_summary;
+#[cfg(feature = "views")]
+pub mod request_id;
+#[cfg(feature = "views")]
+pub mod retry;
+#[cfg(feature = "store-and-forward")]
+pub mod saf_client;
+#[cfg(feature = "schedule")]
+pub mod schedule;
+#[cfg(feature = "views")]
+pub mod shutdown;
+#[cfg(feature = "views")]
+pub mod slow_calls;
+#[cfg(feature = "sparkplug")]
+pub mod sparkplug;
+pub mod stats;
+#[cfg(all(feature = "views", feature = "store-and-forward"))]
+pub mod sync;
+#[cfg(feature = "views")]
+pub mod tag;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timestamps;
+#[cfg(all(feature = "tls", any(feature = "views", feature = "store-and-forward")))]
+pub mod tls;
+#[cfg(any(feature = "views", feature = "store-and-forward"))]
+mod transport;
+#[cfg(feature = "views")]
+pub mod truncation;
+pub mod types;
+#[cfg(feature = "views")]
+pub mod units;
+#[cfg(feature = "views")]
+pub mod view;
+#[cfg(feature = "views")]
+pub mod views_api;
+#[cfg(feature = "views")]
+pub mod views_client;
+#[cfg(feature = "watch")]
+pub mod watch;
 
-pub use views_client::ViewsClient;
+#[cfg(feature = "views")]
+pub use access::{Access, AccessReport};
+#[cfg(feature = "store-and-forward")]
+pub use admin::AdminClient;
+#[cfg(feature = "views")]
+pub use annotations::Annotation;
+#[cfg(feature = "views")]
+pub use bulk::{BulkFetch, Pages};
+#[cfg(feature = "bundle")]
+pub use bundle::{Bundle, BundleExport};
+#[cfg(feature = "cache")]
+pub use cache::{CachedRead, OfflinePolicy, ReadCache, StaleRead};
+#[cfg(feature = "views")]
+pub use capabilities::{Capabilities, Capability, ServerVersion};
+#[cfg(feature = "views")]
+pub use cci_pool::CciPool;
+#[cfg(feature = "views")]
+pub use checkpoint::Checkpoint;
+#[cfg(feature = "views")]
+pub use client_pool::{ClientPool, HealthCheckTask, SiteHealth};
+#[cfg(feature = "views")]
+pub use clock_skew::{ClockSkew, SkewCheck};
+#[cfg(feature = "views")]
+pub use columnar::{RawColumns, RawDataColumns};
+#[cfg(feature = "views")]
+pub use completeness::{CompletenessReport, Gap, GapScan};
+#[cfg(feature = "views")]
+pub use connection::ConnectionState;
+#[cfg(feature = "views")]
+pub use cross_view::PerView;
+pub use descriptor::{FILE_DESCRIPTOR_SET, descriptors};
+pub use error::{BoxError, CanaryError, Classify, Error, ResultStream};
+#[cfg(feature = "views")]
+pub use events::{Event, EventQuery, EventStatus};
+#[cfg(feature = "export")]
+pub use export::{ExportProgress, ExportSummary, Exporter};
+#[cfg(feature = "views")]
+pub use frame::TimeSeriesFrame;
+pub use gapfill::{FillMethod, FilledSeries, GapFill};
+#[cfg(feature = "views")]
+pub use hedge::{HedgePolicy, HedgedClient};
+#[cfg(all(feature = "views", feature = "store-and-forward"))]
+pub use historian::{CanaryHistorian, Verification};
+#[cfg(feature = "views")]
+pub use history::{Aggregate, RawOptions};
+#[cfg(feature = "influx")]
+pub use influx::{InfluxWriter, LineProtocol};
+#[cfg(feature = "inventory")]
+pub use inventory::{Inventory, InventoryDiff, InventoryEntry, InventoryScan};
+#[cfg(feature = "kafka")]
+pub use kafka::{KafkaFormat, KafkaSink, KafkaSinkBuilder};
+#[cfg(feature = "views")]
+pub use key_file::KeyFileWatch;
+#[cfg(feature = "views")]
+pub use lease::{CciLease, LeaseAction, LeaseWarning};
+#[cfg(feature = "views")]
+pub use live::{
+    Deadband, LiveBuffer, LiveFilter, LiveLag, LiveSubscription, OverflowPolicy, Reconcile,
+    Resubscribe, TagFilter,
+};
+#[cfg(feature = "live-state")]
+pub use live_state::SubscriptionState;
+#[cfg(feature = "oauth")]
+pub use oauth::{ClientCredentials, TokenSource};
+#[cfg(feature = "views")]
+pub use probe::{Health, ProbeReport};
+#[cfg(feature = "prometheus")]
+pub use prometheus::{Gauge, PrometheusExporter};
+pub use quality::{Quality, QualityFilter};
+#[cfg(feature = "views")]
+pub use query::Query;
+#[cfg(feature = "views")]
+pub use quality_summary::{BadStretch, QualityScan, QualitySummary};
+#[cfg(feature = "views")]
+pub use request_id::RequestId;
+#[cfg(feature = "views")]
+pub use retry::RetryPolicy;
+#[cfg(feature = "store-and-forward")]
+pub use saf_client::StoreAndForwardClient;
+#[cfg(feature = "schedule")]
+pub use schedule::{CronSchedule, ExportJob, JobOutcome, JobReport, ScheduleConfig};
+#[cfg(feature = "views")]
+pub use slow_calls::{SlowCall, SlowCallLog};
+#[cfg(feature = "sparkplug")]
+pub use sparkplug::{SparkplugBridge, SparkplugMetric};
+pub use stats::{TagStats, TimeWeighted};
+#[cfg(all(feature = "views", feature = "store-and-forward"))]
+pub use sync::{SyncJob, SyncProgress, SyncSummary, TagMap};
+#[cfg(feature = "views")]
+pub use tag::{Tag, TagSubscription};
+#[cfg(all(feature = "tls", any(feature = "views", feature = "store-and-forward")))]
+pub use tls::{TlsPolicy, TlsVersion};
+#[cfg(feature = "views")]
+pub use truncation::{Truncated, TruncationCheck};
+pub use types::{DatasetInfo, TagInfo, TagSeries, Tvq, Value};
+#[cfg(feature = "views")]
+pub use units::UnitConverter;
+#[cfg(feature = "views")]
+pub use view::{Dataset, View};
+#[cfg(feature = "views")]
+pub use views_api::CanaryViewsApi;
+#[cfg(feature = "views")]
+pub use views_client::{ViewsClient, ViewsClientBuilder};
+#[cfg(feature = "watch")]
+pub use watch::{Alert, AlertState, Condition, Watch, WatchConfig, WatchRule, Webhook};
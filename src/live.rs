@@ -0,0 +1,821 @@
+//! Buffered live subscriptions with lag metrics.
+//!
+//! A [`tonic::Streaming`] only holds what the consumer hasn't pulled yet, so
+//! a slow consumer quietly falls behind the historian instead. A
+//! [`LiveSubscription`] reads the stream on a background task into a bounded
+//! buffer, and [`LiveSubscription::lag`] reports how far the consumer trails:
+//! how many updates wait in the buffer, since when, and how many were dropped.
+//! What happens when the buffer is full is up to the [`OverflowPolicy`].
+//!
+//! A subscription opened with [`ViewsClient::subscribe_resubscribing`] also
+//! survives a dropped stream: it subscribes again with backoff and, before
+//! the new stream's updates, emits one update reconciling what was missed
+//! (the tags' current values, or their raw samples since the last one
+//! received), so consumers see every transition across the reconnect.
+//!
+//! [`LiveSubscription::set_filter`] drops insignificant samples before they
+//! reach the buffer: changes inside a tag's [`Deadband`], and samples that
+//! follow the last one passed sooner than a minimum interval.
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use crowsong::live::{LiveBuffer, OverflowPolicy};
+//! use crowsong::canary::views::grpc::api::SubscribeToLiveDataRequest;
+//!
+//! let request = SubscribeToLiveDataRequest {
+//!     tags: vec!["Plant.Line1.Speed".to_string()],
+//!     ..Default::default()
+//! };
+//! let buffer = LiveBuffer::new(1000).overflow(OverflowPolicy::DropOldest);
+//! let mut live = client.subscribe_buffered(request, buffer).await?;
+//! while let Some(update) = live.next().await? {
+//!     println!("{} tags updated, {} dropped", update.tags_and_data.len(), live.lag().dropped);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tonic::Status;
+
+use crate::bulk::BulkFetch;
+use crate::canary::utility::protobuf_shared_types::GrpcTvq;
+use crate::canary::views::grpc::api::{
+    GetTagCurrentValueRequest, SubscribeToLiveDataRequest, SubscribeToLiveDataResponse,
+    TvqsAndAnnotations,
+};
+use crate::connection::ConnectionState;
+use crate::error::{Classify, Error};
+use crate::types::Tvq;
+use crate::views_client::ViewsClient;
+
+/// What a [`LiveSubscription`] does with an update that arrives while its
+/// buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Stop reading until the consumer catches up, letting HTTP/2 flow
+    /// control hold back the server.
+    #[default]
+    Block,
+    /// Discard the oldest buffered update to make room, counting it in
+    /// [`LiveLag::dropped`].
+    DropOldest,
+    /// End the subscription with `RESOURCE_EXHAUSTED`.
+    Error,
+}
+
+/// The size of a [`LiveSubscription`]'s buffer and what to do when it fills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveBuffer {
+    capacity: usize,
+    overflow: OverflowPolicy,
+}
+
+impl Default for LiveBuffer {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl LiveBuffer {
+    /// Buffer up to `capacity` updates (at least one), blocking when full.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            overflow: OverflowPolicy::default(),
+        }
+    }
+
+    /// What to do with an update that arrives while the buffer is full.
+    pub fn overflow(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
+}
+
+/// What a resubscribing [`LiveSubscription`] emits after a dropped stream,
+/// before the new stream's updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reconcile {
+    /// Nothing; transitions during the outage are lost.
+    Nothing,
+    /// Each subscribed tag's current value.
+    #[default]
+    CurrentValues,
+    /// Each tag's raw samples since the last one received, or its current
+    /// value if none was received before the drop.
+    RawSince,
+}
+
+/// How a [`LiveSubscription`] recovers from a dropped stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resubscribe {
+    view: String,
+    reconcile: Reconcile,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl Resubscribe {
+    /// Resubscribe indefinitely, waiting 1 second and then doubling up to 30
+    /// between attempts, and reconcile from the current values of the
+    /// subscribed tags in `view`.
+    pub fn new(view: impl Into<String>) -> Self {
+        Self {
+            view: view.into(),
+            reconcile: Reconcile::default(),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+
+    /// What to emit after resubscribing.
+    pub fn reconcile(mut self, reconcile: Reconcile) -> Self {
+        self.reconcile = reconcile;
+        self
+    }
+
+    /// Wait `initial` before the first attempt, doubling up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Give up, ending the subscription with the last error, after `attempts`
+    /// failed attempts in a row.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+}
+
+/// How far a sample must move from the last one passed to be passed too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "live-state",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Deadband {
+    /// By more than this many engineering units.
+    Absolute(f64),
+    /// By more than this percentage of the last value passed.
+    Percent(f64),
+}
+
+impl Deadband {
+    fn exceeded(self, last: f64, value: f64) -> bool {
+        match self {
+            Deadband::Absolute(band) => (value - last).abs() > band,
+            Deadband::Percent(_) if last == 0.0 => value != 0.0,
+            Deadband::Percent(percent) => ((value - last) / last).abs() * 100.0 > percent,
+        }
+    }
+}
+
+/// Which of one tag's live samples a [`LiveFilter`] passes.
+///
+/// A sample whose quality differs from the last one passed always passes.
+/// Other samples are dropped if they arrive within the minimum interval of
+/// the last one passed, or, for numeric values, stay inside the deadband;
+/// non-numeric values under a deadband pass when they change. Dropped
+/// samples are gone, so the last value of a burst may not be seen until the
+/// tag changes again.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(
+    feature = "live-state",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct TagFilter {
+    deadband: Option<Deadband>,
+    min_interval: Option<Duration>,
+}
+
+impl TagFilter {
+    /// A filter passing every sample.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deadband(mut self, deadband: Deadband) -> Self {
+        self.deadband = Some(deadband);
+        self
+    }
+
+    /// Pass at most one sample per `interval` of sample time.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = Some(interval);
+        self
+    }
+
+    fn passes(&self, last: Option<&Tvq>, tvq: &Tvq) -> bool {
+        let Some(last) = last else {
+            return true;
+        };
+        if tvq.quality != last.quality {
+            return true;
+        }
+        if let Some(min) = self.min_interval
+            && tvq
+                .timestamp
+                .duration_since(last.timestamp)
+                .is_ok_and(|since| since < min)
+        {
+            return false;
+        }
+        match self.deadband {
+            None => true,
+            Some(band) => match (last.value.as_f64(), tvq.value.as_f64()) {
+                (Some(last), Some(value)) => band.exceeded(last, value),
+                _ => tvq.value != last.value,
+            },
+        }
+    }
+}
+
+/// Per-tag [`TagFilter`]s for a [`LiveSubscription`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "live-state",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct LiveFilter {
+    default: TagFilter,
+    tags: HashMap<String, TagFilter>,
+}
+
+impl LiveFilter {
+    /// Apply `default` to every tag without a filter of its own.
+    pub fn new(default: TagFilter) -> Self {
+        Self {
+            default,
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Apply `filter` to `tag_name`.
+    pub fn tag(mut self, tag_name: impl Into<String>, filter: TagFilter) -> Self {
+        self.tags.insert(tag_name.into(), filter);
+        self
+    }
+
+    fn for_tag(&self, tag_name: &str) -> &TagFilter {
+        self.tags.get(tag_name).unwrap_or(&self.default)
+    }
+}
+
+/// Learn the tag names of the aliases `update` introduces.
+pub(crate) fn learn_aliases(
+    aliases: &mut HashMap<i32, String>,
+    update: &SubscribeToLiveDataResponse,
+) {
+    aliases.extend(
+        update
+            .tag_aliases
+            .iter()
+            .map(|(name, alias)| (*alias, name.clone())),
+    );
+}
+
+/// The newest sample time in `update` for each tag with samples, naming
+/// aliased tags by the aliases learned so far and those `update` introduces.
+pub(crate) fn newest_samples<'a>(
+    update: &'a SubscribeToLiveDataResponse,
+    aliases: &'a mut HashMap<i32, String>,
+) -> impl Iterator<Item = (&'a str, SystemTime)> {
+    learn_aliases(aliases, update);
+    let aliases = &*aliases;
+    let named = update
+        .tags_and_data
+        .iter()
+        .map(|(tag, data)| (tag.as_str(), data));
+    let aliased = update
+        .aliases_and_data
+        .iter()
+        .filter_map(|(alias, data)| Some((aliases.get(alias)?.as_str(), data)));
+    named.chain(aliased).filter_map(|(tag, data)| {
+        let newest = data
+            .tvqs
+            .iter()
+            .filter_map(|tvq| tvq.timestamp)
+            .filter_map(|ts| SystemTime::try_from(ts).ok())
+            .max()?;
+        Some((tag, newest))
+    })
+}
+
+/// A [`LiveFilter`] and the last sample it passed for each tag.
+#[derive(Default)]
+struct Filtering {
+    filter: Option<LiveFilter>,
+    passed: HashMap<String, Tvq>,
+    /// Tag names by alias, from the first update of an aliased subscription.
+    aliases: HashMap<i32, String>,
+    dropped: u64,
+}
+
+impl Filtering {
+    /// Drop the samples of `update` the filter rejects, returning whether
+    /// anything is left to deliver.
+    fn apply(&mut self, update: &mut SubscribeToLiveDataResponse) -> bool {
+        learn_aliases(&mut self.aliases, update);
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        let had_data = !update.tags_and_data.is_empty() || !update.aliases_and_data.is_empty();
+        for (tag, data) in &mut update.tags_and_data {
+            Self::retain(filter, &mut self.passed, &mut self.dropped, tag, data);
+        }
+        for (alias, data) in &mut update.aliases_and_data {
+            if let Some(tag) = self.aliases.get(alias) {
+                Self::retain(filter, &mut self.passed, &mut self.dropped, tag, data);
+            }
+        }
+        let empty = |data: &TvqsAndAnnotations| data.tvqs.is_empty() && data.annotations.is_empty();
+        update.tags_and_data.retain(|_, data| !empty(data));
+        update.aliases_and_data.retain(|_, data| !empty(data));
+        !had_data
+            || !update.tags_and_data.is_empty()
+            || !update.aliases_and_data.is_empty()
+            || !update.tag_errors.is_empty()
+            || !update.browse_errors.is_empty()
+            || !update.tag_aliases.is_empty()
+    }
+
+    fn retain(
+        filter: &LiveFilter,
+        passed: &mut HashMap<String, Tvq>,
+        dropped: &mut u64,
+        tag: &str,
+        data: &mut TvqsAndAnnotations,
+    ) {
+        let tag_filter = filter.for_tag(tag);
+        data.tvqs.retain(|grpc| {
+            let tvq = Tvq::from(grpc);
+            if tag_filter.passes(passed.get(tag), &tvq) {
+                passed.insert(tag.to_string(), tvq);
+                true
+            } else {
+                *dropped += 1;
+                false
+            }
+        });
+    }
+}
+
+/// How far a consumer trails its [`LiveSubscription`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LiveLag {
+    /// Updates received but not yet returned by [`LiveSubscription::next`].
+    pub buffered: usize,
+    /// When the oldest buffered update arrived, if any are buffered.
+    pub oldest_pending: Option<SystemTime>,
+    /// Updates discarded under [`OverflowPolicy::DropOldest`].
+    pub dropped: u64,
+    /// Times the stream dropped and was subscribed again.
+    pub resubscribes: u64,
+    /// Samples dropped by the subscription's [`LiveFilter`].
+    pub filtered: u64,
+}
+
+/// State shared by the reader task and the subscription handle.
+#[derive(Default)]
+struct Buffer {
+    queue: VecDeque<(SystemTime, SubscribeToLiveDataResponse)>,
+    dropped: u64,
+    resubscribes: u64,
+    /// Set once the stream ends; `Some(Err)` if it failed.
+    end: Option<Result<(), Status>>,
+    filtering: Filtering,
+}
+
+#[derive(Default)]
+struct Shared {
+    buffer: Mutex<Buffer>,
+    /// Signalled when an update is queued or the stream ends.
+    ready: Notify,
+    /// Signalled when the consumer takes an update.
+    space: Notify,
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buffer> {
+        self.buffer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queue `update`, applying the overflow policy when the buffer is full.
+    /// Updates the filter leaves empty are skipped.
+    async fn push(
+        &self,
+        mut update: SubscribeToLiveDataResponse,
+        buffer: LiveBuffer,
+    ) -> Result<(), Status> {
+        if !self.lock().filtering.apply(&mut update) {
+            return Ok(());
+        }
+        loop {
+            let space = self.space.notified();
+            {
+                let mut queued = self.lock();
+                if queued.queue.len() < buffer.capacity {
+                    queued.queue.push_back((SystemTime::now(), update));
+                    drop(queued);
+                    self.ready.notify_one();
+                    return Ok(());
+                }
+                match buffer.overflow {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropOldest => {
+                        queued.queue.pop_front();
+                        queued.dropped += 1;
+                        queued.queue.push_back((SystemTime::now(), update));
+                        drop(queued);
+                        self.ready.notify_one();
+                        return Ok(());
+                    }
+                    OverflowPolicy::Error => {
+                        return Err(Status::resource_exhausted(format!(
+                            "live subscription fell more than {} updates behind",
+                            buffer.capacity
+                        )));
+                    }
+                }
+            }
+            space.await;
+        }
+    }
+}
+
+impl ViewsClient {
+    /// Subscribe to live data, reading updates into a bounded buffer on a
+    /// background task. See [`live`](crate::live).
+    pub async fn subscribe_buffered(
+        &self,
+        request: SubscribeToLiveDataRequest,
+        buffer: LiveBuffer,
+    ) -> Result<LiveSubscription, Error> {
+        let stream = self.subscribe_to_live_data(request).await?;
+        let subscription = LiveSubscription::spawn(stream, buffer);
+        self.tasks().register(subscription.handle.abort_handle());
+        Ok(subscription)
+    }
+
+    /// Like [`subscribe_buffered`](Self::subscribe_buffered), subscribing
+    /// again when the stream drops or ends. See [`live`](crate::live).
+    ///
+    /// Reconciling needs the subscribed tags by name, so a request with
+    /// `browse_paths` is rejected unless `resubscribe` reconciles
+    /// [`Nothing`](Reconcile::Nothing).
+    pub async fn subscribe_resubscribing(
+        &self,
+        request: SubscribeToLiveDataRequest,
+        buffer: LiveBuffer,
+        resubscribe: Resubscribe,
+    ) -> Result<LiveSubscription, Error> {
+        if !request.browse_paths.is_empty() && resubscribe.reconcile != Reconcile::Nothing {
+            return Err(Status::invalid_argument(
+                "can't reconcile a live subscription to browse paths; list its tags or reconcile nothing",
+            ).into());
+        }
+        let stream = self.subscribe_to_live_data(request.clone()).await?;
+        let resume = Resume {
+            client: self.handle(),
+            request,
+            policy: resubscribe,
+            last_seen: HashMap::new(),
+            aliases: HashMap::new(),
+        };
+        let subscription = LiveSubscription::start(stream, buffer, Some(resume), None, None);
+        self.tasks().register(subscription.handle.abort_handle());
+        Ok(subscription)
+    }
+
+    /// Like [`subscribe_resubscribing`](Self::subscribe_resubscribing) with
+    /// [`Reconcile::RawSince`], first emitting each tag's raw samples since
+    /// `last_seen` (or its current value if it has none there), for picking
+    /// up where an earlier process stopped.
+    #[cfg(feature = "live-state")]
+    pub(crate) async fn subscribe_resuming(
+        &self,
+        request: SubscribeToLiveDataRequest,
+        buffer: LiveBuffer,
+        resubscribe: Resubscribe,
+        last_seen: HashMap<String, SystemTime>,
+        filter: Option<LiveFilter>,
+    ) -> Result<LiveSubscription, Error> {
+        let stream = self.subscribe_to_live_data(request.clone()).await?;
+        let mut resume = Resume {
+            client: self.handle(),
+            request,
+            policy: resubscribe.reconcile(Reconcile::RawSince),
+            last_seen,
+            aliases: HashMap::new(),
+        };
+        let missed = resume.reconcile().await?;
+        let subscription = LiveSubscription::start(stream, buffer, Some(resume), missed, filter);
+        self.tasks().register(subscription.handle.abort_handle());
+        Ok(subscription)
+    }
+}
+
+type LiveStream = tonic::Streaming<SubscribeToLiveDataResponse>;
+
+/// What a resubscribing reader needs to open a new stream.
+struct Resume {
+    client: ViewsClient,
+    request: SubscribeToLiveDataRequest,
+    policy: Resubscribe,
+    /// The newest sample received for each tag, for [`Reconcile::RawSince`].
+    last_seen: HashMap<String, SystemTime>,
+    /// Tag names by alias, for naming aliased samples in `last_seen`.
+    aliases: HashMap<i32, String>,
+}
+
+impl Resume {
+    fn observe(&mut self, update: &SubscribeToLiveDataResponse) {
+        if self.policy.reconcile != Reconcile::RawSince {
+            return;
+        }
+        for (tag, newest) in newest_samples(update, &mut self.aliases) {
+            let last = self.last_seen.entry(tag.to_string()).or_insert(newest);
+            *last = (*last).max(newest);
+        }
+    }
+
+    /// Subscribe again, retrying with backoff, and build the update that
+    /// reconciles the outage.
+    async fn resubscribe(
+        &mut self,
+        mut error: Status,
+    ) -> Result<(LiveStream, Option<SubscribeToLiveDataResponse>), Status> {
+        self.client
+            .state_monitor()
+            .set(ConnectionState::Reconnecting);
+        let mut backoff = self.policy.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            if self.policy.max_attempts.is_some_and(|max| attempts >= max) {
+                return Err(error);
+            }
+            attempts += 1;
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.policy.max_backoff);
+
+            // Replace the ID every handle shares rather than acquiring one
+            // of our own, which nothing would release.
+            if error.is_invalid_cci()
+                && let Err(status) = self.client.replace_cci(self.client.cci()).await
+            {
+                error = status;
+                continue;
+            }
+            let stream = match self
+                .client
+                .subscribe_to_live_data(self.request.clone())
+                .await
+            {
+                Ok(stream) => stream,
+                Err(status) if status.is_retryable() => {
+                    error = status.into();
+                    continue;
+                }
+                Err(status) => return Err(status.into()),
+            };
+            // Reconcile after subscribing, so nothing changes unseen between
+            // the snapshot and the stream's first update.
+            match self.reconcile().await {
+                Ok(snapshot) => return Ok((stream, snapshot)),
+                Err(status) if status.is_retryable() => error = status.into(),
+                Err(status) => return Err(status.into()),
+            }
+        }
+    }
+
+    async fn reconcile(&mut self) -> Result<Option<SubscribeToLiveDataResponse>, Error> {
+        let tags = &self.request.tags;
+        let mut missed: HashMap<String, Vec<GrpcTvq>> = HashMap::new();
+        let mut current = Vec::new();
+        match self.policy.reconcile {
+            Reconcile::Nothing => return Ok(None),
+            Reconcile::CurrentValues => current.extend(tags.iter().cloned()),
+            Reconcile::RawSince => {
+                let now = SystemTime::now();
+                for tag in tags {
+                    let Some(&since) = self.last_seen.get(tag) else {
+                        current.push(tag.clone());
+                        continue;
+                    };
+                    let tag_names = [tag.clone()];
+                    let pages = BulkFetch::new(&self.policy.view, tag_names.clone(), since, now)
+                        .fetch_chunk(&self.client, &tag_names)
+                        .await?;
+                    let tvqs = missed.entry(tag.clone()).or_default();
+                    for page in &pages {
+                        tvqs.extend(
+                            (0..page.len())
+                                .map(|row| page.tvq(row))
+                                .filter(|tvq| tvq.timestamp > since)
+                                .map(|tvq| GrpcTvq::from(&tvq)),
+                        );
+                    }
+                }
+            }
+        }
+        if !current.is_empty() {
+            let resp = self
+                .client
+                .get_tag_current_value(GetTagCurrentValueRequest {
+                    view: self.policy.view.clone(),
+                    tag_names: current,
+                    use_time_extension: None,
+                    quality: 0,
+                    cci: 0,
+                })
+                .await?;
+            for value in &resp.tag_values {
+                missed
+                    .entry(value.tag_item_id.clone())
+                    .or_default()
+                    .push(GrpcTvq::from(&Tvq::from(value)));
+            }
+        }
+        missed.retain(|_, tvqs| !tvqs.is_empty());
+        if missed.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(SubscribeToLiveDataResponse {
+            tags_and_data: missed
+                .into_iter()
+                .map(|(tag, tvqs)| {
+                    let data = TvqsAndAnnotations {
+                        tvqs,
+                        annotations: Vec::new(),
+                    };
+                    (tag, data)
+                })
+                .collect(),
+            ..Default::default()
+        }))
+    }
+}
+
+/// Ends the stream for the consumer if the reader task stops without
+/// saying how, because it was aborted or panicked, so `next` doesn't wait
+/// forever.
+struct EndGuard(Arc<Shared>);
+
+impl Drop for EndGuard {
+    fn drop(&mut self) {
+        let mut buffer = self.0.lock();
+        if buffer.end.is_some() {
+            return;
+        }
+        buffer.end = Some(Err(if std::thread::panicking() {
+            Status::internal("the live subscription reader panicked")
+        } else {
+            Status::cancelled("the live subscription was stopped")
+        }));
+        drop(buffer);
+        self.0.ready.notify_one();
+    }
+}
+
+/// A live subscription read into a bounded buffer, stopped when dropped.
+pub struct LiveSubscription {
+    shared: Arc<Shared>,
+    handle: JoinHandle<()>,
+}
+
+impl LiveSubscription {
+    /// Read `stream` into a buffer on a background task.
+    pub fn spawn(stream: LiveStream, buffer: LiveBuffer) -> Self {
+        Self::start(stream, buffer, None, None, None)
+    }
+
+    /// Read `stream` on a background task, queueing `first` before its
+    /// updates.
+    fn start(
+        mut stream: LiveStream,
+        buffer: LiveBuffer,
+        mut resume: Option<Resume>,
+        mut first: Option<SubscribeToLiveDataResponse>,
+        filter: Option<LiveFilter>,
+    ) -> Self {
+        let shared = Arc::new(Shared::default());
+        shared.lock().filtering.filter = filter;
+        let task = shared.clone();
+        let handle = tokio::spawn(async move {
+            let _ended = EndGuard(task.clone());
+            let end = loop {
+                if let Some(update) = first.take()
+                    && let Err(status) = task.push(update, buffer).await
+                {
+                    break Err(status);
+                }
+                let dropped = match stream.message().await {
+                    Ok(Some(update)) => {
+                        if let Some(resume) = &mut resume {
+                            resume.observe(&update);
+                        }
+                        match task.push(update, buffer).await {
+                            Ok(()) => continue,
+                            // Overflowing isn't a dropped stream.
+                            Err(status) => break Err(status),
+                        }
+                    }
+                    Ok(None) if resume.is_none() => break Ok(()),
+                    // A live stream only ends when the server goes away.
+                    Ok(None) => Status::unavailable("the live stream ended"),
+                    Err(status) => status,
+                };
+                let Some(resume) = resume.as_mut().filter(|_| dropped.is_retryable()) else {
+                    break Err(dropped);
+                };
+                let snapshot = match resume.resubscribe(dropped).await {
+                    Ok((new_stream, snapshot)) => {
+                        stream = new_stream;
+                        snapshot
+                    }
+                    Err(status) => break Err(status),
+                };
+                task.lock().resubscribes += 1;
+                if let Some(snapshot) = snapshot
+                    && let Err(status) = task.push(snapshot, buffer).await
+                {
+                    break Err(status);
+                }
+            };
+            task.lock().end = Some(end);
+            task.ready.notify_one();
+        });
+        Self { shared, handle }
+    }
+
+    /// The next update, or `None` once the stream has ended and every
+    /// buffered update has been returned.
+    pub async fn next(&mut self) -> Result<Option<SubscribeToLiveDataResponse>, Error> {
+        loop {
+            let ready = self.shared.ready.notified();
+            {
+                let mut buffer = self.shared.lock();
+                if let Some((_, update)) = buffer.queue.pop_front() {
+                    drop(buffer);
+                    self.shared.space.notify_one();
+                    return Ok(Some(update));
+                }
+                match &buffer.end {
+                    Some(Ok(())) => return Ok(None),
+                    Some(Err(status)) => return Err(status.clone().into()),
+                    None => {}
+                }
+            }
+            ready.await;
+        }
+    }
+
+    /// How far the consumer trails the stream.
+    pub fn lag(&self) -> LiveLag {
+        let buffer = self.shared.lock();
+        LiveLag {
+            buffered: buffer.queue.len(),
+            oldest_pending: buffer.queue.front().map(|(received, _)| *received),
+            dropped: buffer.dropped,
+            resubscribes: buffer.resubscribes,
+            filtered: buffer.filtering.dropped,
+        }
+    }
+
+    /// Filter samples from here on, or with `None` stop filtering. Updates
+    /// already buffered are delivered as they are.
+    pub fn set_filter(&self, filter: Option<LiveFilter>) {
+        self.shared.lock().filtering.filter = filter;
+    }
+
+    /// Stop reading the stream. Buffered updates are discarded.
+    pub fn stop(self) {}
+}
+
+impl fmt::Debug for LiveSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveSubscription")
+            .field("lag", &self.lag())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LiveSubscription {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
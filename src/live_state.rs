@@ -0,0 +1,195 @@
+//! Saving a live subscription across process restarts.
+//!
+//! An edge collector that restarts loses both its subscription and
+//! whatever changed while it was down. A [`SubscriptionState`] records the
+//! subscription's definition (view, tags, request options, and
+//! [`LiveFilter`]) along with a watermark per tag: the newest sample time
+//! delivered. Saved as JSON and loaded after a restart,
+//! [`ViewsClient::restore_subscription`] subscribes again and first replays
+//! each tag's raw history since its watermark, then continues with live
+//! updates, resubscribing with [`Reconcile::RawSince`] if the stream drops.
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use crowsong::live::LiveBuffer;
+//! use crowsong::live_state::SubscriptionState;
+//!
+//! let path = "collector-state.json";
+//! let mut state = match SubscriptionState::load(path)? {
+//!     Some(state) => state,
+//!     None => SubscriptionState::new("Plant", ["Plant.Line1.Speed", "Plant.Line1.Temp"]),
+//! };
+//! let mut live = client.restore_subscription(&state, LiveBuffer::default()).await?;
+//! while let Some(update) = live.next().await? {
+//!     // ... hand the update downstream, then record it as delivered.
+//!     state.observe(&update);
+//!     state.save(path)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Reconcile::RawSince`]: crate::live::Reconcile::RawSince
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::canary::views::grpc::api::{SubscribeToLiveDataRequest, SubscribeToLiveDataResponse};
+use crate::error::Error;
+use crate::live::{self, LiveBuffer, LiveFilter, LiveSubscription, Resubscribe};
+use crate::views_client::ViewsClient;
+
+/// A live subscription's definition and how far it has been delivered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionState {
+    pub view: String,
+    pub tags: Vec<String>,
+    /// Only send each tag's latest value per reporting interval.
+    #[serde(default)]
+    pub latest_value_only: bool,
+    /// Have the server time-extend unchanged values every interval.
+    #[serde(default)]
+    pub virtual_time_extension: bool,
+    #[serde(default)]
+    pub annotations: bool,
+    /// How often the server reports, in seconds; the server's default if
+    /// unset.
+    #[serde(default)]
+    pub reporting_interval_seconds: Option<f64>,
+    #[serde(default)]
+    pub filter: Option<LiveFilter>,
+    /// The newest sample time delivered for each tag.
+    #[serde(default, with = "watermarks")]
+    pub watermarks: BTreeMap<String, SystemTime>,
+    /// Tag names by alias, from the updates observed so far.
+    #[serde(skip)]
+    aliases: HashMap<i32, String>,
+}
+
+impl SubscriptionState {
+    /// A subscription to `tags` in `view`, with nothing delivered yet.
+    pub fn new(view: impl Into<String>, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            view: view.into(),
+            tags: tags.into_iter().map(Into::into).collect(),
+            latest_value_only: false,
+            virtual_time_extension: false,
+            annotations: false,
+            reporting_interval_seconds: None,
+            filter: None,
+            watermarks: BTreeMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Load a state file, or `None` if it doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_str(&contents).map(Some).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad subscription state in {}: {e}", path.display()),
+            )
+        })
+    }
+
+    /// Write the state file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let contents = serde_json::to_string_pretty(self)?;
+        // Write beside the target and rename, so a crash never leaves a torn file.
+        let partial = path.with_extension("partial");
+        fs::write(&partial, contents)?;
+        fs::rename(partial, path)
+    }
+
+    /// Advance the watermarks past the samples in `update`. Call this once
+    /// the update has been handled, so a restart replays anything that
+    /// wasn't.
+    pub fn observe(&mut self, update: &SubscribeToLiveDataResponse) {
+        for (tag, newest) in live::newest_samples(update, &mut self.aliases) {
+            let mark = self.watermarks.entry(tag.to_string()).or_insert(newest);
+            *mark = (*mark).max(newest);
+        }
+    }
+
+    /// The subscription request this state describes.
+    pub fn request(&self) -> SubscribeToLiveDataRequest {
+        SubscribeToLiveDataRequest {
+            tags: self.tags.clone(),
+            is_lastest_value_only: self.latest_value_only,
+            is_virtual_time_extension_enabled: self.virtual_time_extension,
+            is_annotations_enabled: self.annotations,
+            reporting_interval: self
+                .reporting_interval_seconds
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .and_then(|interval| interval.try_into().ok()),
+            ..Default::default()
+        }
+    }
+}
+
+impl ViewsClient {
+    /// Subscribe as `state` describes, first replaying each tag's raw
+    /// samples since its watermark (or its current value if it has none).
+    /// See [`live_state`](crate::live_state).
+    pub async fn restore_subscription(
+        &self,
+        state: &SubscriptionState,
+        buffer: LiveBuffer,
+    ) -> Result<LiveSubscription, Error> {
+        self.subscribe_resuming(
+            state.request(),
+            buffer,
+            Resubscribe::new(&state.view),
+            state.watermarks.clone().into_iter().collect(),
+            state.filter.clone(),
+        )
+        .await
+    }
+}
+
+/// Watermarks as RFC 3339 strings, so state files stay readable.
+mod watermarks {
+    use std::collections::BTreeMap;
+    use std::time::SystemTime;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::timestamps;
+
+    pub fn serialize<S: Serializer>(
+        marks: &BTreeMap<String, SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            marks
+                .iter()
+                .map(|(tag, mark)| (tag, timestamps::format_rfc3339(*mark))),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, SystemTime>, D::Error> {
+        BTreeMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(tag, mark)| {
+                let mark = timestamps::parse_rfc3339(&mark).map_err(D::Error::custom)?;
+                Ok((tag, mark))
+            })
+            .collect()
+    }
+}
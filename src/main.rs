@@ -1,22 +1,143 @@
-use crowsong::ViewsClient;
+use std::process::ExitCode;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use crowsong::interval::parse_interval;
+use crowsong::timestamps::{TimestampFormat, parse_rfc3339};
+use crowsong::{BoxError, QualityScan, StoreAndForwardClient, SyncJob, TagMap, ViewsClient};
+
+/// The usage line and description of `tag-info`, which needs the
+/// `inventory` feature.
+#[cfg(feature = "inventory")]
+macro_rules! tag_info_usage {
+    (line) => {
+        "       crowsong tag-info --view VIEW [--format json] TAG ...\n"
+    };
+    (text) => {
+        "`tag-info` prints each tag's data type, units, description, oldest and
+latest sample times, and current value, or JSON with --format json.\n"
+    };
+}
+
+#[cfg(not(feature = "inventory"))]
+macro_rules! tag_info_usage {
+    ($part:ident) => {
+        ""
+    };
+}
+
+const USAGE: &str = concat!(
+    "\
+usage: crowsong [check]
+       crowsong sync --view VIEW --start TIME --end TIME (--tag TAG ... | --tags-file FILE)
+                     [--map FROM=TO ...] [--checkpoint FILE] [--window-hours N]
+                     [--max-rate SAMPLES_PER_SEC] [--session NAME]
+       crowsong quality --view VIEW --start TIME --end TIME (--tag TAG ... | --tags-file FILE)
+                        [--bucket-minutes N]
+       crowsong exporter --view VIEW --listen ADDR (--tag TAG ... | --tags-file FILE)
+                         [--map TAG=METRIC ...] [--interval-seconds N] [--stale-seconds N]
+       crowsong inventory [--view VIEW ...] [--include-hidden true] [--out FILE]
+       crowsong inventory-diff (--from FILE | --from-view VIEW) (--to FILE | --to-view VIEW)
+       crowsong export-bundle --view VIEW --start TIME --end TIME --out DIR [--dataset NAME ...]
+       crowsong import-bundle --in DIR [--map FROM=TO ...] [--session NAME]
+",
+    tag_info_usage!(line),
+    "       crowsong annotate --view VIEW --tag TAG --at TIME --text TEXT
+       crowsong annotations --view VIEW --start TIME --end TIME (--tag TAG ... | --tags-file FILE)
+       crowsong watch-rules RULES.yaml
+       crowsong schedule JOBS.yaml
+       crowsong login [ENDPOINT]
+
+The source historian is read from ENDPOINT, API_KEY, and USER_ID; `sync`
+and `import-bundle` write to the Store & Forward service at TARGET_ENDPOINT, using
+TARGET_API_KEY (or API_KEY). A --map rule ending in `*` on both sides
+replaces a prefix, e.g. --map 'Plant1.*=Site.Plant1.*'. Interval flags
+take a number in the unit they name or an interval such as 90m or 1h30m.
+`exporter` serves the tags' current values on http://ADDR/metrics, as
+`canary_tag_value` unless a --map rule names another metric.
+`inventory` writes every tag's properties, data context, and dataset as
+CSV, or as JSON when --out ends in .json, to stdout unless --out is given.
+`inventory-diff` compares two JSON inventories or live views, printing
+tags added (+), removed (-), and with changed properties (~).
+`export-bundle` writes a view's tag metadata and raw history to a bundle
+directory with a checksummed manifest, for carrying to another site;
+`import-bundle` checks a bundle's checksums and writes its history.
+",
+    tag_info_usage!(text),
+    "`annotate` stores a note on a tag at a time, as USER_ID; `annotations`
+lists the notes on tags between two times, oldest first, tab-separated.
+`watch-rules` evaluates the rules in a YAML file against live data and
+POSTs alerts to its webhooks. `schedule` runs the export jobs in a YAML
+file on their cron schedules. `login` reads an API key from stdin and
+stores it in the OS keyring for ENDPOINT; API_KEY and TARGET_API_KEY fall
+back to stored keys when unset. API_KEY_FILE names a file to read the
+source's API key from instead, such as a mounted secret; it is re-read
+when it changes. TIME_FORMAT sets how printed times look, e.g.
+`ms,+02:00` or `epoch,ms`."
+);
+
+/// How printed times are rendered, from TIME_FORMAT.
+static TIME_FORMAT: OnceLock<TimestampFormat> = OnceLock::new();
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv::dotenv()?;
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("crowsong: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: &[String]) -> Result<(), BoxError> {
+    if let Ok(format) = std::env::var("TIME_FORMAT") {
+        let format: TimestampFormat = format.parse().map_err(|e| format!("TIME_FORMAT: {e}"))?;
+        TIME_FORMAT.get_or_init(|| format);
+    }
+    match args.first().map(String::as_str) {
+        None | Some("check") => check().await,
+        Some("sync") => sync(Flags::parse(&args[1..])?).await,
+        Some("quality") => quality(Flags::parse(&args[1..])?).await,
+        Some("exporter") => exporter(Flags::parse(&args[1..])?).await,
+        Some("inventory") => inventory(Flags::parse(&args[1..])?).await,
+        Some("inventory-diff") => inventory_diff(Flags::parse(&args[1..])?).await,
+        Some("export-bundle") => export_bundle(Flags::parse(&args[1..])?).await,
+        Some("import-bundle") => import_bundle(Flags::parse(&args[1..])?).await,
+        Some("tag-info") => tag_info(&args[1..]).await,
+        Some("annotate") => annotate(Flags::parse(&args[1..])?).await,
+        Some("annotations") => annotations(Flags::parse(&args[1..])?).await,
+        Some("watch-rules") => match &args[1..] {
+            [path] => watch_rules(path).await,
+            _ => Err(format!("watch-rules expects a rules file\n\n{USAGE}").into()),
+        },
+        Some("schedule") => match &args[1..] {
+            [path] => schedule(path).await,
+            _ => Err(format!("schedule expects a jobs file\n\n{USAGE}").into()),
+        },
+        Some("login") => match &args[1..] {
+            [] => login(&std::env::var("ENDPOINT")?),
+            [endpoint] => login(endpoint),
+            _ => Err(format!("login expects at most an endpoint\n\n{USAGE}").into()),
+        },
+        Some("-h" | "--help" | "help") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(format!("unknown command {command:?}\n\n{USAGE}").into()),
+    }
+}
+
+/// Connect to the source historian and walk its catalog.
+async fn check() -> Result<(), BoxError> {
     let endpoint = std::env::var("ENDPOINT")?;
-    let api_key = std::env::var("API_KEY")?;
-    let user_id = std::env::var("USER_ID")?;
 
     println!("Connecting to Views service at {endpoint}...");
 
-    let mut client = ViewsClient::connect(
-        &endpoint,
-        &api_key,
-        "crowsong-test",
-        &user_id,
-    )
-    .await?;
+    let client = connect_source("crowsong-test").await?;
 
     println!("Connected! CCI = {}", client.cci());
 
@@ -70,3 +191,684 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Copy history from the source historian to a Store & Forward target.
+async fn sync(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&[
+        "view", "start", "end", "tag", "tags-file", "map", "checkpoint", "window-hours",
+        "max-rate", "session",
+    ])?;
+
+    let tags = flags.tags()?;
+    let map = tag_map(&flags)?;
+
+    let mut job = SyncJob::new(
+        flags.require("view")?,
+        tags,
+        parse_time(flags.require("start")?)?,
+        parse_time(flags.require("end")?)?,
+    )
+    .map(map);
+    if let Some(path) = flags.get("checkpoint") {
+        job = job.checkpoint(path);
+    }
+    if let Some(window) = flags.interval("window-hours", 3600.0)? {
+        job = job.window(window);
+    }
+    if let Some(rate) = flags.get("max-rate") {
+        job = job.max_samples_per_sec(rate.parse()?);
+    }
+
+    let source = connect_source("crowsong-sync").await?;
+    let mut target = connect_target(flags.get("session").unwrap_or("crowsong-sync")).await?;
+
+    let result = job
+        .run_with_progress(&source, &mut target, |p| {
+            eprintln!(
+                "[{}/{}] {} through {} ({} samples)",
+                p.tag_index + 1,
+                p.tag_count,
+                p.tag,
+                format_time(p.through),
+                p.samples
+            );
+        })
+        .await;
+
+    target.close_session().await?;
+    source.disconnect().await?;
+    let summary = result?;
+    println!("Synced {} samples across {} tags.", summary.samples, summary.tags);
+    Ok(())
+}
+
+/// Tag renaming rules from --map flags.
+fn tag_map(flags: &Flags) -> Result<TagMap, String> {
+    let mut map = TagMap::new();
+    for rule in flags.all("map") {
+        let (from, to) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("--map expects FROM=TO, got {rule:?}"))?;
+        map = match (from.strip_suffix('*'), to.strip_suffix('*')) {
+            (Some(from), Some(to)) => map.prefix(from, to),
+            _ => map.rename(from, to),
+        };
+    }
+    Ok(map)
+}
+
+/// Connect to the Store & Forward service at TARGET_ENDPOINT and open a
+/// session.
+async fn connect_target(session: &str) -> Result<StoreAndForwardClient, BoxError> {
+    let target_endpoint = std::env::var("TARGET_ENDPOINT")?;
+    let target_key = api_key("TARGET_API_KEY", &target_endpoint)
+        .or_else(|_| std::env::var("API_KEY"))?;
+    #[cfg(feature = "tls")]
+    let mut target =
+        StoreAndForwardClient::connect_with_tls(target_endpoint, target_key, &tls_policy()).await?;
+    #[cfg(not(feature = "tls"))]
+    let mut target = StoreAndForwardClient::connect(target_endpoint, target_key).await?;
+    target.open_session(session).await?;
+    Ok(target)
+}
+
+/// Print a quality breakdown of tags.
+async fn quality(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["view", "start", "end", "tag", "tags-file", "bucket-minutes"])?;
+
+    let mut scan = QualityScan::new(
+        flags.require("view")?,
+        flags.tags()?,
+        parse_time(flags.require("start")?)?,
+        parse_time(flags.require("end")?)?,
+    );
+    if let Some(bucket) = flags.interval("bucket-minutes", 60.0)? {
+        scan = scan.aggregate_buckets(bucket);
+    }
+
+    let client = connect_source("crowsong-quality").await?;
+    let result = scan.run(&client).await;
+    client.disconnect().await?;
+
+    println!(
+        "{:<40} {:>10} {:>7} {:>10} {:>7}  LONGEST BAD",
+        "TAG", "SAMPLES", "GOOD%", "UNCERTAIN%", "BAD%"
+    );
+    for summary in result? {
+        let longest_bad = match summary.longest_bad {
+            Some(stretch) => format!(
+                "{:?} from {}",
+                stretch.duration(),
+                format_time(stretch.start)
+            ),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<40} {:>10} {:>7.2} {:>10.2} {:>7.2}  {longest_bad}",
+            summary.tag_name,
+            summary.samples,
+            summary.good_percent,
+            summary.uncertain_percent,
+            summary.bad_percent
+        );
+        for (quality, count) in &summary.by_substatus {
+            println!("    {:<36} {count:>10}", quality.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Serve tags' current values as Prometheus metrics.
+#[cfg(feature = "prometheus")]
+async fn exporter(flags: Flags) -> Result<(), BoxError> {
+    use crowsong::{Gauge, PrometheusExporter};
+
+    flags.only(&[
+        "view", "listen", "tag", "tags-file", "map", "interval-seconds", "stale-seconds",
+    ])?;
+
+    let mut metrics = std::collections::HashMap::new();
+    for rule in flags.all("map") {
+        let (tag, metric) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("--map expects TAG=METRIC, got {rule:?}"))?;
+        metrics.insert(tag, metric);
+    }
+    let mut exporter = PrometheusExporter::new(flags.require("view")?);
+    for tag in flags.tags()? {
+        let gauge = match metrics.get(tag.as_str()) {
+            Some(metric) => Gauge::new(tag).metric(metric),
+            None => Gauge::new(tag),
+        };
+        exporter = exporter.gauge(gauge);
+    }
+    if let Some(interval) = flags.interval("interval-seconds", 1.0)? {
+        exporter = exporter.interval(interval);
+    }
+    if let Some(stale_after) = flags.interval("stale-seconds", 1.0)? {
+        exporter = exporter.stale_after(stale_after);
+    }
+
+    let listener = tokio::net::TcpListener::bind(flags.require("listen")?).await?;
+    let client = connect_source("crowsong-exporter").await?;
+    eprintln!("Serving metrics on http://{}/metrics", listener.local_addr()?);
+    exporter.serve(client, listener).await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "prometheus"))]
+async fn exporter(_flags: Flags) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `prometheus` feature".into())
+}
+
+/// Write a metadata inventory of the source historian's tags.
+#[cfg(feature = "inventory")]
+async fn inventory(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["view", "include-hidden", "out"])?;
+
+    let mut scan = crowsong::InventoryScan::new();
+    for view in flags.all("view") {
+        scan = scan.view(view);
+    }
+    if let Some(hidden) = flags.get("include-hidden") {
+        scan = scan.include_hidden(hidden.parse()?);
+    }
+
+    let client = connect_source("crowsong-inventory").await?;
+    let result = scan.run(&client).await;
+    client.disconnect().await?;
+    let inventory = result?;
+
+    match flags.get("out") {
+        Some(path) if path.ends_with(".json") => {
+            inventory.write_json(std::io::BufWriter::new(std::fs::File::create(path)?))?
+        }
+        Some(path) => inventory.write_csv(std::io::BufWriter::new(std::fs::File::create(path)?))?,
+        None => inventory.write_csv(std::io::stdout().lock())?,
+    }
+    eprintln!("Inventoried {} tags.", inventory.tags.len());
+    Ok(())
+}
+
+#[cfg(not(feature = "inventory"))]
+async fn inventory(_flags: Flags) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `inventory` feature".into())
+}
+
+/// Print how two inventories, saved or live, differ.
+#[cfg(feature = "inventory")]
+async fn inventory_diff(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["from", "from-view", "to", "to-view"])?;
+
+    let mut client = None;
+    let from = inventory_side(&flags, "from", "from-view", &mut client).await?;
+    let to = inventory_side(&flags, "to", "to-view", &mut client).await?;
+    if let Some(client) = client {
+        client.disconnect().await?;
+    }
+
+    let diff = from.diff(&to);
+    print!("{diff}");
+    eprintln!(
+        "{} added, {} removed, {} changed.",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    Ok(())
+}
+
+/// One side of a diff: the JSON inventory named by `--<file>`, or a scan of
+/// the view named by `--<view>`, connecting on first use.
+#[cfg(feature = "inventory")]
+async fn inventory_side(
+    flags: &Flags,
+    file: &str,
+    view: &str,
+    client: &mut Option<ViewsClient>,
+) -> Result<crowsong::Inventory, BoxError> {
+    if let Some(path) = flags.get(file) {
+        return Ok(crowsong::Inventory::load(path)?);
+    }
+    let view = flags
+        .get(view)
+        .ok_or_else(|| format!("--{file} or --{view} is required\n\n{USAGE}"))?;
+    if client.is_none() {
+        *client = Some(connect_source("crowsong-inventory").await?);
+    }
+    let client = client.as_ref().expect("connected above");
+    Ok(crowsong::InventoryScan::new().view(view).run(client).await?)
+}
+
+#[cfg(not(feature = "inventory"))]
+async fn inventory_diff(_flags: Flags) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `inventory` feature".into())
+}
+
+/// Write a view's metadata and history to a bundle directory.
+#[cfg(feature = "bundle")]
+async fn export_bundle(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["view", "start", "end", "out", "dataset"])?;
+
+    let mut export = crowsong::BundleExport::new(
+        flags.require("view")?,
+        parse_time(flags.require("start")?)?,
+        parse_time(flags.require("end")?)?,
+    );
+    for dataset in flags.all("dataset") {
+        export = export.dataset(dataset);
+    }
+    let out = flags.require("out")?;
+
+    let client = connect_source("crowsong-bundle").await?;
+    let result = export
+        .run_with_progress(&client, out, |tag, samples| {
+            eprintln!("{tag}: {samples} samples");
+        })
+        .await;
+    client.disconnect().await?;
+    let manifest = result?;
+    println!(
+        "Wrote {} samples across {} tags to {out}.",
+        manifest.samples(),
+        manifest.data.len()
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "bundle"))]
+async fn export_bundle(_flags: Flags) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `bundle` feature".into())
+}
+
+/// Write a bundle's history to a Store & Forward target.
+#[cfg(feature = "bundle")]
+async fn import_bundle(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["in", "map", "session"])?;
+
+    let bundle = crowsong::Bundle::open(flags.require("in")?)?;
+    let map = tag_map(&flags)?;
+    let mut target = connect_target(flags.get("session").unwrap_or("crowsong-import")).await?;
+    let result = bundle
+        .import_with_progress(&mut target, &map, |tag, samples| {
+            eprintln!("{tag}: {samples} samples");
+        })
+        .await;
+    target.close_session().await?;
+    let summary = result?;
+    println!("Imported {} samples across {} tags.", summary.samples, summary.tags);
+    Ok(())
+}
+
+#[cfg(not(feature = "bundle"))]
+async fn import_bundle(_flags: Flags) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `bundle` feature".into())
+}
+
+/// Print what a historian knows about some tags: the first thing to check
+/// when one looks wrong.
+#[cfg(feature = "inventory")]
+async fn tag_info(args: &[String]) -> Result<(), BoxError> {
+    // Tags are positional, among the flags.
+    let (mut tags, mut flag_args) = (Vec::new(), Vec::new());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            flag_args.push(arg.clone());
+            flag_args.extend(args.next().cloned());
+        } else {
+            tags.push(arg.clone());
+        }
+    }
+    let flags = Flags::parse(&flag_args)?;
+    flags.only(&["view", "format"])?;
+    let json = match flags.get("format") {
+        None | Some("table") => false,
+        Some("json") => true,
+        Some(format) => return Err(format!("--format: unknown format {format:?}").into()),
+    };
+    if tags.is_empty() {
+        return Err(format!("tag-info expects at least one tag\n\n{USAGE}").into());
+    }
+
+    let client = connect_source("crowsong-tag-info").await?;
+    let result = tag_reports(&client, flags.require("view")?, &tags).await;
+    client.disconnect().await?;
+    let reports = result?;
+
+    if json {
+        let reports: Vec<_> = reports
+            .iter()
+            .map(|(entry, current)| {
+                let mut report = serde_json::to_value(entry)?;
+                report["current"] = match current {
+                    Some(tvq) => serde_json::json!({
+                        "timestamp": format_time(tvq.timestamp),
+                        "value": tvq.value.to_string(),
+                        "quality": tvq.quality.to_string(),
+                    }),
+                    None => serde_json::Value::Null,
+                };
+                Ok(report)
+            })
+            .collect::<Result<_, serde_json::Error>>()?;
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+    for (entry, current) in &reports {
+        let time = |time: Option<SystemTime>| time.map(format_time).unwrap_or_else(|| "-".to_string());
+        println!("{}", entry.tag_name);
+        println!("  {:<12} {}", "dataset", entry.dataset);
+        println!("  {:<12} {}", "data type", entry.data_type.as_deref().unwrap_or("-"));
+        println!("  {:<12} {}", "units", entry.eng_units().unwrap_or("-"));
+        println!("  {:<12} {}", "description", entry.description().unwrap_or("-"));
+        println!("  {:<12} {}", "oldest", time(entry.oldest));
+        println!("  {:<12} {}", "latest", time(entry.latest));
+        match current {
+            Some(tvq) => println!(
+                "  {:<12} {} ({}) at {}",
+                "current",
+                tvq.value,
+                tvq.quality,
+                format_time(tvq.timestamp)
+            ),
+            None => println!("  {:<12} -", "current"),
+        }
+    }
+    Ok(())
+}
+
+/// Each tag's metadata and current value, if it has one.
+#[cfg(feature = "inventory")]
+async fn tag_reports(
+    client: &ViewsClient,
+    view: &str,
+    tags: &[String],
+) -> Result<Vec<(crowsong::InventoryEntry, Option<crowsong::Tvq>)>, BoxError> {
+    let entries = crowsong::inventory::describe_tags(client, view, tags).await?;
+    let mut reports = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let current = client.tag(view, &entry.tag_name).current().await.ok();
+        reports.push((entry, current));
+    }
+    Ok(reports)
+}
+
+#[cfg(not(feature = "inventory"))]
+async fn tag_info(_args: &[String]) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `inventory` feature".into())
+}
+
+/// Store a note on a tag.
+async fn annotate(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["view", "tag", "at", "text"])?;
+    let view = flags.require("view")?;
+    let tag = flags.require("tag")?;
+    let at = parse_time(flags.require("at")?)?;
+    let text = flags.require("text")?;
+
+    let client = connect_source("crowsong-annotate").await?;
+    let result = client.annotate(view, tag, at, text).await;
+    client.disconnect().await?;
+    result?;
+    println!("Annotated {tag} at {}.", format_time(at));
+    Ok(())
+}
+
+/// Print the notes on tags over a time range.
+async fn annotations(flags: Flags) -> Result<(), BoxError> {
+    flags.only(&["view", "start", "end", "tag", "tags-file"])?;
+    let view = flags.require("view")?;
+    let tags = flags.tags()?;
+    let start = parse_time(flags.require("start")?)?;
+    let end = parse_time(flags.require("end")?)?;
+
+    let client = connect_source("crowsong-annotations").await?;
+    let result = client.get_annotations(view, tags, start, end).await;
+    client.disconnect().await?;
+    for note in result? {
+        let time = note.time.map(format_time).unwrap_or_default();
+        println!("{time}\t{}\t{}\t{}", note.tag, note.user, note.message);
+    }
+    Ok(())
+}
+
+/// Evaluate watch rules and notify their webhooks.
+#[cfg(feature = "watch")]
+async fn watch_rules(path: &str) -> Result<(), BoxError> {
+    let config = crowsong::WatchConfig::load(path)?;
+    let client = connect_source("crowsong-watch").await?;
+    eprintln!(
+        "Watching {} rules on {} tags in {}...",
+        config.rules.len(),
+        crowsong::Watch::new(config.rules.iter().cloned()).tags().len(),
+        config.view
+    );
+    let result = config
+        .run_with_alerts(&client, |alert, error| {
+            println!(
+                "{} {} {} = {:?} ({}) at {}",
+                alert.state.as_str(),
+                alert.rule,
+                alert.tag,
+                alert.tvq.value,
+                alert.tvq.quality,
+                format_time(alert.tvq.timestamp)
+            );
+            if let Some(e) = error {
+                eprintln!("crowsong: notifying {} failed: {e}", alert.rule);
+            }
+        })
+        .await;
+    client.disconnect().await?;
+    Ok(result?)
+}
+
+#[cfg(not(feature = "watch"))]
+async fn watch_rules(_path: &str) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `watch` feature".into())
+}
+
+/// Run export jobs on their schedules.
+#[cfg(feature = "schedule")]
+async fn schedule(path: &str) -> Result<(), BoxError> {
+    use crowsong::JobOutcome;
+
+    let config = crowsong::ScheduleConfig::load(path)?;
+    let client = connect_source("crowsong-schedule").await?;
+    for job in &config.jobs {
+        eprintln!("Scheduled {} ({}) to {}", job.name, job.schedule, job.output);
+    }
+    let result = config
+        .run_with_reports(&client, |report| {
+            let window = format!(
+                "{}..{}",
+                format_time(report.start),
+                format_time(report.end)
+            );
+            match &report.outcome {
+                JobOutcome::Written { path, samples } => {
+                    println!("{} {window}: wrote {samples} samples to {path}", report.job)
+                }
+                JobOutcome::Failed { attempt, error, retry_in } => {
+                    let retry = match retry_in {
+                        Some(wait) => format!("retrying in {}s", wait.as_secs_f64()),
+                        None => "giving up".to_string(),
+                    };
+                    eprintln!(
+                        "crowsong: {} {window}: attempt {attempt} failed ({retry}): {error}",
+                        report.job
+                    );
+                }
+                JobOutcome::Skipped => eprintln!(
+                    "crowsong: {} {window}: skipped, the previous run is still going",
+                    report.job
+                ),
+            }
+        })
+        .await;
+    client.disconnect().await?;
+    Ok(result?)
+}
+
+#[cfg(not(feature = "schedule"))]
+async fn schedule(_path: &str) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `schedule` feature".into())
+}
+
+/// Store an API key read from stdin for `endpoint`.
+#[cfg(feature = "keyring")]
+fn login(endpoint: &str) -> Result<(), BoxError> {
+    use std::io::{BufRead, IsTerminal};
+
+    if std::io::stdin().is_terminal() {
+        eprint!("API key for {endpoint}: ");
+    }
+    let mut key = String::new();
+    std::io::stdin().lock().read_line(&mut key)?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("no API key given".into());
+    }
+    crowsong::credentials::store_api_key(endpoint, key)?;
+    println!("Stored the API key for {endpoint}.");
+    Ok(())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn login(_endpoint: &str) -> Result<(), BoxError> {
+    Err("this crowsong was built without the `keyring` feature".into())
+}
+
+/// The API key for `endpoint`: the `var` environment variable, or else the
+/// key stored by `crowsong login`.
+#[cfg(feature = "keyring")]
+fn api_key(var: &str, endpoint: &str) -> Result<String, BoxError> {
+    if let Ok(key) = std::env::var(var) {
+        return Ok(key);
+    }
+    crowsong::credentials::load_api_key(endpoint)?
+        .ok_or_else(|| format!("{var} is not set and no API key is stored for {endpoint}").into())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn api_key(var: &str, _endpoint: &str) -> Result<String, BoxError> {
+    Ok(std::env::var(var).map_err(|_| format!("{var} is not set"))?)
+}
+
+/// Connect to the source historian from `ENDPOINT`, `API_KEY` (or
+/// `API_KEY_FILE`, reloaded when it changes), and `USER_ID`.
+async fn connect_source(app: &str) -> Result<ViewsClient, BoxError> {
+    let endpoint = std::env::var("ENDPOINT")?;
+    let user_id = std::env::var("USER_ID").unwrap_or_else(|_| "crowsong".to_string());
+    let builder = match std::env::var("API_KEY_FILE") {
+        Ok(path) => ViewsClient::builder(endpoint, "").api_key_file(path),
+        Err(_) => {
+            let api_key = api_key("API_KEY", &endpoint)?;
+            ViewsClient::builder(endpoint, api_key)
+        }
+    };
+    #[cfg(feature = "tls")]
+    let builder = builder.tls_policy(tls_policy());
+    Ok(builder.app(app).user_id(user_id).connect().await?)
+}
+
+/// The TLS policy for both ends: `CA_BUNDLE` adds a PEM file of trusted CA
+/// certificates, and `TLS_ACCEPT_INVALID_CERTS=1` skips verification.
+#[cfg(feature = "tls")]
+fn tls_policy() -> crowsong::tls::TlsPolicy {
+    let mut policy = crowsong::tls::TlsPolicy::new();
+    if let Ok(path) = std::env::var("CA_BUNDLE") {
+        policy = policy.ca_bundle(path);
+    }
+    if std::env::var("TLS_ACCEPT_INVALID_CERTS").is_ok_and(|v| v == "1") {
+        policy = policy.danger_accept_invalid_certs();
+    }
+    policy
+}
+
+/// Parse an RFC 3339 time, also accepting a space separator and a missing offset (UTC).
+fn parse_time(s: &str) -> Result<SystemTime, BoxError> {
+    Ok(parse_rfc3339(s)?)
+}
+
+/// Render a time in the TIME_FORMAT, RFC 3339 in UTC by default.
+fn format_time(time: SystemTime) -> String {
+    TIME_FORMAT.get_or_init(TimestampFormat::default).format(time)
+}
+
+/// `--name value` command-line flags.
+struct Flags(Vec<(String, String)>);
+
+impl Flags {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut flags = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {arg:?}\n\n{USAGE}"))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("--{name} needs a value"))?;
+            flags.push((name.to_string(), value.clone()));
+        }
+        Ok(Self(flags))
+    }
+
+    /// Reject flags not in `known`.
+    fn only(&self, known: &[&str]) -> Result<(), String> {
+        match self.0.iter().find(|(name, _)| !known.contains(&name.as_str())) {
+            Some((name, _)) => Err(format!("unknown flag --{name}\n\n{USAGE}")),
+            None => Ok(()),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.all(name).last()
+    }
+
+    fn all<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> {
+        let name = name.to_string();
+        self.0
+            .iter()
+            .filter(move |(n, _)| *n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// An interval flag: a bare number in `unit` seconds, as the flag's name
+    /// says, or an interval such as `90m` or `1h30m`.
+    fn interval(&self, name: &str, unit: f64) -> Result<Option<Duration>, String> {
+        let Some(value) = self.get(name) else {
+            return Ok(None);
+        };
+        let interval = match value.parse::<f64>() {
+            Ok(n) => Duration::try_from_secs_f64(n * unit).map_err(|e| e.to_string()),
+            Err(_) => parse_interval(value).map_err(|e| e.to_string()),
+        };
+        interval.map(Some).map_err(|e| format!("--{name}: {e}"))
+    }
+
+    fn require(&self, name: &str) -> Result<&str, String> {
+        self.get(name).ok_or_else(|| format!("missing --{name}\n\n{USAGE}"))
+    }
+
+    /// The tags given with `--tag` and listed in `--tags-file`, one per line.
+    fn tags(&self) -> Result<Vec<String>, BoxError> {
+        let mut tags: Vec<String> = self.all("tag").map(str::to_string).collect();
+        if let Some(path) = self.get("tags-file") {
+            let contents = std::fs::read_to_string(path)?;
+            tags.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        if tags.is_empty() {
+            return Err(format!("--tag or --tags-file is required\n\n{USAGE}").into());
+        }
+        Ok(tags)
+    }
+}
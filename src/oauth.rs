@@ -0,0 +1,269 @@
+//! OAuth 2.0 bearer tokens, for Canary behind an OIDC-protected gateway.
+//!
+//! A [`TokenSource`] supplies bearer tokens; [`ClientCredentials`] fetches
+//! them from a token endpoint with the client-credentials grant. A client
+//! built with [`ViewsClientBuilder::bearer_tokens`] fetches a token before
+//! it connects and sends it as `authorization: Bearer …` on every call. A
+//! background task fetches the next token shortly before the current one
+//! expires and swaps it in, so the connection and its CCI carry on
+//! undisturbed. The API key is still sent as `canary-api-token`, unless it
+//! is empty:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), crowsong::BoxError> {
+//! use crowsong::ViewsClient;
+//! use crowsong::oauth::ClientCredentials;
+//!
+//! let tokens = ClientCredentials::new(
+//!     "https://login.example.com/oauth2/token",
+//!     "crowsong-reader",
+//!     std::env::var("CLIENT_SECRET")?,
+//! )?
+//! .scope("canary.read");
+//! let client = ViewsClient::builder("https://canary.example.com:55321", "")
+//!     .bearer_tokens(tokens)
+//!     .connect()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ViewsClientBuilder::bearer_tokens`]: crate::views_client::ViewsClientBuilder::bearer_tokens
+
+use std::fmt;
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::error::Error;
+use crate::transport::Redacted;
+use crate::views_client::SharedBearer;
+
+/// How long before a token expires to fetch the next one, at most.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// How long to wait after a failed refresh before trying again.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// A bearer token and how long it is good for.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token {
+    pub access_token: String,
+    /// The token's lifetime from when it was issued; `None` if it doesn't
+    /// expire, in which case it is never refreshed.
+    pub expires_in: Option<Duration>,
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("access_token", &Redacted)
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
+
+/// Where bearer tokens come from.
+#[tonic::async_trait]
+pub trait TokenSource: Send + Sync + 'static {
+    /// Fetch a fresh token.
+    async fn fetch(&self) -> Result<Token, Error>;
+}
+
+/// Tokens from an OAuth 2.0 token endpoint, using the client-credentials
+/// grant with the client's ID and secret in the request body.
+#[derive(Clone)]
+pub struct ClientCredentials {
+    token_url: http::Uri,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    audience: Option<String>,
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+}
+
+impl ClientCredentials {
+    pub fn new(
+        token_url: &str,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            token_url: token_url.parse().map_err(Error::other)?,
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            scope: None,
+            audience: None,
+            client: Client::builder(TokioExecutor::new()).build(connector),
+        })
+    }
+
+    /// The scopes to ask for, space-separated.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// The API the token is for, for providers that want an `audience`
+    /// parameter (such as Auth0).
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+}
+
+#[tonic::async_trait]
+impl TokenSource for ClientCredentials {
+    async fn fetch(&self) -> Result<Token, Error> {
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+        if let Some(audience) = &self.audience {
+            form.push(("audience", audience.as_str()));
+        }
+        let body = form
+            .iter()
+            .map(|(name, value)| format!("{name}={}", form_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let request = http::Request::post(self.token_url.clone())
+            .header(
+                http::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .header(http::header::ACCEPT, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(Error::other)?;
+        let resp = tokio::time::timeout(Duration::from_secs(10), self.client.request(request))
+            .await
+            .map_err(|_| format!("token endpoint {} timed out", self.token_url))?
+            .map_err(|e| format!("token endpoint {} failed: {e}", self.token_url))?;
+        let status = resp.status();
+        let body = resp
+            .into_body()
+            .collect()
+            .await
+            .map_err(Error::other)?
+            .to_bytes();
+        if !status.is_success() {
+            return Err(format!(
+                "token endpoint {} failed ({status}): {}",
+                self.token_url,
+                String::from_utf8_lossy(&body).trim()
+            )
+            .into());
+        }
+
+        let json: serde_json::Value = serde_json::from_slice(&body).map_err(Error::other)?;
+        let access_token = json["access_token"]
+            .as_str()
+            .ok_or_else(|| format!("token endpoint {} sent no access_token", self.token_url))?
+            .to_string();
+        let expires_in = json["expires_in"]
+            .as_u64()
+            .or_else(|| json["expires_in"].as_str()?.parse().ok())
+            .map(Duration::from_secs);
+        Ok(Token {
+            access_token,
+            expires_in,
+        })
+    }
+}
+
+impl fmt::Debug for ClientCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCredentials")
+            .field("token_url", &self.token_url)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Redacted)
+            .field("scope", &self.scope)
+            .field("audience", &self.audience)
+            .finish()
+    }
+}
+
+/// Percent-encode a form value, leaving only unreserved characters as is.
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Send `token` from the next call on.
+pub(crate) fn set_bearer(bearer: &SharedBearer, token: &Token) -> Result<(), Error> {
+    let value = format!("Bearer {}", token.access_token).parse()?;
+    *bearer.write().unwrap_or_else(PoisonError::into_inner) = Some(value);
+    Ok(())
+}
+
+/// Fetch a new token from `source` shortly before `token` expires, and
+/// again before each later one does. The task holds the token slot rather
+/// than a client, so it doesn't keep the connection alive.
+pub(crate) fn spawn_refresh(
+    bearer: SharedBearer,
+    source: Arc<dyn TokenSource>,
+    mut token: Token,
+) -> TokenRefresh {
+    let handle = tokio::spawn(async move {
+        while let Some(lifetime) = token.expires_in {
+            let margin = REFRESH_MARGIN.min(lifetime / 2);
+            tokio::time::sleep(lifetime - margin).await;
+            // Keep the current token until a new one arrives; if it expires
+            // first, calls fail with UNAUTHENTICATED until then.
+            token = loop {
+                match source.fetch().await {
+                    Ok(next) if set_bearer(&bearer, &next).is_ok() => break next,
+                    _ => tokio::time::sleep(RETRY_DELAY).await,
+                }
+            };
+        }
+    });
+    TokenRefresh { handle }
+}
+
+/// A background bearer token refresh, stopped when dropped.
+pub struct TokenRefresh {
+    handle: JoinHandle<()>,
+}
+
+impl TokenRefresh {
+    pub(crate) fn abort_handle(&self) -> AbortHandle {
+        self.handle.abort_handle()
+    }
+}
+
+impl Drop for TokenRefresh {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl fmt::Debug for TokenRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenRefresh").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,252 @@
+//! Probing a server's gRPC health and reflection services.
+//!
+//! [`ViewsClient::probe`] asks the standard `grpc.health.v1.Health` service
+//! whether the server (and the Views service) is serving, and, if server
+//! reflection is enabled, which services and methods it exposes. The
+//! [`ProbeReport`] compares those with crowsong's vendored protos, which is
+//! the quickest way to diagnose a version mismatch with the server.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use prost::Message;
+use prost_types::FileDescriptorProto;
+use tonic::codegen::tokio_stream;
+use tonic::{Code, Status};
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::v1::{ServerReflectionRequest, ServerReflectionResponse};
+
+use crate::error::Error;
+use crate::views_client::ViewsClient;
+
+const VIEWS_SERVICE: &str = "canary.views.grpc.api.CanaryViewsApiService";
+
+/// Reflection paths, newest first. Both versions share one wire format.
+const REFLECTION_PATHS: [&str; 2] = [
+    "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo",
+    "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+];
+
+/// The answer of the gRPC health service for one service name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Serving,
+    NotServing,
+    /// The health service doesn't know the service name.
+    ServiceUnknown,
+    Unknown,
+    /// The server doesn't implement the health service.
+    Unimplemented,
+}
+
+/// What a server reported about itself.
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    /// The health of the server as a whole.
+    pub server_health: Health,
+    /// The health of the Canary Views service.
+    pub views_health: Health,
+    /// Whether server reflection is enabled.
+    pub reflection: bool,
+    /// The services the server exposes over reflection, with their methods.
+    pub services: BTreeMap<String, Vec<String>>,
+}
+
+impl ProbeReport {
+    /// Methods of the vendored protos missing from services the server
+    /// exposes, as `Service/Method`. Empty when reflection is disabled.
+    pub fn missing_methods(&self) -> Vec<String> {
+        let mut missing = Vec::new();
+        for (service, methods) in vendored_services() {
+            let Some(served) = self.services.get(&service) else {
+                continue;
+            };
+            missing.extend(
+                methods
+                    .iter()
+                    .filter(|m| !served.contains(m))
+                    .map(|m| format!("{service}/{m}")),
+            );
+        }
+        missing
+    }
+
+    /// Methods the server exposes on known services that the vendored protos
+    /// lack, as `Service/Method`.
+    pub fn unknown_methods(&self) -> Vec<String> {
+        let vendored = vendored_services();
+        let mut unknown = Vec::new();
+        for (service, served) in &self.services {
+            let Some(methods) = vendored.get(service) else {
+                continue;
+            };
+            unknown.extend(
+                served
+                    .iter()
+                    .filter(|m| !methods.contains(*m))
+                    .map(|m| format!("{service}/{m}")),
+            );
+        }
+        unknown
+    }
+}
+
+/// The services and method names of crowsong's vendored protos.
+fn vendored_services() -> BTreeMap<String, BTreeSet<String>> {
+    let mut services = BTreeMap::new();
+    for file in &crate::descriptors().file {
+        for (name, methods) in file_services(file) {
+            services.insert(name, methods.into_iter().collect());
+        }
+    }
+    services
+}
+
+/// The fully qualified services of a file, with their method names.
+fn file_services(file: &FileDescriptorProto) -> Vec<(String, Vec<String>)> {
+    file.service
+        .iter()
+        .map(|service| {
+            let name = match file.package() {
+                "" => service.name().to_string(),
+                package => format!("{package}.{}", service.name()),
+            };
+            let methods = service
+                .method
+                .iter()
+                .map(|m| m.name().to_string())
+                .collect();
+            (name, methods)
+        })
+        .collect()
+}
+
+impl ViewsClient {
+    /// Query the server's health and reflection services.
+    ///
+    /// Servers without either service still produce a report, with
+    /// [`Health::Unimplemented`] or `reflection: false`.
+    pub async fn probe(&self) -> Result<ProbeReport, Error> {
+        let server_health = self.health("").await?;
+        let views_health = self.health(VIEWS_SERVICE).await?;
+
+        let mut report = ProbeReport {
+            server_health,
+            views_health,
+            reflection: false,
+            services: BTreeMap::new(),
+        };
+        for path in REFLECTION_PATHS {
+            match self.reflect(path).await {
+                Ok(services) => {
+                    report.reflection = true;
+                    report.services = services;
+                    break;
+                }
+                Err(status) if status.code() == Code::Unimplemented => continue,
+                Err(status) => return Err(status.into()),
+            }
+        }
+        Ok(report)
+    }
+
+    async fn health(&self, service: &str) -> Result<Health, Status> {
+        let mut client = HealthClient::new(self.service());
+        let request = HealthCheckRequest {
+            service: service.to_string(),
+        };
+        match client.check(request).await {
+            Ok(resp) => Ok(match resp.into_inner().status() {
+                ServingStatus::Serving => Health::Serving,
+                ServingStatus::NotServing => Health::NotServing,
+                ServingStatus::ServiceUnknown => Health::ServiceUnknown,
+                ServingStatus::Unknown => Health::Unknown,
+            }),
+            Err(status) if status.code() == Code::Unimplemented => Ok(Health::Unimplemented),
+            Err(status) if status.code() == Code::NotFound => Ok(Health::ServiceUnknown),
+            Err(status) => Err(status),
+        }
+    }
+
+    /// List services and their methods over the reflection service at `path`.
+    async fn reflect(&self, path: &'static str) -> Result<BTreeMap<String, Vec<String>>, Status> {
+        let listed = self
+            .reflection_call(path, vec![MessageRequest::ListServices(String::new())])
+            .await?;
+        let names: Vec<String> = listed
+            .into_iter()
+            .filter_map(|resp| match resp.message_response {
+                Some(MessageResponse::ListServicesResponse(list)) => Some(list.service),
+                _ => None,
+            })
+            .flatten()
+            .map(|s| s.name)
+            .collect();
+
+        let files = self
+            .reflection_call(
+                path,
+                names
+                    .iter()
+                    .map(|name| MessageRequest::FileContainingSymbol(name.clone()))
+                    .collect(),
+            )
+            .await?;
+        let mut described = BTreeMap::new();
+        for resp in files {
+            let Some(MessageResponse::FileDescriptorResponse(files)) = resp.message_response else {
+                continue;
+            };
+            for bytes in files.file_descriptor_proto {
+                let file = FileDescriptorProto::decode(bytes.as_slice())
+                    .map_err(|e| Status::internal(format!("bad reflection descriptor: {e}")))?;
+                described.extend(file_services(&file));
+            }
+        }
+
+        // Services whose descriptors the server wouldn't provide are still listed.
+        Ok(names
+            .into_iter()
+            .map(|name| {
+                let methods = described.get(&name).cloned().unwrap_or_default();
+                (name, methods)
+            })
+            .collect())
+    }
+
+    async fn reflection_call(
+        &self,
+        path: &'static str,
+        requests: Vec<MessageRequest>,
+    ) -> Result<Vec<ServerReflectionResponse>, Error> {
+        let mut grpc = tonic::client::Grpc::new(self.service());
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unknown(format!("Service was not ready: {e}")))?;
+        let codec =
+            tonic_prost::ProstCodec::<ServerReflectionRequest, ServerReflectionResponse>::default();
+        let requests = requests
+            .into_iter()
+            .map(|message_request| ServerReflectionRequest {
+                host: String::new(),
+                message_request: Some(message_request),
+            });
+        let mut stream = grpc
+            .streaming(
+                tonic::Request::new(tokio_stream::iter(requests)),
+                http::uri::PathAndQuery::from_static(path),
+                codec,
+            )
+            .await?
+            .into_inner();
+
+        let mut responses = Vec::new();
+        while let Some(resp) = stream.message().await? {
+            responses.push(resp);
+        }
+        Ok(responses)
+    }
+}
@@ -0,0 +1,321 @@
+//! Serving tags' current values as Prometheus metrics.
+//!
+//! [`PrometheusExporter`] polls the current values of configured tags and
+//! serves them on `/metrics` in the Prometheus text format. Each [`Gauge`]
+//! maps one tag to a metric name and extra labels; by default a tag becomes
+//! a `canary_tag_value` series. Every series is labelled with its view and
+//! tag name.
+//!
+//! A value whose TVQ timestamp is older than the
+//! [staleness limit](PrometheusExporter::stale_after) is left out, so
+//! Prometheus marks the series stale instead of repeating a frozen reading.
+//! The age and quality of every value are exported alongside it as
+//! `canary_tag_age_seconds` and `canary_tag_quality`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+use crate::canary::views::grpc::api::{GetTagCurrentValueRequest, TagCurrentValue};
+use crate::error::Error;
+use crate::types::Value;
+use crate::views_client::ViewsClient;
+
+/// The metric of gauges without a configured name.
+const DEFAULT_METRIC: &str = "canary_tag_value";
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// How one tag is exported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gauge {
+    tag: String,
+    metric: String,
+    labels: BTreeMap<String, String>,
+}
+
+impl Gauge {
+    /// Export `tag` as a `canary_tag_value` series.
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            metric: DEFAULT_METRIC.to_string(),
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// Export under another metric name. Characters Prometheus doesn't allow
+    /// in names are replaced with `_`.
+    pub fn metric(mut self, name: impl AsRef<str>) -> Self {
+        self.metric = metric_name(name.as_ref());
+        self
+    }
+
+    /// Add a constant label to the series.
+    pub fn label(mut self, name: impl AsRef<str>, value: impl Into<String>) -> Self {
+        self.labels.insert(metric_name(name.as_ref()), value.into());
+        self
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+}
+
+/// Polls tags' current values and serves them as Prometheus gauges.
+#[derive(Debug, Clone)]
+pub struct PrometheusExporter {
+    view: String,
+    gauges: Vec<Gauge>,
+    interval: Duration,
+    stale_after: Duration,
+}
+
+impl PrometheusExporter {
+    pub fn new(view: impl Into<String>) -> Self {
+        Self {
+            view: view.into(),
+            gauges: Vec::new(),
+            interval: Duration::from_secs(15),
+            stale_after: Duration::from_secs(300),
+        }
+    }
+
+    /// Export a tag as configured by `gauge`.
+    pub fn gauge(mut self, gauge: Gauge) -> Self {
+        self.gauges.push(gauge);
+        self
+    }
+
+    /// Export tags as `canary_tag_value` series.
+    pub fn tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.gauges.extend(tags.into_iter().map(Gauge::new));
+        self
+    }
+
+    /// How often to poll the historian while serving. Defaults to 15 seconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_millis(100));
+        self
+    }
+
+    /// Leave out values whose timestamp is older than this. Defaults to 5
+    /// minutes.
+    pub fn stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Poll the current values once and render them in the text format.
+    pub async fn collect(&self, client: &ViewsClient) -> Result<String, Error> {
+        let mut tags: Vec<String> = self.gauges.iter().map(|g| g.tag.clone()).collect();
+        tags.sort_unstable();
+        tags.dedup();
+        let resp = client
+            .get_tag_current_value(GetTagCurrentValueRequest {
+                view: self.view.clone(),
+                tag_names: tags,
+                use_time_extension: None,
+                quality: 0,
+                cci: 0,
+            })
+            .await?;
+        let values: HashMap<&str, &TagCurrentValue> = resp
+            .tag_values
+            .iter()
+            .map(|v| (v.tag_item_id.as_str(), v))
+            .collect();
+        Ok(self.render(&values, SystemTime::now()))
+    }
+
+    fn render(&self, values: &HashMap<&str, &TagCurrentValue>, now: SystemTime) -> String {
+        let mut families: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        let mut ages = Vec::new();
+        let mut qualities = Vec::new();
+        for gauge in &self.gauges {
+            let Some(current) = values.get(gauge.tag.as_str()) else {
+                continue;
+            };
+            let tag_labels = labels(&[("view", &self.view), ("tag", &gauge.tag)]);
+            let timestamp = current
+                .timestamp
+                .and_then(|ts| SystemTime::try_from(ts).ok());
+            let age = timestamp.map(|t| now.duration_since(t).unwrap_or_default());
+            if let Some(age) = age {
+                ages.push(format!("{tag_labels} {}", age.as_secs_f64()));
+            }
+            qualities.push(format!("{tag_labels} {}", current.quality));
+
+            let value = current.value.as_ref().map(Value::from).unwrap_or_default();
+            let fresh = age.is_some_and(|age| age <= self.stale_after);
+            if let (true, Some(value)) = (fresh, value.as_f64()) {
+                let mut pairs: Vec<(&str, &str)> = vec![("view", &self.view), ("tag", &gauge.tag)];
+                pairs.extend(gauge.labels.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+                families
+                    .entry(gauge.metric.as_str())
+                    .or_default()
+                    .push(format!("{} {}", labels(&pairs), sample_value(value)));
+            }
+        }
+
+        let mut out = String::new();
+        family(
+            &mut out,
+            "crowsong_exporter_up",
+            "Whether the last poll of the historian succeeded.",
+            &[" 1".to_string()],
+        );
+        for (metric, series) in &families {
+            family(&mut out, metric, "Current value of a Canary tag.", series);
+        }
+        family(
+            &mut out,
+            "canary_tag_age_seconds",
+            "Seconds since the tag's current value was recorded.",
+            &ages,
+        );
+        family(
+            &mut out,
+            "canary_tag_quality",
+            "Quality code of the tag's current value.",
+            &qualities,
+        );
+        out
+    }
+
+    /// Poll the historian every [`interval`](Self::interval) and serve the
+    /// latest metrics over HTTP until accepting a connection fails.
+    pub async fn serve(self, client: ViewsClient, listener: TcpListener) -> io::Result<()> {
+        let metrics = Arc::new(RwLock::new(String::new()));
+        let poller = {
+            let metrics = Arc::clone(&metrics);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(self.interval);
+                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    ticker.tick().await;
+                    let rendered = match self.collect(&client).await {
+                        Ok(rendered) => rendered,
+                        Err(error) => failed_poll(&error),
+                    };
+                    *metrics.write().unwrap_or_else(|e| e.into_inner()) = rendered;
+                }
+            })
+        };
+
+        let result = loop {
+            let io = match listener.accept().await {
+                Ok((io, _)) => io,
+                Err(e) => break Err(e),
+            };
+            let metrics = Arc::clone(&metrics);
+            tokio::spawn(async move {
+                let service = service_fn(move |req: http::Request<hyper::body::Incoming>| {
+                    let metrics = Arc::clone(&metrics);
+                    async move { Ok::<_, Infallible>(respond(req.uri().path(), &metrics)) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(io), service)
+                    .await;
+            });
+        };
+        poller.abort();
+        result
+    }
+}
+
+fn respond(path: &str, metrics: &RwLock<String>) -> http::Response<Full<Bytes>> {
+    let (status, content_type, body) = match path {
+        "/metrics" => {
+            let body = metrics.read().unwrap_or_else(|e| e.into_inner()).clone();
+            (http::StatusCode::OK, CONTENT_TYPE, body)
+        }
+        _ => (
+            http::StatusCode::NOT_FOUND,
+            "text/plain",
+            "not found; metrics are at /metrics\n".to_string(),
+        ),
+    };
+    http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, content_type)
+        .body(Full::new(Bytes::from(body)))
+        .expect("static response parts are valid")
+}
+
+/// The metrics served after a failed poll: no tag series, so they go stale.
+fn failed_poll(error: &Error) -> String {
+    let mut out = format!("# poll failed: {}\n", error.to_string().replace('\n', " "));
+    family(
+        &mut out,
+        "crowsong_exporter_up",
+        "Whether the last poll of the historian succeeded.",
+        &[" 0".to_string()],
+    );
+    out
+}
+
+/// Append a gauge family. Each series is its label set and value.
+fn family(out: &mut String, metric: &str, help: &str, series: &[String]) {
+    if series.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {metric} {help}");
+    let _ = writeln!(out, "# TYPE {metric} gauge");
+    for line in series {
+        let _ = writeln!(out, "{metric}{line}");
+    }
+}
+
+/// Render a label set, escaping values.
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let rendered: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    format!("{{{}}}", rendered.join(","))
+}
+
+fn sample_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Replace characters not allowed in metric and label names with `_`.
+fn metric_name(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
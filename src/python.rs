@@ -1,19 +1,35 @@
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyUserWarning};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple};
 use pyo3::Py;
+#[cfg(feature = "numpy")]
+use numpy::PyArray1;
+#[cfg(all(feature = "numpy", feature = "store-and-forward"))]
+use numpy::PyReadonlyArray1;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::SystemTime;
 use tokio::runtime::Runtime;
 
 type PyObject = Py<pyo3::PyAny>;
 
 use crate::canary::utility::protobuf_shared_types::variant::Kind;
 use crate::canary::views::grpc::api::*;
+use crate::columnar::{RawColumns, RawDataColumns, ValueKind};
+use crate::error::Error;
+use crate::quality::{Quality, QualityFilter};
+use crate::timestamps::{self, TimestampFormat};
+use crate::truncation::{Truncated, TruncationCheck};
+use crate::types::Value;
+use crate::units::UnitConverter;
 
 // ---------------------------------------------------------------------------
 // Helpers for converting protobuf types to Python
 // ---------------------------------------------------------------------------
 
-fn variant_to_py(py: Python<'_>, v: &crate::canary::utility::protobuf_shared_types::Variant) -> PyObject {
+#[doc(hidden)]
+pub fn variant_to_py(py: Python<'_>, v: &crate::canary::utility::protobuf_shared_types::Variant) -> PyObject {
     match &v.kind {
         Some(Kind::Bool(b)) => b.into_pyobject(py).unwrap().to_owned().into_any().unbind(),
         Some(Kind::Int8(i)) => i.into_pyobject(py).unwrap().to_owned().into_any().unbind(),
@@ -32,63 +48,20 @@ fn variant_to_py(py: Python<'_>, v: &crate::canary::utility::protobuf_shared_typ
     }
 }
 
-fn timestamp_to_iso(ts: &prost_types::Timestamp) -> String {
-    let secs = ts.seconds;
-    let nanos = ts.nanos as u64;
-    // Format as ISO 8601 with nanoseconds
-    let dt_secs = secs;
-    let (days_from_epoch, time_secs) = (dt_secs / 86400, dt_secs % 86400);
-    if time_secs < 0 || days_from_epoch < 0 {
-        return format!("{}s{}ns", secs, nanos);
-    }
-    // Simple epoch-based formatting
-    let hours = time_secs / 3600;
-    let mins = (time_secs % 3600) / 60;
-    let s = time_secs % 60;
-
-    // Days since 1970-01-01
-    let mut days = days_from_epoch;
-    let mut year = 1970i64;
-    loop {
-        let days_in_year = if is_leap(year) { 366 } else { 365 };
-        if days < days_in_year {
-            break;
-        }
-        days -= days_in_year;
-        year += 1;
-    }
-    let leap = is_leap(year);
-    let month_days: [i64; 12] = [
-        31, if leap { 29 } else { 28 }, 31, 30, 31, 30,
-        31, 31, 30, 31, 30, 31,
-    ];
-    let mut month = 0usize;
-    for (i, &md) in month_days.iter().enumerate() {
-        if days < md {
-            month = i;
-            break;
-        }
-        days -= md;
-    }
-    let day = days + 1;
-    if nanos > 0 {
-        format!(
-            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
-            year, month + 1, day, hours, mins, s, nanos
-        )
-    } else {
-        format!(
-            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
-            year, month + 1, day, hours, mins, s
-        )
-    }
+/// The format set by `set_timestamp_format`, or `None` for the default.
+static TIMESTAMP_FORMAT: RwLock<Option<TimestampFormat>> = RwLock::new(None);
+
+fn format_time(time: SystemTime) -> String {
+    let format = *TIMESTAMP_FORMAT.read().unwrap_or_else(PoisonError::into_inner);
+    format.unwrap_or_default().format(time)
 }
 
-fn is_leap(year: i64) -> bool {
-    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+fn timestamp_to_iso(ts: &prost_types::Timestamp) -> String {
+    format_time(timestamps::from_proto(ts))
 }
 
-fn tvq_to_py_dict<'py>(py: Python<'py>, tvq: &crate::canary::utility::protobuf_shared_types::GrpcTvq) -> PyResult<Bound<'py, PyDict>> {
+#[doc(hidden)]
+pub fn tvq_to_py_dict<'py>(py: Python<'py>, tvq: &crate::canary::utility::protobuf_shared_types::GrpcTvq) -> PyResult<Bound<'py, PyDict>> {
     let dict = PyDict::new(py);
     if let Some(ts) = &tvq.timestamp {
         dict.set_item("timestamp", timestamp_to_iso(ts))?;
@@ -104,10 +77,212 @@ fn tvq_to_py_dict<'py>(py: Python<'py>, tvq: &crate::canary::utility::protobuf_s
     Ok(dict)
 }
 
+#[doc(hidden)]
+pub fn columns_row_to_py_dict<'py>(py: Python<'py>, columns: &RawColumns, row: usize) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("timestamp", format_time(columns.timestamp(row)))?;
+    let v = columns.values[row];
+    let value: PyObject = match columns.kinds[row] {
+        ValueKind::Null => py.None(),
+        ValueKind::Bool => (v != 0.0).into_pyobject(py)?.to_owned().into_any().unbind(),
+        ValueKind::Int => (v as i64).into_pyobject(py)?.into_any().unbind(),
+        ValueKind::UInt => (v as u64).into_pyobject(py)?.into_any().unbind(),
+        ValueKind::Float => v.into_pyobject(py)?.into_any().unbind(),
+        ValueKind::String | ValueKind::Decimal => match columns.value(row) {
+            Value::String(s) => s.into_pyobject(py)?.into_any().unbind(),
+            Value::Decimal(b) => b.as_slice().into_pyobject(py)?.into_any().unbind(),
+            _ => py.None(),
+        },
+    };
+    dict.set_item("value", value)?;
+    dict.set_item("quality", columns.qualities[row])?;
+    Ok(dict)
+}
+
+fn raw_request(
+    view: &str,
+    tag_names: Vec<String>,
+    windows: &[(SystemTime, SystemTime)],
+    max_count_per_tag: i32,
+    return_bounds: bool,
+) -> PyResult<GetRawDataRequest> {
+    // One request per tag and window, with the window's index as client data.
+    let mut requests = Vec::with_capacity(tag_names.len() * windows.len());
+    for (index, (start, end)) in windows.iter().enumerate() {
+        for tag_name in &tag_names {
+            requests.push(RawTagRequest {
+                tag_name: tag_name.clone(),
+                start_time: Some((*start).into()),
+                end_time: Some((*end).into()),
+                client_data: i32::try_from(index).map_err(err)?,
+                continuation_point: vec![],
+            });
+        }
+    }
+
+    Ok(GetRawDataRequest {
+        view: view.to_string(),
+        requests,
+        max_count_per_tag,
+        return_bounds,
+        return_annotations: false,
+        cci: 0,
+    })
+}
+
+/// An interval argument: a number of seconds, or a string such as "15m",
+/// "1h30m", or "1d".
+#[derive(FromPyObject)]
+enum IntervalArg {
+    Seconds(f64),
+    Text(String),
+}
+
+impl IntervalArg {
+    fn duration(&self) -> PyResult<std::time::Duration> {
+        match self {
+            Self::Seconds(seconds) => std::time::Duration::try_from_secs_f64(*seconds).map_err(err),
+            Self::Text(s) => crate::interval::parse_interval(s).map_err(err),
+        }
+    }
+
+    fn to_proto(&self) -> PyResult<prost_types::Duration> {
+        prost_types::Duration::try_from(self.duration()?).map_err(err)
+    }
+}
+
+/// A time argument: an ISO 8601 string, a `datetime.datetime` (UTC if
+/// naive), a pandas `Timestamp`, or a `numpy.datetime64`. Where a method
+/// takes windows, also a list or array of these.
+#[derive(FromPyObject)]
+enum TimeArg {
+    Text(String),
+    Object(PyObject),
+}
+
+impl TimeArg {
+    /// The time, failing for arrays.
+    fn time(&self, py: Python<'_>) -> PyResult<SystemTime> {
+        match self {
+            Self::Text(s) => iso_to_system_time(s),
+            Self::Object(obj) => {
+                let obj = obj.bind(py);
+                if is_time_array(obj) {
+                    return Err(PyTypeError::new_err("expected a single time, not an array"));
+                }
+                object_time(obj)
+            }
+        }
+    }
+
+    /// Each time of an array argument, or `None` for a single time.
+    fn times(&self, py: Python<'_>) -> PyResult<Option<Vec<SystemTime>>> {
+        let Self::Object(obj) = self else {
+            return Ok(None);
+        };
+        let obj = obj.bind(py);
+        if !is_time_array(obj) {
+            return Ok(None);
+        }
+        let mut times = Vec::new();
+        for item in obj.try_iter()? {
+            let item = item?;
+            times.push(match item.extract::<String>() {
+                Ok(s) => iso_to_system_time(&s)?,
+                Err(_) => object_time(&item)?,
+            });
+        }
+        Ok(Some(times))
+    }
+}
+
+/// The windows of a `start_time`/`end_time` pair: one for two single times,
+/// or one per element for two arrays of the same length.
+fn time_windows(py: Python<'_>, start: &TimeArg, end: &TimeArg) -> PyResult<(Vec<(SystemTime, SystemTime)>, bool)> {
+    match (start.times(py)?, end.times(py)?) {
+        (None, None) => Ok((vec![(start.time(py)?, end.time(py)?)], false)),
+        (Some(starts), Some(ends)) if starts.len() == ends.len() => Ok((starts.into_iter().zip(ends).collect(), true)),
+        (Some(starts), Some(ends)) => Err(err(format!(
+            "start_time has {} times but end_time has {}",
+            starts.len(),
+            ends.len()
+        ))),
+        _ => Err(err("start_time and end_time must both be single times or both arrays")),
+    }
+}
+
+/// Whether `obj` is a list, tuple, or array of times rather than one time.
+fn is_time_array(obj: &Bound<'_, PyAny>) -> bool {
+    obj.is_instance_of::<PyList>()
+        || obj.is_instance_of::<PyTuple>()
+        || obj
+            .getattr("ndim")
+            .and_then(|ndim| ndim.extract::<usize>())
+            .is_ok_and(|ndim| ndim > 0)
+}
+
+/// Convert a datetime, pandas Timestamp, or numpy.datetime64, keeping
+/// nanoseconds where the type has them.
+fn object_time(obj: &Bound<'_, PyAny>) -> PyResult<SystemTime> {
+    let py = obj.py();
+    // A pandas Timestamp's datetime64[ns] form is in UTC.
+    let obj = match obj.getattr("asm8") {
+        Ok(datetime64) => datetime64,
+        Err(_) => obj.clone(),
+    };
+    if obj.hasattr("dtype")? {
+        let nanos: i64 = obj
+            .call_method1("astype", ("datetime64[ns]",))?
+            .call_method1("astype", ("int64",))?
+            .extract()?;
+        if nanos == i64::MIN {
+            return Err(err("NaT is not a time"));
+        }
+        return Ok(timestamps::nanos_to_system_time(nanos));
+    }
+    if obj.hasattr("tzinfo")? && obj.hasattr("microsecond")? {
+        let datetime = py.import("datetime")?;
+        let utc = datetime.getattr("timezone")?.getattr("utc")?;
+        let aware = if obj.getattr("tzinfo")?.is_none() {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("tzinfo", &utc)?;
+            obj.call_method("replace", (), Some(&kwargs))?
+        } else {
+            obj
+        };
+        let epoch = datetime.getattr("datetime")?.call1((1970, 1, 1, 0, 0, 0, 0, utc))?;
+        let delta = aware.sub(epoch)?;
+        let days: i64 = delta.getattr("days")?.extract()?;
+        let seconds: i64 = delta.getattr("seconds")?.extract()?;
+        let micros: i64 = delta.getattr("microseconds")?.extract()?;
+        let nanos = ((days * 86_400 + seconds) * 1_000_000 + micros).saturating_mul(1_000);
+        return Ok(timestamps::nanos_to_system_time(nanos));
+    }
+    Err(PyTypeError::new_err(format!(
+        "expected an ISO 8601 string, datetime, pandas Timestamp, or numpy.datetime64, got {}",
+        obj.get_type().name()?
+    )))
+}
+
+fn iso_to_system_time(s: &str) -> PyResult<std::time::SystemTime> {
+    timestamps::parse_rfc3339(s).map_err(err)
+}
+
 fn err(e: impl std::fmt::Display) -> PyErr {
     PyRuntimeError::new_err(e.to_string())
 }
 
+pyo3::create_exception!(
+    crowsong,
+    NotConnectedError,
+    PyRuntimeError,
+    "Raised by a CanaryView or CanaryWriter call made before connect() or after disconnect() or close()."
+);
+
+fn not_connected() -> PyErr {
+    NotConnectedError::new_err("not connected; call connect() first")
+}
+
 // ---------------------------------------------------------------------------
 // Python classes
 // ---------------------------------------------------------------------------
@@ -119,10 +294,150 @@ fn err(e: impl std::fmt::Display) -> PyErr {
 ///     view = CanaryView("https://host:55321", "api-key")
 ///     print(view.get_version())
 ///     view.disconnect()
+///
+/// Times may be ISO 8601 strings, `datetime.datetime` objects (naive ones are
+/// taken as UTC), pandas `Timestamp`s, or `numpy.datetime64` values, which keep
+/// their nanoseconds.
 #[pyclass]
 pub struct CanaryView {
     rt: Runtime,
     client: Option<crate::ViewsClient>,
+    /// What connect() dials.
+    endpoint: String,
+    api_key: String,
+    app: String,
+    user_id: String,
+    truncation_check: Option<TruncationCheck>,
+    units: UnitConverter,
+    units_cache: HashMap<(String, String), Option<String>>,
+    /// Truncated reads reported by the client, to re-raise as warnings.
+    truncations: Arc<Mutex<Vec<Truncated>>>,
+}
+
+impl CanaryView {
+    /// Fetch raw data as columns, applying any preferred unit conversions.
+    /// Truncated reads are checked for unless `paging`, when the caller
+    /// follows continuation points itself.
+    fn fetch_raw_columns(
+        &mut self,
+        py: Python<'_>,
+        view: &str,
+        req: GetRawDataRequest,
+        paging: bool,
+    ) -> PyResult<RawDataColumns> {
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let resp = if paging {
+            self.rt.block_on(c.raw_data_columnar_page(req)).map_err(Error::from)
+        } else {
+            self.rt.block_on(c.get_raw_data_columnar(req))
+        };
+        self.warn_truncations(py)?;
+        let mut resp = resp.map_err(err)?;
+        if self.units.is_active() {
+            let tags: Vec<String> = resp.raw_data.iter().map(|d| d.tag_name.clone()).collect();
+            let units = self.resolve_units(view, &tags)?;
+            for columns in &mut resp.raw_data {
+                if let Some(from) = units.get(&columns.tag_name) {
+                    self.units.apply_columns(columns, from);
+                }
+            }
+        }
+        Ok(resp)
+    }
+
+    /// Raise a `UserWarning` for each truncated read reported since the last call.
+    fn warn_truncations(&self, py: Python<'_>) -> PyResult<()> {
+        let truncations = std::mem::take(&mut *self.truncations.lock().unwrap_or_else(PoisonError::into_inner));
+        for truncated in truncations {
+            let message = format!(
+                "raw data for {} in {} stopped at max_count_per_tag={} with more data left; \
+                 raise max_count_per_tag or page with get_raw_data_page",
+                truncated.tag_name, truncated.view, truncated.max_count_per_tag
+            );
+            let category = py.get_type::<PyUserWarning>();
+            PyErr::warn(py, category.as_any(), &CString::new(message).map_err(err)?, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Aggregate data for tags over one window.
+    fn aggregate_window<'py>(
+        &mut self,
+        py: Python<'py>,
+        view: &str,
+        tag_names: &[String],
+        (start, end): (SystemTime, SystemTime),
+        interval: prost_types::Duration,
+        aggregate_name: &str,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let requests: Vec<AggregateTagRequest> = tag_names
+            .iter()
+            .map(|tag_name| AggregateTagRequest {
+                tag_name: tag_name.clone(),
+                aggregate_name: aggregate_name.to_string(),
+                aggregate_configuration: None,
+                sloped: false,
+                client_data: 0,
+            })
+            .collect();
+
+        let req = GetAggregateDataRequest {
+            view: view.to_string(),
+            requests,
+            start_time: Some(start.into()),
+            end_time: Some(end.into()),
+            interval: Some(interval),
+            return_annotations: false,
+            cci: 0,
+        };
+
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let mut resp = self.rt.block_on(c.get_aggregate_data(req)).map_err(err)?;
+        if self.units.is_active() {
+            let tags: Vec<String> = resp.aggregated_data.iter().map(|d| d.tag_name.clone()).collect();
+            let units = self.resolve_units(view, &tags)?;
+            for tag_data in &mut resp.aggregated_data {
+                if let Some(from) = units.get(&tag_data.tag_name) {
+                    self.units.apply_grpc(&mut tag_data.tvqs, from);
+                }
+            }
+        }
+
+        let result = PyDict::new(py);
+        for tag_data in &resp.aggregated_data {
+            let tvqs = PyList::empty(py);
+            for tvq in &tag_data.tvqs {
+                tvqs.append(tvq_to_py_dict(py, tvq)?)?;
+            }
+            result.set_item(&tag_data.tag_name, tvqs)?;
+        }
+        Ok(result)
+    }
+
+
+    /// Look up (and cache) the engineering units of the given tags.
+    fn resolve_units(&mut self, view: &str, tag_names: &[String]) -> PyResult<HashMap<String, String>> {
+        let missing: Vec<String> = tag_names
+            .iter()
+            .filter(|t| !self.units_cache.contains_key(&(view.to_string(), (*t).clone())))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let c = self.client.as_mut().ok_or_else(not_connected)?;
+            let found = self.rt.block_on(c.get_eng_units(view, missing.clone())).map_err(err)?;
+            for tag in missing {
+                let units = found.get(&tag).cloned();
+                self.units_cache.insert((view.to_string(), tag), units);
+            }
+        }
+        Ok(tag_names
+            .iter()
+            .filter_map(|t| {
+                let units = self.units_cache.get(&(view.to_string(), t.clone()))?.clone()?;
+                Some((t.clone(), units))
+            })
+            .collect())
+    }
 }
 
 #[pymethods]
@@ -134,54 +449,129 @@ impl CanaryView {
     ///     api_key: The Canary API token
     ///     app: Application name (default: "crowsong")
     ///     user_id: User identifier (default: "python")
+    ///     on_truncation: What to do when a raw read stops at
+    ///         max_count_per_tag with more data left: "warn" (the default)
+    ///         raises a UserWarning, "error" raises an exception, and
+    ///         "ignore" does nothing
+    ///     connect: Connect now (the default). With False, nothing is dialed
+    ///         until connect() is called or the view is entered as a context
+    ///         manager; calls before then raise NotConnectedError
     #[new]
-    #[pyo3(signature = (endpoint, api_key, app="crowsong", user_id="python"))]
-    fn new(endpoint: &str, api_key: &str, app: &str, user_id: &str) -> PyResult<Self> {
-        let rt = Runtime::new().map_err(err)?;
-        let client = rt
-            .block_on(crate::ViewsClient::connect(endpoint, api_key, app, user_id))
+    #[pyo3(signature = (endpoint, api_key, app="crowsong", user_id="python", on_truncation="warn", connect=true))]
+    fn new(endpoint: &str, api_key: &str, app: &str, user_id: &str, on_truncation: &str, connect: bool) -> PyResult<Self> {
+        let truncations = Arc::new(Mutex::new(Vec::new()));
+        let check = match on_truncation {
+            "warn" => {
+                let reported = truncations.clone();
+                Some(TruncationCheck::new().handler(move |truncated| {
+                    reported.lock().unwrap_or_else(PoisonError::into_inner).push(truncated.clone());
+                }))
+            }
+            "error" => Some(TruncationCheck::new().strict(true)),
+            "ignore" => None,
+            other => {
+                return Err(err(format!(
+                    "unknown on_truncation {other:?}; expected \"warn\", \"error\", or \"ignore\""
+                )))
+            }
+        };
+        let mut view = Self {
+            rt: Runtime::new().map_err(err)?,
+            client: None,
+            endpoint: endpoint.to_string(),
+            api_key: api_key.to_string(),
+            app: app.to_string(),
+            user_id: user_id.to_string(),
+            truncation_check: check,
+            units: UnitConverter::with_builtin(),
+            units_cache: HashMap::new(),
+            truncations,
+        };
+        if connect {
+            view.connect()?;
+        }
+        Ok(view)
+    }
+
+    /// Connect to the Canary Views service, if not already connected.
+    fn connect(&mut self) -> PyResult<()> {
+        if self.client.is_some() {
+            return Ok(());
+        }
+        let mut client = self
+            .rt
+            .block_on(crate::ViewsClient::connect(&self.endpoint, &self.api_key, &self.app, &self.user_id))
             .map_err(err)?;
-        Ok(Self {
-            rt,
-            client: Some(client),
-        })
+        client.set_truncation_check(self.truncation_check.clone());
+        self.client = Some(client);
+        Ok(())
+    }
+
+    /// Drop the current connection, if any, and connect again, e.g. after
+    /// the server restarted. Failing to release the old connection is
+    /// ignored; it expires on the server.
+    fn reconnect(&mut self) -> PyResult<()> {
+        if let Some(client) = self.client.take() {
+            let _ = self.rt.block_on(client.shutdown());
+        }
+        self.connect()
+    }
+
+    /// Whether the view is connected.
+    #[getter]
+    fn connected(&self) -> bool {
+        self.client.is_some()
     }
 
     /// Get the client connection ID.
     fn cci(&self) -> PyResult<i32> {
-        Ok(self.client.as_ref().ok_or_else(|| err("disconnected"))?.cci())
+        Ok(self.client.as_ref().ok_or_else(not_connected)?.cci())
     }
 
     /// Test the gRPC connection.
     fn test(&mut self) -> PyResult<()> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         self.rt.block_on(c.test()).map_err(err)
     }
 
     /// Send a keepalive for the client connection.
     fn keepalive(&mut self) -> PyResult<()> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         self.rt.block_on(c.keepalive()).map_err(err)
     }
 
-    /// Disconnect from the Canary Views service.
+    /// Prefetch views, dataset lists, and the aggregate catalog in parallel,
+    /// so the first catalog lookups don't each pay a round trip.
+    fn warm_up(&mut self) -> PyResult<()> {
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.warm_up()).map_err(err)
+    }
+
+    /// Drop cached catalog metadata (views, dataset lists, aggregates).
+    fn clear_cache(&mut self) -> PyResult<()> {
+        self.client.as_mut().ok_or_else(not_connected)?.clear_cache();
+        Ok(())
+    }
+
+    /// Stop background work and disconnect from the Canary Views service.
+    /// connect() dials again.
     fn disconnect(&mut self) -> PyResult<()> {
-        if let Some(mut client) = self.client.take() {
-            self.rt.block_on(client.disconnect()).map_err(err)?;
+        if let Some(client) = self.client.take() {
+            self.rt.block_on(client.shutdown()).map_err(err)?;
         }
         Ok(())
     }
 
     /// Get the service version string.
     fn get_version(&mut self) -> PyResult<String> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_version()).map_err(err)?;
         Ok(resp.version)
     }
 
     /// Get the list of views accessible to this connection.
     fn get_views(&mut self) -> PyResult<Vec<String>> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_views()).map_err(err)?;
         Ok(resp.views)
     }
@@ -193,19 +583,29 @@ impl CanaryView {
     ///     include_hidden: Whether to include hidden datasets (default: False)
     #[pyo3(signature = (view, include_hidden=false))]
     fn get_dataset_list(&mut self, view: &str, include_hidden: bool) -> PyResult<Vec<String>> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_dataset_list(view, include_hidden)).map_err(err)?;
         Ok(resp.datasets)
     }
 
-    /// Get dataset info. Returns a dict of property names to values.
+    /// Get dataset info.
+    ///
+    /// Returns a dict with name, created, last_write, tag_count, size_bytes,
+    /// retention_days, retention_file_count (None when not reported), and
+    /// extra (a dict of any other properties).
     fn get_dataset_info(&mut self, py: Python<'_>, view: &str, dataset_name: &str) -> PyResult<PyObject> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
-        let resp = self.rt.block_on(c.get_dataset_info(view, dataset_name)).map_err(err)?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let info = self.rt.block_on(c.get_dataset_info(view, dataset_name)).map_err(err)?;
+        let to_iso = format_time;
         let dict = PyDict::new(py);
-        for (name, val) in resp.prop_name.iter().zip(resp.prop_value.iter()) {
-            dict.set_item(name, val)?;
-        }
+        dict.set_item("name", &info.name)?;
+        dict.set_item("created", info.created.map(to_iso))?;
+        dict.set_item("last_write", info.last_write.map(to_iso))?;
+        dict.set_item("tag_count", info.tag_count)?;
+        dict.set_item("size_bytes", info.size_bytes)?;
+        dict.set_item("retention_days", info.retention_days)?;
+        dict.set_item("retention_file_count", info.retention_file_count)?;
+        dict.set_item("extra", &info.extra)?;
         Ok(dict.into_any().unbind())
     }
 
@@ -226,7 +626,7 @@ impl CanaryView {
         starting_offset: i32,
         max_count: i32,
     ) -> PyResult<Vec<String>> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self
             .rt
             .block_on(c.get_tag_list(view, dataset_name, starting_offset, max_count))
@@ -234,11 +634,19 @@ impl CanaryView {
         Ok(resp.tag_names)
     }
 
+    /// Get every tag name in a dataset, following the tag list's pages.
+    ///
+    /// Returns a list of tag name strings.
+    fn get_all_tags(&mut self, view: &str, dataset_name: &str) -> PyResult<Vec<String>> {
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.get_all_tags(view, dataset_name)).map_err(err)
+    }
+
     /// Get tag info for specified tags.
     ///
     /// Returns a list of dicts with tag_item_id, item_type, flags, and properties.
     fn get_tag_info(&mut self, py: Python<'_>, view: &str, tag_names: Vec<String>) -> PyResult<PyObject> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_tag_info(view, tag_names)).map_err(err)?;
         let result = PyList::empty(py);
         for info in &resp.tag_infos {
@@ -246,6 +654,7 @@ impl CanaryView {
             d.set_item("tag_item_id", &info.tag_item_id)?;
             d.set_item("item_type", info.item_type)?;
             d.set_item("flags", info.flags)?;
+            d.set_item("eng_units", crate::types::TagInfo::from(info).eng_units())?;
             let props = PyList::empty(py);
             for p in &info.tag_properties {
                 let pd = PyDict::new(py);
@@ -265,7 +674,7 @@ impl CanaryView {
     ///
     /// Returns a list of dicts with tag_item_id, oldest_timestamp, latest_timestamp, etc.
     fn get_tag_data_context(&mut self, py: Python<'_>, view: &str, tag_names: Vec<String>) -> PyResult<PyObject> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_tag_data_context(view, tag_names)).map_err(err)?;
         let result = PyList::empty(py);
         for ctx in &resp.contexts {
@@ -301,11 +710,7 @@ impl CanaryView {
         tag_names: Vec<String>,
         quality: &str,
     ) -> PyResult<PyObject> {
-        let q = match quality {
-            "non_bad" => get_tag_current_value_request::Quality::NonBad,
-            "good" => get_tag_current_value_request::Quality::Good,
-            _ => get_tag_current_value_request::Quality::Any,
-        };
+        let q: QualityFilter = quality.parse().map_err(err)?;
         let req = GetTagCurrentValueRequest {
             view: view.to_string(),
             tag_names,
@@ -313,12 +718,22 @@ impl CanaryView {
             quality: q.into(),
             cci: 0, // filled in by ViewsClient
         };
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
-        let resp = self.rt.block_on(c.get_tag_current_value(req)).map_err(err)?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let mut resp = self.rt.block_on(c.get_tag_current_value(req)).map_err(err)?;
+        let units = if self.units.is_active() {
+            let tags: Vec<String> = resp.tag_values.iter().map(|tv| tv.tag_item_id.clone()).collect();
+            self.resolve_units(view, &tags)?
+        } else {
+            HashMap::new()
+        };
         let result = PyList::empty(py);
-        for tv in &resp.tag_values {
+        for tv in &mut resp.tag_values {
             let d = PyDict::new(py);
             d.set_item("tag_item_id", &tv.tag_item_id)?;
+            if let Some(from) = units.get(&tv.tag_item_id) {
+                let to = tv.value.as_mut().and_then(|v| self.units.apply_variant(v, from));
+                d.set_item("eng_units", to.as_deref().unwrap_or(from))?;
+            }
             if let Some(ts) = &tv.timestamp {
                 d.set_item("timestamp", timestamp_to_iso(ts))?;
             }
@@ -338,56 +753,143 @@ impl CanaryView {
     /// Args:
     ///     view: The view name
     ///     tag_names: List of tag names
-    ///     start_time: ISO 8601 start timestamp string
-    ///     end_time: ISO 8601 end timestamp string
+    ///     start_time: Start time, or a list or array of window start times
+    ///     end_time: End time, or a list or array of window end times
     ///     max_count_per_tag: Max data points per tag (default: 10000)
     ///     return_bounds: Include bounding values (default: False)
     ///
-    /// Returns a dict mapping tag_name -> list of {timestamp, value, quality} dicts.
+    /// Returns a dict mapping tag_name -> list of {timestamp, value, quality} dicts,
+    /// or for windows, a list of such dicts, one per window.
     #[pyo3(signature = (view, tag_names, start_time, end_time, max_count_per_tag=10000, return_bounds=false))]
     fn get_raw_data(
         &mut self,
         py: Python<'_>,
         view: &str,
         tag_names: Vec<String>,
-        start_time: &str,
-        end_time: &str,
+        start_time: TimeArg,
+        end_time: TimeArg,
         max_count_per_tag: i32,
         return_bounds: bool,
     ) -> PyResult<PyObject> {
-        let start = parse_iso_timestamp(start_time).map_err(err)?;
-        let end = parse_iso_timestamp(end_time).map_err(err)?;
-
-        let requests: Vec<RawTagRequest> = tag_names
-            .into_iter()
-            .map(|tag_name| RawTagRequest {
-                tag_name,
-                start_time: Some(start.clone()),
-                end_time: Some(end.clone()),
-                client_data: 0,
-                continuation_point: vec![],
-            })
-            .collect();
+        let (windows, multi) = time_windows(py, &start_time, &end_time)?;
+        let req = raw_request(view, tag_names, &windows, max_count_per_tag, return_bounds)?;
+        let resp = self.fetch_raw_columns(py, view, req, false)?;
 
-        let req = GetRawDataRequest {
-            view: view.to_string(),
-            requests,
-            max_count_per_tag,
-            return_bounds,
-            return_annotations: false,
-            cci: 0,
-        };
+        let results: Vec<Bound<'_, PyDict>> = windows.iter().map(|_| PyDict::new(py)).collect();
+        for columns in &resp.raw_data {
+            let tvqs = PyList::empty(py);
+            for row in 0..columns.len() {
+                tvqs.append(columns_row_to_py_dict(py, columns, row)?)?;
+            }
+            let result = usize::try_from(columns.client_data).ok().and_then(|index| results.get(index));
+            result.ok_or_else(|| err("the server sent data for an unknown window"))?.set_item(&columns.tag_name, tvqs)?;
+        }
+        if multi {
+            return Ok(PyList::new(py, results)?.into_any().unbind());
+        }
+        Ok(results.into_iter().next().expect("one window").into_any().unbind())
+    }
 
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
-        let resp = self.rt.block_on(c.get_raw_data(req)).map_err(err)?;
+    /// Get one page of raw historical data, for driving paging yourself.
+    ///
+    /// Args:
+    ///     view: The view name
+    ///     tag_names: List of tag names
+    ///     start_time: Start time
+    ///     end_time: End time
+    ///     max_count_per_tag: Max data points per tag (default: 10000)
+    ///     return_bounds: Include bounding values (default: False)
+    ///     continuations: Optional dict of tag_name -> the "continuation" of
+    ///         a previous page, to read that tag's next page
+    ///     client_data: Optional dict of tag_name -> int, echoed back in
+    ///         each tag's result
+    ///
+    /// Returns a dict mapping tag_name -> {"tvqs", "continuation", "client_data"}.
+    /// Each tvq dict has a "bound" key: "before" or "after" for bounding
+    /// values outside the range, None for samples within it. "continuation"
+    /// is an opaque bytes token when the tag has more data, else None.
+    #[pyo3(signature = (view, tag_names, start_time, end_time, max_count_per_tag=10000, return_bounds=false, continuations=None, client_data=None))]
+    fn get_raw_data_page(
+        &mut self,
+        py: Python<'_>,
+        view: &str,
+        tag_names: Vec<String>,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        max_count_per_tag: i32,
+        return_bounds: bool,
+        continuations: Option<HashMap<String, Vec<u8>>>,
+        client_data: Option<HashMap<String, i32>>,
+    ) -> PyResult<PyObject> {
+        let (start, end) = (start_time.time(py)?, end_time.time(py)?);
+        let mut req = raw_request(view, tag_names, &[(start, end)], max_count_per_tag, return_bounds)?;
+        let (mut continuations, client_data) = (continuations.unwrap_or_default(), client_data.unwrap_or_default());
+        for tag in &mut req.requests {
+            tag.continuation_point = continuations.remove(&tag.tag_name).unwrap_or_default();
+            tag.client_data = client_data.get(&tag.tag_name).copied().unwrap_or_default();
+        }
+        let resp = self.fetch_raw_columns(py, view, req, true)?;
 
         let result = PyDict::new(py);
-        for tag_data in &resp.raw_data {
+        for columns in &resp.raw_data {
             let tvqs = PyList::empty(py);
-            for tvq in &tag_data.tvqs {
-                tvqs.append(tvq_to_py_dict(py, tvq)?)?;
+            for row in 0..columns.len() {
+                let tvq = columns_row_to_py_dict(py, columns, row)?;
+                let timestamp = columns.timestamp(row);
+                let bound = if timestamp < start {
+                    Some("before")
+                } else if timestamp > end {
+                    Some("after")
+                } else {
+                    None
+                };
+                tvq.set_item("bound", bound)?;
+                tvqs.append(tvq)?;
             }
-            result.set_item(&tag_data.tag_name, tvqs)?;
+            let page = PyDict::new(py);
+            page.set_item("tvqs", tvqs)?;
+            let continuation = (!columns.continuation_point.is_empty())
+                .then(|| PyBytes::new(py, &columns.continuation_point));
+            page.set_item("continuation", continuation)?;
+            page.set_item("client_data", columns.client_data)?;
+            result.set_item(&columns.tag_name, page)?;
+        }
+        Ok(result.into_any().unbind())
+    }
+
+    /// Get raw historical data for tags as numpy arrays.
+    ///
+    /// Takes the same arguments as `get_raw_data`, for a single window. Each
+    /// column is built once in Rust and handed to numpy without copying or
+    /// creating a Python object per sample, so prefer this for large pulls.
+    ///
+    /// Returns a dict mapping tag_name -> {"timestamps", "values", "qualities"},
+    /// where timestamps are int64 nanoseconds since the Unix epoch, values are
+    /// float64 (NaN for null, string, and decimal samples; booleans as 0/1),
+    /// and qualities are uint32.
+    #[cfg(feature = "numpy")]
+    #[pyo3(signature = (view, tag_names, start_time, end_time, max_count_per_tag=10000, return_bounds=false))]
+    fn get_raw_data_numpy(
+        &mut self,
+        py: Python<'_>,
+        view: &str,
+        tag_names: Vec<String>,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        max_count_per_tag: i32,
+        return_bounds: bool,
+    ) -> PyResult<PyObject> {
+        let window = (start_time.time(py)?, end_time.time(py)?);
+        let req = raw_request(view, tag_names, &[window], max_count_per_tag, return_bounds)?;
+        let resp = self.fetch_raw_columns(py, view, req, false)?;
+
+        let result = PyDict::new(py);
+        for columns in resp.raw_data {
+            let arrays = PyDict::new(py);
+            arrays.set_item("timestamps", PyArray1::from_vec(py, columns.timestamps))?;
+            arrays.set_item("values", PyArray1::from_vec(py, columns.values))?;
+            arrays.set_item("qualities", PyArray1::from_vec(py, columns.qualities))?;
+            result.set_item(columns.tag_name, arrays)?;
         }
         Ok(result.into_any().unbind())
     }
@@ -397,67 +899,40 @@ impl CanaryView {
     /// Args:
     ///     view: The view name
     ///     tag_names: List of tag names
-    ///     start_time: ISO 8601 start timestamp string
-    ///     end_time: ISO 8601 end timestamp string
-    ///     interval_seconds: Aggregation interval in seconds
+    ///     start_time: Start time, or a list or array of window start times
+    ///     end_time: End time, or a list or array of window end times
+    ///     interval_seconds: Aggregation interval in seconds, or a string
+    ///         such as "15m" or "1h30m"
     ///     aggregate_name: Aggregate function name (e.g. "TimeAverage")
     ///
-    /// Returns a dict mapping tag_name -> list of {timestamp, value, quality} dicts.
+    /// Returns a dict mapping tag_name -> list of {timestamp, value, quality} dicts,
+    /// or for windows, a list of such dicts, one per window.
     #[pyo3(signature = (view, tag_names, start_time, end_time, interval_seconds, aggregate_name="TimeAverage"))]
     fn get_aggregate_data(
         &mut self,
         py: Python<'_>,
         view: &str,
         tag_names: Vec<String>,
-        start_time: &str,
-        end_time: &str,
-        interval_seconds: i64,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        interval_seconds: IntervalArg,
         aggregate_name: &str,
     ) -> PyResult<PyObject> {
-        let start = parse_iso_timestamp(start_time).map_err(err)?;
-        let end = parse_iso_timestamp(end_time).map_err(err)?;
-
-        let requests: Vec<AggregateTagRequest> = tag_names
-            .into_iter()
-            .map(|tag_name| AggregateTagRequest {
-                tag_name,
-                aggregate_name: aggregate_name.to_string(),
-                aggregate_configuration: None,
-                sloped: false,
-                client_data: 0,
-            })
-            .collect();
-
-        let req = GetAggregateDataRequest {
-            view: view.to_string(),
-            requests,
-            start_time: Some(start),
-            end_time: Some(end),
-            interval: Some(prost_types::Duration {
-                seconds: interval_seconds,
-                nanos: 0,
-            }),
-            return_annotations: false,
-            cci: 0,
-        };
-
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
-        let resp = self.rt.block_on(c.get_aggregate_data(req)).map_err(err)?;
-
-        let result = PyDict::new(py);
-        for tag_data in &resp.aggregated_data {
-            let tvqs = PyList::empty(py);
-            for tvq in &tag_data.tvqs {
-                tvqs.append(tvq_to_py_dict(py, tvq)?)?;
-            }
-            result.set_item(&tag_data.tag_name, tvqs)?;
+        let (windows, multi) = time_windows(py, &start_time, &end_time)?;
+        let interval = interval_seconds.to_proto()?;
+        let mut results = Vec::with_capacity(windows.len());
+        for window in windows {
+            results.push(self.aggregate_window(py, view, &tag_names, window, interval, aggregate_name)?);
         }
-        Ok(result.into_any().unbind())
+        if multi {
+            return Ok(PyList::new(py, results)?.into_any().unbind());
+        }
+        Ok(results.into_iter().next().expect("one window").into_any().unbind())
     }
 
     /// Get available aggregate function names.
     fn get_aggregate_list(&mut self) -> PyResult<Vec<String>> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_aggregate_list()).map_err(err)?;
         Ok(resp
             .aggregates
@@ -466,14 +941,71 @@ impl CanaryView {
             .collect())
     }
 
+    /// Get events (alarms, batches, and other calculated intervals).
+    ///
+    /// Args:
+    ///     start_time: Start time
+    ///     end_time: End time
+    ///     source: Only events whose source starts with this prefix (default: None)
+    ///     calculation: Only events from this event calculation (default: None)
+    ///
+    /// Returns a list of dicts with id, name, calculation, source, start,
+    /// end (None while active), status, and properties (a dict).
+    #[pyo3(signature = (start_time, end_time, source=None, calculation=None))]
+    fn get_events(
+        &mut self,
+        py: Python<'_>,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        source: Option<String>,
+        calculation: Option<String>,
+    ) -> PyResult<PyObject> {
+        let mut query = crate::EventQuery::new(start_time.time(py)?, end_time.time(py)?);
+        if let Some(source) = source {
+            query = query.source(source);
+        }
+        if let Some(calculation) = calculation {
+            query = query.calculation(calculation);
+        }
+
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let events = self.rt.block_on(c.get_events(&query)).map_err(err)?;
+
+        let to_iso = format_time;
+        let result = PyList::empty(py);
+        for event in events {
+            let properties = PyDict::new(py);
+            for (name, value) in event.properties {
+                properties.set_item(name, variant_to_py(py, &value.into()))?;
+            }
+            let d = PyDict::new(py);
+            d.set_item("id", event.id)?;
+            d.set_item("name", event.name)?;
+            d.set_item("calculation", event.calculation)?;
+            d.set_item("source", event.source)?;
+            d.set_item("start", event.start.map(to_iso))?;
+            d.set_item("end", event.end.map(to_iso))?;
+            d.set_item("status", event.status.as_str())?;
+            d.set_item("properties", properties)?;
+            result.append(d)?;
+        }
+        Ok(result.into_any().unbind())
+    }
+
+    /// Get the names of the event calculations.
+    fn get_event_calculation_names(&mut self) -> PyResult<Vec<String>> {
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.get_event_calculation_names()).map_err(err)
+    }
+
     /// Get tag statistics.
     ///
     /// Args:
     ///     view_name: The view name
     ///     tag_id: The tag ID
-    ///     start_time: ISO 8601 start timestamp
-    ///     end_time: ISO 8601 end timestamp
-    ///     interval_seconds: Interval in seconds
+    ///     start_time: Start time
+    ///     end_time: End time
+    ///     interval_seconds: Interval in seconds, or a string such as "1h"
     ///     aggregate_name: Aggregate function (default: "TimeAverage")
     ///     include_std_dev: Include standard deviation (default: True)
     ///     include_percentiles: Include percentiles (default: True)
@@ -486,32 +1018,29 @@ impl CanaryView {
         py: Python<'_>,
         view_name: &str,
         tag_id: &str,
-        start_time: &str,
-        end_time: &str,
-        interval_seconds: i64,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        interval_seconds: IntervalArg,
         aggregate_name: &str,
         include_std_dev: bool,
         include_percentiles: bool,
     ) -> PyResult<PyObject> {
-        let start = parse_iso_timestamp(start_time).map_err(err)?;
-        let end = parse_iso_timestamp(end_time).map_err(err)?;
+        let start = prost_types::Timestamp::from(start_time.time(py)?);
+        let end = prost_types::Timestamp::from(end_time.time(py)?);
 
         let req = GetTagStatisticsRequest {
             view_name: view_name.to_string(),
             tag_id: tag_id.to_string(),
             start_time: Some(start),
             end_time: Some(end),
-            interval: Some(prost_types::Duration {
-                seconds: interval_seconds,
-                nanos: 0,
-            }),
+            interval: Some(interval_seconds.to_proto()?),
             aggregate_name: aggregate_name.to_string(),
             include_std_dev,
             include_percentiles,
             cci: 0,
         };
 
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.get_tag_statistics(req)).map_err(err)?;
 
         let d = PyDict::new(py);
@@ -528,12 +1057,212 @@ impl CanaryView {
         Ok(d.into_any().unbind())
     }
 
+    /// Get a quality breakdown for tags.
+    ///
+    /// Args:
+    ///     view: The view name
+    ///     tag_names: List of tag names
+    ///     start_time: Start time
+    ///     end_time: End time
+    ///     bucket_seconds: Use PercentGood/PercentBad/Count aggregates over
+    ///         buckets of this width, in seconds or as a string such as
+    ///         "15m", instead of raw samples (default: None)
+    ///
+    /// Returns a dict mapping tag_name -> {samples, good_percent,
+    /// uncertain_percent, bad_percent, by_substatus (a dict of quality name ->
+    /// count), longest_bad ({start, end, samples} or None)}.
+    #[pyo3(signature = (view, tag_names, start_time, end_time, bucket_seconds=None))]
+    fn get_quality_summary(
+        &mut self,
+        py: Python<'_>,
+        view: &str,
+        tag_names: Vec<String>,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        bucket_seconds: Option<IntervalArg>,
+    ) -> PyResult<PyObject> {
+        let mut scan = crate::QualityScan::new(view, tag_names, start_time.time(py)?, end_time.time(py)?);
+        if let Some(bucket) = bucket_seconds {
+            scan = scan.aggregate_buckets(bucket.duration()?);
+        }
+
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let summaries = self.rt.block_on(scan.run(c)).map_err(err)?;
+
+        let to_iso = format_time;
+        let result = PyDict::new(py);
+        for summary in summaries {
+            let by_substatus = PyDict::new(py);
+            for (quality, count) in &summary.by_substatus {
+                by_substatus.set_item(quality.to_string(), count)?;
+            }
+            let longest_bad = match summary.longest_bad {
+                Some(stretch) => {
+                    let d = PyDict::new(py);
+                    d.set_item("start", to_iso(stretch.start))?;
+                    d.set_item("end", to_iso(stretch.end))?;
+                    d.set_item("samples", stretch.samples)?;
+                    d.into_any()
+                }
+                None => py.None().into_bound(py),
+            };
+            let d = PyDict::new(py);
+            d.set_item("samples", summary.samples)?;
+            d.set_item("good_percent", summary.good_percent)?;
+            d.set_item("uncertain_percent", summary.uncertain_percent)?;
+            d.set_item("bad_percent", summary.bad_percent)?;
+            d.set_item("by_substatus", by_substatus)?;
+            d.set_item("longest_bad", longest_bad)?;
+            result.set_item(&summary.tag_name, d)?;
+        }
+        Ok(result.into_any().unbind())
+    }
+
+    /// Compute time-weighted statistics for tags from their raw samples.
+    ///
+    /// Args:
+    ///     view: The view name
+    ///     tag_names: List of tag names
+    ///     start_time: Start time
+    ///     end_time: End time
+    ///     sloped: Interpolate linearly between samples instead of holding
+    ///         each value (default: False)
+    ///     quality: Which samples count: "any", "non_bad", or "good"
+    ///         (default: "non_bad")
+    ///     low: Lower limit for in_range_percent (default: None)
+    ///     high: Upper limit for in_range_percent (default: None)
+    ///
+    /// Returns a dict mapping tag_name -> {time_average, minimum, minimum_time,
+    /// maximum, maximum_time, integral (value-seconds), covered_seconds,
+    /// samples, in_range_percent}. Statistics without data are None.
+    #[pyo3(signature = (view, tag_names, start_time, end_time, sloped=false, quality="non_bad", low=None, high=None))]
+    fn get_time_weighted_stats(
+        &mut self,
+        py: Python<'_>,
+        view: &str,
+        tag_names: Vec<String>,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        sloped: bool,
+        quality: &str,
+        low: Option<f64>,
+        high: Option<f64>,
+    ) -> PyResult<PyObject> {
+        let q: QualityFilter = quality.parse().map_err(err)?;
+        let mut stats = crate::TimeWeighted::new(start_time.time(py)?, end_time.time(py)?).quality(q);
+        if sloped {
+            stats = stats.sloped();
+        }
+        match (low, high) {
+            (Some(low), Some(high)) => stats = stats.in_range(low, high),
+            (None, None) => {}
+            _ => return Err(err("low and high must be given together")),
+        }
+
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let tags = self.rt.block_on(stats.fetch(c, view, tag_names)).map_err(err)?;
+
+        let result = PyDict::new(py);
+        for tag in tags {
+            let d = PyDict::new(py);
+            d.set_item("time_average", tag.time_average)?;
+            d.set_item("minimum", tag.minimum.map(|(_, v)| v))?;
+            d.set_item("minimum_time", tag.minimum.map(|(t, _)| format_time(t)))?;
+            d.set_item("maximum", tag.maximum.map(|(_, v)| v))?;
+            d.set_item("maximum_time", tag.maximum.map(|(t, _)| format_time(t)))?;
+            d.set_item("integral", tag.integral)?;
+            d.set_item("covered_seconds", tag.covered.as_secs_f64())?;
+            d.set_item("samples", tag.samples)?;
+            d.set_item("in_range_percent", tag.percent_in_range)?;
+            result.set_item(&tag.tag_name, d)?;
+        }
+        Ok(result.into_any().unbind())
+    }
+
+    /// Get raw data resampled onto a regular grid, with gaps filled or
+    /// marked as NaN.
+    ///
+    /// Args:
+    ///     view: The view name
+    ///     tag_names: List of tag names
+    ///     start_time: Start time
+    ///     end_time: End time
+    ///     interval_seconds: Grid step, in seconds or as a string such as "1m"
+    ///     method: How to fill gaps: "hold", "linear", or "nan" (default: "hold")
+    ///     tag_methods: Dict of tag name -> method overriding `method` (default: None)
+    ///     max_gap_seconds: Leave gaps longer than this as NaN (default: None)
+    ///     quality: Which samples count: "any", "non_bad", or "good"
+    ///         (default: "non_bad")
+    ///
+    /// Returns a dict mapping tag_name -> {timestamps, values, qualities,
+    /// filled}, where filled marks the points that were filled or left NaN.
+    #[pyo3(signature = (view, tag_names, start_time, end_time, interval_seconds, method="hold", tag_methods=None, max_gap_seconds=None, quality="non_bad"))]
+    fn get_raw_data_grid(
+        &mut self,
+        py: Python<'_>,
+        view: &str,
+        tag_names: Vec<String>,
+        start_time: TimeArg,
+        end_time: TimeArg,
+        interval_seconds: IntervalArg,
+        method: &str,
+        tag_methods: Option<HashMap<String, String>>,
+        max_gap_seconds: Option<IntervalArg>,
+        quality: &str,
+    ) -> PyResult<PyObject> {
+        let start = start_time.time(py)?;
+        let end = end_time.time(py)?;
+        let q: QualityFilter = quality.parse().map_err(err)?;
+        let mut fill = crate::GapFill::new(interval_seconds.duration()?)
+            .method(method.parse().map_err(err)?)
+            .quality(q);
+        for (tag, method) in tag_methods.unwrap_or_default() {
+            fill = fill.tag_method(tag, method.parse().map_err(err)?);
+        }
+        if let Some(max_gap) = max_gap_seconds {
+            fill = fill.max_gap(max_gap.duration()?);
+        }
+
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        let grids = self.rt.block_on(fill.fetch(c, view, tag_names, start, end)).map_err(err)?;
+
+        let result = PyDict::new(py);
+        for grid in grids {
+            let d = PyDict::new(py);
+            d.set_item("timestamps", grid.timestamps.iter().map(|t| format_time(*t)).collect::<Vec<_>>())?;
+            d.set_item("values", &grid.values)?;
+            d.set_item("qualities", grid.qualities.iter().map(|q| q.0).collect::<Vec<_>>())?;
+            d.set_item("filled", &grid.filled)?;
+            result.set_item(&grid.tag_name, d)?;
+        }
+        Ok(result.into_any().unbind())
+    }
+
+    /// Get the engineering units of the given tags as a dict of tag name -> units.
+    fn get_eng_units(&mut self, view: &str, tag_names: Vec<String>) -> PyResult<HashMap<String, String>> {
+        self.resolve_units(view, &tag_names)
+    }
+
+    /// Convert values reported in `from_unit` to `to_unit` in all read results.
+    ///
+    /// Built-in conversions cover common temperature, pressure, length, mass,
+    /// volume, and flow units; others can be added with `register_unit_conversion`.
+    fn convert_units(&mut self, from_unit: &str, to_unit: &str) {
+        self.units.prefer(from_unit, to_unit);
+    }
+
+    /// Register a linear conversion `to = from * scale + offset`.
+    #[pyo3(signature = (from_unit, to_unit, scale, offset=0.0))]
+    fn register_unit_conversion(&mut self, from_unit: &str, to_unit: &str, scale: f64, offset: f64) {
+        self.units.register_linear(from_unit, to_unit, scale, offset);
+    }
+
     /// Browse the views tree by node ID path.
     ///
     /// Returns a dict with parent_id_path and children (list of dicts).
     #[pyo3(signature = (node_id_path="", force_reload=false))]
     fn browse(&mut self, py: Python<'_>, node_id_path: &str, force_reload: bool) -> PyResult<PyObject> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.browse(node_id_path, force_reload)).map_err(err)?;
 
         let d = PyDict::new(py);
@@ -579,7 +1308,7 @@ impl CanaryView {
             include_sub_nodes,
             include_properties: false,
         };
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.browse_tags(req)).map_err(err)?;
         Ok(resp.tag_names)
     }
@@ -606,7 +1335,7 @@ impl CanaryView {
             eng_units_or: vec![],
             include_properties: false,
         };
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.search_tags(req)).map_err(err)?;
         Ok(resp.search.iter().map(|s| s.tag_name.clone()).collect())
     }
@@ -615,7 +1344,7 @@ impl CanaryView {
     ///
     /// Returns a list of dicts, one per node on the path.
     fn browse_path(&mut self, py: Python<'_>, tree_path: Vec<String>) -> PyResult<PyObject> {
-        let c = self.client.as_mut().ok_or_else(|| err("disconnected"))?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
         let resp = self.rt.block_on(c.browse_path(tree_path)).map_err(err)?;
         let result = PyList::empty(py);
         for node in &resp.nodes {
@@ -637,8 +1366,9 @@ impl CanaryView {
         Ok(result.into_any().unbind())
     }
 
-    fn __enter__(slf: Py<Self>) -> Py<Self> {
-        slf
+    fn __enter__(slf: Py<Self>, py: Python<'_>) -> PyResult<Py<Self>> {
+        slf.borrow_mut(py).connect()?;
+        Ok(slf)
     }
 
     fn __exit__(
@@ -653,71 +1383,491 @@ impl CanaryView {
     fn __repr__(&self) -> String {
         match &self.client {
             Some(c) => format!("CanaryView(cci={})", c.cci()),
-            None => "CanaryView(disconnected)".to_string(),
+            None => "CanaryView(not connected)".to_string(),
         }
     }
 }
 
-// ---------------------------------------------------------------------------
-// ISO 8601 timestamp parsing (basic)
-// ---------------------------------------------------------------------------
+/// A sample to write: `(time, value)` or `(time, value, quality)`.
+#[cfg(feature = "store-and-forward")]
+#[derive(FromPyObject)]
+enum SampleArg {
+    WithQuality(TimeArg, PyObject, QualityArg),
+    Plain(TimeArg, PyObject),
+}
 
-fn parse_iso_timestamp(s: &str) -> Result<prost_types::Timestamp, String> {
-    // Parse "YYYY-MM-DDThh:mm:ss[.nanos]Z" or "YYYY-MM-DD hh:mm:ss"
-    let s = s.trim().trim_end_matches('Z');
-    let (date_part, time_part) = if let Some(pos) = s.find('T') {
-        (&s[..pos], &s[pos + 1..])
-    } else if let Some(pos) = s.find(' ') {
-        (&s[..pos], &s[pos + 1..])
-    } else {
-        (s, "00:00:00")
-    };
+/// A quality: a numeric code such as 192, or a name such as "Good".
+#[cfg(feature = "store-and-forward")]
+#[derive(FromPyObject)]
+enum QualityArg {
+    Code(u32),
+    Text(String),
+}
 
-    let date_parts: Vec<&str> = date_part.split('-').collect();
-    if date_parts.len() != 3 {
-        return Err(format!("invalid date: {}", date_part));
+#[cfg(feature = "store-and-forward")]
+impl QualityArg {
+    fn quality(&self) -> PyResult<Quality> {
+        match self {
+            Self::Code(code) => Ok(Quality(*code)),
+            Self::Text(s) => s.parse().map_err(err),
+        }
+    }
+}
+
+/// Convert a Python value to a sample value: None, bool, int, float (or
+/// anything with `__float__`, such as numpy scalars), or str.
+#[cfg(feature = "store-and-forward")]
+fn py_to_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    use pyo3::types::{PyBool, PyInt};
+
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    if obj.is_instance_of::<PyBool>() {
+        return Ok(Value::Bool(obj.extract()?));
     }
-    let year: i64 = date_parts[0].parse().map_err(|_| format!("invalid year: {}", date_parts[0]))?;
-    let month: i64 = date_parts[1].parse().map_err(|_| format!("invalid month: {}", date_parts[1]))?;
-    let day: i64 = date_parts[2].parse().map_err(|_| format!("invalid day: {}", date_parts[2]))?;
+    if obj.is_instance_of::<PyInt>() {
+        return match obj.extract::<i64>() {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(_) => Ok(Value::UInt(obj.extract()?)),
+        };
+    }
+    if let Ok(s) = obj.extract::<String>() {
+        return Ok(Value::String(s));
+    }
+    obj.extract::<f64>().map(Value::Float).map_err(|_| {
+        PyTypeError::new_err(format!(
+            "expected None, bool, int, float, or str, got {}",
+            obj.get_type().name().map(|n| n.to_string()).unwrap_or_default()
+        ))
+    })
+}
+
+/// Convert a list of samples to TVQs. Quality defaults to Good.
+#[cfg(feature = "store-and-forward")]
+fn samples_to_tvqs(py: Python<'_>, samples: Vec<SampleArg>) -> PyResult<Vec<crate::Tvq>> {
+    samples
+        .into_iter()
+        .map(|sample| {
+            let (time, value, quality) = match sample {
+                SampleArg::WithQuality(time, value, quality) => (time, value, quality.quality()?),
+                SampleArg::Plain(time, value) => (time, value, Quality::GOOD),
+            };
+            Ok(crate::Tvq {
+                timestamp: time.time(py)?,
+                value: py_to_value(value.bind(py))?,
+                quality,
+            })
+        })
+        .collect()
+}
 
-    let (time_whole, nanos) = if let Some(dot_pos) = time_part.find('.') {
-        let nano_str = &time_part[dot_pos + 1..];
-        let padded = format!("{:0<9}", nano_str);
-        let nanos: i32 = padded[..9].parse().map_err(|_| format!("invalid nanos: {}", nano_str))?;
-        (&time_part[..dot_pos], nanos)
+/// Epoch nanoseconds of an array of times: a numpy datetime64 or int64
+/// (nanoseconds) array, a pandas DatetimeIndex, or a datetime Series.
+#[cfg(all(feature = "numpy", feature = "store-and-forward"))]
+fn array_nanos(obj: &Bound<'_, PyAny>) -> PyResult<Vec<i64>> {
+    let py = obj.py();
+    // A DatetimeIndex's asi8 is in UTC, also when the index is tz-aware.
+    let obj = if obj.hasattr("dt")? {
+        py.import("pandas")?.getattr("DatetimeIndex")?.call1((obj,))?
     } else {
-        (time_part, 0)
+        obj.clone()
     };
+    let nanos = match obj.getattr("asi8") {
+        Ok(asi8) => asi8,
+        Err(_) => {
+            let array = py.import("numpy")?.getattr("asarray")?.call1((obj,))?;
+            match dtype_kind(&array)?.as_str() {
+                "M" => array
+                    .call_method1("astype", ("datetime64[ns]",))?
+                    .call_method1("astype", ("int64",))?,
+                "i" | "u" => array.call_method1("astype", ("int64",))?,
+                kind => {
+                    return Err(PyTypeError::new_err(format!(
+                        "expected datetime64 or int64 nanosecond times, got dtype kind {kind:?}"
+                    )));
+                }
+            }
+        }
+    };
+    let nanos: PyReadonlyArray1<'_, i64> = nanos.extract()?;
+    let nanos: Vec<i64> = nanos.as_array().iter().copied().collect();
+    if nanos.contains(&i64::MIN) {
+        return Err(err("NaT is not a time"));
+    }
+    Ok(nanos)
+}
 
-    let time_parts: Vec<&str> = time_whole.split(':').collect();
-    let hours: i64 = time_parts.first().and_then(|s| s.parse().ok()).unwrap_or(0);
-    let mins: i64 = time_parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(0);
-    let secs: i64 = time_parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(0);
+/// The sample values of an array or Series, with None for NaN and None,
+/// which are skipped. Bool, integer, and float arrays are read without
+/// a Python object per element.
+#[cfg(all(feature = "numpy", feature = "store-and-forward"))]
+fn array_values(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Option<Value>>> {
+    let array = obj.py().import("numpy")?.getattr("asarray")?.call1((obj,))?;
+    Ok(match dtype_kind(&array)?.as_str() {
+        "b" => {
+            let array: PyReadonlyArray1<'_, bool> = array.extract()?;
+            array.as_array().iter().map(|&b| Some(Value::Bool(b))).collect()
+        }
+        "i" => {
+            let array: PyReadonlyArray1<'_, i64> = array.call_method1("astype", ("int64",))?.extract()?;
+            array.as_array().iter().map(|&n| Some(Value::Int(n))).collect()
+        }
+        "u" => {
+            let array: PyReadonlyArray1<'_, u64> = array.call_method1("astype", ("uint64",))?.extract()?;
+            array.as_array().iter().map(|&n| Some(Value::UInt(n))).collect()
+        }
+        "f" => {
+            let array: PyReadonlyArray1<'_, f64> = array.call_method1("astype", ("float64",))?.extract()?;
+            array
+                .as_array()
+                .iter()
+                .map(|&x| (!x.is_nan()).then_some(Value::Float(x)))
+                .collect()
+        }
+        _ => array
+            .call_method0("tolist")?
+            .try_iter()?
+            .map(|item| {
+                let item = item?;
+                if item.is_none() || item.extract::<f64>().is_ok_and(f64::is_nan) {
+                    return Ok(None);
+                }
+                py_to_value(&item).map(Some)
+            })
+            .collect::<PyResult<_>>()?,
+    })
+}
+
+/// Qualities for `len` samples: one quality for all of them, or an array
+/// of codes. Good if `None`.
+#[cfg(all(feature = "numpy", feature = "store-and-forward"))]
+fn array_qualities(obj: Option<&Bound<'_, PyAny>>, len: usize) -> PyResult<Vec<Quality>> {
+    let Some(obj) = obj else {
+        return Ok(vec![Quality::GOOD; len]);
+    };
+    if let Ok(quality) = obj.extract::<QualityArg>() {
+        return Ok(vec![quality.quality()?; len]);
+    }
+    let array = obj.py().import("numpy")?.getattr("asarray")?.call1((obj,))?;
+    let codes: PyReadonlyArray1<'_, u32> = array.call_method1("astype", ("uint32",))?.extract()?;
+    Ok(codes.as_array().iter().map(|&code| Quality(code)).collect())
+}
+
+/// Zip times, values, and qualities of equal length into TVQs, skipping
+/// missing values.
+#[cfg(all(feature = "numpy", feature = "store-and-forward"))]
+fn arrays_to_tvqs(nanos: &[i64], values: Vec<Option<Value>>, qualities: Vec<Quality>) -> PyResult<Vec<crate::Tvq>> {
+    if values.len() != nanos.len() || qualities.len() != nanos.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} times, {} values, and {} qualities; expected as many of each",
+            nanos.len(),
+            values.len(),
+            qualities.len()
+        )));
+    }
+    Ok(nanos
+        .iter()
+        .zip(values)
+        .zip(qualities)
+        .filter_map(|((&nanos, value), quality)| {
+            Some(crate::Tvq {
+                timestamp: timestamps::nanos_to_system_time(nanos),
+                value: value?,
+                quality,
+            })
+        })
+        .collect())
+}
+
+#[cfg(all(feature = "numpy", feature = "store-and-forward"))]
+fn dtype_kind(array: &Bound<'_, PyAny>) -> PyResult<String> {
+    array.getattr("dtype")?.getattr("kind")?.extract()
+}
+
+/// A Python client for writing to a Canary Store & Forward service.
+///
+/// Usage:
+///     from crowsong import CanaryWriter
+///     writer = CanaryWriter("https://host:55291", "api-key")
+///     writer.store_data("Plant", "Pump1.Flow", [("2024-05-01T12:00:00Z", 12.5)])
+///     writer.close()
+///
+/// Samples are `(time, value)` or `(time, value, quality)` tuples. Times are
+/// accepted in the same forms as by CanaryView; values may be None, bool,
+/// int, float, or str; quality is a code such as 192 or a name such as
+/// "Good", and defaults to Good.
+///
+/// For backfills, store_arrays and store_frame take numpy arrays or a pandas
+/// DataFrame instead and build the samples in Rust, without a Python tuple
+/// per sample.
+#[cfg(feature = "store-and-forward")]
+#[pyclass]
+pub struct CanaryWriter {
+    rt: Runtime,
+    client: Option<crate::StoreAndForwardClient>,
+    /// What connect() dials, and the session it opens.
+    endpoint: String,
+    api_key: String,
+    session: String,
+}
 
-    // Convert to Unix epoch seconds
-    let mut total_days: i64 = 0;
-    for y in 1970..year {
-        total_days += if is_leap(y) { 366 } else { 365 };
+#[cfg(feature = "store-and-forward")]
+#[pymethods]
+impl CanaryWriter {
+    /// Create a new connection to a Canary Store & Forward service.
+    ///
+    /// Args:
+    ///     endpoint: The gRPC endpoint URL (e.g. "https://host:55291")
+    ///     api_key: The Canary API token
+    ///     session: The name of the Store & Forward session (default: "crowsong")
+    ///     connect: Connect and open the session now (the default). With
+    ///         False, nothing is dialed until connect() is called or the
+    ///         writer is entered as a context manager
+    #[new]
+    #[pyo3(signature = (endpoint, api_key, session="crowsong", connect=true))]
+    fn new(endpoint: &str, api_key: &str, session: &str, connect: bool) -> PyResult<Self> {
+        let mut writer = Self {
+            rt: Runtime::new().map_err(err)?,
+            client: None,
+            endpoint: endpoint.to_string(),
+            api_key: api_key.to_string(),
+            session: session.to_string(),
+        };
+        if connect {
+            writer.connect()?;
+        }
+        Ok(writer)
     }
-    let leap = is_leap(year);
-    let month_days: [i64; 12] = [
-        31, if leap { 29 } else { 28 }, 31, 30, 31, 30,
-        31, 31, 30, 31, 30, 31,
-    ];
-    for m in 0..(month - 1) as usize {
-        if m < 12 {
-            total_days += month_days[m];
+
+    /// Connect and open the session, if not already connected.
+    fn connect(&mut self) -> PyResult<()> {
+        if self.client.is_some() {
+            return Ok(());
         }
+        let mut client = self
+            .rt
+            .block_on(crate::StoreAndForwardClient::connect(&self.endpoint, &self.api_key))
+            .map_err(err)?;
+        self.rt.block_on(client.open_session(&self.session)).map_err(err)?;
+        self.client = Some(client);
+        Ok(())
     }
-    total_days += day - 1;
 
-    let epoch_secs = total_days * 86400 + hours * 3600 + mins * 60 + secs;
+    /// Whether the writer is connected with a session open.
+    #[getter]
+    fn connected(&self) -> bool {
+        self.client.is_some()
+    }
 
-    Ok(prost_types::Timestamp {
-        seconds: epoch_secs,
-        nanos,
-    })
+    /// Extend the session's expiration time.
+    fn keepalive(&mut self) -> PyResult<()> {
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.keepalive()).map_err(err)
+    }
+
+    /// Write samples to one tag, configuring it first if needed.
+    ///
+    /// Args:
+    ///     dataset: The dataset the tag is in
+    ///     tag: The tag name within the dataset
+    ///     samples: A list of (time, value) or (time, value, quality) tuples
+    fn store_data(&mut self, py: Python<'_>, dataset: &str, tag: &str, samples: Vec<SampleArg>) -> PyResult<()> {
+        let tvqs = samples_to_tvqs(py, samples)?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.store_tvqs(&format!("{dataset}.{tag}"), &tvqs)).map_err(err)
+    }
+
+    /// Write samples to several tags of a dataset, configuring them all in
+    /// one call first.
+    ///
+    /// Args:
+    ///     dataset: The dataset the tags are in
+    ///     data: A dict of tag name to a list of samples, as for store_data
+    fn store_batch(&mut self, py: Python<'_>, dataset: &str, data: HashMap<String, Vec<SampleArg>>) -> PyResult<()> {
+        let mut batch = Vec::with_capacity(data.len());
+        for (tag, samples) in data {
+            batch.push((format!("{dataset}.{tag}"), samples_to_tvqs(py, samples)?));
+        }
+        let paths: Vec<&str> = batch.iter().map(|(path, _)| path.as_str()).collect();
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(async {
+            c.configure_tags(&paths).await?;
+            for (path, tvqs) in &batch {
+                c.store_tvqs(path, tvqs).await?;
+            }
+            Ok::<_, Error>(())
+        })
+        .map_err(err)
+    }
+
+    /// Write arrays of times and values to one tag, configuring it first if
+    /// needed.
+    ///
+    /// Args:
+    ///     dataset: The dataset the tag is in
+    ///     tag: The tag name within the dataset
+    ///     timestamps: A numpy datetime64 array, int64 nanoseconds since the
+    ///         Unix epoch, or a pandas DatetimeIndex or datetime Series
+    ///     values: An array or Series of the same length; NaN and None
+    ///         samples are skipped
+    ///     qualities: A quality for every sample, or an array of codes
+    ///         (default: Good)
+    #[cfg(feature = "numpy")]
+    #[pyo3(signature = (dataset, tag, timestamps, values, qualities=None))]
+    fn store_arrays(
+        &mut self,
+        dataset: &str,
+        tag: &str,
+        timestamps: Bound<'_, PyAny>,
+        values: Bound<'_, PyAny>,
+        qualities: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<()> {
+        let nanos = array_nanos(&timestamps)?;
+        let qualities = array_qualities(qualities.as_ref(), nanos.len())?;
+        let tvqs = arrays_to_tvqs(&nanos, array_values(&values)?, qualities)?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.store_tvqs(&format!("{dataset}.{tag}"), &tvqs)).map_err(err)
+    }
+
+    /// Write a pandas DataFrame with a column per tag, configuring the tags
+    /// all in one call first.
+    ///
+    /// Args:
+    ///     dataset: The dataset the tags are in
+    ///     frame: The DataFrame. Column names are tag names; NaN and None
+    ///         cells are skipped, so a wide frame of tags sampled at
+    ///         different times writes only each tag's own samples
+    ///     time_column: The column holding the times (default: the index)
+    ///     quality: The quality of every sample (default: Good)
+    #[cfg(feature = "numpy")]
+    #[pyo3(signature = (dataset, frame, time_column=None, quality=None))]
+    fn store_frame(
+        &mut self,
+        dataset: &str,
+        frame: Bound<'_, PyAny>,
+        time_column: Option<&str>,
+        quality: Option<QualityArg>,
+    ) -> PyResult<()> {
+        let nanos = match time_column {
+            Some(column) => array_nanos(&frame.get_item(column)?)?,
+            None => array_nanos(&frame.getattr("index")?)?,
+        };
+        let quality = quality.map_or(Ok(Quality::GOOD), |q| q.quality())?;
+        let mut batch = Vec::new();
+        for column in frame.getattr("columns")?.try_iter()? {
+            let column = column?;
+            let name = column.str()?.to_string();
+            if time_column == Some(name.as_str()) {
+                continue;
+            }
+            let values = array_values(&frame.get_item(&column)?)?;
+            let tvqs = arrays_to_tvqs(&nanos, values, vec![quality; nanos.len()])?;
+            batch.push((format!("{dataset}.{name}"), tvqs));
+        }
+        let paths: Vec<&str> = batch.iter().map(|(path, _)| path.as_str()).collect();
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(async {
+            c.configure_tags(&paths).await?;
+            for (path, tvqs) in &batch {
+                c.store_tvqs(path, tvqs).await?;
+            }
+            Ok::<_, Error>(())
+        })
+        .map_err(err)
+    }
+
+    /// Write properties of a tag, such as {"Units": "gpm", "Description": "..."}.
+    fn set_tag_properties(&mut self, dataset: &str, tag: &str, properties: HashMap<String, Bound<'_, PyAny>>) -> PyResult<()> {
+        let properties = properties
+            .into_iter()
+            .map(|(name, value)| Ok((name, py_to_value(&value)?)))
+            .collect::<PyResult<Vec<_>>>()?;
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt
+            .block_on(c.set_tag_properties(&format!("{dataset}.{tag}"), properties))
+            .map_err(err)
+    }
+
+    /// Create a dataset in the historian if it doesn't exist yet.
+    fn create_dataset(&mut self, dataset: &str) -> PyResult<()> {
+        let c = self.client.as_mut().ok_or_else(not_connected)?;
+        self.rt.block_on(c.create_dataset(dataset)).map_err(err)
+    }
+
+    /// Close the session and disconnect. connect() opens a new session.
+    fn close(&mut self) -> PyResult<()> {
+        if let Some(mut client) = self.client.take() {
+            self.rt.block_on(client.close_session()).map_err(err)?;
+        }
+        Ok(())
+    }
+
+    fn __enter__(slf: Py<Self>, py: Python<'_>) -> PyResult<Py<Self>> {
+        slf.borrow_mut(py).connect()?;
+        Ok(slf)
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<PyObject>,
+        _exc_val: Option<PyObject>,
+        _exc_tb: Option<PyObject>,
+    ) -> PyResult<()> {
+        self.close()
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.client {
+            Some(_) => format!("CanaryWriter(session={:?})", self.session),
+            None => "CanaryWriter(not connected)".to_string(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ISO 8601 timestamp parsing
+// ---------------------------------------------------------------------------
+
+#[doc(hidden)]
+pub fn parse_iso_timestamp(s: &str) -> Result<prost_types::Timestamp, String> {
+    timestamps::parse_rfc3339(s)
+        .map(prost_types::Timestamp::from)
+        .map_err(|e| e.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Module-level helpers
+// ---------------------------------------------------------------------------
+
+/// Format a numeric quality code as a readable string (e.g. 192 -> "Good").
+#[pyfunction]
+fn format_quality(code: u32) -> String {
+    Quality(code).to_string()
+}
+
+/// Parse a quality string ("good", "0xC0", "Bad - Not Connected") to its numeric code.
+#[pyfunction]
+fn parse_quality(s: &str) -> PyResult<u32> {
+    Ok(s.parse::<Quality>().map_err(err)?.0)
+}
+
+/// Set how returned timestamps are rendered, e.g. "ms,+02:00" or "epoch,ms".
+/// Options are comma-separated: "rfc3339" or "epoch"; "auto", "s", "ms",
+/// "us", or "ns"; and "utc" or an offset. Epoch times are returned as strings
+/// too. The default is "rfc3339,auto,utc".
+#[pyfunction]
+fn set_timestamp_format(format: &str) -> PyResult<()> {
+    let format: TimestampFormat = format.parse().map_err(err)?;
+    *TIMESTAMP_FORMAT.write().unwrap_or_else(PoisonError::into_inner) = Some(format);
+    Ok(())
+}
+
+/// The current timestamp format, as accepted by `set_timestamp_format`.
+#[pyfunction]
+fn get_timestamp_format() -> String {
+    let format = *TIMESTAMP_FORMAT.read().unwrap_or_else(PoisonError::into_inner);
+    format.unwrap_or_default().to_string()
 }
 
 // ---------------------------------------------------------------------------
@@ -727,5 +1877,12 @@ fn parse_iso_timestamp(s: &str) -> Result<prost_types::Timestamp, String> {
 #[pymodule]
 pub fn crowsong(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<CanaryView>()?;
+    #[cfg(feature = "store-and-forward")]
+    m.add_class::<CanaryWriter>()?;
+    m.add("NotConnectedError", m.py().get_type::<NotConnectedError>())?;
+    m.add_function(wrap_pyfunction!(format_quality, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_quality, m)?)?;
+    m.add_function(wrap_pyfunction!(set_timestamp_format, m)?)?;
+    m.add_function(wrap_pyfunction!(get_timestamp_format, m)?)?;
     Ok(())
 }
@@ -0,0 +1,290 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "views")]
+use crate::canary::views::grpc::api::get_tag_current_value_request;
+
+/// An OPC-style quality code as reported on Canary TVQs.
+///
+/// The upper two bits of the low byte carry the major status (good, uncertain,
+/// bad), the next four the sub-status, and the lowest two the limit bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Quality(pub u32);
+
+const STATUS_MASK: u32 = 0xC0;
+const SUBSTATUS_MASK: u32 = 0xFC;
+const LIMIT_MASK: u32 = 0x03;
+
+/// Known sub-status codes and their readable names, as Canary prints them.
+const NAMES: &[(u32, &str)] = &[
+    (0x00, "Bad"),
+    (0x04, "Bad - Config Error"),
+    (0x08, "Bad - Not Connected"),
+    (0x0C, "Bad - Device Failure"),
+    (0x10, "Bad - Sensor Failure"),
+    (0x14, "Bad - Last Known Value"),
+    (0x18, "Bad - Comm Failure"),
+    (0x1C, "Bad - Out of Service"),
+    (0x20, "Bad - Waiting for Initial Data"),
+    (0x40, "Uncertain"),
+    (0x44, "Uncertain - Last Usable Value"),
+    (0x50, "Uncertain - Sensor Not Accurate"),
+    (0x54, "Uncertain - EU Units Exceeded"),
+    (0x58, "Uncertain - Sub-Normal"),
+    (0xC0, "Good"),
+    (0xD8, "Good - Local Override"),
+];
+
+const LIMITS: [&str; 4] = ["", "Low Limited", "High Limited", "Constant"];
+
+impl Quality {
+    pub const GOOD: Quality = Quality(0xC0);
+    pub const UNCERTAIN: Quality = Quality(0x40);
+    pub const BAD: Quality = Quality(0x00);
+
+    /// Whether the major status is good.
+    pub fn is_good(&self) -> bool {
+        self.0 & STATUS_MASK == 0xC0
+    }
+
+    /// Whether the major status is uncertain.
+    pub fn is_uncertain(&self) -> bool {
+        self.0 & STATUS_MASK == 0x40
+    }
+
+    /// Whether the major status is bad.
+    pub fn is_bad(&self) -> bool {
+        !self.is_good() && !self.is_uncertain()
+    }
+
+    /// The quality with the limit bits cleared.
+    pub fn substatus(&self) -> Quality {
+        Quality(self.0 & SUBSTATUS_MASK)
+    }
+
+    /// The readable name of the sub-status, if it is a known code.
+    pub fn name(&self) -> Option<&'static str> {
+        if self.0 > 0xFF {
+            return None;
+        }
+        NAMES
+            .iter()
+            .find(|(code, _)| *code == self.0 & SUBSTATUS_MASK)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl From<u32> for Quality {
+    fn from(code: u32) -> Self {
+        Quality(code)
+    }
+}
+
+impl From<i32> for Quality {
+    fn from(code: i32) -> Self {
+        Quality(code as u32)
+    }
+}
+
+impl From<Quality> for u32 {
+    fn from(q: Quality) -> Self {
+        q.0
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => {
+                f.write_str(name)?;
+                let limit = LIMITS[(self.0 & LIMIT_MASK) as usize];
+                if !limit.is_empty() {
+                    write!(f, " ({limit})")?;
+                }
+                Ok(())
+            }
+            None => write!(f, "0x{:02X}", self.0),
+        }
+    }
+}
+
+/// Error returned when a quality or quality filter string cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseQualityError(String);
+
+impl fmt::Display for ParseQualityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid quality: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseQualityError {}
+
+/// Normalize a quality name for comparison: case, spacing, and separators are ignored.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl FromStr for Quality {
+    type Err = ParseQualityError;
+
+    /// Parse "0xC0", "192", or a readable name such as "Bad - Not Connected" or
+    /// "Good (Low Limited)".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            return u32::from_str_radix(hex, 16)
+                .map(Quality)
+                .map_err(|_| ParseQualityError(s.to_string()));
+        }
+        if let Ok(code) = s.parse::<u32>() {
+            return Ok(Quality(code));
+        }
+
+        let (name, limit) = match s.find('(') {
+            Some(pos) => (&s[..pos], s[pos + 1..].trim_end_matches(')')),
+            None => (s, ""),
+        };
+        let name = normalize(name);
+        let code = NAMES
+            .iter()
+            .find(|(_, n)| normalize(n) == name)
+            .map(|(code, _)| *code)
+            .ok_or_else(|| ParseQualityError(s.to_string()))?;
+        let limit = normalize(limit);
+        let limit_bits = LIMITS
+            .iter()
+            .position(|l| normalize(l) == limit)
+            .ok_or_else(|| ParseQualityError(s.to_string()))?;
+        Ok(Quality(code | limit_bits as u32))
+    }
+}
+
+/// The quality filter accepted by `get_tag_current_value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QualityFilter {
+    #[default]
+    Any,
+    NonBad,
+    Good,
+}
+
+impl QualityFilter {
+    /// Whether a sample of the given quality passes the filter.
+    pub fn accepts(self, quality: Quality) -> bool {
+        match self {
+            QualityFilter::Any => true,
+            QualityFilter::NonBad => !quality.is_bad(),
+            QualityFilter::Good => quality.is_good(),
+        }
+    }
+}
+
+impl fmt::Display for QualityFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            QualityFilter::Any => "any",
+            QualityFilter::NonBad => "non_bad",
+            QualityFilter::Good => "good",
+        })
+    }
+}
+
+impl FromStr for QualityFilter {
+    type Err = ParseQualityError;
+
+    /// Parse "any", "non_bad" (also "non-bad", "NonBad"), or "good".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "any" => Ok(QualityFilter::Any),
+            "nonbad" => Ok(QualityFilter::NonBad),
+            "good" => Ok(QualityFilter::Good),
+            _ => Err(ParseQualityError(s.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "views")]
+impl From<QualityFilter> for get_tag_current_value_request::Quality {
+    fn from(filter: QualityFilter) -> Self {
+        match filter {
+            QualityFilter::Any => get_tag_current_value_request::Quality::Any,
+            QualityFilter::NonBad => get_tag_current_value_request::Quality::NonBad,
+            QualityFilter::Good => get_tag_current_value_request::Quality::Good,
+        }
+    }
+}
+
+#[cfg(feature = "views")]
+impl From<QualityFilter> for i32 {
+    fn from(filter: QualityFilter) -> Self {
+        get_tag_current_value_request::Quality::from(filter).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_known_codes_by_name() {
+        assert_eq!(Quality::GOOD.to_string(), "Good");
+        assert_eq!(Quality(0x08).to_string(), "Bad - Not Connected");
+        assert_eq!(
+            Quality(0x55).to_string(),
+            "Uncertain - EU Units Exceeded (Low Limited)"
+        );
+        assert_eq!(Quality(0xC3).to_string(), "Good (Constant)");
+        assert_eq!(Quality(0x9C).to_string(), "0x9C");
+        assert_eq!(Quality(0x1C0).to_string(), "0x1C0");
+    }
+
+    #[test]
+    fn parses_what_it_displays() {
+        for code in (0..=0xFF).map(Quality).filter(|q| q.name().is_some()) {
+            assert_eq!(code.to_string().parse(), Ok(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn parses_numbers_and_loose_names() {
+        assert_eq!("0xC0".parse(), Ok(Quality::GOOD));
+        assert_eq!("0X40".parse(), Ok(Quality::UNCERTAIN));
+        assert_eq!("192".parse(), Ok(Quality::GOOD));
+        assert_eq!(" bad-not connected ".parse(), Ok(Quality(0x08)));
+        assert_eq!("good (high limited)".parse(), Ok(Quality(0xC2)));
+        assert!("0xZZ".parse::<Quality>().is_err());
+        assert!("Excellent".parse::<Quality>().is_err());
+        assert!("Good (Sideways)".parse::<Quality>().is_err());
+    }
+
+    #[test]
+    fn classifies_major_status() {
+        assert!(Quality(0xD8).is_good());
+        assert!(Quality(0x44).is_uncertain());
+        assert!(Quality(0x80).is_bad());
+        assert_eq!(Quality(0xC3).substatus(), Quality::GOOD);
+    }
+
+    #[test]
+    fn filters_round_trip_and_accept() {
+        for filter in [
+            QualityFilter::Any,
+            QualityFilter::NonBad,
+            QualityFilter::Good,
+        ] {
+            assert_eq!(filter.to_string().parse(), Ok(filter));
+        }
+        assert_eq!("Non-Bad".parse(), Ok(QualityFilter::NonBad));
+        assert_eq!("NonBad".parse(), Ok(QualityFilter::NonBad));
+        assert!("some".parse::<QualityFilter>().is_err());
+
+        assert!(QualityFilter::Any.accepts(Quality::BAD));
+        assert!(QualityFilter::NonBad.accepts(Quality::UNCERTAIN));
+        assert!(!QualityFilter::NonBad.accepts(Quality::BAD));
+        assert!(!QualityFilter::Good.accepts(Quality::UNCERTAIN));
+    }
+}
@@ -0,0 +1,316 @@
+//! Per-tag quality breakdowns over a time range.
+//!
+//! A [`QualityScan`] produces a [`QualitySummary`] for each tag: the share of
+//! good, uncertain, and bad samples, the number of samples per sub-status,
+//! and the longest unbroken stretch of bad quality.
+//!
+//! By default the scan reads raw samples, so shares are by sample count.
+//! [`QualityScan::aggregate_buckets`] instead asks the server for
+//! `PercentGood`, `PercentBad`, and `Count` aggregates, which transfers far
+//! less data; shares are then the servers' per-bucket percentages averaged
+//! over the buckets with data, sub-status counts are unavailable, and the
+//! longest bad stretch is the longest run of entirely bad buckets.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use crate::bulk::BulkFetch;
+use crate::canary::views::grpc::api::{AggregateTagRequest, GetAggregateDataRequest};
+use crate::error::Error;
+use crate::quality::Quality;
+use crate::types::{Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// An unbroken run of bad-quality samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadStretch {
+    /// The first bad sample.
+    pub start: SystemTime,
+    /// The next sample that was not bad, or the end of the range.
+    pub end: SystemTime,
+    /// The number of bad samples in the run, or 0 when built from aggregates.
+    pub samples: u64,
+}
+
+impl BadStretch {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+/// The quality breakdown of one tag over a time range.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QualitySummary {
+    pub tag_name: String,
+    pub samples: u64,
+    pub good_percent: f64,
+    pub uncertain_percent: f64,
+    pub bad_percent: f64,
+    /// Sample counts by quality with the limit bits cleared.
+    pub by_substatus: BTreeMap<Quality, u64>,
+    pub longest_bad: Option<BadStretch>,
+}
+
+impl QualitySummary {
+    /// Summarize a tag's samples, which must be in time order. `end` closes a
+    /// bad stretch that lasts until the end of the range.
+    pub fn from_tvqs(tag_name: impl Into<String>, tvqs: &[Tvq], end: SystemTime) -> Self {
+        let mut tally = Tally::default();
+        for tvq in tvqs {
+            tally.push(tvq.timestamp, tvq.quality);
+        }
+        tally.finish(tag_name.into(), end)
+    }
+}
+
+/// A running quality count for one tag.
+#[derive(Debug, Default)]
+struct Tally {
+    good: u64,
+    uncertain: u64,
+    bad: u64,
+    by_substatus: BTreeMap<Quality, u64>,
+    current_bad: Option<(SystemTime, u64)>,
+    longest_bad: Option<BadStretch>,
+}
+
+impl Tally {
+    fn push(&mut self, time: SystemTime, quality: Quality) {
+        *self.by_substatus.entry(quality.substatus()).or_default() += 1;
+        if quality.is_bad() {
+            self.bad += 1;
+            let (_, samples) = self.current_bad.get_or_insert((time, 0));
+            *samples += 1;
+            return;
+        }
+        if quality.is_good() {
+            self.good += 1;
+        } else {
+            self.uncertain += 1;
+        }
+        self.close_bad(time);
+    }
+
+    fn close_bad(&mut self, end: SystemTime) {
+        let Some((start, samples)) = self.current_bad.take() else {
+            return;
+        };
+        let stretch = BadStretch {
+            start,
+            end,
+            samples,
+        };
+        if self
+            .longest_bad
+            .is_none_or(|longest| stretch.duration() > longest.duration())
+        {
+            self.longest_bad = Some(stretch);
+        }
+    }
+
+    fn finish(mut self, tag_name: String, end: SystemTime) -> QualitySummary {
+        self.close_bad(end);
+        let samples = self.good + self.uncertain + self.bad;
+        let percent = |n: u64| {
+            if samples == 0 {
+                0.0
+            } else {
+                100.0 * n as f64 / samples as f64
+            }
+        };
+        QualitySummary {
+            tag_name,
+            samples,
+            good_percent: percent(self.good),
+            uncertain_percent: percent(self.uncertain),
+            bad_percent: percent(self.bad),
+            by_substatus: self.by_substatus,
+            longest_bad: self.longest_bad,
+        }
+    }
+}
+
+/// A planned quality breakdown of tags over a time range.
+#[derive(Debug, Clone)]
+pub struct QualityScan {
+    view: String,
+    tags: Vec<String>,
+    start: SystemTime,
+    end: SystemTime,
+    aggregate_bucket: Option<Duration>,
+}
+
+impl QualityScan {
+    pub fn new(
+        view: impl Into<String>,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Self {
+        Self {
+            view: view.into(),
+            tags: tags.into_iter().map(Into::into).collect(),
+            start,
+            end,
+            aggregate_bucket: None,
+        }
+    }
+
+    /// Use `PercentGood`, `PercentBad`, and `Count` aggregates over buckets
+    /// of this width instead of raw samples.
+    pub fn aggregate_buckets(mut self, bucket: Duration) -> Self {
+        self.aggregate_bucket = Some(bucket.max(Duration::from_secs(1)));
+        self
+    }
+
+    /// Run the scan and summarize each tag, in order.
+    pub async fn run(&self, client: &ViewsClient) -> Result<Vec<QualitySummary>, Error> {
+        match self.aggregate_bucket {
+            Some(bucket) => self.run_aggregates(client, bucket).await,
+            None => self.run_raw(client).await,
+        }
+    }
+
+    async fn run_raw(&self, client: &ViewsClient) -> Result<Vec<QualitySummary>, Error> {
+        let mut tallies: Vec<Tally> = self.tags.iter().map(|_| Tally::default()).collect();
+        let pages = BulkFetch::new(&self.view, &self.tags, self.start, self.end)
+            .run(client)
+            .await?;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            let Some(index) = self.tags.iter().position(|t| *t == page.tag_name) else {
+                continue;
+            };
+            for row in 0..page.len() {
+                tallies[index].push(page.timestamp(row), page.quality(row));
+            }
+        }
+        Ok(self
+            .tags
+            .iter()
+            .zip(tallies)
+            .map(|(tag, tally)| tally.finish(tag.clone(), self.end))
+            .collect())
+    }
+
+    async fn run_aggregates(
+        &self,
+        client: &ViewsClient,
+        bucket: Duration,
+    ) -> Result<Vec<QualitySummary>, Error> {
+        const AGGREGATES: [&str; 3] = ["PercentGood", "PercentBad", "Count"];
+
+        let requests = self
+            .tags
+            .iter()
+            .flat_map(|tag| {
+                AGGREGATES
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, aggregate)| AggregateTagRequest {
+                        tag_name: tag.clone(),
+                        aggregate_name: aggregate.to_string(),
+                        aggregate_configuration: None,
+                        sloped: false,
+                        client_data: i as i32,
+                    })
+            })
+            .collect();
+        let resp = client
+            .get_aggregate_data(GetAggregateDataRequest {
+                view: self.view.clone(),
+                requests,
+                start_time: Some(self.start.into()),
+                end_time: Some(self.end.into()),
+                interval: Some(bucket.try_into().map_err(Error::other)?),
+                return_annotations: false,
+                cci: 0,
+            })
+            .await?;
+
+        let mut summaries = Vec::with_capacity(self.tags.len());
+        for tag in &self.tags {
+            let mut series = Vec::with_capacity(AGGREGATES.len());
+            for (i, aggregate) in AGGREGATES.iter().enumerate() {
+                let data = resp
+                    .aggregated_data
+                    .iter()
+                    .find(|d| d.tag_name == *tag && d.client_data == i as i32)
+                    .ok_or_else(|| format!("no {aggregate} returned for {tag}"))?;
+                if data.error_code != 0 {
+                    return Err(format!(
+                        "{aggregate} of {tag} failed ({}): {}",
+                        data.error_code, data.error_message
+                    )
+                    .into());
+                }
+                series.push(data.tvqs.iter().map(Tvq::from).collect::<Vec<_>>());
+            }
+            summaries.push(self.summarize_buckets(tag, bucket, &series[0], &series[1], &series[2]));
+        }
+        Ok(summaries)
+    }
+
+    fn summarize_buckets(
+        &self,
+        tag: &str,
+        bucket: Duration,
+        good: &[Tvq],
+        bad: &[Tvq],
+        counts: &[Tvq],
+    ) -> QualitySummary {
+        let number = |tvq: Option<&Tvq>| match tvq.map(|t| &t.value) {
+            None | Some(Value::Null) => None,
+            Some(value) => value.as_f64(),
+        };
+
+        let mut summary = QualitySummary {
+            tag_name: tag.to_string(),
+            ..QualitySummary::default()
+        };
+        let mut buckets = 0;
+        let mut run: Option<BadStretch> = None;
+        for (i, count) in counts.iter().enumerate() {
+            summary.samples += number(Some(count)).unwrap_or(0.0) as u64;
+            let (Some(good), Some(bad)) = (number(good.get(i)), number(bad.get(i))) else {
+                continue;
+            };
+            buckets += 1;
+            summary.good_percent += good;
+            summary.bad_percent += bad;
+
+            let end = (count.timestamp + bucket).min(self.end);
+            if bad >= 100.0 {
+                let stretch = run.get_or_insert(BadStretch {
+                    start: count.timestamp,
+                    end,
+                    samples: 0,
+                });
+                stretch.end = end;
+            } else {
+                run = None;
+            }
+            if let Some(stretch) = run
+                && summary
+                    .longest_bad
+                    .is_none_or(|longest| stretch.duration() > longest.duration())
+            {
+                summary.longest_bad = Some(stretch);
+            }
+        }
+        if buckets > 0 {
+            summary.good_percent /= buckets as f64;
+            summary.bad_percent /= buckets as f64;
+            summary.uncertain_percent =
+                (100.0 - summary.good_percent - summary.bad_percent).max(0.0);
+        }
+        summary
+    }
+}
@@ -0,0 +1,350 @@
+//! A fluent builder for common history queries.
+//!
+//! [`ViewsClient::query`] starts a [`Query`] that reads raw or aggregated
+//! history for a list of tags in one chain:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use std::time::Duration;
+//!
+//! let series = client
+//!     .query()
+//!     .view("Plant")
+//!     .tags(["Plant.Line1.Temperature", "Plant.Line1.Pressure"])
+//!     .last(Duration::from_secs(8 * 3600))
+//!     .aggregate("TimeAverage", Duration::from_secs(60))
+//!     .fetch()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Tags are requested in chunks, raw history follows each tag's continuation
+//! points until its range is exhausted, and chunks are fetched concurrently
+//! on handles to the client's connection.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use tokio::task::JoinSet;
+
+use crate::bulk::BulkFetch;
+#[cfg(feature = "calendar")]
+use crate::calendar::CalendarInterval;
+use crate::canary::views::grpc::api::{AggregateTagRequest, GetAggregateDataRequest};
+use crate::error::Error;
+use crate::frame::TimeSeriesFrame;
+use crate::history::check_aggregate_status;
+use crate::request_id;
+use crate::types::TagSeries;
+use crate::views_client::ViewsClient;
+
+/// A history query under construction. Start one with
+/// [`ViewsClient::query`].
+pub struct Query<'a> {
+    client: &'a ViewsClient,
+    view: Option<String>,
+    tags: Vec<String>,
+    start: Option<SystemTime>,
+    end: Option<SystemTime>,
+    aggregate: Option<(String, Buckets)>,
+    page_size: i32,
+    tags_per_request: usize,
+    concurrency: usize,
+    eng_units: bool,
+}
+
+impl ViewsClient {
+    /// Start building a history query.
+    pub fn query(&self) -> Query<'_> {
+        Query {
+            view: self.default_view().map(str::to_string),
+            client: self,
+            tags: Vec::new(),
+            start: None,
+            end: None,
+            aggregate: None,
+            page_size: 10_000,
+            tags_per_request: 100,
+            concurrency: 4,
+            eng_units: true,
+        }
+    }
+}
+
+impl Query<'_> {
+    /// The view to read from. Defaults to the client's
+    /// [default view](ViewsClient::set_default_view); required without one.
+    pub fn view(mut self, view: impl Into<String>) -> Self {
+        self.view = Some(view.into());
+        self
+    }
+
+    /// Add a tag.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Add tags.
+    pub fn tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Read `start..end`.
+    pub fn range(mut self, start: SystemTime, end: SystemTime) -> Self {
+        self.start = Some(start);
+        self.end = Some(end);
+        self
+    }
+
+    /// Read from `start`, up to now unless [`until`](Self::until) says
+    /// otherwise.
+    pub fn since(mut self, start: SystemTime) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Read up to `end`.
+    pub fn until(mut self, end: SystemTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Read the `duration` up to now.
+    pub fn last(self, duration: Duration) -> Self {
+        let now = SystemTime::now();
+        self.range(now - duration, now)
+    }
+
+    /// Read `aggregate` (e.g. `"TimeAverage"`) over each `interval` instead
+    /// of raw samples.
+    pub fn aggregate(mut self, aggregate: impl Into<String>, interval: Duration) -> Self {
+        self.aggregate = Some((aggregate.into(), Buckets::Fixed(interval)));
+        self
+    }
+
+    /// Read `aggregate` over calendar days, weeks, or months in a time zone,
+    /// so buckets stay on local boundaries across DST changes.
+    #[cfg(feature = "calendar")]
+    pub fn aggregate_calendar(
+        mut self,
+        aggregate: impl Into<String>,
+        interval: CalendarInterval,
+    ) -> Self {
+        self.aggregate = Some((aggregate.into(), Buckets::Calendar(interval)));
+        self
+    }
+
+    /// The maximum number of raw samples per tag in one page. Defaults to
+    /// 10,000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// How many tags to request per call. Defaults to 100.
+    pub fn tags_per_request(mut self, tags_per_request: usize) -> Self {
+        self.tags_per_request = tags_per_request.max(1);
+        self
+    }
+
+    /// How many calls to have in flight at once. Defaults to 4.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Fill in each series' engineering units from the tags' properties.
+    /// The default.
+    pub fn with_eng_units(mut self) -> Self {
+        self.eng_units = true;
+        self
+    }
+
+    /// Leave each series' engineering units unset, saving the tag info
+    /// call that looks them up.
+    pub fn without_eng_units(mut self) -> Self {
+        self.eng_units = false;
+        self
+    }
+
+    /// Run the query, returning one series per tag in the order the tags
+    /// were added.
+    pub async fn fetch(self) -> Result<Vec<TagSeries>, Error> {
+        request_id::operation(self.run()).await
+    }
+
+    /// Run the query and pivot the results onto a shared timestamp index.
+    pub async fn fetch_frame(self) -> Result<TimeSeriesFrame, Error> {
+        Ok(TimeSeriesFrame::from_series(&self.fetch().await?))
+    }
+
+    async fn run(self) -> Result<Vec<TagSeries>, Error> {
+        let view = self.view.clone().ok_or("query has no view")?;
+        if self.tags.is_empty() {
+            return Err("query has no tags".into());
+        }
+        let start = self.start.ok_or("query has no start time")?;
+        let end = self.end.unwrap_or_else(SystemTime::now);
+
+        let chunks: Vec<Vec<String>> = self
+            .tags
+            .chunks(self.tags_per_request)
+            .map(<[String]>::to_vec)
+            .collect();
+        let results: Vec<TagSeries> = match &self.aggregate {
+            None => {
+                let fetch = BulkFetch::new(&view, Vec::<String>::new(), start, end)
+                    .page_size(self.page_size);
+                let calls = chunks.into_iter().map(|chunk| {
+                    let fetch = fetch.clone();
+                    let client = self.client.handle();
+                    async move { fetch.fetch_chunk(&client, &chunk).await }
+                });
+                let mut series = Vec::new();
+                for page in bounded(calls, self.concurrency)
+                    .await?
+                    .into_iter()
+                    .flatten()
+                {
+                    if page.error_code != 0 {
+                        return Err(format!(
+                            "reading {} failed ({}): {}",
+                            page.tag_name, page.error_code, page.error_message
+                        )
+                        .into());
+                    }
+                    series.push(TagSeries::from(&page));
+                }
+                series
+            }
+            Some((aggregate, buckets)) => {
+                let runs = buckets.runs(start, end);
+                let requests = chunks
+                    .iter()
+                    .flat_map(|chunk| runs.iter().map(move |run| (chunk, run)));
+                let calls = requests.map(|(chunk, &(start, end, interval))| {
+                    let request = GetAggregateDataRequest {
+                        view: view.clone(),
+                        requests: chunk
+                            .iter()
+                            .map(|tag_name| AggregateTagRequest {
+                                tag_name: tag_name.clone(),
+                                aggregate_name: aggregate.clone(),
+                                aggregate_configuration: None,
+                                sloped: false,
+                                client_data: 0,
+                            })
+                            .collect(),
+                        start_time: Some(start.into()),
+                        end_time: Some(end.into()),
+                        interval: prost_types::Duration::try_from(interval).ok(),
+                        return_annotations: false,
+                        cci: 0,
+                    };
+                    let view = view.clone();
+                    let client = self.client.handle();
+                    async move {
+                        check_aggregate_status(client.get_aggregate_data(request).await?, view)
+                    }
+                });
+                let mut series = Vec::new();
+                for resp in bounded(calls, self.concurrency).await? {
+                    for data in &resp.aggregated_data {
+                        if data.error_code != 0 {
+                            return Err(format!(
+                                "aggregating {} failed ({}): {}",
+                                data.tag_name, data.error_code, data.error_message
+                            )
+                            .into());
+                        }
+                        series.push(TagSeries::from(data));
+                    }
+                }
+                series
+            }
+        };
+
+        // Merge a tag's pages and put the series in request order.
+        let mut ordered: Vec<TagSeries> = self
+            .tags
+            .iter()
+            .map(|tag| TagSeries {
+                tag_name: tag.clone(),
+                ..Default::default()
+            })
+            .collect();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for (i, tag) in self.tags.iter().enumerate() {
+            index.entry(tag.clone()).or_insert(i);
+        }
+        for series in results {
+            match index.get(&series.tag_name) {
+                Some(&i) => ordered[i].tvqs.extend(series.tvqs),
+                None => {
+                    index.insert(series.tag_name.clone(), ordered.len());
+                    ordered.push(series);
+                }
+            }
+        }
+        if self.eng_units {
+            let units = self.client.get_eng_units(&view, self.tags.clone()).await?;
+            for series in &mut ordered {
+                series.eng_units = units.get(&series.tag_name).cloned();
+            }
+        }
+        Ok(ordered)
+    }
+}
+
+/// How an aggregate query is bucketed.
+enum Buckets {
+    Fixed(Duration),
+    #[cfg(feature = "calendar")]
+    Calendar(CalendarInterval),
+}
+
+impl Buckets {
+    /// The requests covering `start..end`, as `(start, end, interval)`.
+    fn runs(&self, start: SystemTime, end: SystemTime) -> Vec<(SystemTime, SystemTime, Duration)> {
+        match self {
+            Self::Fixed(interval) => vec![(start, end, *interval)],
+            #[cfg(feature = "calendar")]
+            Self::Calendar(calendar) => calendar.runs(start, end),
+        }
+    }
+}
+
+/// Run `calls` with at most `limit` in flight, returning their results in
+/// call order.
+async fn bounded<T, Fut>(
+    calls: impl IntoIterator<Item = Fut>,
+    limit: usize,
+) -> Result<Vec<T>, Error>
+where
+    Fut: Future<Output = Result<T, Error>> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut calls = calls.into_iter().enumerate();
+    let mut tasks = JoinSet::new();
+    let mut results = Vec::new();
+    loop {
+        while tasks.len() < limit {
+            let Some((i, call)) = calls.next() else {
+                break;
+            };
+            tasks.spawn(request_id::inherit(async move { (i, call.await) }));
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        // Tasks are never aborted, so a join error is always a panic.
+        let (i, result) = joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+        results.push((i, result?));
+    }
+    results.sort_by_key(|(i, _)| *i);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
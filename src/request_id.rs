@@ -0,0 +1,141 @@
+//! Request IDs for correlating client logs with Canary server logs.
+//!
+//! Every call to the Views service carries a UUID in its `x-request-id`
+//! metadata. Calls that make up one logical operation share an ID: the pages
+//! of a bulk read, the chunks of a query, and the attempts of a hedged read
+//! all go out under the ID of the operation that started them. Errors from
+//! the client's calls name the ID, so a failure in a client log can be found
+//! in the server's logs during a support case.
+//!
+//! Wrap work of your own in [`scope`] to send all of its calls under one ID:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use crowsong::request_id::{self, RequestId};
+//!
+//! let id = RequestId::new();
+//! eprintln!("nightly check {id}");
+//! request_id::scope(id, async {
+//!     let views = client.get_views().await?;
+//!     for view in views.views {
+//!         client.get_dataset_list(view, false).await?;
+//!     }
+//!     Ok::<_, crowsong::Error>(())
+//! })
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use tonic::Status;
+use tonic::metadata::AsciiMetadataValue;
+use uuid::Uuid;
+
+/// The metadata key request IDs are sent under.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// The ID of one logical operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(Uuid);
+
+impl RequestId {
+    /// A new random ID.
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    /// The ID of the operation the current task is running, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(|id| *id).ok()
+    }
+
+    fn to_metadata(self) -> AsciiMetadataValue {
+        // A hyphenated UUID is always valid metadata.
+        AsciiMetadataValue::try_from(self.to_string()).expect("request ID is ASCII")
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+impl FromStr for RequestId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.parse()?))
+    }
+}
+
+/// Run `operation`, sending every call it makes under `id`. Scopes nest; the
+/// innermost wins. Tasks spawned by `operation` don't inherit the ID.
+pub async fn scope<F: Future>(id: RequestId, operation: F) -> F::Output {
+    CURRENT.scope(id, operation).await
+}
+
+/// Run `operation` under the current operation's ID, or a new one if there
+/// is none.
+pub(crate) async fn operation<F: Future>(operation: F) -> F::Output {
+    match RequestId::current() {
+        Some(_) => operation.await,
+        None => scope(RequestId::new(), operation).await,
+    }
+}
+
+/// `future` under the current operation's ID, for spawning as part of it.
+pub(crate) fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let id = RequestId::current();
+    async move {
+        match id {
+            Some(id) => scope(id, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Make one call under the current operation's ID, or its own, naming the
+/// ID in any error.
+pub(crate) async fn call<T>(call: impl Future<Output = Result<T, Status>>) -> Result<T, Status> {
+    let id = RequestId::current().unwrap_or_default();
+    scope(id, call)
+        .await
+        .map_err(|status| with_request_id(status, id))
+}
+
+/// The ID to send with a call made now: the current operation's, or a new
+/// one.
+pub(crate) fn for_call() -> AsciiMetadataValue {
+    RequestId::current().unwrap_or_default().to_metadata()
+}
+
+/// `status` with `id` appended to its message and added to its metadata.
+/// The original status becomes the source.
+fn with_request_id(status: Status, id: RequestId) -> Status {
+    let mut metadata = status.metadata().clone();
+    metadata.insert(REQUEST_ID_HEADER, id.to_metadata());
+    let mut tagged = Status::with_details_and_metadata(
+        status.code(),
+        format!("{} (request id {id})", status.message()),
+        status.details().to_vec().into(),
+        metadata,
+    );
+    tagged.set_source(Arc::new(status));
+    tagged
+}
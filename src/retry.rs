@@ -0,0 +1,141 @@
+//! Retrying calls that fail for transient reasons.
+//!
+//! A [`RetryPolicy`], given to [`ViewsClientBuilder::retry_policy`] or
+//! [`ViewsClient::set_retry_policy`], resends calls that fail with
+//! `UNAVAILABLE`, `DEADLINE_EXCEEDED`, `RESOURCE_EXHAUSTED`, `ABORTED`, or a
+//! transport error, waiting between attempts with exponential backoff and
+//! jitter:
+//!
+//! ```
+//! use std::time::Duration;
+//! use crowsong::RetryPolicy;
+//!
+//! let policy = RetryPolicy::new()
+//!     .max_attempts(5)
+//!     .backoff(Duration::from_millis(250), Duration::from_secs(10))
+//!     .max_elapsed(Duration::from_secs(30));
+//! ```
+//!
+//! Other failures, such as a missing tag or a rejected API key, return at
+//! once. A rejected client connection ID is handled separately; see
+//! [`ViewsClientBuilder::reacquire_cci`]. Reads are safe to resend. Storing
+//! an annotation is not, since each call adds a note, so
+//! [`ViewsClient::annotate`] is never resent.
+//!
+//! [`ViewsClientBuilder::retry_policy`]: crate::views_client::ViewsClientBuilder::retry_policy
+//! [`ViewsClientBuilder::reacquire_cci`]: crate::views_client::ViewsClientBuilder::reacquire_cci
+//! [`ViewsClient::set_retry_policy`]: crate::ViewsClient::set_retry_policy
+//! [`ViewsClient::annotate`]: crate::ViewsClient::annotate
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+use tonic::Status;
+
+use crate::error::Classify;
+
+/// How many times, and how patiently, to resend a call that failed for a
+/// transient reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_elapsed: Option<Duration>,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            max_elapsed: None,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Three attempts in all, waiting 200 ms and then doubling up to 5
+    /// seconds between them, with jitter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most attempts, counting the first. At least 1.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `initial` before the second attempt, doubling up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Don't start an attempt that would begin more than `elapsed` after the
+    /// first.
+    pub fn max_elapsed(mut self, elapsed: Duration) -> Self {
+        self.max_elapsed = Some(elapsed);
+        self
+    }
+
+    /// Wait a random time between half the backoff and all of it, so clients
+    /// that failed together don't retry together. On by default.
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Run `attempt` until it succeeds, fails for a reason retrying won't
+    /// fix, or the policy gives up, returning the last result.
+    pub(crate) async fn run<T, Fut>(&self, mut attempt: impl FnMut() -> Fut) -> Result<T, Status>
+    where
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        let started = Instant::now();
+        let mut backoff = self.initial_backoff;
+        let mut attempts = 1;
+        loop {
+            let status = match attempt().await {
+                Err(status) if is_transient(&status) => status,
+                result => return result,
+            };
+            let delay = self.delay(backoff);
+            if attempts >= self.max_attempts
+                || self
+                    .max_elapsed
+                    .is_some_and(|max| started.elapsed() + delay > max)
+            {
+                return Err(status);
+            }
+            tokio::time::sleep(delay).await;
+            backoff = backoff.saturating_mul(2).min(self.max_backoff);
+            attempts += 1;
+        }
+    }
+
+    fn delay(&self, backoff: Duration) -> Duration {
+        if !self.jitter {
+            return backoff;
+        }
+        backoff.mul_f64(0.5 + 0.5 * random_fraction())
+    }
+}
+
+/// Whether resending the same call could succeed. A rejected client
+/// connection ID would only be rejected again.
+fn is_transient(status: &Status) -> bool {
+    status.is_retryable() && !status.is_invalid_cci()
+}
+
+/// A random number in `0.0..=1.0`, from the randomly keyed std hasher.
+fn random_fraction() -> f64 {
+    RandomState::new().hash_one(Instant::now()) as f64 / u64::MAX as f64
+}
@@ -0,0 +1,353 @@
+//! Writing tag data through the Store & Forward service.
+//!
+//! [`StoreAndForwardClient`] opens a session with the service, configures
+//! tags the first time they are written, and sends samples as data
+//! elements. Tags are addressed by path, `Dataset.Tag`.
+//!
+//! Besides samples, a session can create datasets
+//! ([`create_dataset`](StoreAndForwardClient::create_dataset)), write tag
+//! properties such as units and descriptions
+//! ([`set_tag_properties`](StoreAndForwardClient::set_tag_properties)), and
+//! change its own settings
+//! ([`configure_setting`](StoreAndForwardClient::configure_setting)).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::SystemTime;
+
+use tonic::Status;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::canary::store_and_forward2::grpc::api::canary_store_and_forward_api_service_client::CanaryStoreAndForwardApiServiceClient;
+use crate::canary::store_and_forward2::grpc::api::*;
+use crate::error::Error;
+use crate::quality::Quality;
+#[cfg(feature = "tls")]
+use crate::tls::{TlsPolicy, tls_channel};
+use crate::transport::Redacted;
+#[cfg(not(feature = "tls"))]
+use crate::transport::plain_channel;
+use crate::types::{Tvq, Value};
+
+/// The collector type reported when opening sessions.
+const COLLECTOR_TYPE: &str = "crowsong";
+
+/// The most elements sent in one `Write` call.
+const WRITE_BATCH: usize = 10_000;
+
+/// A client for writing to a Canary Store & Forward service.
+pub struct StoreAndForwardClient {
+    inner: CanaryStoreAndForwardApiServiceClient<Channel>,
+    api_key: String,
+    session_token: Option<String>,
+    tag_ids: HashMap<String, i32>,
+}
+
+impl fmt::Debug for StoreAndForwardClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreAndForwardClient")
+            .field("api_key", &Redacted)
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| Redacted),
+            )
+            .field("tag_ids", &self.tag_ids.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StoreAndForwardClient {
+    /// Connect to a Store & Forward service. No session is opened yet.
+    pub async fn connect(
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let endpoint = Endpoint::from_shared(endpoint.into())?;
+        #[cfg(feature = "tls")]
+        let channel = tls_channel(endpoint, &TlsPolicy::default(), None)?;
+        #[cfg(not(feature = "tls"))]
+        let channel = plain_channel(endpoint)?;
+        Ok(Self::from_channel(channel, api_key))
+    }
+
+    /// Connect as [`connect`](Self::connect) does, verifying `https`
+    /// endpoints as `policy` says. See [`tls`](crate::tls).
+    #[cfg(feature = "tls")]
+    pub async fn connect_with_tls(
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+        policy: &TlsPolicy,
+    ) -> Result<Self, Error> {
+        let endpoint = Endpoint::from_shared(endpoint.into())?;
+        let channel = tls_channel(endpoint, policy, None)?;
+        Ok(Self::from_channel(channel, api_key))
+    }
+
+    fn from_channel(channel: Channel, api_key: impl Into<String>) -> Self {
+        Self {
+            inner: CanaryStoreAndForwardApiServiceClient::new(channel),
+            api_key: api_key.into(),
+            session_token: None,
+            tag_ids: HashMap::new(),
+        }
+    }
+
+    /// Test the gRPC connection.
+    pub async fn test(&mut self) -> Result<(), Error> {
+        self.inner.test(()).await?;
+        Ok(())
+    }
+
+    /// The token of the open session, if any.
+    pub fn session_token(&self) -> Option<&str> {
+        self.session_token.as_deref()
+    }
+
+    /// Open a session, closing any session that is already open.
+    pub async fn open_session(&mut self, name: impl Into<String>) -> Result<(), Error> {
+        if self.session_token.is_some() {
+            self.close_session().await?;
+        }
+        let resp = self
+            .inner
+            .open_session(OpenSessionRequest {
+                name: name.into(),
+                collector_type: COLLECTOR_TYPE.to_string(),
+                context: Some(OpenSessionContext {
+                    context: Some(open_session_context::Context::CollectorContext(
+                        OpenSessionCollectorContext {
+                            secure_access_token_context: Some(ApiAccessTokenContext {
+                                nullable_api_access_token: Some(self.api_key.clone()),
+                            }),
+                        },
+                    )),
+                }),
+                nullable_destination: None,
+                vendor_code: String::new(),
+            })
+            .await?
+            .into_inner();
+        let status = resp.status();
+        match (status, resp.result.and_then(|r| r.result)) {
+            (ResponseStatus::Good, Some(open_session_result::Result::SessionToken(token))) => {
+                self.session_token = Some(token);
+                Ok(())
+            }
+            (status, Some(open_session_result::Result::Error(error))) => {
+                Err(response_error("OpenSession", status, Some(error)).into())
+            }
+            (status, _) => Err(response_error("OpenSession", status, None).into()),
+        }
+    }
+
+    /// Extend the expiration time of the open session.
+    pub async fn keepalive(&mut self) -> Result<(), Error> {
+        let session_token = self.require_session()?;
+        let resp = self
+            .inner
+            .keep_alive(KeepAliveRequest { session_token })
+            .await?
+            .into_inner();
+        check("KeepAlive", resp.status(), resp.nullable_error)
+    }
+
+    /// Close the open session. Does nothing if no session is open.
+    pub async fn close_session(&mut self) -> Result<(), Error> {
+        let Some(session_token) = self.session_token.take() else {
+            return Ok(());
+        };
+        self.tag_ids.clear();
+        let resp = self
+            .inner
+            .close_session(CloseSessionRequest { session_token })
+            .await?
+            .into_inner();
+        check("CloseSession", resp.status(), resp.nullable_error)
+    }
+
+    /// Configure tags for the open session and return their session tag IDs.
+    /// Tags that are already configured are not sent again.
+    pub async fn configure_tags(
+        &mut self,
+        tag_paths: &[impl AsRef<str>],
+    ) -> Result<Vec<i32>, Error> {
+        let session_token = self.require_session()?;
+        let missing: Vec<ConfigureTagRequest> = tag_paths
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|path| !self.tag_ids.contains_key(*path))
+            .map(|path| ConfigureTagRequest {
+                tag_path: path.to_string(),
+                ..ConfigureTagRequest::default()
+            })
+            .collect();
+        if !missing.is_empty() {
+            let resp = self
+                .inner
+                .configure_tags(ConfigureTagsRequest {
+                    session_token,
+                    tags: missing,
+                })
+                .await?
+                .into_inner();
+            check("ConfigureTags", resp.status(), None)?;
+            for pair in resp.results {
+                let path = pair.request.map(|r| r.tag_path).unwrap_or_default();
+                match pair.result.and_then(|r| r.result) {
+                    Some(configure_tag_result::Result::TagId(id)) => {
+                        self.tag_ids.insert(path, id);
+                    }
+                    Some(configure_tag_result::Result::Error(error)) => {
+                        return Err(Status::invalid_argument(format!(
+                            "configuring {path} failed: {error}"
+                        ))
+                        .into());
+                    }
+                    None => {}
+                }
+            }
+        }
+        tag_paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                self.tag_ids.get(path).copied().ok_or_else(|| {
+                    Status::internal(format!("the server did not configure {path}")).into()
+                })
+            })
+            .collect()
+    }
+
+    /// Write samples to a tag, configuring it first if needed.
+    pub async fn store_tvqs(&mut self, tag_path: &str, tvqs: &[Tvq]) -> Result<(), Error> {
+        let tag_id = self.configure_tags(&[tag_path]).await?[0];
+        for batch in tvqs.chunks(WRITE_BATCH) {
+            let elements = batch
+                .iter()
+                .map(|tvq| StreamElement {
+                    kind: Some(stream_element::Kind::Data(DataElement {
+                        tag_id,
+                        timestamp: Some(tvq.timestamp.into()),
+                        value: Some(tvq.value.clone().into()),
+                        quality: tvq.quality.0 as i32,
+                    })),
+                })
+                .collect();
+            self.write(elements).await?;
+        }
+        Ok(())
+    }
+
+    /// Write properties of a tag, such as `"Units"` or `"Description"`,
+    /// configuring it first if needed. Each is stamped with the current time.
+    pub async fn set_tag_properties(
+        &mut self,
+        tag_path: &str,
+        properties: impl IntoIterator<Item = (impl Into<String>, Value)>,
+    ) -> Result<(), Error> {
+        let tag_id = self.configure_tags(&[tag_path]).await?[0];
+        let now = SystemTime::now();
+        let elements = properties
+            .into_iter()
+            .map(|(name, value)| StreamElement {
+                kind: Some(stream_element::Kind::Property(PropertyElement {
+                    tag_id,
+                    timestamp: Some(now.into()),
+                    value: Some(value.into()),
+                    quality: Quality::GOOD.0 as i32,
+                    name: name.into(),
+                    nullable_description: None,
+                })),
+            })
+            .collect();
+        self.write(elements).await
+    }
+
+    /// Create `dataset` in the historian if it doesn't exist yet, by turning
+    /// on dataset creation for the session and starting a file in it. Tags
+    /// configured afterwards may then be written to the new dataset.
+    pub async fn create_dataset(&mut self, dataset: impl Into<String>) -> Result<(), Error> {
+        self.configure_setting(SettingKind::IsCreateDataSetEnabled, Value::Bool(true))
+            .await?;
+        self.write(vec![StreamElement {
+            kind: Some(stream_element::Kind::FileNew(FileNewElement {
+                data_set: dataset.into(),
+                file_time: Some(SystemTime::now().into()),
+            })),
+        }])
+        .await
+    }
+
+    /// Change one setting of the open session, such as its inactivity
+    /// timeout or whether it may insert into existing data.
+    pub async fn configure_setting(
+        &mut self,
+        kind: SettingKind,
+        value: Value,
+    ) -> Result<(), Error> {
+        let session_token = self.require_session()?;
+        let resp = self
+            .inner
+            .configure_settings(ConfigureSettingsRequest {
+                session_token,
+                settings: vec![ConfigureSettingRequest {
+                    kind: kind as i32,
+                    value: Some(value.into()),
+                }],
+            })
+            .await?
+            .into_inner();
+        check("ConfigureSettings", resp.status(), None)?;
+        match resp
+            .results
+            .into_iter()
+            .find_map(|pair| pair.result?.nullable_error)
+        {
+            Some(error) => Err(Status::invalid_argument(format!(
+                "setting {} failed: {error}",
+                kind.as_str_name()
+            ))
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Write raw stream elements to the open session.
+    pub async fn write(&mut self, elements: Vec<StreamElement>) -> Result<(), Error> {
+        let session_token = self.require_session()?;
+        let resp = self
+            .inner
+            .write(WriteRequest {
+                session_token,
+                elements,
+            })
+            .await?
+            .into_inner();
+        check("Write", resp.status(), resp.nullable_error)
+    }
+
+    fn require_session(&self) -> Result<String, Error> {
+        self.session_token
+            .clone()
+            .ok_or_else(|| Status::failed_precondition("no Store & Forward session is open").into())
+    }
+}
+
+fn check(call: &str, status: ResponseStatus, error: Option<String>) -> Result<(), Error> {
+    match status {
+        ResponseStatus::Good => Ok(()),
+        status => Err(response_error(call, status, error).into()),
+    }
+}
+
+fn response_error(call: &str, status: ResponseStatus, error: Option<String>) -> Status {
+    let message = match error {
+        Some(error) => format!("{call} failed ({}): {error}", status.as_str_name()),
+        None => format!("{call} failed ({})", status.as_str_name()),
+    };
+    match status {
+        ResponseStatus::BadAccessDenied => Status::permission_denied(message),
+        ResponseStatus::BadNoSession => Status::failed_precondition(message),
+        _ => Status::unknown(message),
+    }
+}
@@ -0,0 +1,559 @@
+//! Recurring export jobs on cron schedules.
+//!
+//! An [`ExportJob`] reads raw history for a list of tags and writes it to a
+//! CSV or Parquet file (or, with the `s3` feature, an S3-compatible object
+//! store). Each run exports the window between the job's previous scheduled
+//! time and this one, so an `@hourly` job writes the last hour.
+//!
+//! [`ScheduleConfig`] is the YAML form used by `crowsong schedule`:
+//!
+//! ```yaml
+//! jobs:
+//!   - name: line1-hourly
+//!     schedule: "@hourly"
+//!     delay_seconds: 300
+//!     view: Plant
+//!     tags_file: line1-tags.txt
+//!     output: s3://historian/plant/{date}/line1-{start}.parquet
+//!   - name: utilities-daily
+//!     schedule: "30 0 * * mon-fri"
+//!     view: Plant
+//!     tags: [Plant.Utilities.Steam, Plant.Utilities.Water]
+//!     output: /data/exports/{job}/{date}.csv
+//!     retries: 5
+//! ```
+//!
+//! Schedules are standard five-field cron expressions evaluated in UTC; see
+//! [`CronSchedule`]. A job whose previous run is still going when it comes
+//! due again skips that run, and its next run covers the skipped window as
+//! well. Failed runs are retried after `retry_seconds`, doubling each time,
+//! up to `retries` times.
+//!
+//! Output paths may contain `{job}`, `{start}` and `{end}` (compact UTC
+//! times such as `20240501T120000Z`), and `{date}` and `{hour}` of the
+//! window's start. Paths ending in `.parquet` are written as Parquet (with
+//! the `parquet` feature), one row per sample with a `tag` column ahead of
+//! the [`crate::interop::arrow::schema`] columns; anything else is CSV with
+//! `tag,timestamp,value,quality` columns, timestamps rendered in the job's
+//! `timestamp_format` (RFC 3339 in UTC by default). Local files are written to a
+//! temporary name and renamed into place. `s3://bucket/key` objects are
+//! written with credentials, region, and endpoint from the usual `AWS_*`
+//! environment variables.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Datelike, Utc};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::bulk::BulkFetch;
+use crate::error::Error;
+use crate::export::{Output, compact_time, write_output};
+use crate::request_id::{self, RequestId};
+use crate::timestamps::{TimestampFormat, nanos_to_system_time, system_time_to_nanos};
+use crate::views_client::ViewsClient;
+
+/// A five-field cron expression: minute, hour, day of month, month, and
+/// day of week, evaluated in UTC.
+///
+/// Fields take `*`, numbers, ranges (`1-5`), steps (`*/15`, `10-50/20`),
+/// and comma-separated lists of these. Months and days of the week may be
+/// named (`jan`, `mon`), and Sunday is `0` or `7`. As in Vixie cron, when
+/// both the day of month and the day of week are restricted, a day matching
+/// either one fires. `@hourly`, `@daily` (`@midnight`), `@weekly`,
+/// `@monthly`, and `@yearly` (`@annually`) are also accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far [`CronSchedule::next_after`] and
+/// [`CronSchedule::previous_before`] look before giving up. Long enough for
+/// `0 0 29 2 *` across a century year.
+const SEARCH_DAYS: i64 = 9 * 366;
+
+impl CronSchedule {
+    /// The first time strictly after `time` the schedule fires, or `None` if
+    /// it never does (e.g. `0 0 30 2 *`).
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let mut t = (unix_seconds(time).div_euclid(60) + 1) * 60;
+        let limit = t + SEARCH_DAYS * 86_400;
+        while t < limit {
+            let day = t.div_euclid(86_400) * 86_400;
+            if !self.day_matches(day) {
+                t = day + 86_400;
+                continue;
+            }
+            let hour = (t - day) / 3_600;
+            if bit(self.hours, hour) {
+                let minute = (t - day - hour * 3_600) / 60;
+                if let Some(m) = (minute..60).find(|&m| bit(self.minutes, m)) {
+                    return Some(from_unix_seconds(day + hour * 3_600 + m * 60));
+                }
+            }
+            t = day + (hour + 1) * 3_600;
+        }
+        None
+    }
+
+    /// The last time strictly before `time` the schedule fired, or `None` if
+    /// it never did.
+    pub fn previous_before(&self, time: SystemTime) -> Option<SystemTime> {
+        let seconds = unix_seconds(time);
+        let mut t = (seconds + 59).div_euclid(60) * 60 - 60;
+        let limit = t - SEARCH_DAYS * 86_400;
+        while t > limit {
+            let day = t.div_euclid(86_400) * 86_400;
+            if !self.day_matches(day) {
+                t = day - 60;
+                continue;
+            }
+            let hour = (t - day) / 3_600;
+            if bit(self.hours, hour) {
+                let minute = (t - day - hour * 3_600) / 60;
+                if let Some(m) = (0..=minute).rev().find(|&m| bit(self.minutes, m)) {
+                    return Some(from_unix_seconds(day + hour * 3_600 + m * 60));
+                }
+            }
+            t = day + hour * 3_600 - 60;
+        }
+        None
+    }
+
+    /// Whether the schedule fires on the UTC day starting at `day` seconds
+    /// since the epoch.
+    fn day_matches(&self, day: i64) -> bool {
+        let Some(date) = DateTime::from_timestamp(day, 0).map(|t| t.date_naive()) else {
+            return false;
+        };
+        let by_date = bit(self.days, date.day().into());
+        let by_weekday = bit(self.weekdays, date.weekday().num_days_from_sunday().into());
+        let day_ok = if self.days_restricted && self.weekdays_restricted {
+            by_date || by_weekday
+        } else {
+            by_date && by_weekday
+        };
+        day_ok && bit(self.months, date.month().into())
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// An unparseable cron expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCronError(String);
+
+impl fmt::Display for ParseCronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cron expression: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseCronError {}
+
+impl FromStr for CronSchedule {
+    type Err = ParseCronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = s.trim();
+        let expression = match source {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(ParseCronError(format!(
+                "{source:?} has {} fields; expected minute, hour, day of month, month, and day of week",
+                fields.len()
+            )));
+        };
+        let field = |field: &str, min, max, names| {
+            parse_field(field, min, max, names)
+                .map_err(|e| ParseCronError(format!("{e} in {source:?}")))
+        };
+        let mut weekday_bits = field(weekdays, 0, 7, WEEKDAYS)?;
+        if bit(weekday_bits, 7) {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            source: source.to_string(),
+            minutes: field(minutes, 0, 59, &[])?,
+            hours: field(hours, 0, 23, &[])?,
+            days: field(days, 1, 31, &[])?,
+            months: field(months, 1, 12, MONTHS)?,
+            weekdays: weekday_bits,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = ParseCronError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Parse one cron field into a bit set of the values it matches. `names`
+/// name the values from `min` up.
+fn parse_field(field: &str, min: i64, max: i64, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<i64, String> {
+        let n = match names.iter().position(|name| name.eq_ignore_ascii_case(s)) {
+            Some(i) => i as i64 + min,
+            None => s.parse().map_err(|_| format!("bad value {s:?}"))?,
+        };
+        if !(min..=max).contains(&n) {
+            return Err(format!("{n} is outside {min}-{max}"));
+        }
+        Ok(n)
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("bad step {step:?}")),
+            },
+            None => (part, None),
+        };
+        let (low, high) = if range == "*" {
+            (min, max)
+        } else if let Some((low, high)) = range.split_once('-') {
+            (value(low)?, value(high)?)
+        } else {
+            // `5/15` means every 15 from 5 on.
+            let low = value(range)?;
+            (low, if step.is_some() { max } else { low })
+        };
+        if low > high {
+            return Err(format!("backwards range {range:?}"));
+        }
+        for n in (low..=high).step_by(step.unwrap_or(1)) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+fn bit(bits: u64, n: i64) -> bool {
+    bits & (1 << n) != 0
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    system_time_to_nanos(time).div_euclid(1_000_000_000)
+}
+
+fn from_unix_seconds(seconds: i64) -> SystemTime {
+    nanos_to_system_time(seconds * 1_000_000_000)
+}
+
+/// A recurring export of raw history for a list of tags.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportJob {
+    pub name: String,
+    pub schedule: CronSchedule,
+    pub view: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A file of tag names, one per line (`#` starts a comment), read each
+    /// time the job runs and added to `tags`.
+    #[serde(default)]
+    pub tags_file: Option<PathBuf>,
+    /// Where each run writes, with placeholders for the job and its window.
+    pub output: String,
+    /// How long after the scheduled time to run, giving late data time to
+    /// arrive. The window still ends at the scheduled time.
+    #[serde(default)]
+    pub delay_seconds: f64,
+    /// How many times to retry a failed run. Defaults to 3.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// The wait before the first retry, doubling for each one after.
+    /// Defaults to 60.
+    #[serde(default = "default_retry_seconds")]
+    pub retry_seconds: f64,
+    /// How CSV timestamps are rendered, e.g. `epoch,ms`; see
+    /// [`TimestampFormat`]. Parquet always stores epoch nanoseconds.
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_seconds() -> f64 {
+    60.0
+}
+
+impl ExportJob {
+    /// The job's tags, including those in its tags file.
+    pub fn tag_names(&self) -> Result<Vec<String>, Error> {
+        let mut tags = self.tags.clone();
+        if let Some(path) = &self.tags_file {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("reading {} failed: {e}", path.display()))?;
+            tags.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        if tags.is_empty() {
+            return Err(format!("job {} has no tags", self.name).into());
+        }
+        Ok(tags)
+    }
+
+    /// The output path for the window `start..end`.
+    pub fn output_path(&self, start: SystemTime, end: SystemTime) -> String {
+        let date = DateTime::<Utc>::from(start);
+        self.output
+            .replace("{job}", &self.name)
+            .replace("{start}", &compact_time(start))
+            .replace("{end}", &compact_time(end))
+            .replace("{date}", &date.format("%Y-%m-%d").to_string())
+            .replace("{hour}", &date.format("%H").to_string())
+    }
+
+    /// Export `start..end` once, without retrying. Returns the output path
+    /// and the number of samples written.
+    pub async fn run(
+        &self,
+        client: &ViewsClient,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<(String, u64), Error> {
+        let tags = self.tag_names()?;
+        let pages = BulkFetch::new(&self.view, tags, start, end)
+            .run(client)
+            .await?;
+        let mut output = Output::new(&self.output, self.timestamp_format)?;
+        let mut samples = 0;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            output.push(&page)?;
+            samples += page.len() as u64;
+        }
+        let path = self.output_path(start, end);
+        let contents = output.finish()?;
+        write_output(&path, contents).await?;
+        Ok((path, samples))
+    }
+
+    /// Check the schedule fires and the output format is available.
+    fn validate(&self) -> Result<(), Error> {
+        if self.schedule.next_after(SystemTime::now()).is_none() {
+            return Err(format!("job {}: {} never fires", self.name, self.schedule).into());
+        }
+        Output::new(&self.output, self.timestamp_format)
+            .map_err(|e| format!("job {}: {e}", self.name))?;
+        Duration::try_from_secs_f64(self.delay_seconds)
+            .map_err(|e| format!("job {}: bad delay_seconds: {e}", self.name))?;
+        Duration::try_from_secs_f64(self.retry_seconds)
+            .map_err(|e| format!("job {}: bad retry_seconds: {e}", self.name))?;
+        Ok(())
+    }
+
+    fn retry_backoff(&self, attempt: u32) -> Duration {
+        Duration::from_secs_f64(self.retry_seconds) * 2u32.saturating_pow(attempt - 1)
+    }
+}
+
+/// What happened to one scheduled run of a job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobReport {
+    pub job: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub outcome: JobOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobOutcome {
+    /// The run wrote `samples` samples to `path`.
+    Written { path: String, samples: u64 },
+    /// An attempt failed, and will be retried after `retry_in` if set.
+    Failed {
+        attempt: u32,
+        error: String,
+        retry_in: Option<Duration>,
+    },
+    /// The previous run was still going, so this one was skipped; the next
+    /// run covers its window.
+    Skipped,
+}
+
+/// Export jobs, as read from YAML.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub jobs: Vec<ExportJob>,
+}
+
+impl ScheduleConfig {
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        let config: Self = serde_yaml::from_str(yaml).map_err(Error::other)?;
+        for job in &config.jobs {
+            job.validate()?;
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_yaml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Run the jobs on their schedules until a schedule runs out.
+    pub async fn run(&self, client: &ViewsClient) -> Result<(), Error> {
+        self.run_with_reports(client, |_| {}).await
+    }
+
+    /// Like [`run`](Self::run), calling `on_report` as runs finish, fail, or
+    /// are skipped. Runs of different jobs may overlap, each on its own
+    /// handle to `client`.
+    pub async fn run_with_reports(
+        &self,
+        client: &ViewsClient,
+        mut on_report: impl FnMut(&JobReport),
+    ) -> Result<(), Error> {
+        struct Scheduled {
+            job: Arc<ExportJob>,
+            /// The start of the next run's window.
+            from: SystemTime,
+            /// The scheduled time of the next run, which ends its window.
+            next: SystemTime,
+            delay: Duration,
+            running: Arc<AtomicBool>,
+        }
+
+        let now = SystemTime::now();
+        let mut jobs = Vec::with_capacity(self.jobs.len());
+        for job in &self.jobs {
+            job.validate()?;
+            let next = job.schedule.next_after(now).ok_or("schedule never fires")?;
+            jobs.push(Scheduled {
+                from: job.schedule.previous_before(next).unwrap_or(now),
+                next,
+                delay: Duration::from_secs_f64(job.delay_seconds),
+                running: Arc::new(AtomicBool::new(false)),
+                job: Arc::new(job.clone()),
+            });
+        }
+        let (reports, mut received) = mpsc::unbounded_channel();
+
+        loop {
+            let Some(due) = jobs.iter_mut().min_by_key(|s| s.next + s.delay) else {
+                return Ok(());
+            };
+            let wait = (due.next + due.delay)
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            tokio::select! {
+                report = received.recv() => {
+                    if let Some(report) = report {
+                        on_report(&report);
+                    }
+                    continue;
+                }
+                _ = tokio::time::sleep(wait) => {}
+            }
+
+            let (start, end) = (due.from, due.next);
+            if due.running.swap(true, Ordering::AcqRel) {
+                on_report(&JobReport {
+                    job: due.job.name.clone(),
+                    start,
+                    end,
+                    outcome: JobOutcome::Skipped,
+                });
+            } else {
+                due.from = end;
+                let job = due.job.clone();
+                let running = due.running.clone();
+                let reports = reports.clone();
+                let client = client.handle();
+                // Every attempt at a window goes out under one request ID.
+                tokio::spawn(request_id::scope(RequestId::new(), async move {
+                    let mut attempt = 0;
+                    loop {
+                        attempt += 1;
+                        let result = job
+                            .run(&client, start, end)
+                            .await
+                            .map_err(|e| e.to_string());
+                        let (outcome, retry_in) = match result {
+                            Ok((path, samples)) => (JobOutcome::Written { path, samples }, None),
+                            Err(error) => {
+                                let retry_in =
+                                    (attempt <= job.retries).then(|| job.retry_backoff(attempt));
+                                (
+                                    JobOutcome::Failed {
+                                        attempt,
+                                        error,
+                                        retry_in,
+                                    },
+                                    retry_in,
+                                )
+                            }
+                        };
+                        let _ = reports.send(JobReport {
+                            job: job.name.clone(),
+                            start,
+                            end,
+                            outcome,
+                        });
+                        match retry_in {
+                            Some(wait) => tokio::time::sleep(wait).await,
+                            None => break,
+                        }
+                    }
+                    running.store(false, Ordering::Release);
+                }));
+            }
+            match due.job.schedule.next_after(due.next) {
+                Some(next) => due.next = next,
+                None => return Err(format!("job {}: schedule ran out", due.job.name).into()),
+            }
+        }
+    }
+}
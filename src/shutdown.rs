@@ -0,0 +1,82 @@
+//! Shutting a client down cleanly, for service restarts.
+//!
+//! A [`ViewsClient`] runs background work that outlives any one call: the
+//! lease watch, API key file and bearer token refreshes, and the readers of
+//! live subscriptions opened through it. Each stops when whatever holds it is
+//! dropped, but handles held elsewhere can keep it going. [`ViewsClient::shutdown`]
+//! stops all of it, then releases the client connection ID, and resolves
+//! once everything is torn down:
+//!
+//! ```no_run
+//! # async fn example(client: crowsong::ViewsClient, stop: tokio::sync::oneshot::Receiver<()>) -> Result<(), crowsong::Error> {
+//! // E.g. on ctrl-c, or when the service manager asks.
+//! stop.await.ok();
+//! client.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Watches started with [`ViewsClient::watch_api_key_file`] belong to the
+//! caller and keep running; stop them by dropping them. To shut down a
+//! [`CanaryHistorian`](crate::historian::CanaryHistorian), including its
+//! Store & Forward session, use its `close`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::task::AbortHandle;
+
+use crate::error::Error;
+use crate::views_client::ViewsClient;
+
+/// The background tasks of one connection, shared by every handle on it.
+#[derive(Debug, Default)]
+pub(crate) struct Tasks {
+    running: Mutex<Vec<AbortHandle>>,
+    closed: AtomicBool,
+}
+
+impl Tasks {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Stop `task` when the connection shuts down, or now if it already has.
+    pub(crate) fn register(&self, task: AbortHandle) {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.closed.load(Ordering::Acquire) {
+            task.abort();
+            return;
+        }
+        running.retain(|task| !task.is_finished());
+        running.push(task);
+    }
+
+    /// Abort every task, and any registered later, and wait for them to end.
+    async fn stop_all(&self) {
+        let running = {
+            let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+            self.closed.store(true, Ordering::Release);
+            std::mem::take(&mut *running)
+        };
+        for task in &running {
+            task.abort();
+        }
+        // An aborted task ends the next time it is polled.
+        for task in &running {
+            while !task.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+}
+
+impl ViewsClient {
+    /// Stop this connection's background work, then release the client
+    /// connection ID. Resolves once every task has ended; the error, if any,
+    /// is the release's. Other handles on the connection fail from here on.
+    pub async fn shutdown(self) -> Result<(), Error> {
+        self.tasks().stop_all().await;
+        self.disconnect().await
+    }
+}
@@ -0,0 +1,158 @@
+//! Reporting data calls that take longer than a threshold.
+//!
+//! A busy historian is usually being hammered by a few dashboards. With a
+//! [`SlowCallLog`] on the client, every data call (raw, aggregate, current
+//! value, and statistics reads) that takes at least the threshold is
+//! reported as a [`SlowCall`]: which method, view, how many tags, how long a
+//! range, how many rows came back, and how long it took. By default the
+//! report is written to stderr as one logfmt line:
+//!
+//! ```text
+//! crowsong: slow call method=GetRawData view=Plant tags=250 span=86400s rows=1250000 elapsed=4.180s request_id=…
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use tonic::Status;
+
+use crate::request_id::{self, RequestId};
+
+/// One data call that took at least the slow-call threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowCall {
+    /// The RPC, e.g. `"GetRawData"`.
+    pub method: &'static str,
+    pub view: String,
+    /// How many tags the call asked for.
+    pub tags: usize,
+    /// The length of the requested time range, for calls that have one.
+    pub span: Option<Duration>,
+    /// Samples (or current values) returned; 0 if the call failed.
+    pub rows: usize,
+    pub elapsed: Duration,
+    /// Whether the call failed.
+    pub failed: bool,
+    /// The ID the call was sent under.
+    pub request_id: Option<RequestId>,
+}
+
+impl fmt::Display for SlowCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slow call method={} view={:?} tags={}",
+            self.method, self.view, self.tags
+        )?;
+        if let Some(span) = self.span {
+            write!(f, " span={}s", span.as_secs())?;
+        }
+        write!(
+            f,
+            " rows={} elapsed={:.3}s",
+            self.rows,
+            self.elapsed.as_secs_f64()
+        )?;
+        if self.failed {
+            f.write_str(" failed=true")?;
+        }
+        if let Some(id) = self.request_id {
+            write!(f, " request_id={id}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where slow calls go, and how slow a call has to be.
+#[derive(Clone)]
+pub struct SlowCallLog {
+    threshold: Duration,
+    handler: Arc<dyn Fn(&SlowCall) + Send + Sync>,
+}
+
+impl SlowCallLog {
+    /// Report calls taking at least `threshold` to stderr.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            handler: Arc::new(|call| eprintln!("crowsong: {call}")),
+        }
+    }
+
+    /// Pass slow calls to `handler` instead, e.g. to forward them to a
+    /// logging framework or metrics.
+    pub fn handler(mut self, handler: impl Fn(&SlowCall) + Send + Sync + 'static) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+}
+
+impl fmt::Debug for SlowCallLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowCallLog")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Run `call`, reporting it to `log` if it is slow. `call` describes the
+/// request, with `rows` and `elapsed` left to fill in; `rows` counts the
+/// response's rows.
+pub(crate) async fn timed<T>(
+    log: Option<&SlowCallLog>,
+    mut call: SlowCall,
+    future: impl Future<Output = Result<T, Status>>,
+    rows: impl FnOnce(&T) -> usize,
+) -> Result<T, Status> {
+    let Some(log) = log else {
+        return future.await;
+    };
+    request_id::operation(async {
+        let started = Instant::now();
+        let result = future.await;
+        call.elapsed = started.elapsed();
+        if call.elapsed >= log.threshold {
+            call.rows = result.as_ref().map_or(0, rows);
+            call.failed = result.is_err();
+            call.request_id = RequestId::current();
+            (log.handler)(&call);
+        }
+        result
+    })
+    .await
+}
+
+impl SlowCall {
+    /// A call to `method` on `view` for `tags` tags, not yet made.
+    pub(crate) fn new(method: &'static str, view: &str, tags: usize) -> Self {
+        Self {
+            method,
+            view: view.to_string(),
+            tags,
+            span: None,
+            rows: 0,
+            elapsed: Duration::ZERO,
+            failed: false,
+            request_id: None,
+        }
+    }
+
+    /// Set the span from a request's start and end times.
+    pub(crate) fn range(
+        mut self,
+        start: Option<&prost_types::Timestamp>,
+        end: Option<&prost_types::Timestamp>,
+    ) -> Self {
+        let time = |ts: Option<&prost_types::Timestamp>| SystemTime::try_from(*ts?).ok();
+        if let (Some(start), Some(end)) = (time(start), time(end)) {
+            self.span = end.duration_since(start).ok();
+        }
+        self
+    }
+}
@@ -0,0 +1,590 @@
+//! Republishing live data as Sparkplug B metrics over MQTT.
+//!
+//! A [`SparkplugBridge`] acts as a Sparkplug edge node: it subscribes to
+//! live data for its tags and publishes every update as a metric of the
+//! node or of one of its devices, so a Canary view can feed a unified
+//! namespace without glue services.
+//!
+//! On connecting, the bridge publishes `NBIRTH` and a `DBIRTH` per device,
+//! carrying every metric's name, alias, data type, and latest value; updates
+//! then go out as `NDATA`/`DDATA` by alias. An `NDEATH` is registered as the
+//! MQTT last will. Births are republished after a reconnect, when a host
+//! application sends the `Node Control/Rebirth` command, and when a tag's
+//! values change data type. Each metric carries its Canary quality code as
+//! a `Quality` property.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+use prost::Message;
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use tokio::sync::mpsc;
+
+use crate::canary::views::grpc::api::{GetTagCurrentValueRequest, SubscribeToLiveDataRequest};
+use crate::error::Error;
+use crate::timestamps::system_time_to_nanos;
+use crate::types::{Tvq, Value};
+use crate::views_client::ViewsClient;
+
+use payload::{Metric, MetricValue, Payload, PropertySet, PropertyValue, PropertyValueKind};
+
+/// The Sparkplug B payload messages the bridge publishes, a subset of
+/// `sparkplug_b.proto`.
+pub mod payload {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Payload {
+        #[prost(uint64, optional, tag = "1")]
+        pub timestamp: Option<u64>,
+        #[prost(message, repeated, tag = "2")]
+        pub metrics: Vec<Metric>,
+        #[prost(uint64, optional, tag = "3")]
+        pub seq: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Metric {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(uint64, optional, tag = "2")]
+        pub alias: Option<u64>,
+        #[prost(uint64, optional, tag = "3")]
+        pub timestamp: Option<u64>,
+        #[prost(uint32, optional, tag = "4")]
+        pub datatype: Option<u32>,
+        #[prost(bool, optional, tag = "7")]
+        pub is_null: Option<bool>,
+        #[prost(message, optional, tag = "9")]
+        pub properties: Option<PropertySet>,
+        #[prost(oneof = "MetricValue", tags = "10, 11, 12, 13, 14, 15")]
+        pub value: Option<MetricValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum MetricValue {
+        #[prost(uint32, tag = "10")]
+        IntValue(u32),
+        #[prost(uint64, tag = "11")]
+        LongValue(u64),
+        #[prost(float, tag = "12")]
+        FloatValue(f32),
+        #[prost(double, tag = "13")]
+        DoubleValue(f64),
+        #[prost(bool, tag = "14")]
+        BooleanValue(bool),
+        #[prost(string, tag = "15")]
+        StringValue(String),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PropertySet {
+        #[prost(string, repeated, tag = "1")]
+        pub keys: Vec<String>,
+        #[prost(message, repeated, tag = "2")]
+        pub values: Vec<PropertyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PropertyValue {
+        #[prost(uint32, optional, tag = "1")]
+        pub r#type: Option<u32>,
+        #[prost(bool, optional, tag = "2")]
+        pub is_null: Option<bool>,
+        #[prost(oneof = "PropertyValueKind", tags = "3, 4")]
+        pub value: Option<PropertyValueKind>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum PropertyValueKind {
+        #[prost(uint32, tag = "3")]
+        IntValue(u32),
+        #[prost(uint64, tag = "4")]
+        LongValue(u64),
+    }
+
+    /// Sparkplug B data type codes.
+    pub mod datatype {
+        pub const INT32: u32 = 3;
+        pub const INT64: u32 = 4;
+        pub const UINT64: u32 = 8;
+        pub const DOUBLE: u32 = 10;
+        pub const BOOLEAN: u32 = 11;
+        pub const STRING: u32 = 12;
+    }
+}
+
+use payload::datatype;
+
+const NAMESPACE: &str = "spBv1.0";
+const REBIRTH: &str = "Node Control/Rebirth";
+
+/// How one tag is published.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparkplugMetric {
+    tag: String,
+    name: String,
+    device: Option<String>,
+}
+
+impl SparkplugMetric {
+    /// Publish `tag` as a metric of the edge node, named after the tag with
+    /// `.` separators turned into Sparkplug's `/` folders.
+    pub fn new(tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        Self {
+            name: tag.replace('.', "/"),
+            tag,
+            device: None,
+        }
+    }
+
+    /// Publish under another metric name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Publish as a metric of this device of the edge node.
+    pub fn device(mut self, device_id: impl Into<String>) -> Self {
+        self.device = Some(device_id.into());
+        self
+    }
+}
+
+/// A Sparkplug B edge node republishing a view's live data.
+#[derive(Debug, Clone)]
+pub struct SparkplugBridge {
+    view: String,
+    group_id: String,
+    edge_node_id: String,
+    metrics: Vec<SparkplugMetric>,
+    reporting_interval: Duration,
+    qos: QoS,
+}
+
+impl SparkplugBridge {
+    pub fn new(
+        view: impl Into<String>,
+        group_id: impl Into<String>,
+        edge_node_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            view: view.into(),
+            group_id: group_id.into(),
+            edge_node_id: edge_node_id.into(),
+            metrics: Vec::new(),
+            reporting_interval: Duration::from_secs(1),
+            qos: QoS::AtLeastOnce,
+        }
+    }
+
+    /// Publish a tag as configured by `metric`.
+    pub fn metric(mut self, metric: SparkplugMetric) -> Self {
+        self.metrics.push(metric);
+        self
+    }
+
+    /// Publish tags as metrics of the edge node.
+    pub fn tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.metrics
+            .extend(tags.into_iter().map(SparkplugMetric::new));
+        self
+    }
+
+    /// Publish tags as metrics of a device of the edge node.
+    pub fn device(
+        mut self,
+        device_id: impl Into<String>,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let device_id = device_id.into();
+        self.metrics.extend(
+            tags.into_iter()
+                .map(|tag| SparkplugMetric::new(tag).device(device_id.clone())),
+        );
+        self
+    }
+
+    /// How often the historian reports live updates. Defaults to 1 second.
+    pub fn reporting_interval(mut self, interval: Duration) -> Self {
+        self.reporting_interval = interval;
+        self
+    }
+
+    /// The MQTT QoS of published messages. Defaults to at least once.
+    pub fn qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Bridge live data until the subscription ends or fails. The bridge sets
+    /// the NDEATH last will on `options`; MQTT connection errors are retried.
+    pub async fn run(&self, client: &ViewsClient, mut options: MqttOptions) -> Result<(), Error> {
+        let mut node = NodeState::new(self);
+        let current = client
+            .get_tag_current_value(GetTagCurrentValueRequest {
+                view: self.view.clone(),
+                tag_names: node.tags().map(str::to_string).collect(),
+                use_time_extension: None,
+                quality: 0,
+                cci: 0,
+            })
+            .await?;
+        for value in &current.tag_values {
+            node.update(&value.tag_item_id, Tvq::from(value));
+        }
+
+        options.set_last_will(LastWill::new(
+            self.topic("NDEATH", None),
+            node.death().encode_to_vec(),
+            self.qos,
+            false,
+        ));
+        let (mqtt, mut events) = spawn_event_loop(options);
+
+        let mut live = client
+            .subscribe_to_live_data(SubscribeToLiveDataRequest {
+                tags: node.tags().map(str::to_string).collect(),
+                reporting_interval: self.reporting_interval.try_into().ok(),
+                ..Default::default()
+            })
+            .await?;
+
+        let mut online = false;
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some(MqttEvent::Connected) => {
+                        online = true;
+                        mqtt.subscribe(self.topic("NCMD", None), self.qos).await.map_err(Error::other)?;
+                        self.publish_births(&mqtt, &mut node).await?;
+                    }
+                    Some(MqttEvent::Disconnected) => online = false,
+                    Some(MqttEvent::Command(payload)) => {
+                        if online && is_rebirth(&payload) {
+                            self.publish_births(&mqtt, &mut node).await?;
+                        }
+                    }
+                    None => return Err("MQTT event loop stopped".into()),
+                },
+                message = live.message() => {
+                    let Some(resp) = message? else {
+                        break;
+                    };
+                    let mut updated = Vec::new();
+                    let mut rebirth = false;
+                    for (tag, data) in &resp.tags_and_data {
+                        for tvq in data.tvqs.iter().map(Tvq::from) {
+                            let Some((index, changed_type)) = node.update(tag, tvq.clone()) else {
+                                continue;
+                            };
+                            rebirth |= changed_type;
+                            updated.push((index, tvq));
+                        }
+                    }
+                    if !online {
+                        continue;
+                    }
+                    if rebirth {
+                        self.publish_births(&mqtt, &mut node).await?;
+                    } else {
+                        self.publish_data(&mqtt, &mut node, &updated).await?;
+                    }
+                }
+            }
+        }
+        mqtt.publish(
+            self.topic("NDEATH", None),
+            self.qos,
+            false,
+            node.death().encode_to_vec(),
+        )
+        .await
+        .map_err(Error::other)?;
+        mqtt.disconnect().await.map_err(Error::other)?;
+        Ok(())
+    }
+
+    async fn publish_births(&self, mqtt: &AsyncClient, node: &mut NodeState) -> Result<(), Error> {
+        node.seq = 0;
+        let now = millis(SystemTime::now());
+        let mut metrics = vec![Metric {
+            name: Some("bdSeq".to_string()),
+            datatype: Some(datatype::INT64),
+            value: Some(MetricValue::LongValue(node.bd_seq)),
+            ..Default::default()
+        }];
+        metrics.push(Metric {
+            name: Some(REBIRTH.to_string()),
+            datatype: Some(datatype::BOOLEAN),
+            value: Some(MetricValue::BooleanValue(false)),
+            ..Default::default()
+        });
+        metrics.extend(node.birth_metrics(None));
+        let payload = node.payload(now, metrics);
+        mqtt.publish(self.topic("NBIRTH", None), self.qos, false, payload)
+            .await
+            .map_err(Error::other)?;
+
+        for device in node.devices() {
+            let metrics = node.birth_metrics(Some(&device));
+            let payload = node.payload(now, metrics);
+            mqtt.publish(
+                self.topic("DBIRTH", Some(&device)),
+                self.qos,
+                false,
+                payload,
+            )
+            .await
+            .map_err(Error::other)?;
+        }
+        Ok(())
+    }
+
+    async fn publish_data(
+        &self,
+        mqtt: &AsyncClient,
+        node: &mut NodeState,
+        updated: &[(usize, Tvq)],
+    ) -> Result<(), Error> {
+        let mut by_device: BTreeMap<Option<&str>, Vec<Metric>> = BTreeMap::new();
+        for (index, tvq) in updated {
+            let state = &node.metrics[*index];
+            let mut metric = data_metric(tvq, state.datatype);
+            metric.alias = Some(*index as u64);
+            by_device
+                .entry(self.metrics[*index].device.as_deref())
+                .or_default()
+                .push(metric);
+        }
+        let now = millis(SystemTime::now());
+        for (device, metrics) in by_device {
+            let kind = if device.is_some() { "DDATA" } else { "NDATA" };
+            let payload = node.payload(now, metrics);
+            mqtt.publish(self.topic(kind, device), self.qos, false, payload)
+                .await
+                .map_err(Error::other)?;
+        }
+        Ok(())
+    }
+
+    fn topic(&self, kind: &str, device: Option<&str>) -> String {
+        match device {
+            Some(device) => format!(
+                "{NAMESPACE}/{}/{kind}/{}/{device}",
+                self.group_id, self.edge_node_id
+            ),
+            None => format!("{NAMESPACE}/{}/{kind}/{}", self.group_id, self.edge_node_id),
+        }
+    }
+}
+
+/// The latest value and birth data type of one metric.
+#[derive(Debug, Default)]
+struct MetricState {
+    datatype: Option<u32>,
+    last: Option<Tvq>,
+}
+
+/// What the edge node has told, or will tell, its host applications.
+struct NodeState {
+    config: Vec<SparkplugMetric>,
+    metrics: Vec<MetricState>,
+    by_tag: HashMap<String, usize>,
+    bd_seq: u64,
+    seq: u64,
+}
+
+impl NodeState {
+    fn new(bridge: &SparkplugBridge) -> Self {
+        Self {
+            config: bridge.metrics.clone(),
+            metrics: bridge
+                .metrics
+                .iter()
+                .map(|_| MetricState::default())
+                .collect(),
+            by_tag: bridge
+                .metrics
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (m.tag.clone(), i))
+                .collect(),
+            bd_seq: 0,
+            seq: 0,
+        }
+    }
+
+    fn tags(&self) -> impl Iterator<Item = &str> {
+        self.config.iter().map(|m| m.tag.as_str())
+    }
+
+    fn devices(&self) -> Vec<String> {
+        let mut devices: Vec<String> = self
+            .config
+            .iter()
+            .filter_map(|m| m.device.clone())
+            .collect();
+        devices.sort();
+        devices.dedup();
+        devices
+    }
+
+    /// Record a tag's new value. Returns the metric index and whether its
+    /// data type differs from the one last announced in a birth.
+    fn update(&mut self, tag: &str, tvq: Tvq) -> Option<(usize, bool)> {
+        let index = *self.by_tag.get(tag)?;
+        let state = &mut self.metrics[index];
+        let changed_type = match (state.datatype, value_datatype(&tvq.value)) {
+            (Some(old), Some(new)) => old != new,
+            _ => false,
+        };
+        state.last = Some(tvq);
+        Some((index, changed_type))
+    }
+
+    /// The birth metrics of the node (`None`) or a device, fixing each
+    /// metric's announced data type.
+    fn birth_metrics(&mut self, device: Option<&str>) -> Vec<Metric> {
+        let mut metrics = Vec::new();
+        for (index, config) in self.config.iter().enumerate() {
+            if config.device.as_deref() != device {
+                continue;
+            }
+            let state = &mut self.metrics[index];
+            let datatype = state
+                .last
+                .as_ref()
+                .and_then(|tvq| value_datatype(&tvq.value))
+                .unwrap_or(datatype::DOUBLE);
+            state.datatype = Some(datatype);
+            let mut metric = match &state.last {
+                Some(tvq) => data_metric(tvq, Some(datatype)),
+                None => Metric {
+                    datatype: Some(datatype),
+                    is_null: Some(true),
+                    ..Default::default()
+                },
+            };
+            metric.name = Some(config.name.clone());
+            metric.alias = Some(index as u64);
+            metrics.push(metric);
+        }
+        metrics
+    }
+
+    fn death(&self) -> Payload {
+        Payload {
+            timestamp: Some(millis(SystemTime::now())),
+            metrics: vec![Metric {
+                name: Some("bdSeq".to_string()),
+                datatype: Some(datatype::INT64),
+                value: Some(MetricValue::LongValue(self.bd_seq)),
+                ..Default::default()
+            }],
+            seq: None,
+        }
+    }
+
+    /// Encode a payload with the next sequence number.
+    fn payload(&mut self, timestamp: u64, metrics: Vec<Metric>) -> Vec<u8> {
+        let seq = self.seq;
+        self.seq = (self.seq + 1) % 256;
+        Payload {
+            timestamp: Some(timestamp),
+            metrics,
+            seq: Some(seq),
+        }
+        .encode_to_vec()
+    }
+}
+
+/// A data metric for a sample, without its name or alias.
+fn data_metric(tvq: &Tvq, datatype: Option<u32>) -> Metric {
+    let datatype = datatype.or_else(|| value_datatype(&tvq.value));
+    let value = match (&tvq.value, datatype) {
+        (Value::Null, _) => None,
+        (Value::Bool(b), _) => Some(MetricValue::BooleanValue(*b)),
+        (Value::String(s), _) => Some(MetricValue::StringValue(s.clone())),
+        (Value::Int(i), Some(datatype::INT64)) => Some(MetricValue::LongValue(*i as u64)),
+        (Value::UInt(u), Some(datatype::UINT64)) => Some(MetricValue::LongValue(*u)),
+        (value, _) => value.as_f64().map(MetricValue::DoubleValue),
+    };
+    Metric {
+        timestamp: Some(millis(tvq.timestamp)),
+        datatype,
+        is_null: value.is_none().then_some(true),
+        properties: Some(PropertySet {
+            keys: vec!["Quality".to_string()],
+            values: vec![PropertyValue {
+                r#type: Some(datatype::INT32),
+                is_null: None,
+                value: Some(PropertyValueKind::IntValue(tvq.quality.0)),
+            }],
+        }),
+        value,
+        ..Default::default()
+    }
+}
+
+/// The Sparkplug data type of a value, or `None` for a null.
+fn value_datatype(value: &Value) -> Option<u32> {
+    match value {
+        Value::Null => None,
+        Value::Bool(_) => Some(datatype::BOOLEAN),
+        Value::Int(_) => Some(datatype::INT64),
+        Value::UInt(_) => Some(datatype::UINT64),
+        Value::String(_) => Some(datatype::STRING),
+        Value::Float(_) | Value::Decimal(_) => Some(datatype::DOUBLE),
+    }
+}
+
+fn is_rebirth(payload: &[u8]) -> bool {
+    Payload::decode(payload).is_ok_and(|p| {
+        p.metrics.iter().any(|m| {
+            m.name.as_deref() == Some(REBIRTH) && m.value == Some(MetricValue::BooleanValue(true))
+        })
+    })
+}
+
+fn millis(time: SystemTime) -> u64 {
+    (system_time_to_nanos(time) / 1_000_000).max(0) as u64
+}
+
+/// What the bridge needs to hear from the MQTT event loop.
+enum MqttEvent {
+    Connected,
+    Disconnected,
+    /// An NCMD payload.
+    Command(Vec<u8>),
+}
+
+/// Drive the MQTT event loop on its own task, so publishing never waits on
+/// the loop being polled. Connection errors are retried after a pause.
+fn spawn_event_loop(options: MqttOptions) -> (AsyncClient, mpsc::UnboundedReceiver<MqttEvent>) {
+    let (mqtt, mut event_loop) = AsyncClient::new(options, 64);
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let event = match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => MqttEvent::Connected,
+                Ok(Event::Incoming(Packet::Publish(publish)))
+                    if publish.topic.contains("/NCMD/") =>
+                {
+                    MqttEvent::Command(publish.payload.to_vec())
+                }
+                Ok(_) => continue,
+                Err(_) => {
+                    if tx.send(MqttEvent::Disconnected).is_err() {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    (mqtt, rx)
+}
@@ -0,0 +1,336 @@
+//! Time-weighted statistics computed from raw samples.
+//!
+//! Some servers expose only a few aggregates, and reports often want
+//! several statistics over one read. [`TimeWeighted`] computes them from raw
+//! TVQs the way Canary's aggregates do: each sample's value holds until the
+//! next sample (or, when [`sloped`](TimeWeighted::sloped), ramps linearly
+//! to it), a sample before the range carries its value into it, and time
+//! under a rejected-quality or non-numeric sample is a gap rather than a
+//! zero.
+//!
+//! ```
+//! use std::time::{Duration, SystemTime};
+//! use crowsong::stats::TimeWeighted;
+//! use crowsong::{Quality, Tvq, Value};
+//!
+//! let t0 = SystemTime::UNIX_EPOCH;
+//! let tvq = |secs, value| Tvq {
+//!     timestamp: t0 + Duration::from_secs(secs),
+//!     value: Value::Float(value),
+//!     quality: Quality(192),
+//! };
+//! let stats = TimeWeighted::new(t0, t0 + Duration::from_secs(40))
+//!     .compute(&[tvq(0, 10.0), tvq(30, 20.0)]);
+//! assert_eq!(stats.time_average, Some(12.5));
+//! ```
+
+use std::time::{Duration, SystemTime};
+
+use crate::quality::QualityFilter;
+use crate::types::Tvq;
+
+/// Time-weighted statistics of one tag over a range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagStats {
+    pub tag_name: String,
+    /// The time-weighted average over the covered time, or `None` if no
+    /// time was covered.
+    pub time_average: Option<f64>,
+    /// The smallest sample in the range and when it was recorded.
+    pub minimum: Option<(SystemTime, f64)>,
+    /// The largest sample in the range and when it was recorded.
+    pub maximum: Option<(SystemTime, f64)>,
+    /// The integral of the value over time, in value-seconds.
+    pub integral: f64,
+    /// How much of the range had a usable value.
+    pub covered: Duration,
+    /// Usable samples inside the range.
+    pub samples: usize,
+    /// The share of covered time the value spent inside the
+    /// [`in_range`](TimeWeighted::in_range) limits, as a percentage.
+    pub percent_in_range: Option<f64>,
+}
+
+impl TagStats {
+    /// The totalizer for a value measured per `unit` of time, e.g. a flow in
+    /// m³/h totals with `Duration::from_secs(3600)`.
+    pub fn total(&self, unit: Duration) -> f64 {
+        self.integral / unit.as_secs_f64()
+    }
+}
+
+/// Settings for computing [`TagStats`] over `start..end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWeighted {
+    start: SystemTime,
+    end: SystemTime,
+    sloped: bool,
+    quality: QualityFilter,
+    in_range: Option<(f64, f64)>,
+}
+
+/// A stretch of time with a usable value, ramping from `from` to `to`.
+struct Segment {
+    seconds: f64,
+    from: f64,
+    to: f64,
+}
+
+impl TimeWeighted {
+    pub fn new(start: SystemTime, end: SystemTime) -> Self {
+        Self {
+            start,
+            end,
+            sloped: false,
+            quality: QualityFilter::NonBad,
+            in_range: None,
+        }
+    }
+
+    /// Interpolate linearly between samples instead of holding each value,
+    /// as Canary does for sloped aggregates.
+    pub fn sloped(mut self) -> Self {
+        self.sloped = true;
+        self
+    }
+
+    /// Which samples count. Defaults to [`QualityFilter::NonBad`].
+    pub fn quality(mut self, quality: QualityFilter) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Also report the share of time spent within `low..=high`.
+    pub fn in_range(mut self, low: f64, high: f64) -> Self {
+        self.in_range = Some((low.min(high), low.max(high)));
+        self
+    }
+
+    /// Compute statistics from one tag's samples, in time order. Include the
+    /// last sample before the range to carry its value into the start.
+    pub fn compute(&self, tvqs: &[Tvq]) -> TagStats {
+        let mut stats = TagStats::default();
+        for tvq in tvqs {
+            let Some(value) = self.usable(tvq) else {
+                continue;
+            };
+            if tvq.timestamp < self.start || tvq.timestamp >= self.end {
+                continue;
+            }
+            stats.samples += 1;
+            if stats.minimum.is_none_or(|(_, min)| value < min) {
+                stats.minimum = Some((tvq.timestamp, value));
+            }
+            if stats.maximum.is_none_or(|(_, max)| value > max) {
+                stats.maximum = Some((tvq.timestamp, value));
+            }
+        }
+
+        let mut covered = 0.0;
+        let mut inside = 0.0;
+        for segment in self.segments(tvqs) {
+            covered += segment.seconds;
+            stats.integral += (segment.from + segment.to) / 2.0 * segment.seconds;
+            if let Some((low, high)) = self.in_range {
+                inside += segment.seconds * fraction_within(segment.from, segment.to, low, high);
+            }
+        }
+        stats.covered = Duration::from_secs_f64(covered);
+        if covered > 0.0 {
+            stats.time_average = Some(stats.integral / covered);
+            if self.in_range.is_some() {
+                stats.percent_in_range = Some(inside / covered * 100.0);
+            }
+        }
+        stats
+    }
+
+    /// Read raw samples for `tags` over the range, with the bounding
+    /// samples, and compute each tag's statistics, in order.
+    #[cfg(feature = "views")]
+    pub async fn fetch(
+        &self,
+        client: &crate::views_client::ViewsClient,
+        view: &str,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Vec<TagStats>, crate::error::Error> {
+        let tags: Vec<String> = tags.into_iter().map(Into::into).collect();
+        let mut series: Vec<Vec<Tvq>> = vec![Vec::new(); tags.len()];
+        let pages = crate::bulk::BulkFetch::new(view, &tags, self.start, self.end)
+            .bounds(true)
+            .run(client)
+            .await?;
+        for page in pages {
+            let page = page?;
+            if page.error_code != 0 {
+                return Err(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                )
+                .into());
+            }
+            if let Some(i) = tags.iter().position(|t| *t == page.tag_name) {
+                series[i].extend((0..page.len()).map(|row| page.tvq(row)));
+            }
+        }
+        Ok(tags
+            .into_iter()
+            .zip(series)
+            .map(|(tag_name, tvqs)| TagStats {
+                tag_name,
+                ..self.compute(&tvqs)
+            })
+            .collect())
+    }
+
+    fn usable(&self, tvq: &Tvq) -> Option<f64> {
+        if !self.quality.accepts(tvq.quality) {
+            return None;
+        }
+        tvq.value.as_f64().filter(|v| v.is_finite())
+    }
+
+    /// The covered stretches of the range, each sample's value lasting
+    /// until the next sample or the end of the range.
+    fn segments(&self, tvqs: &[Tvq]) -> Vec<Segment> {
+        let seconds =
+            |a: SystemTime, b: SystemTime| b.duration_since(a).map_or(0.0, |d| d.as_secs_f64());
+        let mut segments = Vec::new();
+        for (i, tvq) in tvqs.iter().enumerate() {
+            let Some(value) = self.usable(tvq) else {
+                continue;
+            };
+            let next = tvqs.get(i + 1);
+            let until = next.map_or(self.end, |n| n.timestamp);
+            let from = tvq.timestamp.max(self.start);
+            let to = until.min(self.end);
+            if from >= to {
+                continue;
+            }
+            let at = |time: SystemTime| match next.and_then(|n| self.usable(n)) {
+                Some(next_value) if self.sloped => {
+                    let span = seconds(tvq.timestamp, until);
+                    if span > 0.0 {
+                        value + (next_value - value) * seconds(tvq.timestamp, time) / span
+                    } else {
+                        value
+                    }
+                }
+                _ => value,
+            };
+            segments.push(Segment {
+                seconds: seconds(from, to),
+                from: at(from),
+                to: at(to),
+            });
+        }
+        segments
+    }
+}
+
+/// The fraction of a linear ramp from `from` to `to` that lies within
+/// `low..=high`.
+fn fraction_within(from: f64, to: f64, low: f64, high: f64) -> f64 {
+    if from == to {
+        return if (low..=high).contains(&from) {
+            1.0
+        } else {
+            0.0
+        };
+    }
+    let (a, b) = (from.min(to), from.max(to));
+    ((b.min(high) - a.max(low)) / (b - a)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quality::Quality;
+    use crate::types::Value;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn tvq(secs: u64, value: f64) -> Tvq {
+        Tvq {
+            timestamp: at(secs),
+            value: Value::Float(value),
+            quality: Quality::GOOD,
+        }
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn leaves_time_before_the_first_sample_uncovered() {
+        let range = TimeWeighted::new(at(0), at(40));
+        let tvqs = [tvq(10, 10.0), tvq(30, 20.0)];
+
+        let held = range.compute(&tvqs);
+        assert_eq!(held.covered, Duration::from_secs(30));
+        assert!(close(held.time_average, 400.0 / 30.0));
+        assert_eq!(held.integral, 400.0);
+        assert_eq!(held.samples, 2);
+        assert_eq!(held.minimum, Some((at(10), 10.0)));
+        assert_eq!(held.maximum, Some((at(30), 20.0)));
+
+        let sloped = range.sloped().compute(&tvqs);
+        assert_eq!(sloped.covered, Duration::from_secs(30));
+        assert!(close(sloped.time_average, 500.0 / 30.0));
+    }
+
+    #[test]
+    fn carries_a_sample_before_the_range_into_it() {
+        let range = TimeWeighted::new(at(100), at(140));
+        let tvqs = [tvq(90, 4.0), tvq(110, 8.0)];
+
+        let held = range.compute(&tvqs);
+        assert_eq!(held.covered, Duration::from_secs(40));
+        assert!(close(held.time_average, 7.0));
+        // The bounding sample weighs in but isn't one of the range's samples.
+        assert_eq!(held.samples, 1);
+        assert_eq!(held.minimum, Some((at(110), 8.0)));
+
+        // Ramping from 4 at 90 to 8 at 110 is at 6 when the range starts.
+        let sloped = range.sloped().compute(&tvqs);
+        assert!(close(sloped.time_average, (70.0 + 240.0) / 40.0));
+    }
+
+    #[test]
+    fn skips_rejected_and_non_numeric_samples() {
+        let bad = Tvq {
+            quality: Quality::BAD,
+            ..tvq(20, 1_000.0)
+        };
+        let text = Tvq {
+            value: Value::String("off".into()),
+            ..tvq(30, 0.0)
+        };
+        let stats = TimeWeighted::new(at(0), at(40)).compute(&[tvq(0, 2.0), bad, text]);
+        assert_eq!(stats.covered, Duration::from_secs(20));
+        assert!(close(stats.time_average, 2.0));
+        assert_eq!(stats.samples, 1);
+
+        let empty = TimeWeighted::new(at(0), at(40)).compute(&[]);
+        assert_eq!((empty.time_average, empty.covered), (None, Duration::ZERO));
+    }
+
+    #[test]
+    fn reports_time_in_range_and_totals() {
+        let range = TimeWeighted::new(at(0), at(40)).in_range(5.0, 15.0);
+        let held = range.compute(&[tvq(0, 10.0), tvq(10, 20.0)]);
+        assert!(close(held.percent_in_range, 25.0));
+
+        // Ramping 0 to 20 over 20s spends half of it between 5 and 15.
+        let sloped = range
+            .sloped()
+            .compute(&[tvq(0, 0.0), tvq(20, 20.0), tvq(40, 20.0)]);
+        assert!(close(sloped.percent_in_range, 25.0));
+
+        let flow = TimeWeighted::new(at(0), at(7_200)).compute(&[tvq(0, 3.0)]);
+        assert!(close(Some(flow.total(Duration::from_secs(3_600))), 6.0));
+    }
+}
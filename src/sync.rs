@@ -0,0 +1,244 @@
+//! Copying history from one historian to another.
+//!
+//! A [`SyncJob`] reads each tag's raw history from a source [`ViewsClient`]
+//! in fixed windows and writes it to a target through a
+//! [`StoreAndForwardClient`], renaming tags with a [`TagMap`]. With a
+//! [`Checkpoint`] file, the end of every completed window is recorded per
+//! tag, so an interrupted sync picks up where it stopped instead of starting
+//! over.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::bulk::BulkFetch;
+use crate::error::Error;
+use crate::saf_client::StoreAndForwardClient;
+use crate::types::Tvq;
+use crate::views_client::ViewsClient;
+
+pub use crate::checkpoint::Checkpoint;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rule {
+    Exact { from: String, to: String },
+    Prefix { from: String, to: String },
+}
+
+/// How source tag names become target tag paths.
+///
+/// Rules are tried in the order they were added and the first match wins.
+/// Tags no rule matches keep their name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagMap {
+    rules: Vec<Rule>,
+}
+
+impl TagMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map one tag to a new path.
+    pub fn rename(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.rules.push(Rule::Exact {
+            from: from.into(),
+            to: to.into(),
+        });
+        self
+    }
+
+    /// Replace a leading `from` with `to`, e.g. moving `Plant1.` to `Site.Plant1.`.
+    pub fn prefix(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.rules.push(Rule::Prefix {
+            from: from.into(),
+            to: to.into(),
+        });
+        self
+    }
+
+    /// The target path of a source tag.
+    pub fn map(&self, tag: &str) -> String {
+        for rule in &self.rules {
+            match rule {
+                Rule::Exact { from, to } if from == tag => return to.clone(),
+                Rule::Prefix { from, to } => {
+                    if let Some(rest) = tag.strip_prefix(from.as_str()) {
+                        return format!("{to}{rest}");
+                    }
+                }
+                _ => {}
+            }
+        }
+        tag.to_string()
+    }
+}
+
+/// Where a running sync is, reported after every completed window.
+#[derive(Debug, Clone)]
+pub struct SyncProgress {
+    /// The source tag being copied.
+    pub tag: String,
+    /// The position of the tag in the job, from 0.
+    pub tag_index: usize,
+    pub tag_count: usize,
+    /// How far the tag has been copied.
+    pub through: SystemTime,
+    /// Samples written so far by the whole job.
+    pub samples: u64,
+}
+
+/// What a finished sync did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SyncSummary {
+    pub tags: usize,
+    pub samples: u64,
+}
+
+/// A planned copy of raw history between historians.
+#[derive(Debug, Clone)]
+pub struct SyncJob {
+    view: String,
+    tags: Vec<String>,
+    start: SystemTime,
+    end: SystemTime,
+    map: TagMap,
+    checkpoint: Option<PathBuf>,
+    window: Duration,
+    page_size: i32,
+    max_samples_per_sec: Option<f64>,
+}
+
+impl SyncJob {
+    pub fn new(
+        view: impl Into<String>,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Self {
+        Self {
+            view: view.into(),
+            tags: tags.into_iter().map(Into::into).collect(),
+            start,
+            end,
+            map: TagMap::default(),
+            checkpoint: None,
+            window: Duration::from_secs(24 * 3600),
+            page_size: 10_000,
+            max_samples_per_sec: None,
+        }
+    }
+
+    /// How to name the tags on the target. Defaults to keeping source names.
+    pub fn map(mut self, map: TagMap) -> Self {
+        self.map = map;
+        self
+    }
+
+    /// Record progress in a checkpoint file and resume from it.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    /// How much history to read and write at a time. Progress is
+    /// checkpointed after each window. Defaults to one day.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window.max(Duration::from_secs(1));
+        self
+    }
+
+    /// The maximum number of samples per tag in one read. Defaults to 10,000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Write at most this many samples per second on average.
+    pub fn max_samples_per_sec(mut self, rate: f64) -> Self {
+        self.max_samples_per_sec = (rate > 0.0).then_some(rate);
+        self
+    }
+
+    /// Copy every tag. `target` must have an open session.
+    pub async fn run(
+        &self,
+        source: &ViewsClient,
+        target: &mut StoreAndForwardClient,
+    ) -> Result<SyncSummary, Error> {
+        self.run_with_progress(source, target, |_| {}).await
+    }
+
+    /// Copy every tag, calling `progress` after each completed window.
+    pub async fn run_with_progress(
+        &self,
+        source: &ViewsClient,
+        target: &mut StoreAndForwardClient,
+        mut progress: impl FnMut(&SyncProgress),
+    ) -> Result<SyncSummary, Error> {
+        let mut checkpoint = self.checkpoint.clone().map(Checkpoint::load).transpose()?;
+        let started = Instant::now();
+        let mut summary = SyncSummary::default();
+
+        for (tag_index, tag) in self.tags.iter().enumerate() {
+            let target_tag = self.map.map(tag);
+            let mut cursor = checkpoint
+                .as_ref()
+                .and_then(|c| c.get(tag))
+                .map_or(self.start, |mark| mark.max(self.start));
+
+            while cursor < self.end {
+                let window_end = cursor
+                    .checked_add(self.window)
+                    .unwrap_or(self.end)
+                    .min(self.end);
+                let pages = BulkFetch::new(&self.view, [tag], cursor, window_end)
+                    .page_size(self.page_size)
+                    .run(source)
+                    .await?;
+                for page in pages {
+                    let page = page?;
+                    if page.error_code != 0 {
+                        return Err(format!(
+                            "reading {tag} failed ({}): {}",
+                            page.error_code, page.error_message
+                        )
+                        .into());
+                    }
+                    let tvqs: Vec<Tvq> = (0..page.len()).map(|row| page.tvq(row)).collect();
+                    if tvqs.is_empty() {
+                        continue;
+                    }
+                    target.store_tvqs(&target_tag, &tvqs).await?;
+                    summary.samples += tvqs.len() as u64;
+                    self.throttle(started, summary.samples).await;
+                }
+
+                cursor = window_end;
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.set(tag.as_str(), cursor);
+                    checkpoint.save()?;
+                }
+                progress(&SyncProgress {
+                    tag: tag.clone(),
+                    tag_index,
+                    tag_count: self.tags.len(),
+                    through: cursor,
+                    samples: summary.samples,
+                });
+            }
+            summary.tags += 1;
+        }
+        Ok(summary)
+    }
+
+    /// Sleep until writing `samples` since `started` is within the rate limit.
+    async fn throttle(&self, started: Instant, samples: u64) {
+        let Some(rate) = self.max_samples_per_sec else {
+            return;
+        };
+        let due = Duration::from_secs_f64(samples as f64 / rate);
+        if let Some(ahead) = due.checked_sub(started.elapsed()) {
+            tokio::time::sleep(ahead).await;
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Handles on individual tags.
+//!
+//! A [`Tag`] carries its view and path along with a handle to the client's
+//! connection, so exploratory code can browse or search for tags and then
+//! read each one without repeating identifiers:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! use std::time::{Duration, SystemTime};
+//!
+//! let found = client.search_tags(Default::default()).await?;
+//! for mut tag in client.tags_from_search("Plant", &found) {
+//!     let now = SystemTime::now();
+//!     let hourly = tag
+//!         .aggregate(now - Duration::from_secs(86_400)..now, Duration::from_secs(3600), "TimeAverage")
+//!         .await?;
+//!     println!("{tag}: {} = {:?}", tag.current().await?.value, hourly.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+use tonic::Status;
+
+use crate::bulk::BulkFetch;
+use crate::canary::views::grpc::api::{
+    AggregateTagRequest, BrowseTagsResponse, GetAggregateDataRequest, GetTagCurrentValueRequest,
+    SearchTagsResponse, SubscribeToLiveDataRequest, SubscribeToLiveDataResponse,
+};
+use crate::error::Error;
+use crate::types::{TagInfo, Tvq};
+use crate::views_client::ViewsClient;
+
+/// One tag in one view, with its own handle to the client's connection.
+pub struct Tag {
+    client: ViewsClient,
+    view: String,
+    path: String,
+}
+
+impl ViewsClient {
+    /// A handle on the tag at `path` in `view`.
+    pub fn tag(&self, view: impl Into<String>, path: impl Into<String>) -> Tag {
+        Tag {
+            client: self.data_handle(),
+            view: view.into(),
+            path: path.into(),
+        }
+    }
+
+    /// Handles on the tags of a browse result, read through `view`.
+    pub fn tags_from_browse(&self, view: &str, browsed: &BrowseTagsResponse) -> Vec<Tag> {
+        browsed
+            .tag_names
+            .iter()
+            .map(|path| self.tag(view, path))
+            .collect()
+    }
+
+    /// Handles on the tags of a search result, read through `view`.
+    pub fn tags_from_search(&self, view: &str, found: &SearchTagsResponse) -> Vec<Tag> {
+        found
+            .search
+            .iter()
+            .map(|tag| self.tag(view, &tag.tag_name))
+            .collect()
+    }
+}
+
+impl Tag {
+    pub fn view(&self) -> &str {
+        &self.view
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The tag's metadata and properties.
+    pub async fn info(&mut self) -> Result<TagInfo, Error> {
+        let resp = self
+            .client
+            .get_tag_info(&self.view, vec![self.path.clone()])
+            .await?;
+        resp.tag_infos
+            .iter()
+            .find(|info| info.tag_item_id == self.path)
+            .map(TagInfo::from)
+            .ok_or_else(|| self.not_found())
+    }
+
+    /// The tag's current value.
+    pub async fn current(&mut self) -> Result<Tvq, Error> {
+        let resp = self
+            .client
+            .get_tag_current_value(GetTagCurrentValueRequest {
+                view: self.view.clone(),
+                tag_names: vec![self.path.clone()],
+                use_time_extension: None,
+                quality: 0,
+                cci: 0,
+            })
+            .await?;
+        resp.tag_values
+            .iter()
+            .find(|current| current.tag_item_id == self.path)
+            .map(Tvq::from)
+            .ok_or_else(|| self.not_found())
+    }
+
+    /// Every raw sample in `range`, following continuation points.
+    pub async fn raw(&mut self, range: Range<SystemTime>) -> Result<Vec<Tvq>, Error> {
+        let tags = [self.path.clone()];
+        let pages = BulkFetch::new(&self.view, tags.clone(), range.start, range.end)
+            .fetch_chunk(&self.client, &tags)
+            .await?;
+        let mut tvqs = Vec::new();
+        for page in pages {
+            if page.error_code != 0 {
+                return Err(Status::unknown(format!(
+                    "reading {} failed ({}): {}",
+                    page.tag_name, page.error_code, page.error_message
+                ))
+                .into());
+            }
+            tvqs.extend((0..page.len()).map(|row| page.tvq(row)));
+        }
+        Ok(tvqs)
+    }
+
+    /// `aggregate` (e.g. `"TimeAverage"`) over each `interval` of `range`.
+    pub async fn aggregate(
+        &mut self,
+        range: Range<SystemTime>,
+        interval: Duration,
+        aggregate: &str,
+    ) -> Result<Vec<Tvq>, Error> {
+        let resp = self
+            .client
+            .get_aggregate_data(GetAggregateDataRequest {
+                view: self.view.clone(),
+                requests: vec![AggregateTagRequest {
+                    tag_name: self.path.clone(),
+                    aggregate_name: aggregate.to_string(),
+                    aggregate_configuration: None,
+                    sloped: false,
+                    client_data: 0,
+                }],
+                start_time: Some(range.start.into()),
+                end_time: Some(range.end.into()),
+                interval: prost_types::Duration::try_from(interval).ok(),
+                return_annotations: false,
+                cci: 0,
+            })
+            .await?;
+        let data = resp
+            .aggregated_data
+            .iter()
+            .find(|data| data.tag_name == self.path)
+            .ok_or_else(|| self.not_found())?;
+        if data.error_code != 0 {
+            return Err(Status::unknown(format!(
+                "aggregating {} failed ({}): {}",
+                data.tag_name, data.error_code, data.error_message
+            ))
+            .into());
+        }
+        Ok(data.tvqs.iter().map(Tvq::from).collect())
+    }
+
+    /// Subscribe to the tag's live updates.
+    pub async fn subscribe(&mut self) -> Result<TagSubscription, Error> {
+        let stream = self
+            .client
+            .subscribe_to_live_data(SubscribeToLiveDataRequest {
+                tags: vec![self.path.clone()],
+                ..Default::default()
+            })
+            .await?;
+        Ok(TagSubscription {
+            stream,
+            path: self.path.clone(),
+        })
+    }
+
+    fn not_found(&self) -> Error {
+        Status::not_found(format!("tag {} not found in {}", self.path, self.view)).into()
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tag")
+            .field("view", &self.view)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A live subscription to one tag.
+pub struct TagSubscription {
+    stream: tonic::Streaming<SubscribeToLiveDataResponse>,
+    path: String,
+}
+
+impl TagSubscription {
+    /// The samples of the next update, or `None` when the subscription
+    /// ends.
+    pub async fn next(&mut self) -> Result<Option<Vec<Tvq>>, Error> {
+        loop {
+            let Some(mut resp) = self.stream.message().await? else {
+                return Ok(None);
+            };
+            if let Some(data) = resp.tags_and_data.remove(&self.path) {
+                return Ok(Some(data.tvqs.iter().map(Tvq::from).collect()));
+            }
+        }
+    }
+}
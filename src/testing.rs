@@ -0,0 +1,43 @@
+//! Test support for code built on crowsong, enabled with the `testing` feature.
+//!
+//! [`MockViewsServer`] implements the Views service in memory and serves it
+//! over an in-process channel, so tests can run against seeded data without a
+//! real historian. [`Recorder`] and [`Replayer`] capture the traffic of a real
+//! session to a [`Fixture`] file and serve it back later. [`TestServer`] puts
+//! the mock on a real TCP port, optionally behind TLS, for end-to-end tests of
+//! the connection path. [`generate`] produces deterministic synthetic tags and
+//! history to seed them with.
+
+mod fixture;
+pub mod generate;
+mod harness;
+mod mock;
+
+pub use fixture::{Exchange, Fixture, Recorder, Replayer};
+pub use harness::{TestServer, TestServerBuilder};
+pub use mock::{EVENTS_PAGE_SIZE, Fault, MOCK_API_KEY, MockViewsServer, RecordedRequest};
+
+use http::Uri;
+use hyper_util::rt::TokioIo;
+use tokio::io::DuplexStream;
+use tokio::sync::mpsc;
+use tonic::transport::{Channel, Endpoint};
+use tower::service_fn;
+
+/// A lazily-connected channel whose connections arrive on the returned
+/// receiver as in-memory streams, ready to be served.
+fn in_process_channel() -> (Channel, mpsc::UnboundedReceiver<DuplexStream>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let channel = Endpoint::from_static("http://crowsong.mock").connect_with_connector_lazy(
+        service_fn(move |_: Uri| {
+            let tx = tx.clone();
+            async move {
+                let (client, server) = tokio::io::duplex(64 * 1024);
+                tx.send(server)
+                    .map_err(|_| std::io::Error::other("in-process server stopped"))?;
+                Ok::<_, std::io::Error>(TokioIo::new(client))
+            }
+        }),
+    );
+    (channel, rx)
+}
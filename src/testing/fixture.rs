@@ -0,0 +1,384 @@
+use std::convert::Infallible;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, ready};
+
+use bytes::Bytes;
+use http_body::{Body, Frame};
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::Incoming;
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use prost::Message;
+use tokio::io::DuplexStream;
+use tokio::sync::mpsc;
+use tonic::codegen::tokio_stream;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+use tower::ServiceExt;
+
+use crate::ViewsClient;
+use crate::error::Error;
+use crate::testing::in_process_channel;
+use crate::tls::{TlsPolicy, tls_channel};
+
+/// Length of the gRPC message prefix (compression flag and length).
+const GRPC_HEADER_LEN: usize = 5;
+
+/// One recorded call: the request sent and the response the service gave.
+///
+/// Bodies are kept as gRPC-framed bytes; streaming responses hold every
+/// message received, back to back. The API token is never recorded.
+#[derive(Clone, PartialEq, Message)]
+pub struct Exchange {
+    /// The gRPC path, e.g. `/canary.views.grpc.api.CanaryViewsApiService/GetRawData`.
+    #[prost(string, tag = "1")]
+    pub method: String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub request: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub response: Vec<u8>,
+    #[prost(int32, tag = "4")]
+    pub grpc_status: i32,
+    #[prost(string, tag = "5")]
+    pub grpc_message: String,
+}
+
+impl Exchange {
+    /// The RPC name, e.g. `"GetRawData"`.
+    pub fn rpc(&self) -> &str {
+        self.method.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// Decode the request as its protobuf message type.
+    pub fn decode_request<M: Message + Default>(&self) -> Result<M, prost::DecodeError> {
+        M::decode(self.request.get(GRPC_HEADER_LEN..).unwrap_or_default())
+    }
+
+    /// Decode every response message as its protobuf message type.
+    pub fn decode_responses<M: Message + Default>(&self) -> Result<Vec<M>, prost::DecodeError> {
+        let mut messages = Vec::new();
+        let mut rest = self.response.as_slice();
+        while rest.len() >= GRPC_HEADER_LEN {
+            let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+            let end = (GRPC_HEADER_LEN + len).min(rest.len());
+            messages.push(M::decode(&rest[GRPC_HEADER_LEN..end])?);
+            rest = &rest[end..];
+        }
+        Ok(messages)
+    }
+}
+
+/// A recorded session, stored as a binary protobuf file.
+#[derive(Clone, PartialEq, Message)]
+pub struct Fixture {
+    #[prost(message, repeated, tag = "1")]
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Fixture {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::decode(bytes.as_slice())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.encode_to_vec())
+    }
+}
+
+type ExchangeLog = Arc<Mutex<Vec<Exchange>>>;
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Serve each in-process connection with `handler` over HTTP/2.
+fn serve<F, Fut>(mut rx: mpsc::UnboundedReceiver<DuplexStream>, handler: F)
+where
+    F: Fn(http::Request<Incoming>) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = http::Response<tonic::body::Body>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        while let Some(io) = rx.recv().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let handler = handler.clone();
+                    async move { Ok::<_, Infallible>(handler(req).await) }
+                });
+                let _ = http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(io), service)
+                    .await;
+            });
+        }
+    });
+}
+
+/// Records every call made through its channels, for replay with [`Replayer`].
+///
+/// Responses are passed through to the client as they arrive, so live
+/// subscriptions keep streaming while they are recorded.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    log: ExchangeLog,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect to a Canary Views service, recording the session.
+    pub async fn connect(
+        &self,
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+        app: impl Into<String>,
+        user_id: impl Into<String>,
+    ) -> Result<ViewsClient, Error> {
+        let endpoint = Endpoint::from_shared(endpoint.into())?;
+        let channel = tls_channel(endpoint, &TlsPolicy::default(), None)?;
+        self.connect_channel(channel, api_key, app, user_id).await
+    }
+
+    /// Connect over an existing channel (e.g. [`MockViewsServer::channel`]),
+    /// recording the session.
+    ///
+    /// [`MockViewsServer::channel`]: crate::testing::MockViewsServer::channel
+    pub async fn connect_channel(
+        &self,
+        upstream: Channel,
+        api_key: impl Into<String>,
+        app: impl Into<String>,
+        user_id: impl Into<String>,
+    ) -> Result<ViewsClient, Error> {
+        ViewsClient::with_channel(self.record_channel(upstream), api_key, app, user_id).await
+    }
+
+    /// Wrap an existing channel so that calls made through it are recorded.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn record_channel(&self, upstream: Channel) -> Channel {
+        let (channel, rx) = in_process_channel();
+        let log = self.log.clone();
+        serve(rx, move |req| forward(upstream.clone(), log.clone(), req));
+        channel
+    }
+
+    /// The calls recorded so far. Streams still open are recorded up to this point.
+    pub fn fixture(&self) -> Fixture {
+        Fixture {
+            exchanges: lock(&self.log).clone(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        self.fixture().save(path)
+    }
+}
+
+async fn forward(
+    upstream: Channel,
+    log: ExchangeLog,
+    req: http::Request<Incoming>,
+) -> http::Response<tonic::body::Body> {
+    let (parts, body) = req.into_parts();
+    let request = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => return Status::internal(e.to_string()).into_http(),
+    };
+    let method = parts.uri.path().to_string();
+    let upstream_req =
+        http::Request::from_parts(parts, tonic::body::Body::new(Full::new(request.clone())));
+    let resp = match upstream.oneshot(upstream_req).await {
+        Ok(resp) => resp,
+        Err(e) => return Status::unavailable(e.to_string()).into_http(),
+    };
+
+    let mut exchange = Exchange {
+        method,
+        request: request.to_vec(),
+        ..Default::default()
+    };
+    // A trailers-only response carries its status in the headers.
+    record_status(&mut exchange, resp.headers());
+    let index = {
+        let mut log = lock(&log);
+        log.push(exchange);
+        log.len() - 1
+    };
+    resp.map(|inner| tonic::body::Body::new(TeeBody { inner, log, index }))
+}
+
+fn record_status(exchange: &mut Exchange, headers: &http::HeaderMap) {
+    if let Some(code) = headers
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+    {
+        exchange.grpc_status = code;
+    }
+    if let Some(message) = headers.get("grpc-message").and_then(|v| v.to_str().ok()) {
+        exchange.grpc_message = percent_decode(message);
+    }
+}
+
+/// Decode a percent-encoded `grpc-message`.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// A response body that copies each frame into its slot in the log as it
+/// passes through.
+struct TeeBody {
+    inner: tonic::body::Body,
+    log: ExchangeLog,
+    index: usize,
+}
+
+impl Body for TeeBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Status>>> {
+        let this = &mut *self;
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        let mut log = lock(&this.log);
+        let exchange = &mut log[this.index];
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    exchange.response.extend_from_slice(data);
+                } else if let Some(trailers) = frame.trailers_ref() {
+                    record_status(exchange, trailers);
+                }
+            }
+            Some(Err(status)) => {
+                exchange.grpc_status = status.code() as i32;
+                exchange.grpc_message = status.message().to_string();
+            }
+            None => {}
+        }
+        drop(log);
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+/// Serves a [`Fixture`] back in place of a live service.
+///
+/// Each request is answered with the first unused recorded exchange for the
+/// same RPC and identical request bytes. Failing that, a previously used
+/// identical exchange is reused (e.g. repeated keepalives), and finally the
+/// next unused exchange for the same RPC is served, so sessions whose
+/// requests embed the current time still replay in order. Requests with no
+/// recording fail with `NOT_FOUND`.
+#[derive(Clone)]
+pub struct Replayer {
+    exchanges: Arc<Vec<Exchange>>,
+    used: Arc<Mutex<Vec<bool>>>,
+}
+
+impl Replayer {
+    pub fn new(fixture: Fixture) -> Self {
+        let used = vec![false; fixture.exchanges.len()];
+        Self {
+            exchanges: Arc::new(fixture.exchanges),
+            used: Arc::new(Mutex::new(used)),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(Fixture::load(path)?))
+    }
+
+    /// Serve the fixture over a fresh in-process channel.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn channel(&self) -> Channel {
+        let (channel, rx) = in_process_channel();
+        let replayer = self.clone();
+        serve(rx, move |req| {
+            let replayer = replayer.clone();
+            async move { replayer.respond(req).await }
+        });
+        channel
+    }
+
+    /// Connect a [`ViewsClient`] to the fixture. The client connection ID is
+    /// the one from the recording.
+    pub async fn client(&self) -> Result<ViewsClient, Error> {
+        ViewsClient::with_channel(self.channel(), "replay", "crowsong-replay", "replay").await
+    }
+
+    fn find(&self, method: &str, request: &[u8]) -> Option<&Exchange> {
+        let mut used = lock(&self.used);
+        let same_rpc = |i: &usize| self.exchanges[*i].method == method;
+        let identical = |i: &usize| same_rpc(i) && self.exchanges[*i].request == request;
+        let all = 0..self.exchanges.len();
+        let index = all
+            .clone()
+            .filter(identical)
+            .find(|i| !used[*i])
+            .or_else(|| all.clone().rfind(identical))
+            .or_else(|| all.clone().filter(same_rpc).find(|i| !used[*i]))?;
+        used[index] = true;
+        Some(&self.exchanges[index])
+    }
+
+    async fn respond(&self, req: http::Request<Incoming>) -> http::Response<tonic::body::Body> {
+        let method = req.uri().path().to_string();
+        let request = match req.into_body().collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => return Status::internal(e.to_string()).into_http(),
+        };
+        let Some(exchange) = self.find(&method, &request) else {
+            return Status::not_found(format!("no recorded response for {method}")).into_http();
+        };
+        let status = Status::new(
+            Code::from(exchange.grpc_status),
+            exchange.grpc_message.clone(),
+        );
+        if exchange.response.is_empty() {
+            return status.into_http();
+        }
+
+        let mut trailers = http::HeaderMap::new();
+        let _ = status.add_header(&mut trailers);
+        let frames = [
+            Frame::data(Bytes::from(exchange.response.clone())),
+            Frame::trailers(trailers),
+        ];
+        let body = StreamBody::new(tokio_stream::iter(frames.map(Ok::<_, Infallible>)));
+        http::Response::builder()
+            .header("content-type", "application/grpc")
+            .body(tonic::body::Body::new(body))
+            .unwrap_or_default()
+    }
+}
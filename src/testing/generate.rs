@@ -0,0 +1,391 @@
+//! Deterministic synthetic tags and TVQ series.
+//!
+//! Everything here is driven by an explicit seed, so the same call always
+//! produces the same namespace and the same samples. Use it to seed a
+//! [`MockViewsServer`], feed examples, or benchmark exports without access to a
+//! plant historian.
+
+use std::f64::consts::TAU;
+use std::time::{Duration, SystemTime};
+
+use crate::quality::Quality;
+use crate::testing::MockViewsServer;
+use crate::types::{TagSeries, Tvq, Value};
+
+/// Quality reported for samples inside a dropout: "Bad - Not Connected".
+const DROPOUT_QUALITY: Quality = Quality(0x08);
+
+/// A small, seedable PRNG (SplitMix64). Not for anything but test data.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A normally distributed float (Box-Muller).
+    pub fn gaussian(&mut self, mean: f64, std_dev: f64) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        mean + std_dev * (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+
+    /// A uniform integer in `[0, n)`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
+
+/// The noise-free shape of a series over time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signal {
+    Constant(f64),
+    Sine {
+        offset: f64,
+        amplitude: f64,
+        period: Duration,
+    },
+    /// Cycle through `levels`, holding each for `hold`.
+    Step {
+        levels: Vec<f64>,
+        hold: Duration,
+    },
+    /// Rise from `from` to `to` over `period`, then start again.
+    Sawtooth {
+        from: f64,
+        to: f64,
+        period: Duration,
+    },
+}
+
+impl Signal {
+    /// The value `elapsed` after the start of the series.
+    pub fn at(&self, elapsed: Duration) -> f64 {
+        let t = elapsed.as_secs_f64();
+        match self {
+            Signal::Constant(v) => *v,
+            Signal::Sine {
+                offset,
+                amplitude,
+                period,
+            } => offset + amplitude * (TAU * t / period.as_secs_f64().max(f64::EPSILON)).sin(),
+            Signal::Step { levels, hold } => {
+                if levels.is_empty() {
+                    return 0.0;
+                }
+                let index = (t / hold.as_secs_f64().max(f64::EPSILON)) as usize;
+                levels[index % levels.len()]
+            }
+            Signal::Sawtooth { from, to, period } => {
+                let period = period.as_secs_f64().max(f64::EPSILON);
+                from + (to - from) * (t % period) / period
+            }
+        }
+    }
+}
+
+/// How to turn a [`Signal`] into samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesSpec {
+    pub signal: Signal,
+    /// Standard deviation of Gaussian noise added to each sample.
+    pub noise: f64,
+    /// Probability that a sample starts a dropout of 1–5 bad, null samples.
+    pub dropout: f64,
+    /// Probability that a sample is reported with uncertain quality.
+    pub uncertain: f64,
+    /// Report values as booleans (non-zero is `true`).
+    pub discrete: bool,
+    /// Round values to this many decimal places.
+    pub decimals: Option<u32>,
+    /// Clamp values (after noise) to `(min, max)`.
+    pub range: Option<(f64, f64)>,
+}
+
+impl Default for SeriesSpec {
+    fn default() -> Self {
+        Self {
+            signal: Signal::Constant(0.0),
+            noise: 0.0,
+            dropout: 0.0,
+            uncertain: 0.0,
+            discrete: false,
+            decimals: None,
+            range: None,
+        }
+    }
+}
+
+impl SeriesSpec {
+    pub fn new(signal: Signal) -> Self {
+        Self {
+            signal,
+            ..Default::default()
+        }
+    }
+
+    pub fn noise(mut self, std_dev: f64) -> Self {
+        self.noise = std_dev;
+        self
+    }
+
+    pub fn dropout(mut self, probability: f64) -> Self {
+        self.dropout = probability;
+        self
+    }
+
+    pub fn uncertain(mut self, probability: f64) -> Self {
+        self.uncertain = probability;
+        self
+    }
+
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    /// Generate `count` samples spaced `interval` apart, starting at `start`.
+    pub fn generate(
+        &self,
+        seed: u64,
+        start: SystemTime,
+        interval: Duration,
+        count: usize,
+    ) -> Vec<Tvq> {
+        let mut rng = Rng::new(seed);
+        let mut dropout_left = 0;
+        (0..count)
+            .map(|i| {
+                let elapsed = interval * i as u32;
+                let timestamp = start + elapsed;
+                if dropout_left == 0 && rng.next_f64() < self.dropout {
+                    dropout_left = 1 + rng.below(5);
+                }
+                if dropout_left > 0 {
+                    dropout_left -= 1;
+                    return Tvq {
+                        timestamp,
+                        value: Value::Null,
+                        quality: DROPOUT_QUALITY,
+                    };
+                }
+
+                let mut v = self.signal.at(elapsed);
+                if self.noise > 0.0 {
+                    v = rng.gaussian(v, self.noise);
+                }
+                if let Some((min, max)) = self.range {
+                    v = v.clamp(min, max);
+                }
+                let value = if self.discrete {
+                    Value::Bool(v.round() != 0.0)
+                } else {
+                    Value::Float(match self.decimals {
+                        Some(d) => {
+                            let scale = 10f64.powi(d as i32);
+                            (v * scale).round() / scale
+                        }
+                        None => v,
+                    })
+                };
+                let quality = if rng.next_f64() < self.uncertain {
+                    Quality::UNCERTAIN
+                } else {
+                    Quality::GOOD
+                };
+                Tvq {
+                    timestamp,
+                    value,
+                    quality,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A synthetic tag with its metadata and how its history is generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedTag {
+    pub name: String,
+    pub eng_units: String,
+    pub description: String,
+    pub spec: SeriesSpec,
+}
+
+impl GeneratedTag {
+    /// This tag's samples. The seed is mixed with the tag name, so a tag's
+    /// history does not depend on which other tags were generated.
+    pub fn series(
+        &self,
+        seed: u64,
+        start: SystemTime,
+        interval: Duration,
+        count: usize,
+    ) -> TagSeries {
+        TagSeries {
+            tag_name: self.name.clone(),
+            eng_units: Some(self.eng_units.clone()).filter(|u| !u.is_empty()),
+            tvqs: self
+                .spec
+                .generate(seed ^ fnv1a(&self.name), start, interval, count),
+        }
+    }
+}
+
+const SITES: &[&str] = &["North", "South", "East", "West", "Central"];
+const AREAS: &[&str] = &["Boiler", "Compressor", "Cooling", "Pump", "Reactor", "Tank"];
+
+/// Measurement name, units, and description for each tag of a generated unit.
+const MEASUREMENTS: &[(&str, &str, &str)] = &[
+    ("Temperature", "degF", "Process temperature"),
+    ("Pressure", "psi", "Discharge pressure"),
+    ("Flow", "gpm", "Outlet flow"),
+    ("Level", "%", "Vessel level"),
+    ("Speed", "rpm", "Motor speed"),
+    ("Vibration", "in/s", "Bearing vibration"),
+    ("Running", "", "Motor running status"),
+];
+
+/// A typical series for a measurement, with per-unit variation drawn from `rng`.
+fn measurement_spec(measurement: &str, rng: &mut Rng) -> SeriesSpec {
+    let jitter = |rng: &mut Rng, base: f64, spread: f64| base + spread * (rng.next_f64() - 0.5);
+    let minutes = |rng: &mut Rng, base: u64| {
+        Duration::from_secs(60 * (base + rng.below(base as usize) as u64))
+    };
+    let spec = match measurement {
+        "Temperature" => SeriesSpec::new(Signal::Sine {
+            offset: jitter(rng, 180.0, 40.0),
+            amplitude: jitter(rng, 12.0, 6.0),
+            period: minutes(rng, 60),
+        })
+        .noise(0.4),
+        "Pressure" => SeriesSpec::new(Signal::Sine {
+            offset: jitter(rng, 120.0, 30.0),
+            amplitude: jitter(rng, 8.0, 4.0),
+            period: minutes(rng, 45),
+        })
+        .noise(0.8),
+        "Flow" => SeriesSpec::new(Signal::Constant(jitter(rng, 450.0, 100.0))).noise(6.0),
+        "Level" => SeriesSpec::new(Signal::Sawtooth {
+            from: jitter(rng, 20.0, 10.0),
+            to: jitter(rng, 85.0, 10.0),
+            period: minutes(rng, 120),
+        })
+        .noise(0.2)
+        .range(0.0, 100.0),
+        "Speed" => SeriesSpec::new(Signal::Step {
+            levels: vec![0.0, 1750.0, 3500.0, 1750.0],
+            hold: minutes(rng, 30),
+        })
+        .noise(3.0)
+        .range(0.0, f64::MAX),
+        "Vibration" => SeriesSpec::new(Signal::Constant(jitter(rng, 0.15, 0.1)))
+            .noise(0.02)
+            .range(0.0, f64::MAX),
+        "Running" => SeriesSpec {
+            discrete: true,
+            ..SeriesSpec::new(Signal::Step {
+                levels: vec![1.0, 1.0, 1.0, 0.0],
+                hold: minutes(rng, 30),
+            })
+        },
+        _ => SeriesSpec::new(Signal::Constant(0.0)),
+    };
+    SeriesSpec {
+        dropout: 0.002,
+        uncertain: 0.005,
+        decimals: (!spec.discrete).then_some(3),
+        ..spec
+    }
+}
+
+/// A synthetic plant: a realistic `Site.AreaNN.Measurement` tag namespace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plant {
+    seed: u64,
+    tags: Vec<GeneratedTag>,
+}
+
+impl Plant {
+    /// Generate a plant with `units` equipment units of seven measurements each.
+    pub fn new(seed: u64, units: usize) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut tags = Vec::with_capacity(units * MEASUREMENTS.len());
+        for unit in 0..units {
+            let site = SITES[unit / AREAS.len() % SITES.len()];
+            let area = AREAS[unit % AREAS.len()];
+            let number = unit / (AREAS.len() * SITES.len()) + 1;
+            for (measurement, eng_units, description) in MEASUREMENTS {
+                tags.push(GeneratedTag {
+                    name: format!("{site}.{area}{number:02}.{measurement}"),
+                    eng_units: eng_units.to_string(),
+                    description: format!("{area} {number} {}", description.to_lowercase()),
+                    spec: measurement_spec(measurement, &mut rng),
+                });
+            }
+        }
+        Self { seed, tags }
+    }
+
+    pub fn tags(&self) -> &[GeneratedTag] {
+        &self.tags
+    }
+
+    pub fn tag_names(&self) -> Vec<String> {
+        self.tags.iter().map(|t| t.name.clone()).collect()
+    }
+
+    /// Samples for every tag.
+    pub fn series(&self, start: SystemTime, interval: Duration, count: usize) -> Vec<TagSeries> {
+        self.tags
+            .iter()
+            .map(|t| t.series(self.seed, start, interval, count))
+            .collect()
+    }
+
+    /// Register every tag, its properties, and its history with a mock.
+    pub fn seed_mock(
+        &self,
+        mock: &MockViewsServer,
+        view: &str,
+        dataset: &str,
+        start: SystemTime,
+        interval: Duration,
+        count: usize,
+    ) {
+        for tag in &self.tags {
+            mock.add_tag(view, dataset, &tag.name).set_tag_property(
+                view,
+                &tag.name,
+                "Description",
+                &tag.description,
+            );
+            if !tag.eng_units.is_empty() {
+                mock.set_tag_property(view, &tag.name, "EngUnits", &tag.eng_units);
+            }
+            let series = tag.series(self.seed, start, interval, count);
+            mock.add_tvqs(view, &tag.name, series.tvqs);
+        }
+    }
+}
+
+/// FNV-1a, used to derive stable per-tag seeds.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
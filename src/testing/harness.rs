@@ -0,0 +1,165 @@
+use std::net::SocketAddr;
+
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+
+use crate::ViewsClient;
+use crate::canary::views::grpc::api::canary_views_api_service_server::CanaryViewsApiServiceServer;
+use crate::error::Error;
+use crate::testing::{MOCK_API_KEY, MockViewsServer};
+use crate::tls::TlsPolicy;
+
+/// Builder for a [`TestServer`].
+pub struct TestServerBuilder {
+    mock: MockViewsServer,
+    tls: bool,
+    addr: SocketAddr,
+}
+
+impl TestServerBuilder {
+    /// Serve over TLS with a freshly generated self-signed certificate.
+    pub fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// The address to listen on. Defaults to an ephemeral port on 127.0.0.1.
+    pub fn addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    /// Bind the listener and start serving in the background.
+    pub async fn start(self) -> Result<TestServer, Error> {
+        let incoming = TcpIncoming::bind(self.addr)?;
+        let addr = incoming.local_addr()?;
+
+        let mut server = Server::builder();
+        let mut ca_pem = None;
+        if self.tls {
+            if rustls::crypto::CryptoProvider::get_default().is_none() {
+                let _ = rustls::crypto::ring::default_provider().install_default();
+            }
+            let cert = rcgen::generate_simple_self_signed(vec![
+                "localhost".to_string(),
+                addr.ip().to_string(),
+            ])
+            .map_err(Error::other)?;
+            let identity = Identity::from_pem(cert.cert.pem(), cert.signing_key.serialize_pem());
+            server = server.tls_config(ServerTlsConfig::new().identity(identity))?;
+            ca_pem = Some(cert.cert.pem());
+        }
+
+        let (shutdown, rx) = oneshot::channel::<()>();
+        let service = CanaryViewsApiServiceServer::new(self.mock.clone());
+        let task = tokio::spawn(async move {
+            let _ = server
+                .add_service(service)
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = rx.await;
+                })
+                .await;
+        });
+
+        Ok(TestServer {
+            mock: self.mock,
+            addr,
+            ca_pem,
+            shutdown: Some(shutdown),
+            task: Some(task),
+        })
+    }
+}
+
+/// A [`MockViewsServer`] listening on a real TCP port, optionally over TLS.
+///
+/// Unlike [`MockViewsServer::client`], clients go through
+/// [`ViewsClient::connect`], exercising the same transport, TLS, and API key
+/// path as a production connection. Auth checks, latency, and faults are
+/// configured on the mock. The server stops when dropped.
+pub struct TestServer {
+    mock: MockViewsServer,
+    addr: SocketAddr,
+    /// The self-signed certificate, when serving over TLS.
+    ca_pem: Option<String>,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub fn builder(mock: MockViewsServer) -> TestServerBuilder {
+        TestServerBuilder {
+            mock,
+            tls: false,
+            addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        }
+    }
+
+    /// Start a plain-text server on an ephemeral port.
+    pub async fn start(mock: MockViewsServer) -> Result<Self, Error> {
+        Self::builder(mock).start().await
+    }
+
+    /// The mock backing this server, for seeding and inspecting requests.
+    pub fn mock(&self) -> &MockViewsServer {
+        &self.mock
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The URL to connect to. Over TLS, also give the client
+    /// [`tls_policy`](Self::tls_policy).
+    pub fn endpoint(&self) -> String {
+        let scheme = if self.ca_pem.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        format!("{scheme}://{}", self.addr)
+    }
+
+    /// Connect a client with [`MOCK_API_KEY`].
+    pub async fn connect(&self) -> Result<ViewsClient, Error> {
+        self.connect_with_key(MOCK_API_KEY).await
+    }
+
+    /// The TLS policy that trusts this server's self-signed certificate.
+    pub fn tls_policy(&self) -> TlsPolicy {
+        match &self.ca_pem {
+            Some(pem) => TlsPolicy::new().native_roots(false).ca_pem(pem.as_bytes()),
+            None => TlsPolicy::new(),
+        }
+    }
+
+    /// Connect a client with a specific API key.
+    pub async fn connect_with_key(&self, api_key: impl Into<String>) -> Result<ViewsClient, Error> {
+        ViewsClient::builder(self.endpoint(), api_key)
+            .app("crowsong-test")
+            .user_id("test")
+            .tls_policy(self.tls_policy())
+            .connect()
+            .await
+    }
+
+    /// Stop the server and wait for it to finish.
+    pub async fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,1131 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use prost::Message;
+use tokio::sync::mpsc;
+use tonic::codegen::BoxStream;
+use tonic::codegen::tokio_stream::StreamExt;
+use tonic::codegen::tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request, Response, Status};
+
+use crate::ViewsClient;
+use crate::canary::calculations::grpc::common::Event;
+use crate::canary::utility::protobuf_shared_types::GrpcTvq;
+use crate::canary::views::grpc::api::canary_views_api_service_server::{
+    CanaryViewsApiService, CanaryViewsApiServiceServer,
+};
+use crate::canary::views::grpc::api::*;
+use crate::error::Error;
+use crate::quality::Quality;
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::testing::in_process_channel;
+use crate::types::{Tvq, Value};
+
+/// The API token sent by clients from [`MockViewsServer::client`].
+pub const MOCK_API_KEY: &str = "mock-api-key";
+
+/// The number of events the mock returns per `FindEvents` page.
+pub const EVENTS_PAGE_SIZE: usize = 100;
+
+/// A request received by the mock, kept in its encoded protobuf form.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// The RPC name, e.g. `"GetRawData"`.
+    pub method: String,
+    /// The `canary-api-token` metadata sent with the request, if any.
+    pub api_token: Option<String>,
+    /// The `x-request-id` metadata sent with the request, if any.
+    pub request_id: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Decode the request body as its protobuf message type.
+    pub fn decode<M: Message + Default>(&self) -> Result<M, prost::DecodeError> {
+        M::decode(self.body.as_slice())
+    }
+}
+
+#[derive(Default)]
+struct MockTag {
+    properties: BTreeMap<String, String>,
+    tvqs: Vec<Tvq>,
+}
+
+#[derive(Default)]
+struct MockView {
+    datasets: BTreeMap<String, MockDataset>,
+    tags: BTreeMap<String, MockTag>,
+}
+
+#[derive(Default)]
+struct MockDataset {
+    properties: Vec<(String, String)>,
+    tags: Vec<String>,
+}
+
+/// A failure the mock returns in place of a normal response.
+///
+/// By default a fault applies to every RPC, indefinitely; narrow it with
+/// [`Fault::on`], [`Fault::after`], and [`Fault::times`].
+#[derive(Debug, Clone)]
+pub struct Fault {
+    method: Option<String>,
+    code: Code,
+    message: String,
+    skip: u32,
+    remaining: Option<u32>,
+}
+
+impl Fault {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            method: None,
+            code,
+            message: message.into(),
+            skip: 0,
+            remaining: None,
+        }
+    }
+
+    /// Only fail calls to one RPC, e.g. `"GetRawData"`.
+    pub fn on(mut self, method: impl Into<String>) -> Self {
+        self.method = Some(method.into());
+        self
+    }
+
+    /// Let the first `n` matching calls through before failing.
+    pub fn after(mut self, n: u32) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Fail `n` matching calls, then stop.
+    pub fn times(mut self, n: u32) -> Self {
+        self.remaining = Some(n);
+        self
+    }
+
+    fn matches(&self, method: &str) -> bool {
+        self.method.as_deref().is_none_or(|m| m == method)
+    }
+}
+
+type LiveSender = mpsc::Sender<Result<SubscribeToLiveDataResponse, Status>>;
+
+struct MockState {
+    version: String,
+    views: BTreeMap<String, MockView>,
+    aggregates: Vec<(String, String)>,
+    next_cci: i32,
+    active_ccis: BTreeSet<i32>,
+    requests: Vec<RecordedRequest>,
+    api_key: Option<String>,
+    latency: Duration,
+    clock_offset: f64,
+    stuck_paging: bool,
+    faults: Vec<Fault>,
+    subscribers: Vec<(BTreeSet<String>, LiveSender)>,
+    events: Vec<Event>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            version: "crowsong-mock".to_string(),
+            views: BTreeMap::new(),
+            aggregates: [
+                ("TimeAverage", "Time-weighted average over the interval"),
+                ("Average", "Average of the samples in the interval"),
+                ("Minimum", "Minimum value in the interval"),
+                ("Maximum", "Maximum value in the interval"),
+                ("Count", "Number of samples in the interval"),
+                ("Total", "Sum of the samples in the interval"),
+                ("First", "First value in the interval"),
+                ("Last", "Last value in the interval"),
+                (
+                    "PercentGood",
+                    "Percent of the samples in the interval with good quality",
+                ),
+                (
+                    "PercentBad",
+                    "Percent of the samples in the interval with bad quality",
+                ),
+            ]
+            .into_iter()
+            .map(|(n, d)| (n.to_string(), d.to_string()))
+            .collect(),
+            next_cci: 1,
+            active_ccis: BTreeSet::new(),
+            requests: Vec::new(),
+            api_key: None,
+            latency: Duration::ZERO,
+            clock_offset: 0.0,
+            stuck_paging: false,
+            faults: Vec::new(),
+            subscribers: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+/// An in-memory Views service for tests.
+///
+/// Seed it with views, datasets, tags, properties, and TVQs, then obtain a
+/// connected [`ViewsClient`] with [`MockViewsServer::client`]. Every request
+/// the mock receives is recorded and can be inspected afterwards. RPCs the
+/// mock does not model return `UNIMPLEMENTED`.
+#[derive(Clone, Default)]
+pub struct MockViewsServer {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockViewsServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Set the version string reported by `GetWebServiceVersion`.
+    pub fn set_version(&self, version: impl Into<String>) -> &Self {
+        self.state().version = version.into();
+        self
+    }
+
+    /// Add an empty view.
+    pub fn add_view(&self, view: impl Into<String>) -> &Self {
+        self.state().views.entry(view.into()).or_default();
+        self
+    }
+
+    /// Add a dataset to a view, creating the view if needed.
+    pub fn add_dataset(&self, view: impl Into<String>, dataset: impl Into<String>) -> &Self {
+        self.state()
+            .views
+            .entry(view.into())
+            .or_default()
+            .datasets
+            .entry(dataset.into())
+            .or_default();
+        self
+    }
+
+    /// Set a property reported by `GetDatasetInfo`.
+    pub fn set_dataset_property(
+        &self,
+        view: impl Into<String>,
+        dataset: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> &Self {
+        let mut state = self.state();
+        let dataset = state
+            .views
+            .entry(view.into())
+            .or_default()
+            .datasets
+            .entry(dataset.into())
+            .or_default();
+        let name = name.into();
+        dataset.properties.retain(|(n, _)| *n != name);
+        dataset.properties.push((name, value.into()));
+        self
+    }
+
+    /// Add a tag to a dataset, creating the view and dataset if needed.
+    ///
+    /// The tag name is used verbatim in every response, so seed it the way the
+    /// real service would report it (e.g. `"Plant.Line1.Temperature"`).
+    pub fn add_tag(
+        &self,
+        view: impl Into<String>,
+        dataset: impl Into<String>,
+        tag: impl Into<String>,
+    ) -> &Self {
+        let tag = tag.into();
+        let mut state = self.state();
+        let view = state.views.entry(view.into()).or_default();
+        let dataset = view.datasets.entry(dataset.into()).or_default();
+        if !dataset.tags.contains(&tag) {
+            dataset.tags.push(tag.clone());
+        }
+        view.tags.entry(tag).or_default();
+        self
+    }
+
+    /// Set a tag property reported by `GetTagInfo` (e.g. `"EngUnits"`).
+    pub fn set_tag_property(
+        &self,
+        view: impl Into<String>,
+        tag: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> &Self {
+        self.state()
+            .views
+            .entry(view.into())
+            .or_default()
+            .tags
+            .entry(tag.into())
+            .or_default()
+            .properties
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Append history samples to a tag. Samples are kept sorted by timestamp.
+    pub fn add_tvqs(
+        &self,
+        view: impl Into<String>,
+        tag: impl Into<String>,
+        tvqs: impl IntoIterator<Item = Tvq>,
+    ) -> &Self {
+        let mut state = self.state();
+        let tag = state
+            .views
+            .entry(view.into())
+            .or_default()
+            .tags
+            .entry(tag.into())
+            .or_default();
+        tag.tvqs.extend(tvqs);
+        tag.tvqs.sort_by_key(|tvq| tvq.timestamp);
+        self
+    }
+
+    /// Append a sample to a tag's history and push it to live subscribers of the tag.
+    pub fn publish_live(&self, view: impl Into<String>, tag: impl Into<String>, tvq: Tvq) {
+        let tag = tag.into();
+        self.add_tvqs(view, tag.clone(), [tvq.clone()]);
+        let resp = SubscribeToLiveDataResponse {
+            tags_and_data: [(
+                tag.clone(),
+                TvqsAndAnnotations {
+                    tvqs: vec![GrpcTvq::from(&tvq)],
+                    annotations: vec![],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut state = self.state();
+        state.subscribers.retain(|(tags, tx)| {
+            if !tags.contains(&tag) {
+                return !tx.is_closed();
+            }
+            tx.try_send(Ok(resp.clone())).is_ok()
+        });
+    }
+
+    /// Add an event to the results of `FindEvents`, which pages them
+    /// [`EVENTS_PAGE_SIZE`] at a time and ignores search criteria.
+    pub fn add_event(&self, event: Event) -> &Self {
+        self.state().events.push(event);
+        self
+    }
+
+    /// Reject requests whose `canary-api-token` is not `key` with `UNAUTHENTICATED`.
+    pub fn require_api_key(&self, key: impl Into<String>) -> &Self {
+        self.state().api_key = Some(key.into());
+        self
+    }
+
+    /// Delay every response by `latency`.
+    pub fn set_latency(&self, latency: Duration) -> &Self {
+        self.state().latency = latency;
+        self
+    }
+
+    /// Run the clock `ParseTimestamp` resolves `Now` against this many
+    /// seconds ahead of the local clock, or behind if negative.
+    pub fn set_clock_offset(&self, seconds: f64) -> &Self {
+        self.state().clock_offset = seconds;
+        self
+    }
+
+    /// Answer every `GetRawData` page with the continuation point it was
+    /// asked for, or the first page's if none, as a server stuck paging
+    /// would. A tag with no samples then gets an empty page with a point.
+    pub fn set_stuck_paging(&self, stuck: bool) -> &Self {
+        self.state().stuck_paging = stuck;
+        self
+    }
+
+    /// Add a fault. Faults are checked in the order they were added and the
+    /// first match fails the call.
+    pub fn inject_fault(&self, fault: Fault) -> &Self {
+        self.state().faults.push(fault);
+        self
+    }
+
+    /// Remove all injected faults.
+    pub fn clear_faults(&self) {
+        self.state().faults.clear();
+    }
+
+    /// Invalidate every issued client connection ID, as a service restart would.
+    pub fn expire_connections(&self) {
+        self.state().active_ccis.clear();
+    }
+
+    /// All requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The requests received for one RPC, e.g. `"GetRawData"`.
+    pub fn requests_for(&self, method: &str) -> Vec<RecordedRequest> {
+        self.state()
+            .requests
+            .iter()
+            .filter(|r| r.method == method)
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state().requests.clear();
+    }
+
+    /// Serve the mock over a fresh in-process channel.
+    ///
+    /// Must be called from within a Tokio runtime; the server task lives as
+    /// long as the returned channel (and its clones).
+    pub fn channel(&self) -> Channel {
+        let (channel, rx) = in_process_channel();
+        let service = CanaryViewsApiServiceServer::new(self.clone());
+        tokio::spawn(async move {
+            let incoming = UnboundedReceiverStream::new(rx).map(Ok::<_, std::io::Error>);
+            let _ = Server::builder()
+                .add_service(service)
+                .serve_with_incoming(incoming)
+                .await;
+        });
+        channel
+    }
+
+    /// Connect a [`ViewsClient`] to the mock over an in-process channel.
+    pub async fn client(&self) -> Result<ViewsClient, Error> {
+        ViewsClient::with_channel(self.channel(), MOCK_API_KEY, "crowsong-mock", "mock").await
+    }
+
+    fn record<M: Message>(&self, method: &str, request: &Request<M>) {
+        let metadata = |key| {
+            request
+                .metadata()
+                .get(key)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        self.state().requests.push(RecordedRequest {
+            method: method.to_string(),
+            api_token: metadata("canary-api-token"),
+            request_id: metadata(REQUEST_ID_HEADER),
+            body: request.get_ref().encode_to_vec(),
+        });
+    }
+
+    /// Record a request, then apply the configured latency, API key check, and faults.
+    async fn intercept<M: Message>(
+        &self,
+        method: &str,
+        request: &Request<M>,
+    ) -> Result<(), Status> {
+        self.record(method, request);
+        let latency = self.state().latency;
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let mut state = self.state();
+        if let Some(key) = &state.api_key {
+            let token = state.requests.last().and_then(|r| r.api_token.as_ref());
+            if token != Some(key) {
+                return Err(Status::unauthenticated("invalid API token"));
+            }
+        }
+        for fault in state.faults.iter_mut().filter(|f| f.matches(method)) {
+            if fault.skip > 0 {
+                fault.skip -= 1;
+                continue;
+            }
+            match &mut fault.remaining {
+                Some(0) => continue,
+                Some(n) => *n -= 1,
+                None => {}
+            }
+            return Err(Status::new(fault.code, fault.message.clone()));
+        }
+        Ok(())
+    }
+
+    fn check_cci(&self, cci: i32) -> Result<(), Status> {
+        if self.state().active_ccis.contains(&cci) {
+            Ok(())
+        } else {
+            Err(Status::failed_precondition(format!(
+                "Invalid client connection id: {cci}"
+            )))
+        }
+    }
+}
+
+fn to_system_time(ts: Option<prost_types::Timestamp>) -> Option<SystemTime> {
+    ts.and_then(|ts| SystemTime::try_from(ts).ok())
+}
+
+fn grpc_tvqs(tvqs: &[Tvq]) -> Vec<GrpcTvq> {
+    tvqs.iter().map(GrpcTvq::from).collect()
+}
+
+/// The percentage of samples whose quality satisfies `pred`, or `None` without samples.
+fn percent(samples: &[&Tvq], pred: impl Fn(Quality) -> bool) -> Option<Value> {
+    let matching = samples.iter().filter(|t| pred(t.quality)).count();
+    (!samples.is_empty()).then(|| Value::Float(100.0 * matching as f64 / samples.len() as f64))
+}
+
+/// Compute one aggregate bucket, or `None` for an unknown aggregate name.
+fn aggregate(name: &str, start: SystemTime, samples: &[&Tvq]) -> Option<Tvq> {
+    let numeric: Vec<f64> = samples.iter().filter_map(|t| t.value.as_f64()).collect();
+    let value = match name {
+        "Count" => Some(Value::Int(samples.len() as i64)),
+        "First" => samples.first().map(|t| t.value.clone()),
+        "Last" => samples.last().map(|t| t.value.clone()),
+        "Average" | "TimeAverage" | "TimeAverage2" => (!numeric.is_empty())
+            .then(|| Value::Float(numeric.iter().sum::<f64>() / numeric.len() as f64)),
+        "Minimum" => numeric.iter().copied().reduce(f64::min).map(Value::Float),
+        "Maximum" => numeric.iter().copied().reduce(f64::max).map(Value::Float),
+        "Total" => Some(Value::Float(numeric.iter().sum())),
+        "PercentGood" => percent(samples, |q| q.is_good()),
+        "PercentBad" => percent(samples, |q| q.is_bad()),
+        _ => return None,
+    };
+    Some(match value {
+        Some(value) => Tvq {
+            timestamp: start,
+            value,
+            quality: Quality::GOOD,
+        },
+        None => Tvq {
+            timestamp: start,
+            value: Value::Null,
+            quality: Quality::BAD,
+        },
+    })
+}
+
+#[tonic::async_trait]
+impl CanaryViewsApiService for MockViewsServer {
+    async fn test(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        self.intercept("Test", &request).await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_web_service_version(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<GetWebServiceVersionResponse>, Status> {
+        self.intercept("GetWebServiceVersion", &request).await?;
+        Ok(Response::new(GetWebServiceVersionResponse {
+            status: None,
+            version: self.state().version.clone(),
+        }))
+    }
+
+    async fn get_web_service_interface_version(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<GetWebServiceInterfaceVersionResponse>, Status> {
+        self.intercept("GetWebServiceInterfaceVersion", &request)
+            .await?;
+        Ok(Response::new(GetWebServiceInterfaceVersionResponse {
+            status: None,
+            version: self.state().version.clone(),
+        }))
+    }
+
+    async fn get_client_connection_id(
+        &self,
+        request: Request<GetClientConnectionIdRequest>,
+    ) -> Result<Response<GetClientConnectionIdResponse>, Status> {
+        self.intercept("GetClientConnectionId", &request).await?;
+        let mut state = self.state();
+        let cci = state.next_cci;
+        state.next_cci += 1;
+        state.active_ccis.insert(cci);
+        Ok(Response::new(GetClientConnectionIdResponse {
+            status: None,
+            cci,
+        }))
+    }
+
+    async fn release_client_connection_id(
+        &self,
+        request: Request<ReleaseClientConnectionIdRequest>,
+    ) -> Result<Response<ReleaseClientConnectionIdResponse>, Status> {
+        self.intercept("ReleaseClientConnectionId", &request)
+            .await?;
+        let cci = request.get_ref().cci;
+        self.check_cci(cci)?;
+        self.state().active_ccis.remove(&cci);
+        Ok(Response::new(ReleaseClientConnectionIdResponse {
+            status: None,
+        }))
+    }
+
+    async fn keepalive_client_connection_id(
+        &self,
+        request: Request<KeepaliveClientConnectionIdRequest>,
+    ) -> Result<Response<KeepaliveClientConnectionIdResponse>, Status> {
+        self.intercept("KeepaliveClientConnectionId", &request)
+            .await?;
+        self.check_cci(request.get_ref().cci)?;
+        Ok(Response::new(KeepaliveClientConnectionIdResponse {
+            status: None,
+        }))
+    }
+
+    async fn parse_timestamp(
+        &self,
+        request: Request<ParseTimestampRequest>,
+    ) -> Result<Response<ParseTimestampResponse>, Status> {
+        self.intercept("ParseTimestamp", &request).await?;
+        let text = request.get_ref().timestamp.trim();
+        let time = if text.eq_ignore_ascii_case("now") {
+            let offset = self.state().clock_offset;
+            let now = SystemTime::now();
+            if offset >= 0.0 {
+                now.checked_add(Duration::from_secs_f64(offset))
+            } else {
+                now.checked_sub(Duration::from_secs_f64(-offset))
+            }
+        } else {
+            crate::timestamps::parse_rfc3339(text).ok()
+        };
+        Ok(Response::new(ParseTimestampResponse {
+            status: None,
+            extended_status: match time {
+                Some(_) => parse_timestamp_response::Status::Unspecified,
+                None => parse_timestamp_response::Status::ParseFailed,
+            } as i32,
+            timestamp: time.map(Into::into),
+        }))
+    }
+
+    async fn get_views(
+        &self,
+        request: Request<GetViewsRequest>,
+    ) -> Result<Response<GetViewsResponse>, Status> {
+        self.intercept("GetViews", &request).await?;
+        self.check_cci(request.get_ref().cci)?;
+        Ok(Response::new(GetViewsResponse {
+            status: None,
+            views: self.state().views.keys().cloned().collect(),
+        }))
+    }
+
+    async fn get_dataset_info(
+        &self,
+        request: Request<GetDatasetInfoRequest>,
+    ) -> Result<Response<GetDatasetInfoResponse>, Status> {
+        self.intercept("GetDatasetInfo", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetDatasetInfoResponse {
+                extended_status: get_dataset_info_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let dataset = view
+            .datasets
+            .get(&req.dataset_name)
+            .ok_or_else(|| Status::not_found(format!("dataset not found: {}", req.dataset_name)))?;
+        let mut prop_name = vec!["TagCount".to_string()];
+        let mut prop_value = vec![dataset.tags.len().to_string()];
+        for (name, value) in &dataset.properties {
+            prop_name.push(name.clone());
+            prop_value.push(value.clone());
+        }
+        Ok(Response::new(GetDatasetInfoResponse {
+            status: None,
+            extended_status: 0,
+            prop_name,
+            prop_value,
+        }))
+    }
+
+    async fn get_data_set_list(
+        &self,
+        request: Request<GetDataSetListRequest>,
+    ) -> Result<Response<GetDataSetListResponse>, Status> {
+        self.intercept("GetDataSetList", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let state = self.state();
+        Ok(Response::new(match state.views.get(&req.view) {
+            Some(view) => GetDataSetListResponse {
+                datasets: view.datasets.keys().cloned().collect(),
+                ..Default::default()
+            },
+            None => GetDataSetListResponse {
+                extended_status: get_data_set_list_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            },
+        }))
+    }
+
+    async fn get_tag_list(
+        &self,
+        request: Request<GetTagListRequest>,
+    ) -> Result<Response<GetTagListResponse>, Status> {
+        self.intercept("GetTagList", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetTagListResponse {
+                extended_status: get_tag_list_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let tags = view
+            .datasets
+            .get(&req.dataset_name)
+            .map(|d| d.tags.as_slice())
+            .unwrap_or_default();
+        let max = if req.max_count > 0 {
+            req.max_count as usize
+        } else {
+            usize::MAX
+        };
+        let tag_names: Vec<String> = tags
+            .iter()
+            .skip(req.starting_offset.max(0) as usize)
+            .take(max)
+            .cloned()
+            .collect();
+        Ok(Response::new(GetTagListResponse {
+            tag_types: vec![0; tag_names.len()],
+            tag_names,
+            starting_offset: req.starting_offset,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_tag_info(
+        &self,
+        request: Request<GetTagInfoRequest>,
+    ) -> Result<Response<GetTagInfoResponse>, Status> {
+        self.intercept("GetTagInfo", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetTagInfoResponse {
+                extended_status: get_tag_info_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let tag_infos = req
+            .tag_names
+            .iter()
+            .filter_map(|name| {
+                let tag = view.tags.get(name)?;
+                Some(TagInfo {
+                    tag_item_id: name.clone(),
+                    item_type: 0,
+                    flags: 0,
+                    tag_properties: tag
+                        .properties
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (n, v))| TagProp {
+                            prop_name: n.clone(),
+                            prop_index: i as i32,
+                            prop_description: String::new(),
+                            prop_value: v.clone(),
+                            data_type: "String".to_string(),
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+        Ok(Response::new(GetTagInfoResponse {
+            tag_infos,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_tag_data_context(
+        &self,
+        request: Request<GetTagDataContextRequest>,
+    ) -> Result<Response<GetTagDataContextResponse>, Status> {
+        self.intercept("GetTagDataContext", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetTagDataContextResponse {
+                extended_status: get_tag_data_context_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let contexts = req
+            .tag_names
+            .iter()
+            .filter_map(|name| {
+                let tag = view.tags.get(name)?;
+                let latest = tag.tvqs.last();
+                Some(TagDataContext {
+                    tag_item_id: name.clone(),
+                    oldest_timestamp: tag.tvqs.first().map(|t| t.timestamp.into()),
+                    latest_timestamp: latest.map(|t| t.timestamp.into()),
+                    latest_value_data_type: String::new(),
+                    latest_value: latest.map(|t| t.value.to_string()).unwrap_or_default(),
+                    latest_quailty: latest.map(|t| t.quality.0).unwrap_or_default(),
+                })
+            })
+            .collect();
+        Ok(Response::new(GetTagDataContextResponse {
+            contexts,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_tag_current_value(
+        &self,
+        request: Request<GetTagCurrentValueRequest>,
+    ) -> Result<Response<GetTagCurrentValueResponse>, Status> {
+        self.intercept("GetTagCurrentValue", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let filter = req.quality();
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetTagCurrentValueResponse {
+                extended_status: get_tag_current_value_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let tag_values = req
+            .tag_names
+            .iter()
+            .filter_map(|name| {
+                let tvq = view
+                    .tags
+                    .get(name)?
+                    .tvqs
+                    .iter()
+                    .rev()
+                    .find(|t| match filter {
+                        get_tag_current_value_request::Quality::Any => true,
+                        get_tag_current_value_request::Quality::NonBad => !t.quality.is_bad(),
+                        get_tag_current_value_request::Quality::Good => t.quality.is_good(),
+                    })?;
+                Some(TagCurrentValue {
+                    tag_item_id: name.clone(),
+                    timestamp: Some(tvq.timestamp.into()),
+                    value: Some(tvq.value.clone().into()),
+                    quality: tvq.quality.0 as i32,
+                })
+            })
+            .collect();
+        Ok(Response::new(GetTagCurrentValueResponse {
+            tag_values,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_raw_data(
+        &self,
+        request: Request<GetRawDataRequest>,
+    ) -> Result<Response<GetRawDataResponse>, Status> {
+        self.intercept("GetRawData", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        if req.requests.is_empty() {
+            return Ok(Response::new(GetRawDataResponse {
+                extended_status: get_raw_data_response::Status::NoTagsInRequest.into(),
+                ..Default::default()
+            }));
+        }
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetRawDataResponse {
+                extended_status: get_raw_data_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let max = if req.max_count_per_tag > 0 {
+            req.max_count_per_tag as usize
+        } else {
+            usize::MAX
+        };
+        let raw_data = req
+            .requests
+            .iter()
+            .map(|tag_req| {
+                let Some(tag) = view.tags.get(&tag_req.tag_name) else {
+                    return RawTagData {
+                        tag_name: tag_req.tag_name.clone(),
+                        client_data: tag_req.client_data,
+                        error_message: format!("tag not found: {}", tag_req.tag_name),
+                        error_code: 1,
+                        ..Default::default()
+                    };
+                };
+                let start = to_system_time(tag_req.start_time).unwrap_or(SystemTime::UNIX_EPOCH);
+                let end = to_system_time(tag_req.end_time);
+                let in_range: Vec<&Tvq> = tag
+                    .tvqs
+                    .iter()
+                    .filter(|t| t.timestamp >= start && end.is_none_or(|end| t.timestamp < end))
+                    .collect();
+                let offset = tag_req
+                    .continuation_point
+                    .as_slice()
+                    .try_into()
+                    .map(u64::from_le_bytes)
+                    .unwrap_or(0) as usize;
+                let page: Vec<Tvq> = in_range
+                    .iter()
+                    .skip(offset)
+                    .take(max)
+                    .map(|t| (*t).clone())
+                    .collect();
+                let next = offset + page.len();
+                RawTagData {
+                    tag_name: tag_req.tag_name.clone(),
+                    client_data: tag_req.client_data,
+                    tvqs: grpc_tvqs(&page),
+                    continuation_point: if state.stuck_paging {
+                        (offset as u64).to_le_bytes().to_vec()
+                    } else if next < in_range.len() {
+                        (next as u64).to_le_bytes().to_vec()
+                    } else {
+                        Vec::new()
+                    },
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Ok(Response::new(GetRawDataResponse {
+            raw_data,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_aggregate_data(
+        &self,
+        request: Request<GetAggregateDataRequest>,
+    ) -> Result<Response<GetAggregateDataResponse>, Status> {
+        self.intercept("GetAggregateData", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        let start = to_system_time(req.start_time)
+            .ok_or_else(|| Status::invalid_argument("start_time is required"))?;
+        let end = to_system_time(req.end_time)
+            .ok_or_else(|| Status::invalid_argument("end_time is required"))?;
+        let interval = req
+            .interval
+            .and_then(|d| Duration::try_from(d).ok())
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| Status::invalid_argument("interval must be positive"))?;
+        let state = self.state();
+        let Some(view) = state.views.get(&req.view) else {
+            return Ok(Response::new(GetAggregateDataResponse {
+                extended_status: get_aggregate_data_response::Status::ViewNotFound.into(),
+                ..Default::default()
+            }));
+        };
+        let mut aggregated_data = Vec::new();
+        for tag_req in &req.requests {
+            let Some(tag) = view.tags.get(&tag_req.tag_name) else {
+                aggregated_data.push(AggregateTagData {
+                    tag_name: tag_req.tag_name.clone(),
+                    client_data: tag_req.client_data,
+                    error_message: format!("tag not found: {}", tag_req.tag_name),
+                    error_code: 1,
+                    ..Default::default()
+                });
+                continue;
+            };
+            let mut tvqs = Vec::new();
+            let mut bucket = start;
+            while bucket < end {
+                let bucket_end = bucket + interval;
+                let samples: Vec<&Tvq> = tag
+                    .tvqs
+                    .iter()
+                    .filter(|t| t.timestamp >= bucket && t.timestamp < bucket_end)
+                    .collect();
+                let tvq =
+                    aggregate(&tag_req.aggregate_name, bucket, &samples).ok_or_else(|| {
+                        Status::invalid_argument(format!(
+                            "unknown aggregate: {}",
+                            tag_req.aggregate_name
+                        ))
+                    })?;
+                tvqs.push(GrpcTvq::from(&tvq));
+                bucket = bucket_end;
+            }
+            aggregated_data.push(AggregateTagData {
+                tag_name: tag_req.tag_name.clone(),
+                client_data: tag_req.client_data,
+                tvqs,
+                ..Default::default()
+            });
+        }
+        Ok(Response::new(GetAggregateDataResponse {
+            aggregated_data,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_aggregate_list(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<GetAggregateListResponse>, Status> {
+        self.intercept("GetAggregateList", &request).await?;
+        Ok(Response::new(GetAggregateListResponse {
+            status: None,
+            aggregates: self
+                .state()
+                .aggregates
+                .iter()
+                .map(|(name, description)| AggregateDefinition {
+                    aggregate_name: name.clone(),
+                    aggregate_description: description.clone(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn find_events(
+        &self,
+        request: Request<FindEventsRequest>,
+    ) -> Result<Response<FindEventsResponse>, Status> {
+        self.intercept("FindEvents", &request).await?;
+        let offset = request
+            .get_ref()
+            .continuation_point
+            .as_slice()
+            .try_into()
+            .map(u64::from_le_bytes)
+            .unwrap_or(0) as usize;
+        let state = self.state();
+        let events: Vec<Event> = state
+            .events
+            .iter()
+            .skip(offset)
+            .take(EVENTS_PAGE_SIZE)
+            .cloned()
+            .collect();
+        let next = offset + events.len();
+        Ok(Response::new(FindEventsResponse {
+            status: None,
+            extended_status: 0,
+            events,
+            continuation_point: if next < state.events.len() {
+                (next as u64).to_le_bytes().to_vec()
+            } else {
+                Vec::new()
+            },
+        }))
+    }
+
+    async fn get_event_calculation_names(
+        &self,
+        request: Request<GetEventCalculationNamesRequest>,
+    ) -> Result<Response<GetEventCalculationNamesResponse>, Status> {
+        self.intercept("GetEventCalculationNames", &request).await?;
+        let names: BTreeSet<String> = self
+            .state()
+            .events
+            .iter()
+            .map(|e| e.calculation_name.clone())
+            .collect();
+        Ok(Response::new(GetEventCalculationNamesResponse {
+            status: None,
+            extended_status: 0,
+            event_calculation_names: names.into_iter().collect(),
+        }))
+    }
+
+    async fn subscribe_to_live_data(
+        &self,
+        request: Request<SubscribeToLiveDataRequest>,
+    ) -> Result<Response<BoxStream<SubscribeToLiveDataResponse>>, Status> {
+        self.intercept("SubscribeToLiveData", &request).await?;
+        let req = request.into_inner();
+        self.check_cci(req.cci)?;
+        if req.tags.is_empty() {
+            return Err(Status::invalid_argument("no tags in request"));
+        }
+        let (tx, rx) = mpsc::channel(1024);
+        self.state()
+            .subscribers
+            .push((req.tags.into_iter().collect(), tx));
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn browse_tags(
+        &self,
+        request: Request<BrowseTagsRequest>,
+    ) -> Result<Response<BrowseTagsResponse>, Status> {
+        self.intercept("BrowseTags", &request).await?;
+        let req = request.into_inner();
+        let state = self.state();
+        let max = if req.max_count > 0 {
+            req.max_count as usize
+        } else {
+            usize::MAX
+        };
+        let matches: Vec<String> = state
+            .views
+            .values()
+            .flat_map(|v| v.tags.keys())
+            .filter(|t| t.starts_with(&req.node_id_browse))
+            .filter(|t| t.contains(&req.search_context))
+            .cloned()
+            .collect();
+        Ok(Response::new(BrowseTagsResponse {
+            status: None,
+            more_data_available: matches.len() > max,
+            tag_names: matches.into_iter().take(max).collect(),
+            search_context: req.search_context,
+        }))
+    }
+
+    async fn search_tags(
+        &self,
+        request: Request<SearchTagsRequest>,
+    ) -> Result<Response<SearchTagsResponse>, Status> {
+        self.intercept("SearchTags", &request).await?;
+        let req = request.into_inner();
+        let state = self.state();
+        let search = state
+            .views
+            .values()
+            .flat_map(|v| v.tags.keys())
+            .filter(|t| req.tag_and.iter().all(|p| t.contains(p.as_str())))
+            .filter(|t| req.tag_or.is_empty() || req.tag_or.iter().any(|p| t.contains(p.as_str())))
+            .map(|t| SearchTags {
+                tag_name: t.clone(),
+                properties: vec![],
+            })
+            .collect();
+        Ok(Response::new(SearchTagsResponse {
+            status: None,
+            search,
+        }))
+    }
+}
@@ -0,0 +1,399 @@
+//! Timestamp conversion shared by the Python bindings, the CLI, and the
+//! exporters.
+//!
+//! Text timestamps are RFC 3339 in UTC, formatted by [`format_rfc3339`] and
+//! parsed by [`parse_rfc3339`]; integer timestamps are nanoseconds since the
+//! Unix epoch. Calendar arithmetic is left to `chrono`, so offsets, leap
+//! years, and times before 1970 are handled the same everywhere.
+//!
+//! Outputs that let the consumer choose take a [`TimestampFormat`] instead,
+//! which sets the precision, the UTC offset, and RFC 3339 or epoch notation.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+
+/// Layouts accepted by [`parse_rfc3339`] for times without an offset.
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Error returned when a timestamp string cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimestampError(String);
+
+impl fmt::Display for ParseTimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid time {:?}; expected e.g. 2024-05-01T12:00:00Z",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseTimestampError {}
+
+/// Parse an RFC 3339 time such as `2024-05-01T12:00:00.5+02:00`.
+///
+/// Also accepts a space instead of the `T`, a missing offset (taken as
+/// UTC), a missing seconds field (as in `2024-05-01T12:00Z`), and a bare
+/// date (midnight UTC).
+pub fn parse_rfc3339(s: &str) -> Result<SystemTime, ParseTimestampError> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.into());
+    }
+    // A trailing `Z` on a time RFC 3339 rejects, such as one without seconds,
+    // says UTC, as a missing offset does.
+    let naive = s.strip_suffix('Z').unwrap_or(s);
+    for format in NAIVE_FORMATS {
+        if let Ok(time) = NaiveDateTime::parse_from_str(naive, format) {
+            return Ok(time.and_utc().into());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc().into())
+        .ok_or_else(|| ParseTimestampError(s.to_string()))
+}
+
+/// Format `time` as RFC 3339 in UTC, with nanoseconds unless it falls on a
+/// whole second, e.g. `2024-05-01T12:00:00Z` or
+/// `2024-05-01T12:00:00.250000000Z`.
+pub fn format_rfc3339(time: SystemTime) -> String {
+    TimestampFormat::default().format(time)
+}
+
+/// How many fractional digits a [`TimestampFormat`] keeps. Finer digits are
+/// truncated, not rounded, so a time never moves into the next second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    /// Nanoseconds, or none when the time falls on a whole second.
+    #[default]
+    Auto,
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Notation {
+    #[default]
+    Rfc3339,
+    Epoch,
+}
+
+/// How timestamps are rendered as text.
+///
+/// The default is what [`format_rfc3339`] produces. Options are also parsed
+/// from a comma-separated string in any order, as taken by the `TIME_FORMAT`
+/// environment variable, the exporters' YAML, and the Python bindings:
+/// `rfc3339` or `epoch`; `auto`, `s`, `ms`, `us`, or `ns`; and `utc` (or
+/// `Z`) or an offset such as `+02:00`. For example `ms,+02:00` renders
+/// `2024-05-01T14:00:00.250+02:00`, and `epoch,ms` renders `1714564800250`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    any(feature = "schedule", feature = "watch"),
+    derive(serde::Deserialize),
+    serde(try_from = "String")
+)]
+pub struct TimestampFormat {
+    notation: Notation,
+    precision: Precision,
+    offset: FixedOffset,
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        Self::rfc3339()
+    }
+}
+
+impl TimestampFormat {
+    /// RFC 3339 in UTC with [`Precision::Auto`].
+    pub fn rfc3339() -> Self {
+        Self {
+            notation: Notation::Rfc3339,
+            precision: Precision::Auto,
+            offset: FixedOffset::east_opt(0).expect("zero offset"),
+        }
+    }
+
+    /// Time since the Unix epoch: whole units of the precision, or decimal
+    /// seconds with [`Precision::Auto`]. The offset does not apply.
+    pub fn epoch() -> Self {
+        Self {
+            notation: Notation::Epoch,
+            ..Self::rfc3339()
+        }
+    }
+
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Render RFC 3339 times at `offset` instead of UTC.
+    pub fn offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn format(&self, time: SystemTime) -> String {
+        match self.notation {
+            Notation::Rfc3339 => {
+                let time = DateTime::<Utc>::from(time).with_timezone(&self.offset);
+                let seconds = match self.precision {
+                    Precision::Auto if time.timestamp_subsec_nanos() == 0 => SecondsFormat::Secs,
+                    Precision::Auto | Precision::Nanos => SecondsFormat::Nanos,
+                    Precision::Seconds => SecondsFormat::Secs,
+                    Precision::Millis => SecondsFormat::Millis,
+                    Precision::Micros => SecondsFormat::Micros,
+                };
+                time.to_rfc3339_opts(seconds, true)
+            }
+            Notation::Epoch => {
+                let nanos = system_time_to_nanos(time);
+                match self.precision {
+                    Precision::Auto if nanos % 1_000_000_000 != 0 => {
+                        let sign = if nanos < 0 { "-" } else { "" };
+                        let abs = nanos.unsigned_abs();
+                        format!("{sign}{}.{:09}", abs / 1_000_000_000, abs % 1_000_000_000)
+                    }
+                    Precision::Auto | Precision::Seconds => {
+                        nanos.div_euclid(1_000_000_000).to_string()
+                    }
+                    Precision::Millis => nanos.div_euclid(1_000_000).to_string(),
+                    Precision::Micros => nanos.div_euclid(1_000).to_string(),
+                    Precision::Nanos => nanos.to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for TimestampFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let notation = match self.notation {
+            Notation::Rfc3339 => "rfc3339",
+            Notation::Epoch => "epoch",
+        };
+        let precision = match self.precision {
+            Precision::Auto => "auto",
+            Precision::Seconds => "s",
+            Precision::Millis => "ms",
+            Precision::Micros => "us",
+            Precision::Nanos => "ns",
+        };
+        write!(f, "{notation},{precision}")?;
+        match self.offset.local_minus_utc() {
+            0 => f.write_str(",utc"),
+            _ => write!(f, ",{}", self.offset),
+        }
+    }
+}
+
+/// An unparseable [`TimestampFormat`] string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimestampFormatError(String);
+
+impl fmt::Display for ParseTimestampFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid timestamp format option {:?}; expected rfc3339, epoch, auto, s, ms, us, ns, utc, or an offset such as +02:00",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseTimestampFormatError {}
+
+impl FromStr for TimestampFormat {
+    type Err = ParseTimestampFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut format = Self::rfc3339();
+        for option in s.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            match option.to_ascii_lowercase().as_str() {
+                "rfc3339" => format.notation = Notation::Rfc3339,
+                "epoch" => format.notation = Notation::Epoch,
+                "auto" => format.precision = Precision::Auto,
+                "s" => format.precision = Precision::Seconds,
+                "ms" => format.precision = Precision::Millis,
+                "us" => format.precision = Precision::Micros,
+                "ns" => format.precision = Precision::Nanos,
+                "utc" | "z" => format.offset = FixedOffset::east_opt(0).expect("zero offset"),
+                _ => {
+                    format.offset = parse_offset(option)
+                        .ok_or_else(|| ParseTimestampFormatError(option.to_string()))?
+                }
+            }
+        }
+        Ok(format)
+    }
+}
+
+impl TryFrom<String> for TimestampFormat {
+    type Error = ParseTimestampFormatError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// `+HH:MM`, `-HH:MM`, `+HHMM`, or `+HH`.
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let digits = rest.replacen(':', "", 1);
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3_600 + minutes * 60))
+}
+
+/// Convert a protobuf timestamp into a `SystemTime`, saturating at the
+/// epoch if it is out of range.
+pub fn from_proto(ts: &prost_types::Timestamp) -> SystemTime {
+    SystemTime::try_from(*ts).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Convert nanoseconds since the Unix epoch into a `SystemTime`.
+pub fn nanos_to_system_time(nanos: i64) -> SystemTime {
+    if nanos >= 0 {
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos as u64)
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_nanos(nanos.unsigned_abs())
+    }
+}
+
+/// Convert a `SystemTime` into nanoseconds since the Unix epoch.
+pub fn system_time_to_nanos(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i64,
+        Err(e) => -(e.duration().as_nanos() as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64, nanos: u32) -> SystemTime {
+        nanos_to_system_time(secs * 1_000_000_000 + i64::from(nanos))
+    }
+
+    #[test]
+    fn formats_whole_and_fractional_seconds() {
+        assert_eq!(format_rfc3339(at(1_714_564_800, 0)), "2024-05-01T12:00:00Z");
+        assert_eq!(
+            format_rfc3339(at(1_714_564_800, 250_000_000)),
+            "2024-05-01T12:00:00.250000000Z"
+        );
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        for time in [
+            at(1_714_564_800, 0),
+            at(1_714_564_800, 1),
+            at(1_714_564_800, 999_999_999),
+            at(-86_400, 500_000_000),
+        ] {
+            assert_eq!(parse_rfc3339(&format_rfc3339(time)), Ok(time));
+        }
+    }
+
+    #[test]
+    fn parses_offsets_into_utc() {
+        let utc = at(1_714_564_800, 500_000_000);
+        assert_eq!(parse_rfc3339("2024-05-01T14:00:00.5+02:00"), Ok(utc));
+        assert_eq!(parse_rfc3339("2024-05-01T07:30:00.5-04:30"), Ok(utc));
+        let format = TimestampFormat::rfc3339()
+            .precision(Precision::Millis)
+            .offset(FixedOffset::east_opt(2 * 3_600).unwrap());
+        assert_eq!(format.format(utc), "2024-05-01T14:00:00.500+02:00");
+        assert_eq!(parse_rfc3339(&format.format(utc)), Ok(utc));
+    }
+
+    #[test]
+    fn parses_relaxed_forms() {
+        let noon = at(1_714_564_800, 0);
+        assert_eq!(parse_rfc3339("2024-05-01 12:00:00"), Ok(noon));
+        assert_eq!(parse_rfc3339("2024-05-01T12:00Z"), Ok(noon));
+        assert_eq!(parse_rfc3339(" 2024-05-01T12:00 "), Ok(noon));
+        assert_eq!(parse_rfc3339("2024-05-01"), Ok(at(1_714_521_600, 0)));
+        assert!(parse_rfc3339("yesterday").is_err());
+    }
+
+    #[test]
+    fn truncates_to_the_precision() {
+        let time = at(1_714_564_800, 123_456_789);
+        let format = |precision| TimestampFormat::rfc3339().precision(precision).format(time);
+        assert_eq!(format(Precision::Seconds), "2024-05-01T12:00:00Z");
+        assert_eq!(format(Precision::Millis), "2024-05-01T12:00:00.123Z");
+        assert_eq!(format(Precision::Micros), "2024-05-01T12:00:00.123456Z");
+        assert_eq!(format(Precision::Nanos), "2024-05-01T12:00:00.123456789Z");
+    }
+
+    #[test]
+    fn formats_epoch_times() {
+        let epoch = |precision, time| TimestampFormat::epoch().precision(precision).format(time);
+        let time = at(1_714_564_800, 250_000_000);
+        assert_eq!(epoch(Precision::Auto, time), "1714564800.250000000");
+        assert_eq!(epoch(Precision::Millis, time), "1714564800250");
+        assert_eq!(epoch(Precision::Auto, at(1_714_564_800, 0)), "1714564800");
+        assert_eq!(epoch(Precision::Auto, at(-1, 500_000_000)), "-0.500000000");
+        assert_eq!(epoch(Precision::Seconds, at(-1, 500_000_000)), "-1");
+    }
+
+    #[test]
+    fn format_options_round_trip() {
+        for text in [
+            "rfc3339,auto,utc",
+            "epoch,ms,utc",
+            "rfc3339,ns,+02:00",
+            "rfc3339,s,-05:30",
+        ] {
+            let format: TimestampFormat = text.parse().unwrap();
+            assert_eq!(format.to_string(), text);
+        }
+        assert_eq!(
+            "ms, +0200".parse::<TimestampFormat>().unwrap().to_string(),
+            "rfc3339,ms,+02:00"
+        );
+        assert_eq!("".parse(), Ok(TimestampFormat::default()));
+        assert!("ms,+02:60".parse::<TimestampFormat>().is_err());
+        assert!("fortnightly".parse::<TimestampFormat>().is_err());
+    }
+
+    #[test]
+    fn nanos_round_trip() {
+        for nanos in [0, 1, -1, 1_714_564_800_123_456_789, -86_400_000_000_001] {
+            assert_eq!(system_time_to_nanos(nanos_to_system_time(nanos)), nanos);
+        }
+    }
+}
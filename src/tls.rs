@@ -0,0 +1,307 @@
+//! TLS protocol versions, cipher suites, and certificate verification.
+//!
+//! By default `https` connections offer TLS 1.2 and 1.3 with the default
+//! cipher suites of rustls's `ring` provider. A [`TlsPolicy`], given to
+//! [`ViewsClientBuilder::tls_policy`], narrows that to what a security
+//! policy allows, and says which certificates to trust:
+//!
+//! ```
+//! use crowsong::tls::{TlsPolicy, TlsVersion};
+//!
+//! let policy = TlsPolicy::new()
+//!     .min_version(TlsVersion::Tls13)
+//!     .cipher_suites(["TLS13_AES_256_GCM_SHA384", "TLS13_CHACHA20_POLY1305_SHA256"])
+//!     .ca_bundle("/etc/canary/ca.pem");
+//! ```
+//!
+//! Server certificates are verified against the operating system's
+//! certificate store, plus any CA bundles the policy adds. A Canary server
+//! with a self-signed certificate needs that certificate, or the CA that
+//! issued it, added with [`TlsPolicy::ca_bundle`];
+//! [`TlsPolicy::danger_accept_invalid_certs`] turns verification off
+//! altogether, which lets anyone on the network path impersonate the server.
+//!
+//! Cipher suites are named as in the IANA registry, with TLS 1.3 suites
+//! prefixed `TLS13_` as rustls does, e.g.
+//! `TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384`. Connecting fails if a name is
+//! unknown or the policy leaves no suite for any allowed version.
+//!
+//! This module, and rustls with it, comes with the `tls` feature, on by
+//! default. Builds without it speak plaintext HTTP/2 only, for edge devices
+//! talking to a sidecar on localhost, and refuse `https` endpoints.
+//!
+//! [`ViewsClientBuilder::tls_policy`]: crate::views_client::ViewsClientBuilder::tls_policy
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::Uri;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioIo;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use rustls::{ClientConfig, RootCertStore, SupportedProtocolVersion};
+use tokio_rustls::TlsConnector;
+use tonic::transport::{Channel, Endpoint};
+use tower::Service;
+use tower::service_fn;
+
+use crate::error::Error;
+
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
+/// A TLS protocol version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TlsVersion {
+    #[default]
+    Tls12,
+    Tls13,
+}
+
+/// CA certificates to trust on top of, or instead of, the system's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CaSource {
+    File(PathBuf),
+    Pem(Vec<u8>),
+}
+
+/// Which TLS versions and cipher suites a connection may use, and which
+/// server certificates it accepts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsPolicy {
+    min_version: TlsVersion,
+    cipher_suites: Option<Vec<String>>,
+    ca: Vec<CaSource>,
+    skip_native_roots: bool,
+    accept_invalid_certs: bool,
+}
+
+impl TlsPolicy {
+    /// TLS 1.2 and later, with the default cipher suites, verifying server
+    /// certificates against the system's certificate store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The oldest version to offer. Defaults to TLS 1.2.
+    pub fn min_version(mut self, version: TlsVersion) -> Self {
+        self.min_version = version;
+        self
+    }
+
+    /// Only offer these cipher suites, in order of preference.
+    pub fn cipher_suites(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.cipher_suites = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Also trust the CA certificates in the PEM file at `path`, read when
+    /// connecting.
+    pub fn ca_bundle(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca.push(CaSource::File(path.into()));
+        self
+    }
+
+    /// Also trust the CA certificates in `pem`.
+    pub fn ca_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.ca.push(CaSource::Pem(pem.into()));
+        self
+    }
+
+    /// Whether to trust the operating system's certificate store. On by
+    /// default; turn it off to trust only the policy's own CA bundles.
+    pub fn native_roots(mut self, enabled: bool) -> Self {
+        self.skip_native_roots = !enabled;
+        self
+    }
+
+    /// Accept any server certificate without verifying it. Only for tests
+    /// and servers whose certificate can't be had: the connection is still
+    /// encrypted, but anyone on the network path can impersonate the server.
+    pub fn danger_accept_invalid_certs(mut self) -> Self {
+        self.accept_invalid_certs = true;
+        self
+    }
+
+    /// The protocol versions to offer.
+    pub(crate) fn versions(&self) -> &'static [&'static SupportedProtocolVersion] {
+        match self.min_version {
+            TlsVersion::Tls12 => rustls::ALL_VERSIONS,
+            TlsVersion::Tls13 => TLS13_ONLY,
+        }
+    }
+
+    /// The `ring` provider, limited to the policy's cipher suites.
+    pub(crate) fn provider(&self) -> Result<CryptoProvider, Error> {
+        let mut provider = rustls::crypto::ring::default_provider();
+        if let Some(names) = &self.cipher_suites {
+            let available = std::mem::take(&mut provider.cipher_suites);
+            for name in names {
+                let suite = available
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite()).eq_ignore_ascii_case(name))
+                    .ok_or_else(|| {
+                        Error::Tls(format!("unknown or unsupported cipher suite {name:?}"))
+                    })?;
+                provider.cipher_suites.push(*suite);
+            }
+        }
+        Ok(provider)
+    }
+
+    /// The server certificate verifier: webpki against the trusted roots,
+    /// unless the policy accepts invalid certificates.
+    fn verifier(
+        &self,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Arc<dyn ServerCertVerifier>, Error> {
+        if self.accept_invalid_certs {
+            return Ok(Arc::new(AcceptAnyCert));
+        }
+        let mut roots = RootCertStore::empty();
+        if !self.skip_native_roots {
+            // Unreadable system certificates are skipped, as browsers do.
+            roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+        }
+        for source in &self.ca {
+            let (pem, origin) = match source {
+                CaSource::File(path) => {
+                    let pem = std::fs::read(path).map_err(|e| {
+                        Error::Tls(format!("reading CA bundle {}: {e}", path.display()))
+                    })?;
+                    (pem, path.display().to_string())
+                }
+                CaSource::Pem(pem) => (pem.clone(), "CA PEM".to_string()),
+            };
+            let certs = CertificateDer::pem_slice_iter(&pem)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Error::Tls(format!("parsing {origin}: {e}")))?;
+            if certs.is_empty() {
+                return Err(Error::Tls(format!("{origin} holds no certificates")));
+            }
+            for cert in certs {
+                roots.add(cert).map_err(|e| {
+                    Error::Tls(format!("trusting a certificate from {origin}: {e}"))
+                })?;
+            }
+        }
+        if roots.is_empty() {
+            return Err(Error::Tls(
+                "no trusted CA certificates: the system store is empty or off, and no CA bundle was given"
+                    .to_string(),
+            ));
+        }
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .map_err(|e| Error::Tls(e.to_string()))?;
+        Ok(verifier)
+    }
+}
+
+#[derive(Debug)]
+struct AcceptAnyCert;
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &rustls::pki_types::CertificateDer<'_>,
+        _dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &rustls::pki_types::CertificateDer<'_>,
+        _dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+trait TonicIo: hyper::rt::Read + hyper::rt::Write {}
+impl<T> TonicIo for T where T: hyper::rt::Read + hyper::rt::Write {}
+
+/// Build a lazily-connected channel to `endpoint`, using TLS as `policy`
+/// allows for `https` URLs. The channel dials with its own connector, so
+/// TCP keepalive is set here rather than on the endpoint.
+pub(crate) fn tls_channel(
+    endpoint: Endpoint,
+    policy: &TlsPolicy,
+    tcp_keepalive: Option<Duration>,
+) -> Result<Channel, Error> {
+    if crypto::CryptoProvider::get_default().is_none() {
+        let _ = crypto::ring::default_provider().install_default();
+    }
+
+    let provider = Arc::new(policy.provider()?);
+    let verifier = policy.verifier(provider.clone())?;
+
+    let mut config = ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(policy.versions())
+        .map_err(|e| Error::Tls(e.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth();
+
+    config.alpn_protocols.push(b"h2".to_vec());
+
+    let tls = TlsConnector::from(Arc::new(config));
+
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_keepalive(tcp_keepalive);
+
+    type BoxedIo = Box<dyn TonicIo + Send + Unpin>;
+
+    let connector = service_fn(move |uri: Uri| {
+        let tls = tls.clone();
+        let mut http = http.clone();
+        async move {
+            let tcp = http.call(uri.clone()).await?;
+            let tcp = tcp.into_inner();
+            if uri.scheme_str() == Some("https") {
+                let host = uri
+                    .host()
+                    .ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing host")
+                    })?
+                    .to_string();
+                let server_name = rustls::pki_types::ServerName::try_from(host).map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid server name")
+                })?;
+                let tls_stream = tls.connect(server_name, tcp).await?;
+                Ok::<BoxedIo, Box<dyn std::error::Error + Send + Sync>>(Box::new(TokioIo::new(
+                    tls_stream,
+                )))
+            } else {
+                Ok::<BoxedIo, Box<dyn std::error::Error + Send + Sync>>(Box::new(TokioIo::new(tcp)))
+            }
+        }
+    });
+
+    Ok(Channel::new(connector, endpoint))
+}
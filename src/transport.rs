@@ -0,0 +1,32 @@
+//! The channel every client connects over, and helpers shared by the
+//! Views and Store & Forward clients.
+
+use std::fmt;
+
+#[cfg(not(feature = "tls"))]
+use tonic::transport::{Channel, Endpoint};
+
+#[cfg(not(feature = "tls"))]
+use crate::error::Error;
+
+/// Stands in for a secret in `Debug` output.
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Build a lazily-connected plaintext channel to `endpoint`, for builds
+/// without the `tls` feature, which have no TLS stack to offer `https` URLs.
+#[cfg(not(feature = "tls"))]
+pub(crate) fn plain_channel(endpoint: Endpoint) -> Result<Channel, Error> {
+    if endpoint.uri().scheme_str() == Some("https") {
+        return Err(Error::Tls(format!(
+            "{} needs TLS, but crowsong was built without the `tls` feature",
+            endpoint.uri()
+        )));
+    }
+    Ok(endpoint.connect_lazy())
+}
@@ -0,0 +1,129 @@
+//! Reporting raw reads cut short by `max_count_per_tag`.
+//!
+//! One `GetRawData` call returns at most `max_count_per_tag` samples per
+//! tag, with a continuation point for the rest. A caller that reads one page
+//! and ignores the continuation point silently gets a prefix of the range,
+//! which looks like a complete answer. With a [`TruncationCheck`] on the
+//! client (the default), [`ViewsClient::get_raw_data`] and
+//! [`ViewsClient::get_raw_data_columnar`] report every tag that came back
+//! full with more to read, as one stderr line by default:
+//!
+//! ```text
+//! crowsong: truncated raw read view="Plant" tag="Plant.Line1.Speed" rows=10000 max_count_per_tag=10000 request_id=…
+//! ```
+//!
+//! or, with [`TruncationCheck::strict`], fail the call instead. Reads that
+//! follow continuation points, such as [`BulkFetch`](crate::BulkFetch),
+//! aren't checked.
+//!
+//! [`ViewsClient::get_raw_data`]: crate::ViewsClient::get_raw_data
+//! [`ViewsClient::get_raw_data_columnar`]: crate::ViewsClient::get_raw_data_columnar
+
+use std::fmt;
+use std::sync::Arc;
+
+use tonic::Status;
+
+use crate::request_id::RequestId;
+
+/// A tag whose raw read stopped at `max_count_per_tag` with more to read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncated {
+    pub view: String,
+    pub tag_name: String,
+    /// Samples returned.
+    pub rows: usize,
+    pub max_count_per_tag: i32,
+    /// The ID the call was sent under.
+    pub request_id: Option<RequestId>,
+}
+
+impl fmt::Display for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "truncated raw read view={:?} tag={:?} rows={} max_count_per_tag={}",
+            self.view, self.tag_name, self.rows, self.max_count_per_tag
+        )?;
+        if let Some(id) = self.request_id {
+            write!(f, " request_id={id}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What to do about truncated raw reads.
+#[derive(Clone)]
+pub struct TruncationCheck {
+    strict: bool,
+    handler: Arc<dyn Fn(&Truncated) + Send + Sync>,
+}
+
+impl Default for TruncationCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TruncationCheck {
+    /// Report truncated reads to stderr.
+    pub fn new() -> Self {
+        Self {
+            strict: false,
+            handler: Arc::new(|truncated| eprintln!("crowsong: {truncated}")),
+        }
+    }
+
+    /// Fail truncated reads with `OUT_OF_RANGE` instead of reporting them.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Pass truncated reads to `handler` instead, e.g. to forward them to a
+    /// logging framework.
+    pub fn handler(mut self, handler: impl Fn(&Truncated) + Send + Sync + 'static) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Check one response, given each tag's name, row count, and whether it
+    /// has a continuation point.
+    pub(crate) fn check<'a>(
+        &self,
+        view: &str,
+        max_count_per_tag: i32,
+        tags: impl IntoIterator<Item = (&'a str, usize, bool)>,
+    ) -> Result<(), Status> {
+        let limit = usize::try_from(max_count_per_tag).unwrap_or(0);
+        for (tag_name, rows, more) in tags {
+            if !more || rows < limit {
+                continue;
+            }
+            let truncated = Truncated {
+                view: view.to_string(),
+                tag_name: tag_name.to_string(),
+                rows,
+                max_count_per_tag,
+                request_id: RequestId::current(),
+            };
+            if self.strict {
+                return Err(Status::out_of_range(truncated.to_string()));
+            }
+            (self.handler)(&truncated);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TruncationCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TruncationCheck")
+            .field("strict", &self.strict)
+            .finish_non_exhaustive()
+    }
+}
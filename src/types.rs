@@ -0,0 +1,326 @@
+//! Typed, protobuf-free views of Canary results.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::canary::utility::protobuf_shared_types::variant::Kind;
+use crate::canary::utility::protobuf_shared_types::{GrpcTvq, Variant};
+#[cfg(feature = "views")]
+use crate::canary::views::grpc::api;
+use crate::quality::Quality;
+use crate::timestamps;
+
+/// Tag property names Canary uses for engineering units, in lookup order.
+const ENG_UNITS_PROPS: &[&str] = &["EngUnits", "Eng Units", "Units", "EngineeringUnits"];
+
+/// A decoded tag value.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Decimal(Vec<u8>),
+}
+
+impl Value {
+    /// The value as a float, if it is numeric or boolean.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::Int(i) => Some(*i as f64),
+            Value::UInt(u) => Some(*u as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Int(i) => write!(f, "{i}"),
+            Value::UInt(u) => write!(f, "{u}"),
+            Value::Float(v) => write!(f, "{v}"),
+            Value::String(s) => f.write_str(s),
+            Value::Decimal(b) => {
+                for byte in b {
+                    write!(f, "{byte:02x}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&Variant> for Value {
+    fn from(v: &Variant) -> Self {
+        match &v.kind {
+            Some(Kind::Bool(b)) => Value::Bool(*b),
+            Some(Kind::Int8(i)) | Some(Kind::Int16(i)) | Some(Kind::Int32(i)) => {
+                Value::Int(*i as i64)
+            }
+            Some(Kind::Int64(i)) => Value::Int(*i),
+            Some(Kind::UInt8(u)) | Some(Kind::UInt16(u)) | Some(Kind::UInt32(u)) => {
+                Value::UInt(*u as u64)
+            }
+            Some(Kind::UInt64(u)) => Value::UInt(*u),
+            Some(Kind::Float(f)) => Value::Float(*f as f64),
+            Some(Kind::Double(d)) => Value::Float(*d),
+            Some(Kind::String(s)) => Value::String(s.clone()),
+            Some(Kind::Decimal(b)) => Value::Decimal(b.clone()),
+            None => Value::Null,
+        }
+    }
+}
+
+impl From<Value> for Variant {
+    fn from(v: Value) -> Self {
+        let kind = match v {
+            Value::Null => None,
+            Value::Bool(b) => Some(Kind::Bool(b)),
+            Value::Int(i) => Some(Kind::Int64(i)),
+            Value::UInt(u) => Some(Kind::UInt64(u)),
+            Value::Float(f) => Some(Kind::Double(f)),
+            Value::String(s) => Some(Kind::String(s)),
+            Value::Decimal(b) => Some(Kind::Decimal(b)),
+        };
+        Variant { kind }
+    }
+}
+
+/// A single timestamp/value/quality sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tvq {
+    pub timestamp: SystemTime,
+    pub value: Value,
+    pub quality: Quality,
+}
+
+impl From<&GrpcTvq> for Tvq {
+    fn from(tvq: &GrpcTvq) -> Self {
+        Self {
+            timestamp: tvq
+                .timestamp
+                .and_then(|ts| SystemTime::try_from(ts).ok())
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+            value: tvq.value.as_ref().map(Value::from).unwrap_or_default(),
+            quality: Quality(tvq.quality),
+        }
+    }
+}
+
+impl From<&Tvq> for GrpcTvq {
+    fn from(tvq: &Tvq) -> Self {
+        GrpcTvq {
+            timestamp: Some(tvq.timestamp.into()),
+            value: Some(tvq.value.clone().into()),
+            quality: tvq.quality.0,
+        }
+    }
+}
+
+#[cfg(feature = "views")]
+impl From<&api::TagCurrentValue> for Tvq {
+    fn from(current: &api::TagCurrentValue) -> Self {
+        Self {
+            timestamp: current
+                .timestamp
+                .and_then(|ts| SystemTime::try_from(ts).ok())
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+            value: current.value.as_ref().map(Value::from).unwrap_or_default(),
+            quality: Quality(current.quality as u32),
+        }
+    }
+}
+
+/// The samples returned for one tag, with its engineering units when known.
+///
+/// Data responses don't carry units, so a series converted from one has
+/// none; `ViewsClient::query` looks them up unless told not to.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TagSeries {
+    pub tag_name: String,
+    pub eng_units: Option<String>,
+    pub tvqs: Vec<Tvq>,
+}
+
+#[cfg(feature = "views")]
+impl From<&api::RawTagData> for TagSeries {
+    fn from(data: &api::RawTagData) -> Self {
+        Self {
+            tag_name: data.tag_name.clone(),
+            eng_units: None,
+            tvqs: data.tvqs.iter().map(Tvq::from).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "views")]
+impl From<&api::AggregateTagData> for TagSeries {
+    fn from(data: &api::AggregateTagData) -> Self {
+        Self {
+            tag_name: data.tag_name.clone(),
+            eng_units: None,
+            tvqs: data.tvqs.iter().map(Tvq::from).collect(),
+        }
+    }
+}
+
+/// Tag metadata with its properties keyed by name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TagInfo {
+    pub tag_item_id: String,
+    pub item_type: i32,
+    pub flags: u32,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl TagInfo {
+    /// The engineering units property, if the tag has a non-empty one.
+    pub fn eng_units(&self) -> Option<&str> {
+        eng_units(&self.properties)
+    }
+
+    /// The description property, if present.
+    pub fn description(&self) -> Option<&str> {
+        self.properties.get("Description").map(String::as_str)
+    }
+}
+
+/// The first non-empty engineering units property in `properties`.
+pub(crate) fn eng_units(properties: &BTreeMap<String, String>) -> Option<&str> {
+    ENG_UNITS_PROPS
+        .iter()
+        .filter_map(|name| properties.get(*name))
+        .map(|units| units.trim())
+        .find(|units| !units.is_empty())
+}
+
+#[cfg(feature = "views")]
+impl From<&api::TagInfo> for TagInfo {
+    fn from(info: &api::TagInfo) -> Self {
+        Self {
+            tag_item_id: info.tag_item_id.clone(),
+            item_type: info.item_type,
+            flags: info.flags,
+            properties: info
+                .tag_properties
+                .iter()
+                .map(|p| (p.prop_name.clone(), p.prop_value.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Dataset properties as returned by `get_dataset_info`, parsed to proper types.
+///
+/// The Views API documents no property names, so each field lists the names
+/// it is read from, compared ignoring case, spaces, and punctuation.
+/// Properties that are not recognized, or that fail to parse or are out of
+/// range for their field, are kept verbatim in `extra`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DatasetInfo {
+    pub name: String,
+    /// `Created`, `CreatedTime`, `CreationTime`, or `CreateDate`.
+    pub created: Option<SystemTime>,
+    /// `LastWrite`, `LastWriteTime`, `LastUpdate`, `LastUpdated`, or
+    /// `Modified`.
+    pub last_write: Option<SystemTime>,
+    /// `TagCount`, `NumTags`, or `Tags`.
+    pub tag_count: Option<u64>,
+    /// `Size`, `SizeBytes`, `DiskSize`, or `DataSize`, in bytes or with a
+    /// unit such as `512 KB`.
+    pub size_bytes: Option<u64>,
+    /// `RetentionDays`, `Retention`, or `RetentionPeriod`.
+    pub retention_days: Option<u32>,
+    /// `RetentionFiles`, `RetentionFileCount`, `MaxFiles`, or `MaxFileCount`.
+    pub retention_file_count: Option<u32>,
+    pub extra: BTreeMap<String, String>,
+}
+
+impl DatasetInfo {
+    /// Build from the parallel property name/value lists of a `GetDatasetInfoResponse`.
+    pub fn from_props(name: impl Into<String>, names: &[String], values: &[String]) -> Self {
+        let mut info = DatasetInfo {
+            name: name.into(),
+            ..Default::default()
+        };
+        for (prop, value) in names.iter().zip(values.iter()) {
+            let parsed = match prop_key(prop).as_str() {
+                "created" | "createdtime" | "creationtime" | "createdate" => {
+                    parse_prop_time(value).map(|t| info.created = Some(t))
+                }
+                "lastwrite" | "lastwritetime" | "lastupdate" | "lastupdated" | "modified" => {
+                    parse_prop_time(value).map(|t| info.last_write = Some(t))
+                }
+                "tagcount" | "numtags" | "tags" => {
+                    parse_count(value).map(|n| info.tag_count = Some(n))
+                }
+                "size" | "sizebytes" | "disksize" | "datasize" => {
+                    parse_size(value).map(|n| info.size_bytes = Some(n))
+                }
+                "retentiondays" | "retention" | "retentionperiod" => parse_count(value)
+                    .and_then(|n| u32::try_from(n).ok())
+                    .map(|n| info.retention_days = Some(n)),
+                "retentionfiles" | "retentionfilecount" | "maxfiles" | "maxfilecount" => {
+                    parse_count(value)
+                        .and_then(|n| u32::try_from(n).ok())
+                        .map(|n| info.retention_file_count = Some(n))
+                }
+                _ => None,
+            };
+            if parsed.is_none() {
+                info.extra.insert(prop.clone(), value.clone());
+            }
+        }
+        info
+    }
+}
+
+/// Normalize a property name: lower-case alphanumerics only.
+fn prop_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Parse an RFC 3339 timestamp, also accepting a space separator and a missing offset (UTC).
+fn parse_prop_time(s: &str) -> Option<SystemTime> {
+    timestamps::parse_rfc3339(s).ok()
+}
+
+/// Parse a non-negative integer count, ignoring thousands separators.
+fn parse_count(s: &str) -> Option<u64> {
+    s.trim().replace(',', "").parse().ok()
+}
+
+/// Parse a size such as "1048576", "512 KB", or "1.5 GB" into bytes.
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim().replace(',', "");
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" | "bytes" => 1.0,
+        "kb" | "kib" => 1024.0,
+        "mb" | "mib" => 1024.0 * 1024.0,
+        "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        "tb" | "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * scale) as u64)
+}
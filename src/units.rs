@@ -0,0 +1,366 @@
+//! Engineering unit conversion for tag values.
+//!
+//! A [`UnitConverter`] holds a table of known conversions (a small built-in
+//! set plus any user-registered ones) and the preferred target unit for each
+//! source unit. Applying it rewrites numeric values and their reported units.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::canary::utility::protobuf_shared_types::variant::Kind;
+use crate::canary::utility::protobuf_shared_types::{GrpcTvq, Variant};
+use crate::columnar::{RawColumns, ValueKind};
+use crate::types::{TagSeries, Value};
+
+/// A single conversion from one unit to another.
+#[derive(Clone)]
+pub enum Conversion {
+    /// `to = from * scale + offset`
+    Linear { scale: f64, offset: f64 },
+    /// An arbitrary user-provided function.
+    Custom(Arc<dyn Fn(f64) -> f64 + Send + Sync>),
+}
+
+impl Conversion {
+    pub fn apply(&self, value: f64) -> f64 {
+        match self {
+            Conversion::Linear { scale, offset } => value * scale + offset,
+            Conversion::Custom(f) => f(value),
+        }
+    }
+}
+
+impl fmt::Debug for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conversion::Linear { scale, offset } => f
+                .debug_struct("Linear")
+                .field("scale", scale)
+                .field("offset", offset)
+                .finish(),
+            Conversion::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Built-in linear conversions: (from, to, scale, offset).
+const BUILTIN: &[(&str, &str, f64, f64)] = &[
+    ("degF", "degC", 5.0 / 9.0, -32.0 * 5.0 / 9.0),
+    ("degC", "degF", 9.0 / 5.0, 32.0),
+    ("degC", "K", 1.0, 273.15),
+    ("K", "degC", 1.0, -273.15),
+    ("psi", "bar", 0.068_947_572_9, 0.0),
+    ("bar", "psi", 14.503_773_8, 0.0),
+    ("psi", "kPa", 6.894_757_29, 0.0),
+    ("kPa", "psi", 0.145_037_738, 0.0),
+    ("bar", "kPa", 100.0, 0.0),
+    ("kPa", "bar", 0.01, 0.0),
+    ("psig", "barg", 0.068_947_572_9, 0.0),
+    ("barg", "psig", 14.503_773_8, 0.0),
+    ("psia", "bara", 0.068_947_572_9, 0.0),
+    ("bara", "psia", 14.503_773_8, 0.0),
+    ("ft", "m", 0.3048, 0.0),
+    ("m", "ft", 1.0 / 0.3048, 0.0),
+    ("in", "mm", 25.4, 0.0),
+    ("mm", "in", 1.0 / 25.4, 0.0),
+    ("lb", "kg", 0.453_592_37, 0.0),
+    ("kg", "lb", 1.0 / 0.453_592_37, 0.0),
+    ("gal", "L", 3.785_411_784, 0.0),
+    ("L", "gal", 1.0 / 3.785_411_784, 0.0),
+    ("gpm", "m3/h", 0.227_124_707, 0.0),
+    ("m3/h", "gpm", 1.0 / 0.227_124_707, 0.0),
+];
+
+/// Map common spellings of a unit onto the names used in the conversion table.
+///
+/// Gauge and absolute pressures keep their suffix: they differ by the
+/// atmospheric pressure, so the table never converts one into the other.
+///
+/// Single letters that could mean something else, such as `C` or `F` (SI
+/// prefixes) and `m` (metres or minutes), get no alias: they are matched
+/// only as spelled in the table, so `m` is metres and `M` is no unit.
+fn canonical(unit: &str) -> String {
+    let trimmed = unit.trim();
+    let key: String = trimmed
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let name = match key.as_str() {
+        "degf" | "°f" | "deg.f" | "fahrenheit" => "degF",
+        "degc" | "°c" | "deg.c" | "celsius" => "degC",
+        "kelvin" => "K",
+        "psi" => "psi",
+        "psig" => "psig",
+        "psia" => "psia",
+        "bar" => "bar",
+        "barg" => "barg",
+        "bara" => "bara",
+        "kpa" => "kPa",
+        "ft" | "feet" => "ft",
+        "meters" | "metres" => "m",
+        "in" | "inch" | "inches" => "in",
+        "mm" => "mm",
+        "lb" | "lbs" => "lb",
+        "kg" => "kg",
+        "gal" | "gallons" => "gal",
+        "l" | "liters" | "litres" => "L",
+        "gpm" => "gpm",
+        "m3/h" | "m³/h" | "m3/hr" => "m3/h",
+        _ => return trimmed.to_string(),
+    };
+    name.to_string()
+}
+
+/// Converts values between engineering units according to a preference map.
+#[derive(Debug, Clone, Default)]
+pub struct UnitConverter {
+    conversions: HashMap<(String, String), Conversion>,
+    targets: HashMap<String, String>,
+}
+
+impl UnitConverter {
+    /// An empty converter with no known conversions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A converter pre-loaded with the built-in unit table.
+    pub fn with_builtin() -> Self {
+        let mut converter = Self::new();
+        for (from, to, scale, offset) in BUILTIN {
+            converter.register_linear(*from, *to, *scale, *offset);
+        }
+        converter
+    }
+
+    /// Register a linear conversion `to = from * scale + offset`.
+    pub fn register_linear(
+        &mut self,
+        from: impl AsRef<str>,
+        to: impl AsRef<str>,
+        scale: f64,
+        offset: f64,
+    ) -> &mut Self {
+        self.register(from, to, Conversion::Linear { scale, offset })
+    }
+
+    /// Register an arbitrary conversion function.
+    pub fn register_fn(
+        &mut self,
+        from: impl AsRef<str>,
+        to: impl AsRef<str>,
+        f: impl Fn(f64) -> f64 + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.register(from, to, Conversion::Custom(Arc::new(f)))
+    }
+
+    fn register(
+        &mut self,
+        from: impl AsRef<str>,
+        to: impl AsRef<str>,
+        conversion: Conversion,
+    ) -> &mut Self {
+        self.conversions.insert(
+            (canonical(from.as_ref()), canonical(to.as_ref())),
+            conversion,
+        );
+        self
+    }
+
+    /// Convert values reported in `from` into `to` whenever they are applied.
+    pub fn prefer(&mut self, from: impl AsRef<str>, to: impl AsRef<str>) -> &mut Self {
+        self.targets
+            .insert(canonical(from.as_ref()), canonical(to.as_ref()));
+        self
+    }
+
+    /// Whether any target preferences are configured.
+    pub fn is_active(&self) -> bool {
+        !self.targets.is_empty()
+    }
+
+    /// Convert a single value between two units, if a conversion is known.
+    pub fn convert(&self, value: f64, from: &str, to: &str) -> Option<f64> {
+        let (from, to) = (canonical(from), canonical(to));
+        if from == to {
+            return Some(value);
+        }
+        self.conversions.get(&(from, to)).map(|c| c.apply(value))
+    }
+
+    /// The preferred target unit and conversion for values reported in `from`.
+    pub fn target_for(&self, from: &str) -> Option<(&str, &Conversion)> {
+        let from = canonical(from);
+        let to = self.targets.get(&from)?;
+        let conversion = self.conversions.get(&(from, to.clone()))?;
+        Some((to.as_str(), conversion))
+    }
+
+    /// Convert a value according to the preferences, returning the new value and unit.
+    pub fn convert_value(&self, value: &Value, from: &str) -> Option<(Value, String)> {
+        let (to, conversion) = self.target_for(from)?;
+        let converted = match value {
+            Value::Int(_) | Value::UInt(_) | Value::Float(_) => {
+                Value::Float(conversion.apply(value.as_f64()?))
+            }
+            other => other.clone(),
+        };
+        Some((converted, to.to_string()))
+    }
+
+    /// Convert a series in place according to the preferences, updating its units.
+    pub fn apply(&self, series: &mut TagSeries) {
+        let Some(from) = series.eng_units.clone() else {
+            return;
+        };
+        let Some((to, conversion)) = self.target_for(&from) else {
+            return;
+        };
+        for tvq in &mut series.tvqs {
+            if let Some(v) = tvq.value.as_f64()
+                && !matches!(tvq.value, Value::Bool(_))
+            {
+                tvq.value = Value::Float(conversion.apply(v));
+            }
+        }
+        series.eng_units = Some(to.to_string());
+    }
+
+    /// Convert raw protobuf TVQs in place, returning the new unit if a conversion applied.
+    pub fn apply_grpc(&self, tvqs: &mut [GrpcTvq], from: &str) -> Option<String> {
+        let (to, conversion) = self.target_for(from)?;
+        for variant in tvqs.iter_mut().filter_map(|tvq| tvq.value.as_mut()) {
+            convert_variant(conversion, variant);
+        }
+        Some(to.to_string())
+    }
+
+    /// Convert columnar samples in place, returning the new unit if a conversion applied.
+    pub fn apply_columns(&self, columns: &mut RawColumns, from: &str) -> Option<String> {
+        let (to, conversion) = self.target_for(from)?;
+        for (value, kind) in columns.values.iter_mut().zip(columns.kinds.iter_mut()) {
+            if matches!(kind, ValueKind::Int | ValueKind::UInt | ValueKind::Float) {
+                *value = conversion.apply(*value);
+                *kind = ValueKind::Float;
+            }
+        }
+        Some(to.to_string())
+    }
+
+    /// Convert a single protobuf value in place, returning the new unit if a conversion applied.
+    pub fn apply_variant(&self, variant: &mut Variant, from: &str) -> Option<String> {
+        let (to, conversion) = self.target_for(from)?;
+        convert_variant(conversion, variant);
+        Some(to.to_string())
+    }
+}
+
+fn convert_variant(conversion: &Conversion, variant: &mut Variant) {
+    let v = match variant.kind {
+        Some(Kind::Int8(i)) | Some(Kind::Int16(i)) | Some(Kind::Int32(i)) => i as f64,
+        Some(Kind::Int64(i)) => i as f64,
+        Some(Kind::UInt8(u)) | Some(Kind::UInt16(u)) | Some(Kind::UInt32(u)) => u as f64,
+        Some(Kind::UInt64(u)) => u as f64,
+        Some(Kind::Float(f)) => f as f64,
+        Some(Kind::Double(d)) => d,
+        _ => return,
+    };
+    variant.kind = Some(Kind::Double(conversion.apply(v)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::quality::Quality;
+    use crate::types::Tvq;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn converts_between_builtin_units() {
+        let units = UnitConverter::with_builtin();
+        assert!(close(units.convert(212.0, "degF", "degC").unwrap(), 100.0));
+        assert!(close(units.convert(0.0, "°C", "K").unwrap(), 273.15));
+        assert!(close(units.convert(1.0, "bar", "kPa").unwrap(), 100.0));
+        assert!(close(
+            units.convert(10.0, "PSIG", "barg").unwrap(),
+            0.689_475_729
+        ));
+        assert_eq!(units.convert(7.0, "gal", "gallons"), Some(7.0));
+        assert_eq!(units.convert(1.0, "ft", "kg"), None);
+    }
+
+    #[test]
+    fn needs_full_symbols_for_ambiguous_letters() {
+        let units = UnitConverter::with_builtin();
+        assert_eq!(units.convert(212.0, "F", "degC"), None);
+        assert_eq!(units.convert(100.0, "degC", "f"), None);
+        assert_eq!(units.convert(0.0, "c", "K"), None);
+        assert_eq!(units.convert(0.0, "k", "degC"), None);
+        assert!(close(units.convert(0.0, "K", "degC").unwrap(), -273.15));
+        assert!(close(units.convert(1.0, "m", "ft").unwrap(), 1.0 / 0.3048));
+        assert!(close(
+            units.convert(1.0, "Metres", "ft").unwrap(),
+            1.0 / 0.3048
+        ));
+        assert_eq!(units.convert(1.0, "M", "ft"), None);
+    }
+
+    #[test]
+    fn keeps_gauge_and_absolute_pressure_apart() {
+        let units = UnitConverter::with_builtin();
+        assert_eq!(units.convert(14.7, "psia", "psig"), None);
+        assert_eq!(units.convert(1.0, "barg", "bara"), None);
+        assert_eq!(units.convert(1.0, "psig", "bar"), None);
+        assert_eq!(units.convert(1.0, "psig", "psi"), None);
+    }
+
+    #[test]
+    fn applies_preferences_to_series() {
+        let mut units = UnitConverter::with_builtin();
+        units.prefer("degF", "degC");
+        assert!(units.is_active());
+        let tvq = |value| Tvq {
+            timestamp: SystemTime::UNIX_EPOCH,
+            value,
+            quality: Quality::GOOD,
+        };
+        let mut series = TagSeries {
+            tag_name: "Oven.Temp".into(),
+            eng_units: Some("°F".into()),
+            tvqs: vec![tvq(Value::Int(32)), tvq(Value::Bool(true))],
+        };
+        units.apply(&mut series);
+        assert_eq!(series.eng_units.as_deref(), Some("degC"));
+        assert_eq!(series.tvqs[0].value, Value::Float(0.0));
+        assert_eq!(series.tvqs[1].value, Value::Bool(true));
+
+        let mut other = TagSeries {
+            eng_units: Some("psi".into()),
+            ..series.clone()
+        };
+        units.apply(&mut other);
+        assert_eq!(other.eng_units.as_deref(), Some("psi"));
+    }
+
+    #[test]
+    fn registers_custom_conversions() {
+        let mut units = UnitConverter::new();
+        units
+            .register_fn("counts", "percent", |v| v / 40.95)
+            .prefer("counts", "percent");
+        assert!(close(
+            units.convert(4095.0, "counts", "percent").unwrap(),
+            100.0
+        ));
+        let (value, unit) = units.convert_value(&Value::UInt(0), "counts").unwrap();
+        assert_eq!((value, unit.as_str()), (Value::Float(0.0), "percent"));
+        assert!(units.convert_value(&Value::Int(1), "degF").is_none());
+    }
+}
@@ -0,0 +1,365 @@
+//! Handles on views and datasets.
+//!
+//! [`ViewsClient::view`] checks a view exists and returns a [`View`] that
+//! remembers its name; [`View::dataset`] does the same one level down. Reads
+//! through a handle are scoped to it, so scripts that walk several datasets
+//! don't assemble view and tag names by hand:
+//!
+//! ```no_run
+//! # async fn example(client: &crowsong::ViewsClient) -> Result<(), crowsong::Error> {
+//! let mut view = client.view("Plant").await?;
+//! for mut dataset in view.datasets().await? {
+//!     let info = dataset.info().await?;
+//!     println!("{}: {:?} tags", dataset.name(), info.tag_count);
+//!     for mut tag in dataset.tags().await? {
+//!         println!("  {tag} = {}", tag.current().await?.value);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Code that only ever reads one view can make it the client's default with
+//! [`ViewsClient::set_default_view`] (and a dataset with
+//! [`ViewsClient::set_default_dataset`]), then reach it through
+//! [`ViewsClient::current_view`] and [`ViewsClient::current_dataset`] without
+//! passing names around. [`ViewsClient::query`] reads from it too.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::mpsc;
+use tonic::Status;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
+
+use crate::canary::views::grpc::api::{
+    GetTagDataContextResponse, GetTagInfoResponse, get_tag_list_response,
+};
+use crate::error::{Error, ResultStream};
+use crate::query::Query;
+use crate::request_id;
+use crate::tag::Tag;
+use crate::types::{DatasetInfo, TagSeries};
+use crate::views_client::ViewsClient;
+
+/// How many tag names to list per `GetTagList` call.
+const TAG_LIST_PAGE: i32 = 10_000;
+
+/// One view, with its own handle to the client's connection.
+pub struct View {
+    client: ViewsClient,
+    name: String,
+}
+
+impl ViewsClient {
+    /// A handle on `view`, or `NotFound` if this connection can't see it.
+    pub async fn view(&self, view: impl Into<String>) -> Result<View, Error> {
+        let name = view.into();
+        if !self.get_views().await?.views.contains(&name) {
+            return Err(Status::not_found(format!("view {name} not found")).into());
+        }
+        Ok(View {
+            client: self.handle(),
+            name,
+        })
+    }
+
+    /// A handle on the [default view](Self::set_default_view), without
+    /// checking it exists. `FailedPrecondition` if there is none.
+    pub fn current_view(&self) -> Result<View, Error> {
+        let name = self
+            .default_view()
+            .ok_or_else(|| Status::failed_precondition("no default view is set"))?;
+        Ok(View {
+            client: self.handle(),
+            name: name.to_string(),
+        })
+    }
+
+    /// A handle on the [default dataset](Self::set_default_dataset) of the
+    /// default view, without checking it exists. `FailedPrecondition` if
+    /// either is unset.
+    pub fn current_dataset(&self) -> Result<Dataset, Error> {
+        let view = self.current_view()?;
+        let name = self
+            .default_dataset()
+            .ok_or_else(|| Status::failed_precondition("no default dataset is set"))?;
+        Ok(view.dataset_handle(name.to_string()))
+    }
+
+    /// Every tag name in `dataset`, following the tag list's pages until one
+    /// comes back short.
+    pub async fn get_all_tags(
+        &self,
+        view: impl Into<String>,
+        dataset: impl Into<String>,
+    ) -> Result<Vec<String>, Error> {
+        let (view, dataset) = (view.into(), dataset.into());
+        let mut tags = Vec::new();
+        loop {
+            let page = tag_list_page(self, &view, &dataset, tags.len() as i32).await?;
+            let count = page.len();
+            tags.extend(page);
+            if count < TAG_LIST_PAGE as usize {
+                return Ok(tags);
+            }
+        }
+    }
+
+    /// Like [`get_all_tags`](Self::get_all_tags), but yielding names as the
+    /// pages arrive, for datasets too large to hold at once. A background
+    /// task fetches one page ahead of the reader and stops when the stream
+    /// is dropped. An error ends the stream.
+    pub fn tag_list_stream(
+        &self,
+        view: impl Into<String>,
+        dataset: impl Into<String>,
+    ) -> ResultStream<String> {
+        let client = self.handle();
+        let (view, dataset) = (view.into(), dataset.into());
+        let (tx, rx) = mpsc::channel(TAG_LIST_PAGE as usize);
+        tokio::spawn(request_id::inherit(async move {
+            let mut offset = 0;
+            loop {
+                let page = match tag_list_page(&client, &view, &dataset, offset).await {
+                    Ok(page) => page,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                let count = page.len();
+                for tag in page {
+                    if tx.send(Ok(tag)).await.is_err() {
+                        return;
+                    }
+                }
+                if count < TAG_LIST_PAGE as usize {
+                    return;
+                }
+                offset += count as i32;
+            }
+        }));
+        Box::pin(ReceiverStream::new(rx))
+    }
+}
+
+impl View {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Handles on the view's datasets, leaving out hidden ones.
+    pub async fn datasets(&mut self) -> Result<Vec<Dataset>, Error> {
+        let datasets = self.client.get_dataset_list(&self.name, false).await?;
+        Ok(datasets
+            .datasets
+            .into_iter()
+            .map(|name| self.dataset_handle(name))
+            .collect())
+    }
+
+    /// A handle on `dataset`, or `NotFound` if the view has no such dataset,
+    /// hidden or not.
+    pub async fn dataset(&mut self, dataset: impl Into<String>) -> Result<Dataset, Error> {
+        let name = dataset.into();
+        for include_hidden in [false, true] {
+            let datasets = self
+                .client
+                .get_dataset_list(&self.name, include_hidden)
+                .await?;
+            if datasets.datasets.contains(&name) {
+                return Ok(self.dataset_handle(name));
+            }
+        }
+        Err(Status::not_found(format!("dataset {name} not found in {}", self.name)).into())
+    }
+
+    /// The properties of `tags` in this view.
+    pub async fn tag_info(&mut self, tags: Vec<String>) -> Result<GetTagInfoResponse, Error> {
+        self.client.get_tag_info(&self.name, tags).await
+    }
+
+    /// The engineering units of `tags` in this view, keyed by tag name.
+    pub async fn eng_units(&mut self, tags: Vec<String>) -> Result<HashMap<String, String>, Error> {
+        self.client.get_eng_units(&self.name, tags).await
+    }
+
+    /// The oldest and latest sample times of `tags` in this view.
+    pub async fn data_context(
+        &mut self,
+        tags: Vec<String>,
+    ) -> Result<GetTagDataContextResponse, Error> {
+        self.client.get_tag_data_context(&self.name, tags).await
+    }
+
+    /// A handle on the tag at `path` in this view.
+    pub fn tag(&self, path: impl Into<String>) -> Tag {
+        self.client.tag(&self.name, path)
+    }
+
+    /// Start a query against this view.
+    pub fn query(&mut self) -> Query<'_> {
+        let view = self.name.clone();
+        self.client.query().view(view)
+    }
+
+    /// The raw samples of `tags` in `range`, one series per tag.
+    pub async fn raw(
+        &mut self,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        range: Range<SystemTime>,
+    ) -> Result<Vec<TagSeries>, Error> {
+        self.query()
+            .tags(tags)
+            .range(range.start, range.end)
+            .fetch()
+            .await
+    }
+
+    /// `aggregate` (e.g. `"TimeAverage"`) of `tags` over each `interval` of
+    /// `range`, one series per tag.
+    pub async fn aggregate(
+        &mut self,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        range: Range<SystemTime>,
+        interval: Duration,
+        aggregate: &str,
+    ) -> Result<Vec<TagSeries>, Error> {
+        self.query()
+            .tags(tags)
+            .range(range.start, range.end)
+            .aggregate(aggregate, interval)
+            .fetch()
+            .await
+    }
+
+    fn dataset_handle(&self, name: String) -> Dataset {
+        Dataset {
+            client: self.client.data_handle(),
+            view: self.name.clone(),
+            name,
+        }
+    }
+}
+
+impl fmt::Display for View {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl fmt::Debug for View {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("View")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One dataset in one view, with its own handle to the client's connection.
+///
+/// Tag names given to a dataset handle may leave out the dataset: `tag("Temp")`
+/// and `tag("Line1.Temp")` on dataset `Line1` both mean `Line1.Temp`.
+pub struct Dataset {
+    client: ViewsClient,
+    view: String,
+    name: String,
+}
+
+impl Dataset {
+    pub fn view(&self) -> &str {
+        &self.view
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The dataset's properties.
+    pub async fn info(&mut self) -> Result<DatasetInfo, Error> {
+        self.client.get_dataset_info(&self.view, &self.name).await
+    }
+
+    /// The names of every tag in the dataset, following the tag list's pages.
+    pub async fn tag_names(&mut self) -> Result<Vec<String>, Error> {
+        self.client.get_all_tags(&self.view, &self.name).await
+    }
+
+    /// Handles on every tag in the dataset.
+    pub async fn tags(&mut self) -> Result<Vec<Tag>, Error> {
+        let names = self.tag_names().await?;
+        Ok(names
+            .into_iter()
+            .map(|name| self.client.tag(&self.view, name))
+            .collect())
+    }
+
+    /// A handle on one of the dataset's tags.
+    pub fn tag(&self, name: &str) -> Tag {
+        self.client.tag(&self.view, self.path(name))
+    }
+
+    /// The raw samples of the dataset's `tags` in `range`, one series per
+    /// tag.
+    pub async fn raw(
+        &mut self,
+        tags: impl IntoIterator<Item = impl AsRef<str>>,
+        range: Range<SystemTime>,
+    ) -> Result<Vec<TagSeries>, Error> {
+        let paths: Vec<String> = tags.into_iter().map(|t| self.path(t.as_ref())).collect();
+        self.client
+            .query()
+            .view(&self.view)
+            .tags(paths)
+            .range(range.start, range.end)
+            .fetch()
+            .await
+    }
+
+    /// The full name of the dataset's tag `name`.
+    fn path(&self, name: &str) -> String {
+        match name.strip_prefix(&self.name) {
+            Some(rest) if rest.starts_with('.') => name.to_string(),
+            _ => format!("{}.{name}", self.name),
+        }
+    }
+}
+
+impl fmt::Display for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl fmt::Debug for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dataset")
+            .field("view", &self.view)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One page of a dataset's tag list, starting at `offset`.
+async fn tag_list_page(
+    client: &ViewsClient,
+    view: &str,
+    dataset: &str,
+    offset: i32,
+) -> Result<Vec<String>, Error> {
+    let page = client
+        .get_tag_list(view, dataset, offset, TAG_LIST_PAGE)
+        .await?;
+    match page.extended_status() {
+        get_tag_list_response::Status::Unspecified => Ok(page.tag_names),
+        get_tag_list_response::Status::ViewNotFound => {
+            Err(Status::not_found(format!("view {view} not found")).into())
+        }
+        get_tag_list_response::Status::PluginTagListError => {
+            Err(Status::internal(format!("listing the tags of {dataset} failed")).into())
+        }
+    }
+}
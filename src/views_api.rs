@@ -0,0 +1,298 @@
+//! An object-safe trait over the high-level [`ViewsClient`] API.
+//!
+//! Code that depends on [`CanaryViewsApi`] rather than the concrete client can
+//! be handed a fake, a `mockall` mock, or a `Box<dyn CanaryViewsApi>` in tests.
+
+use std::collections::HashMap;
+
+use tonic::codegen::tokio_stream::StreamExt;
+
+use crate::canary::views::grpc::api::*;
+use crate::columnar::RawDataColumns;
+use crate::error::{Error, ResultStream};
+use crate::types::DatasetInfo;
+use crate::views_client::ViewsClient;
+
+/// The high-level Views operations, as implemented by [`ViewsClient`].
+#[tonic::async_trait]
+pub trait CanaryViewsApi: Send {
+    /// The client connection ID.
+    fn cci(&self) -> i32;
+
+    /// Release the client connection ID.
+    async fn disconnect(&mut self) -> Result<(), Error>;
+
+    /// Send a keepalive for the client connection.
+    async fn keepalive(&mut self) -> Result<(), Error>;
+
+    /// Test the gRPC connection.
+    async fn test(&mut self) -> Result<(), Error>;
+
+    /// Get the service version.
+    async fn get_version(&mut self) -> Result<GetWebServiceVersionResponse, Error>;
+
+    /// Prefetch views, dataset lists, and the aggregate catalog.
+    async fn warm_up(&mut self) -> Result<(), Error>;
+
+    /// Get the list of views accessible to this connection.
+    async fn get_views(&mut self) -> Result<GetViewsResponse, Error>;
+
+    /// Get the datasets for a view.
+    async fn get_dataset_list(
+        &mut self,
+        view: &str,
+        include_hidden: bool,
+    ) -> Result<GetDataSetListResponse, Error>;
+
+    /// Get dataset info, parsed into a typed [`DatasetInfo`].
+    async fn get_dataset_info(
+        &mut self,
+        view: &str,
+        dataset_name: &str,
+    ) -> Result<DatasetInfo, Error>;
+
+    /// Get the tag list for a dataset.
+    async fn get_tag_list(
+        &mut self,
+        view: &str,
+        dataset_name: &str,
+        starting_offset: i32,
+        max_count: i32,
+    ) -> Result<GetTagListResponse, Error>;
+
+    /// Get tag info for the specified tags.
+    async fn get_tag_info(
+        &mut self,
+        view: &str,
+        tag_names: Vec<String>,
+    ) -> Result<GetTagInfoResponse, Error>;
+
+    /// Get the engineering units of the specified tags, keyed by tag name.
+    async fn get_eng_units(
+        &mut self,
+        view: &str,
+        tag_names: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// Get tag data context (temporal bounds) for specified tags.
+    async fn get_tag_data_context(
+        &mut self,
+        view: &str,
+        tag_names: Vec<String>,
+    ) -> Result<GetTagDataContextResponse, Error>;
+
+    /// Get the current value of specified tags.
+    async fn get_tag_current_value(
+        &mut self,
+        request: GetTagCurrentValueRequest,
+    ) -> Result<GetTagCurrentValueResponse, Error>;
+
+    /// Get raw data for tags within a time range.
+    async fn get_raw_data(
+        &mut self,
+        request: GetRawDataRequest,
+    ) -> Result<GetRawDataResponse, Error>;
+
+    /// Get raw data for tags, decoded into compact per-tag columns.
+    async fn get_raw_data_columnar(
+        &mut self,
+        request: GetRawDataRequest,
+    ) -> Result<RawDataColumns, Error>;
+
+    /// Get aggregate data for tags.
+    async fn get_aggregate_data(
+        &mut self,
+        request: GetAggregateDataRequest,
+    ) -> Result<GetAggregateDataResponse, Error>;
+
+    /// Get tag statistics.
+    async fn get_tag_statistics(
+        &mut self,
+        request: GetTagStatisticsRequest,
+    ) -> Result<GetTagStatisticsResponse, Error>;
+
+    /// Get the list of available aggregates.
+    async fn get_aggregate_list(&mut self) -> Result<GetAggregateListResponse, Error>;
+
+    /// Subscribe to live data updates.
+    async fn subscribe_to_live_data(
+        &mut self,
+        request: SubscribeToLiveDataRequest,
+    ) -> Result<ResultStream<SubscribeToLiveDataResponse>, Error>;
+
+    /// Browse the views tree by node ID.
+    async fn browse(
+        &mut self,
+        node_id_path: &str,
+        force_reload: bool,
+    ) -> Result<BrowseResponse, Error>;
+
+    /// Browse tags at a specified node.
+    async fn browse_tags(
+        &mut self,
+        request: BrowseTagsRequest,
+    ) -> Result<BrowseTagsResponse, Error>;
+
+    /// Search for tags matching criteria.
+    async fn search_tags(
+        &mut self,
+        request: SearchTagsRequest,
+    ) -> Result<SearchTagsResponse, Error>;
+
+    /// Browse by tree path.
+    async fn browse_path(&mut self, tree_path: Vec<String>) -> Result<BrowsePathResponse, Error>;
+}
+
+#[tonic::async_trait]
+impl CanaryViewsApi for ViewsClient {
+    fn cci(&self) -> i32 {
+        ViewsClient::cci(self)
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Error> {
+        ViewsClient::disconnect(self).await
+    }
+
+    async fn keepalive(&mut self) -> Result<(), Error> {
+        ViewsClient::keepalive(self).await
+    }
+
+    async fn test(&mut self) -> Result<(), Error> {
+        ViewsClient::test(self).await
+    }
+
+    async fn get_version(&mut self) -> Result<GetWebServiceVersionResponse, Error> {
+        ViewsClient::get_version(self).await
+    }
+
+    async fn warm_up(&mut self) -> Result<(), Error> {
+        ViewsClient::warm_up(self).await
+    }
+
+    async fn get_views(&mut self) -> Result<GetViewsResponse, Error> {
+        ViewsClient::get_views(self).await
+    }
+
+    async fn get_dataset_list(
+        &mut self,
+        view: &str,
+        include_hidden: bool,
+    ) -> Result<GetDataSetListResponse, Error> {
+        ViewsClient::get_dataset_list(self, view, include_hidden).await
+    }
+
+    async fn get_dataset_info(
+        &mut self,
+        view: &str,
+        dataset_name: &str,
+    ) -> Result<DatasetInfo, Error> {
+        ViewsClient::get_dataset_info(self, view, dataset_name).await
+    }
+
+    async fn get_tag_list(
+        &mut self,
+        view: &str,
+        dataset_name: &str,
+        starting_offset: i32,
+        max_count: i32,
+    ) -> Result<GetTagListResponse, Error> {
+        ViewsClient::get_tag_list(self, view, dataset_name, starting_offset, max_count).await
+    }
+
+    async fn get_tag_info(
+        &mut self,
+        view: &str,
+        tag_names: Vec<String>,
+    ) -> Result<GetTagInfoResponse, Error> {
+        ViewsClient::get_tag_info(self, view, tag_names).await
+    }
+
+    async fn get_eng_units(
+        &mut self,
+        view: &str,
+        tag_names: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        ViewsClient::get_eng_units(self, view, tag_names).await
+    }
+
+    async fn get_tag_data_context(
+        &mut self,
+        view: &str,
+        tag_names: Vec<String>,
+    ) -> Result<GetTagDataContextResponse, Error> {
+        ViewsClient::get_tag_data_context(self, view, tag_names).await
+    }
+
+    async fn get_tag_current_value(
+        &mut self,
+        request: GetTagCurrentValueRequest,
+    ) -> Result<GetTagCurrentValueResponse, Error> {
+        ViewsClient::get_tag_current_value(self, request).await
+    }
+
+    async fn get_raw_data(
+        &mut self,
+        request: GetRawDataRequest,
+    ) -> Result<GetRawDataResponse, Error> {
+        ViewsClient::get_raw_data(self, request).await
+    }
+
+    async fn get_raw_data_columnar(
+        &mut self,
+        request: GetRawDataRequest,
+    ) -> Result<RawDataColumns, Error> {
+        ViewsClient::get_raw_data_columnar(self, request).await
+    }
+
+    async fn get_aggregate_data(
+        &mut self,
+        request: GetAggregateDataRequest,
+    ) -> Result<GetAggregateDataResponse, Error> {
+        ViewsClient::get_aggregate_data(self, request).await
+    }
+
+    async fn get_tag_statistics(
+        &mut self,
+        request: GetTagStatisticsRequest,
+    ) -> Result<GetTagStatisticsResponse, Error> {
+        ViewsClient::get_tag_statistics(self, request).await
+    }
+
+    async fn get_aggregate_list(&mut self) -> Result<GetAggregateListResponse, Error> {
+        ViewsClient::get_aggregate_list(self).await
+    }
+
+    async fn subscribe_to_live_data(
+        &mut self,
+        request: SubscribeToLiveDataRequest,
+    ) -> Result<ResultStream<SubscribeToLiveDataResponse>, Error> {
+        let stream = ViewsClient::subscribe_to_live_data(self, request).await?;
+        Ok(Box::pin(stream.map(|update| update.map_err(Error::from))))
+    }
+
+    async fn browse(
+        &mut self,
+        node_id_path: &str,
+        force_reload: bool,
+    ) -> Result<BrowseResponse, Error> {
+        ViewsClient::browse(self, node_id_path, force_reload).await
+    }
+
+    async fn browse_tags(
+        &mut self,
+        request: BrowseTagsRequest,
+    ) -> Result<BrowseTagsResponse, Error> {
+        ViewsClient::browse_tags(self, request).await
+    }
+
+    async fn search_tags(
+        &mut self,
+        request: SearchTagsRequest,
+    ) -> Result<SearchTagsResponse, Error> {
+        ViewsClient::search_tags(self, request).await
+    }
+
+    async fn browse_path(&mut self, tree_path: Vec<String>) -> Result<BrowsePathResponse, Error> {
+        ViewsClient::browse_path(self, tree_path).await
+    }
+}
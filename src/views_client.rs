@@ -1,66 +1,55 @@
-use http::Uri;
-use hyper_util::client::legacy::connect::HttpConnector;
-use hyper_util::rt::TokioIo;
-use rustls::ClientConfig;
-use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
-use rustls::crypto;
-use std::sync::Arc;
-use tokio_rustls::TlsConnector;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::time::Duration;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
 use tonic::service::Interceptor;
 use tonic::service::interceptor::InterceptedService;
 use tonic::transport::{Channel, Endpoint};
-use tower::Service;
-use tower::service_fn;
 
 use crate::canary::views::grpc::api::canary_views_api_service_client::CanaryViewsApiServiceClient;
+use crate::capabilities::{Capabilities, Capability, ServerVersion};
 use crate::canary::views::grpc::api::*;
+use crate::clock_skew::SkewCheck;
+use crate::columnar::RawDataColumns;
+use crate::connection::{ConnectionState, StateMonitor};
+use crate::error::{CanaryError, Classify, Error, ResultStream};
+use crate::key_file::KeyFileWatch;
+use crate::lease::{CciLease, LeaseClock, LeaseWatch};
+#[cfg(feature = "oauth")]
+use crate::oauth::{TokenRefresh, TokenSource};
+use crate::request_id::{self, REQUEST_ID_HEADER};
+use crate::retry::RetryPolicy;
+use crate::shutdown::Tasks;
+use crate::slow_calls::{self, SlowCall, SlowCallLog};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsPolicy, tls_channel};
+use crate::transport::Redacted;
+#[cfg(not(feature = "tls"))]
+use crate::transport::plain_channel;
+use crate::truncation::TruncationCheck;
+use crate::types::{DatasetInfo, TagInfo};
 
-#[derive(Debug)]
-struct AcceptAnyCert;
-
-impl ServerCertVerifier for AcceptAnyCert {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &rustls::pki_types::CertificateDer<'_>,
-        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
-        _server_name: &rustls::pki_types::ServerName<'_>,
-        _ocsp_response: &[u8],
-        _now: rustls::pki_types::UnixTime,
-    ) -> Result<ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
-    }
+/// How long [`ViewsClientBuilder::connect`] waits by default.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
 
-    fn verify_tls12_signature(
-        &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
-    }
-
-    fn verify_tls13_signature(
-        &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
-    }
-
-    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        rustls::crypto::ring::default_provider()
-            .signature_verification_algorithms
-            .supported_schemes()
-    }
-}
+/// An API key shared by every handle on a connection.
+pub(crate) type SharedApiKey = Arc<RwLock<tonic::metadata::AsciiMetadataValue>>;
 
-trait TonicIo: hyper::rt::Read + hyper::rt::Write {}
-impl<T> TonicIo for T where T: hyper::rt::Read + hyper::rt::Write {}
+/// A bearer token (the whole `authorization` value) shared by every handle
+/// on a connection, if one is in use.
+pub(crate) type SharedBearer = Arc<RwLock<Option<tonic::metadata::AsciiMetadataValue>>>;
 
+/// Adds the API key, and a bearer token if there is one, to every request.
+/// Clones share both, so [`ViewsClient::set_api_key`] and token refreshes
+/// reach every handle on a connection.
 #[derive(Clone)]
 pub struct ApiKeyInterceptor {
-    api_key: tonic::metadata::MetadataValue<tonic::metadata::Ascii>,
+    api_key: SharedApiKey,
+    bearer: SharedBearer,
 }
 
 impl Interceptor for ApiKeyInterceptor {
@@ -68,185 +57,955 @@ impl Interceptor for ApiKeyInterceptor {
         &mut self,
         mut request: tonic::Request<()>,
     ) -> Result<tonic::Request<()>, tonic::Status> {
-        request
-            .metadata_mut()
-            .insert("canary-api-token", self.api_key.clone());
+        let api_key = self
+            .api_key
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let bearer = self
+            .bearer
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let metadata = request.metadata_mut();
+        // Behind a token-checking gateway the API key may be left empty.
+        if !api_key.is_empty() {
+            metadata.insert("canary-api-token", api_key);
+        }
+        if let Some(bearer) = bearer {
+            metadata.insert("authorization", bearer);
+        }
+        metadata.insert(REQUEST_ID_HEADER, request_id::for_call());
         Ok(request)
     }
 }
 
+impl fmt::Debug for ApiKeyInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyInterceptor")
+            .field("api_key", &Redacted)
+            .field("bearer", &Redacted)
+            .finish()
+    }
+}
+
+/// The generated client every call goes through.
+pub(crate) type Inner = CanaryViewsApiServiceClient<InterceptedService<Channel, ApiKeyInterceptor>>;
+
+/// A client connection ID shared by every handle on it, so that a
+/// replacement reaches them all.
+#[derive(Debug)]
+struct SharedCci {
+    id: AtomicI32,
+    /// Held while acquiring a replacement, so handles that fail together
+    /// acquire one between them.
+    replacing: tokio::sync::Mutex<()>,
+}
+
+impl SharedCci {
+    fn new(id: i32) -> Arc<Self> {
+        Arc::new(Self {
+            id: AtomicI32::new(id),
+            replacing: tokio::sync::Mutex::new(()),
+        })
+    }
+}
+
+/// Catalog metadata that rarely changes during a session.
+#[derive(Debug, Clone, Default)]
+struct MetadataCache {
+    views: Option<GetViewsResponse>,
+    dataset_lists: HashMap<(String, bool), GetDataSetListResponse>,
+    aggregates: Option<GetAggregateListResponse>,
+}
+
+/// Builder for a [`ViewsClient`] connection, with HTTP/2 transport tuning.
+///
+/// The hyper defaults (64 KiB stream and connection windows) cap throughput
+/// at roughly one window per round trip, which throttles large raw-data
+/// transfers over high-latency WAN links. Raise the windows, or enable the
+/// adaptive window, for those deployments.
+#[derive(Clone)]
+pub struct ViewsClientBuilder {
+    endpoint: String,
+    api_key: String,
+    app: String,
+    user_id: String,
+    initial_stream_window_size: Option<u32>,
+    initial_connection_window_size: Option<u32>,
+    http2_adaptive_window: Option<bool>,
+    concurrency_limit: Option<usize>,
+    #[cfg(feature = "tls")]
+    tls_policy: TlsPolicy,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    http2_keepalive_interval: Option<Duration>,
+    http2_keepalive_timeout: Option<Duration>,
+    skew_check: Option<SkewCheck>,
+    cci_lease: Option<CciLease>,
+    reacquire_cci: bool,
+    retry: Option<RetryPolicy>,
+    #[cfg(feature = "keyring")]
+    keyring: bool,
+    api_key_file: Option<PathBuf>,
+    slow_calls: Option<SlowCallLog>,
+    truncation: Option<TruncationCheck>,
+    #[cfg(feature = "oauth")]
+    token_source: Option<Arc<dyn TokenSource>>,
+}
+
+impl ViewsClientBuilder {
+    /// Replace the API key given to [`ViewsClient::builder`].
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = api_key.into();
+        self
+    }
+
+    /// The application name reported when acquiring the client connection ID.
+    /// Defaults to `"crowsong"`.
+    pub fn app(mut self, app: impl Into<String>) -> Self {
+        self.app = app.into();
+        self
+    }
+
+    /// The user reported when acquiring the client connection ID. Defaults to `"rust"`.
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = user_id.into();
+        self
+    }
+
+    /// The HTTP/2 flow-control window for each stream, in bytes.
+    pub fn initial_stream_window_size(mut self, size: u32) -> Self {
+        self.initial_stream_window_size = Some(size);
+        self
+    }
+
+    /// The HTTP/2 flow-control window for the whole connection, in bytes.
+    pub fn initial_connection_window_size(mut self, size: u32) -> Self {
+        self.initial_connection_window_size = Some(size);
+        self
+    }
+
+    /// Size the flow-control windows from the measured bandwidth-delay
+    /// product. Overrides the fixed window sizes.
+    pub fn http2_adaptive_window(mut self, enabled: bool) -> Self {
+        self.http2_adaptive_window = Some(enabled);
+        self
+    }
+
+    /// The maximum number of requests in flight on the channel at once.
+    pub fn concurrency_limit(mut self, limit: usize) -> Self {
+        self.concurrency_limit = Some(limit);
+        self
+    }
+
+    /// The TLS versions and cipher suites `https` endpoints may use. See
+    /// [`tls`](crate::tls).
+    #[cfg(feature = "tls")]
+    pub fn tls_policy(mut self, policy: TlsPolicy) -> Self {
+        self.tls_policy = policy;
+        self
+    }
+
+    /// The longest [`connect`](Self::connect) may take to dial, complete the
+    /// TLS handshake, and acquire the client connection ID, or `None` to wait
+    /// indefinitely. Defaults to 30 seconds.
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// The longest each call may take, or `None` to wait indefinitely. The
+    /// server is told the deadline too. Defaults to `None`; a streaming
+    /// call's deadline covers only its first response.
+    pub fn request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Send TCP keepalive probes after the connection has been idle this
+    /// long, or `None` to leave them off, the default. Keeps NAT and
+    /// firewall state alive between infrequent polls.
+    pub fn tcp_keepalive(mut self, idle: Option<Duration>) -> Self {
+        self.tcp_keepalive = idle;
+        self
+    }
+
+    /// Send an HTTP/2 ping this often, even while no call is in flight, so
+    /// a dead connection is noticed before the next call rather than by it.
+    /// Off by default.
+    pub fn http2_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keepalive_interval = Some(interval);
+        self
+    }
+
+    /// How long to wait for a keepalive ping's answer before closing the
+    /// connection. Defaults to 20 seconds.
+    pub fn http2_keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.http2_keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// Compare the server's clock with the local one at connect, reporting
+    /// skew at or above the check's threshold, or `None` to skip the check.
+    /// Defaults to warning on stderr at 5 seconds; see
+    /// [`clock_skew`](crate::clock_skew).
+    pub fn clock_skew_check(mut self, check: Option<SkewCheck>) -> Self {
+        self.skew_check = check;
+        self
+    }
+
+    /// The server's connection timeout, after which an unused client
+    /// connection ID expires, and whether to warn or send a keepalive as it
+    /// nears. See [`lease`](crate::lease).
+    pub fn cci_lease(mut self, lease: CciLease) -> Self {
+        self.cci_lease = Some(lease);
+        self
+    }
+
+    /// When the server rejects the client connection ID, say after a restart
+    /// or once it expired, acquire a new one and retry the call once. On by
+    /// default. The new ID replaces the old one for every handle on it.
+    pub fn reacquire_cci(mut self, enabled: bool) -> Self {
+        self.reacquire_cci = enabled;
+        self
+    }
+
+    /// Resend calls that fail for transient reasons as `policy` says, or
+    /// `None` to fail at once, the default. See [`retry`](crate::retry).
+    pub fn retry_policy(mut self, policy: Option<RetryPolicy>) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Use the API key stored for this endpoint in the platform credential
+    /// store (see [`credentials`](crate::credentials)), falling back to the
+    /// key given to [`ViewsClient::builder`] if none is stored.
+    #[cfg(feature = "keyring")]
+    pub fn api_key_from_keyring(mut self) -> Self {
+        self.keyring = true;
+        self
+    }
+
+    /// Read the API key from the file at `path` instead, such as a mounted
+    /// Kubernetes secret, and swap in the new key whenever the file changes.
+    /// The file is checked every 30 seconds while the client or any handle
+    /// on it is alive.
+    pub fn api_key_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.api_key_file = Some(path.into());
+        self
+    }
+
+    /// Report data calls that take at least the log's threshold.
+    pub fn slow_call_log(mut self, log: SlowCallLog) -> Self {
+        self.slow_calls = Some(log);
+        self
+    }
+
+    /// What to do about raw reads cut short by `max_count_per_tag`; `None`
+    /// turns the check off. Defaults to reporting them on stderr. See
+    /// [`truncation`](crate::truncation).
+    pub fn truncation_check(mut self, check: Option<TruncationCheck>) -> Self {
+        self.truncation = check;
+        self
+    }
+
+    /// Send bearer tokens from `source` on every call, fetching the first
+    /// before connecting and refreshing it before it expires. See
+    /// [`oauth`](crate::oauth).
+    #[cfg(feature = "oauth")]
+    pub fn bearer_tokens(mut self, source: impl TokenSource) -> Self {
+        self.token_source = Some(Arc::new(source));
+        self
+    }
+
+    /// Connect and acquire a client connection ID.
+    pub async fn connect(self) -> Result<ViewsClient, Error> {
+        #[cfg(feature = "keyring")]
+        let api_key = if self.keyring {
+            crate::credentials::load_api_key(&self.endpoint)
+                .map_err(|e| Error::Credentials(e.to_string()))?
+                .unwrap_or(self.api_key)
+        } else {
+            self.api_key
+        };
+        #[cfg(not(feature = "keyring"))]
+        let api_key = self.api_key;
+        let api_key = match &self.api_key_file {
+            Some(path) => crate::key_file::read_key_file(path).map_err(|e| {
+                Error::Credentials(format!("reading the API key from {}: {e}", path.display()))
+            })?,
+            None => api_key,
+        };
+        let mut endpoint = Endpoint::from_shared(self.endpoint)?
+            .initial_stream_window_size(self.initial_stream_window_size)
+            .initial_connection_window_size(self.initial_connection_window_size);
+        if let Some(enabled) = self.http2_adaptive_window {
+            endpoint = endpoint.http2_adaptive_window(enabled);
+        }
+        if let Some(limit) = self.concurrency_limit {
+            endpoint = endpoint.concurrency_limit(limit);
+        }
+        if let Some(timeout) = self.request_timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some(interval) = self.http2_keepalive_interval {
+            endpoint = endpoint
+                .http2_keep_alive_interval(interval)
+                .keep_alive_while_idle(true);
+        }
+        if let Some(timeout) = self.http2_keepalive_timeout {
+            endpoint = endpoint.keep_alive_timeout(timeout);
+        }
+        #[cfg(feature = "tls")]
+        let channel = tls_channel(endpoint, &self.tls_policy, self.tcp_keepalive)?;
+        #[cfg(not(feature = "tls"))]
+        let channel = plain_channel(endpoint.tcp_keepalive(self.tcp_keepalive))?;
+
+        let bearer = SharedBearer::default();
+        #[cfg(feature = "oauth")]
+        let token = match &self.token_source {
+            Some(source) => {
+                let token = source
+                    .fetch()
+                    .await
+                    .map_err(|e| Error::Other(format!("fetching a bearer token: {e}").into()))?;
+                crate::oauth::set_bearer(&bearer, &token)?;
+                Some(token)
+            }
+            None => None,
+        };
+
+        // The channel connects lazily, so the first call dials and shakes
+        // hands too; bounding it bounds the whole connect.
+        let connecting =
+            ViewsClient::handshake(channel, api_key, bearer.clone(), self.app, self.user_id);
+        let mut client = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connecting)
+                .await
+                .map_err(|_| Error::Timeout(timeout))??,
+            None => connecting.await?,
+        };
+        // Servers that can't resolve `Now` just go unchecked.
+        if let Some(check) = &self.skew_check {
+            let measuring = client.clock_skew();
+            let skew = match self.connect_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, measuring).await.ok(),
+                None => Some(measuring.await),
+            };
+            if let Some(Ok(skew)) = skew {
+                check.check(&skew);
+            }
+        }
+        client.slow_calls = self.slow_calls;
+        client.truncation = self.truncation;
+        client.reacquire_cci = self.reacquire_cci;
+        client.retry = self.retry;
+        if let Some(path) = self.api_key_file {
+            let watch = crate::key_file::spawn_watch(
+                client.api_key(),
+                path,
+                crate::key_file::KEY_FILE_POLL,
+            );
+            client.tasks.register(watch.abort_handle());
+            client.key_watch = Some(Arc::new(watch));
+        }
+        if let Some(lease) = self.cci_lease {
+            client.lease_timeout = Some(lease.timeout());
+            let watch = crate::lease::spawn_watch(&client, client.lease_clock.clone(), lease);
+            client.tasks.register(watch.abort_handle());
+            client.lease_watch = Some(Arc::new(watch));
+        }
+        #[cfg(feature = "oauth")]
+        if let (Some(source), Some(token)) = (self.token_source, token) {
+            let refresh = crate::oauth::spawn_refresh(bearer, source, token);
+            client.tasks.register(refresh.abort_handle());
+            client.token_refresh = Some(Arc::new(refresh));
+        }
+        Ok(client)
+    }
+}
+
+impl fmt::Debug for ViewsClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut builder = f.debug_struct("ViewsClientBuilder");
+        builder
+            .field("endpoint", &self.endpoint)
+            .field("api_key", &Redacted)
+            .field("app", &self.app)
+            .field("user_id", &self.user_id)
+            .field(
+                "initial_stream_window_size",
+                &self.initial_stream_window_size,
+            )
+            .field(
+                "initial_connection_window_size",
+                &self.initial_connection_window_size,
+            )
+            .field("http2_adaptive_window", &self.http2_adaptive_window)
+            .field("concurrency_limit", &self.concurrency_limit);
+        #[cfg(feature = "tls")]
+        builder.field("tls_policy", &self.tls_policy);
+        builder
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("tcp_keepalive", &self.tcp_keepalive)
+            .field("http2_keepalive_interval", &self.http2_keepalive_interval)
+            .field("http2_keepalive_timeout", &self.http2_keepalive_timeout)
+            .field("skew_check", &self.skew_check)
+            .field("cci_lease", &self.cci_lease)
+            .field("reacquire_cci", &self.reacquire_cci)
+            .field("retry", &self.retry);
+        #[cfg(feature = "keyring")]
+        builder.field("keyring", &self.keyring);
+        builder
+            .field("api_key_file", &self.api_key_file)
+            .field("slow_calls", &self.slow_calls)
+            .field("truncation", &self.truncation);
+        #[cfg(feature = "oauth")]
+        builder.field("bearer_tokens", &self.token_source.is_some());
+        builder.finish()
+    }
+}
+
+/// A connection to a Canary Views service.
+///
+/// Clones share the channel, the client connection ID, and the metadata
+/// cache, and calls take `&self`, so one client can be cloned into tasks or
+/// shared behind an `Arc` to issue calls concurrently. Settings changed with
+/// the `set_` methods apply to the clone they're changed on and clones made
+/// from it afterwards.
+#[derive(Clone)]
 pub struct ViewsClient {
-    inner: CanaryViewsApiServiceClient<InterceptedService<Channel, ApiKeyInterceptor>>,
-    cci: i32,
+    inner: Inner,
+    /// The same service as `inner`, for calls that need a custom codec.
+    service: InterceptedService<Channel, ApiKeyInterceptor>,
+    cci: Arc<SharedCci>,
+    /// Whether to replace a rejected CCI and retry the call.
+    reacquire_cci: bool,
+    retry: Option<RetryPolicy>,
+    app: String,
+    user_id: String,
+    capabilities: Capabilities,
+    /// Shared by clones.
+    cache: Arc<Mutex<MetadataCache>>,
+    api_key: SharedApiKey,
+    /// Reloads `api_key` from a file; stops when the last handle is dropped.
+    key_watch: Option<Arc<KeyFileWatch>>,
+    /// Refreshes the bearer token; stops when the last handle is dropped.
+    #[cfg(feature = "oauth")]
+    token_refresh: Option<Arc<TokenRefresh>>,
+    slow_calls: Option<SlowCallLog>,
+    truncation: Option<TruncationCheck>,
+    default_view: Option<String>,
+    default_dataset: Option<String>,
+    /// When the CCI last answered a call, shared by handles on the same CCI.
+    lease_clock: Arc<LeaseClock>,
+    lease_timeout: Option<Duration>,
+    /// Warns or sends keepalives; stops when the last handle is dropped.
+    lease_watch: Option<Arc<LeaseWatch>>,
+    /// Background work to stop on [`shutdown`](Self::shutdown), shared by
+    /// every handle on the connection.
+    tasks: Arc<Tasks>,
+}
+
+impl fmt::Debug for ViewsClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ViewsClient")
+            .field("cci", &self.cci())
+            .field("app", &self.app)
+            .field("user_id", &self.user_id)
+            .field("capabilities", &self.capabilities)
+            .field("api_key", &Redacted)
+            .finish_non_exhaustive()
+    }
 }
 
 impl ViewsClient {
+    /// Start configuring a connection to a Canary Views service.
+    pub fn builder(endpoint: impl Into<String>, api_key: impl Into<String>) -> ViewsClientBuilder {
+        ViewsClientBuilder {
+            endpoint: endpoint.into(),
+            api_key: api_key.into(),
+            app: "crowsong".to_string(),
+            user_id: "rust".to_string(),
+            initial_stream_window_size: None,
+            initial_connection_window_size: None,
+            http2_adaptive_window: None,
+            concurrency_limit: None,
+            #[cfg(feature = "tls")]
+            tls_policy: TlsPolicy::default(),
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            request_timeout: None,
+            tcp_keepalive: None,
+            http2_keepalive_interval: None,
+            http2_keepalive_timeout: None,
+            skew_check: Some(SkewCheck::default()),
+            cci_lease: None,
+            reacquire_cci: true,
+            retry: None,
+            #[cfg(feature = "keyring")]
+            keyring: false,
+            api_key_file: None,
+            slow_calls: None,
+            truncation: Some(TruncationCheck::new()),
+            #[cfg(feature = "oauth")]
+            token_source: None,
+        }
+    }
+
     /// Connect to a Canary Views service and acquire a client connection ID.
     pub async fn connect(
         endpoint: impl Into<String>,
         api_key: impl Into<String>,
         app: impl Into<String>,
         user_id: impl Into<String>,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        if crypto::CryptoProvider::get_default().is_none() {
-            let _ = crypto::ring::default_provider().install_default();
-        }
+    ) -> Result<Self, Error> {
+        Self::builder(endpoint, api_key)
+            .app(app)
+            .user_id(user_id)
+            .connect()
+            .await
+    }
 
-        let verifier = Arc::new(AcceptAnyCert);
-
-        let mut config = ClientConfig::builder()
-            .dangerous()
-            .with_custom_certificate_verifier(verifier)
-            .with_no_client_auth();
-
-        config.alpn_protocols.push(b"h2".to_vec());
-
-        let tls = TlsConnector::from(Arc::new(config));
-
-        let mut http = HttpConnector::new();
-        http.enforce_http(false);
-
-        type BoxedIo = Box<dyn TonicIo + Send + Unpin>;
-
-        let connector = service_fn(move |uri: Uri| {
-            let tls = tls.clone();
-            let mut http = http.clone();
-            async move {
-                let tcp = http.call(uri.clone()).await?;
-                let tcp = tcp.into_inner();
-                if uri.scheme_str() == Some("https") {
-                    let host = uri
-                        .host()
-                        .ok_or_else(|| {
-                            std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing host")
-                        })?
-                        .to_string();
-                    let server_name =
-                        rustls::pki_types::ServerName::try_from(host).map_err(|_| {
-                            std::io::Error::new(
-                                std::io::ErrorKind::InvalidInput,
-                                "invalid server name",
-                            )
-                        })?;
-                    let tls_stream = tls.connect(server_name, tcp).await?;
-                    Ok::<BoxedIo, Box<dyn std::error::Error + Send + Sync>>(Box::new(TokioIo::new(
-                        tls_stream,
-                    )))
-                } else {
-                    Ok::<BoxedIo, Box<dyn std::error::Error + Send + Sync>>(Box::new(TokioIo::new(
-                        tcp,
-                    )))
-                }
-            }
-        });
+    /// Acquire a client connection ID over an already-built channel, for
+    /// transports [`builder`](Self::builder) can't make: a custom TLS stack
+    /// or connector, a service mesh sidecar, or an in-process channel in
+    /// tests. Only the handshake is done here; the channel's own settings
+    /// stand, and the builder's options (timeouts, lease, key file) don't
+    /// apply.
+    pub async fn with_channel(
+        channel: Channel,
+        api_key: impl Into<String>,
+        app: impl Into<String>,
+        user_id: impl Into<String>,
+    ) -> Result<Self, Error> {
+        Self::handshake(channel, api_key, SharedBearer::default(), app, user_id).await
+    }
 
-        let endpoint = Endpoint::from_shared(endpoint.into())?;
-        let channel = Channel::new(connector, endpoint);
+    /// Acquire a client connection ID, sending whatever bearer token
+    /// `bearer` holds at the time of each call.
+    async fn handshake(
+        channel: Channel,
+        api_key: impl Into<String>,
+        bearer: SharedBearer,
+        app: impl Into<String>,
+        user_id: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let api_key: SharedApiKey = Arc::new(RwLock::new(api_key.into().parse()?));
+        let interceptor = ApiKeyInterceptor {
+            api_key: api_key.clone(),
+            bearer,
+        };
+        let service = InterceptedService::new(channel, interceptor);
+        let mut inner = CanaryViewsApiServiceClient::new(service.clone());
+        let (app, user_id) = (app.into(), user_id.into());
 
-        let api_key: tonic::metadata::MetadataValue<_> = api_key.into().parse()?;
-        let interceptor = ApiKeyInterceptor { api_key };
-        let mut inner = CanaryViewsApiServiceClient::with_interceptor(channel, interceptor);
+        let resp = request_id::call(
+            inner.get_client_connection_id(GetClientConnectionIdRequest {
+                app: app.clone(),
+                user_id: user_id.clone(),
+            }),
+        )
+        .await?
+        .into_inner();
 
-        let resp = inner
-            .get_client_connection_id(GetClientConnectionIdRequest {
-                app: app.into(),
-                user_id: user_id.into(),
-            })
-            .await?
-            .into_inner();
+        // Servers that won't report a version are assumed to support everything.
+        let version = match inner.get_web_service_version(()).await {
+            Ok(version) => ServerVersion::parse(&version.into_inner().version),
+            Err(_) => None,
+        };
 
         Ok(Self {
             inner,
-            cci: resp.cci,
+            service,
+            cci: SharedCci::new(resp.cci),
+            reacquire_cci: true,
+            retry: None,
+            app,
+            user_id,
+            capabilities: Capabilities::new(version),
+            cache: Arc::default(),
+            api_key,
+            key_watch: None,
+            #[cfg(feature = "oauth")]
+            token_refresh: None,
+            slow_calls: None,
+            truncation: Some(TruncationCheck::new()),
+            default_view: None,
+            default_dataset: None,
+            lease_clock: LeaseClock::new(StateMonitor::new()),
+            lease_timeout: None,
+            lease_watch: None,
+            tasks: Tasks::new(),
         })
     }
 
+    /// Acquire another client connection ID over the same channel and credentials.
+    pub(crate) async fn with_new_cci(&self) -> Result<Self, tonic::Status> {
+        let mut client = self.handle();
+        // The new CCI has its own lease; a watch on this one doesn't cover it.
+        client.lease_clock = LeaseClock::new(self.lease_clock.state().clone());
+        client.lease_watch = None;
+        client.cci = SharedCci::new(self.acquire_cci().await?);
+        Ok(client)
+    }
+
+    async fn acquire_cci(&self) -> Result<i32, tonic::Status> {
+        let mut inner = self.inner.clone();
+        Ok(request_id::call(
+            inner.get_client_connection_id(GetClientConnectionIdRequest {
+                app: self.app.clone(),
+                user_id: self.user_id.clone(),
+            }),
+        )
+        .await?
+        .into_inner()
+        .cci)
+    }
+
+    /// Send the call `call` makes, resending it as the retry policy says.
+    pub(crate) async fn call<T, F, Fut>(&self, call: F) -> Result<T, tonic::Status>
+    where
+        F: Fn(Inner) -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    {
+        self.call_with_cci(|inner, _| call(inner)).await
+    }
+
+    /// Send the call `call` makes with the client connection ID, resending
+    /// it as the retry policy says. If the server rejects the ID and
+    /// reacquiring is on, replace it and send the call once more.
+    pub(crate) async fn call_with_cci<T, F, Fut>(&self, call: F) -> Result<T, tonic::Status>
+    where
+        F: Fn(Inner, i32) -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    {
+        match &self.retry {
+            // Every attempt goes out under one request ID.
+            Some(policy) => {
+                request_id::operation(policy.run(|| self.attempt_with_cci(&call))).await
+            }
+            None => self.attempt_with_cci(&call).await,
+        }
+    }
+
+    async fn attempt_with_cci<T, F, Fut>(&self, call: &F) -> Result<T, tonic::Status>
+    where
+        F: Fn(Inner, i32) -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    {
+        // Boxed so the futures of callers that nest several calls stay
+        // small enough for a default thread stack.
+        let cci = self.cci();
+        let result = self
+            .lease_clock
+            .track(Box::pin(request_id::call(call(self.inner.clone(), cci))))
+            .await;
+        match result {
+            Err(status) if self.reacquire_cci && status.is_invalid_cci() => {
+                Box::pin(self.replace_cci(cci)).await?;
+                self.lease_clock
+                    .track(Box::pin(request_id::call(call(
+                        self.inner.clone(),
+                        self.cci(),
+                    ))))
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    /// Send the call `call` makes with the client connection ID exactly
+    /// once, for writes that aren't safe to repeat. A rejected ID is still
+    /// replaced if reacquiring is on, but the call isn't resent; the caller
+    /// gets the rejection.
+    pub(crate) async fn call_with_cci_once<T, F, Fut>(&self, call: F) -> Result<T, tonic::Status>
+    where
+        F: FnOnce(Inner, i32) -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    {
+        let cci = self.cci();
+        let result = self
+            .lease_clock
+            .track(Box::pin(request_id::call(call(self.inner.clone(), cci))))
+            .await;
+        if let Err(status) = &result
+            && self.reacquire_cci
+            && status.is_invalid_cci()
+        {
+            Box::pin(self.replace_cci(cci)).await?;
+        }
+        result
+    }
+
+    /// Acquire a new client connection ID for every handle on `stale`,
+    /// unless another handle already has.
+    pub(crate) async fn replace_cci(&self, stale: i32) -> Result<(), tonic::Status> {
+        let _replacing = self.cci.replacing.lock().await;
+        if self.cci() != stale {
+            return Ok(());
+        }
+        let cci = self.acquire_cci().await?;
+        self.cci.id.store(cci, Ordering::Release);
+        Ok(())
+    }
+
+    /// Send `api_key` on every later call from this client and every handle
+    /// on its connection. Use it when a rotated key comes from somewhere
+    /// other than a file; see [`ViewsClientBuilder::api_key_file`] for
+    /// reloading one automatically.
+    pub fn set_api_key(
+        &self,
+        api_key: &str,
+    ) -> Result<(), tonic::metadata::errors::InvalidMetadataValue> {
+        let api_key = api_key.parse()?;
+        *self.api_key.write().unwrap_or_else(PoisonError::into_inner) = api_key;
+        Ok(())
+    }
+
+    /// Report data calls that take at least the log's threshold, or stop
+    /// reporting them. Applies to this client and handles made from it
+    /// afterwards.
+    pub fn set_slow_call_log(&mut self, log: Option<SlowCallLog>) {
+        self.slow_calls = log;
+    }
+
+    /// Resend calls that fail for transient reasons as `policy` says, or
+    /// stop resending them. Applies to this client and handles made from it
+    /// afterwards.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry = policy;
+    }
+
+    /// Change what happens to truncated raw reads, or stop checking for
+    /// them. Applies to this client and handles made from it afterwards.
+    pub fn set_truncation_check(&mut self, check: Option<TruncationCheck>) {
+        self.truncation = check;
+    }
+
+    /// The view [`current_view`](Self::current_view) and [`query`](Self::query)
+    /// use when none is given, for code that only reads one view. Clears the
+    /// default dataset. Applies to this client and handles made from it
+    /// afterwards.
+    pub fn set_default_view(&mut self, view: impl Into<String>) {
+        self.default_view = Some(view.into());
+        self.default_dataset = None;
+    }
+
+    /// The dataset of the default view that
+    /// [`current_dataset`](Self::current_dataset) uses.
+    pub fn set_default_dataset(&mut self, dataset: impl Into<String>) {
+        self.default_dataset = Some(dataset.into());
+    }
+
+    /// Forget the default view and dataset.
+    pub fn clear_defaults(&mut self) {
+        self.default_view = None;
+        self.default_dataset = None;
+    }
+
+    pub fn default_view(&self) -> Option<&str> {
+        self.default_view.as_deref()
+    }
+
+    pub fn default_dataset(&self) -> Option<&str> {
+        self.default_dataset.as_deref()
+    }
+
     /// Release the client connection ID.
-    pub async fn disconnect(&mut self) -> Result<(), tonic::Status> {
-        self.inner
-            .release_client_connection_id(ReleaseClientConnectionIdRequest { cci: self.cci })
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        self.lease_clock
+            .track(request_id::call(
+                self.inner
+                    .clone()
+                    .release_client_connection_id(ReleaseClientConnectionIdRequest {
+                        cci: self.cci(),
+                    }),
+            ))
             .await?;
+        self.state_monitor().set(ConnectionState::Disconnected);
         Ok(())
     }
 
     /// Send a keepalive for the client connection.
-    pub async fn keepalive(&mut self) -> Result<(), tonic::Status> {
-        self.inner
-            .keepalive_client_connection_id(KeepaliveClientConnectionIdRequest { cci: self.cci })
+    pub async fn keepalive(&self) -> Result<(), Error> {
+        self.lease_clock
+            .track(request_id::call(
+                self.inner.clone().keepalive_client_connection_id(
+                    KeepaliveClientConnectionIdRequest { cci: self.cci() },
+                ),
+            ))
             .await?;
         Ok(())
     }
 
     /// Test the gRPC connection.
-    pub async fn test(&mut self) -> Result<(), tonic::Status> {
-        self.inner.test(()).await?;
+    pub async fn test(&self) -> Result<(), Error> {
+        self.call(|mut inner| async move { inner.test(()).await })
+            .await?;
         Ok(())
     }
 
     /// Get the service version.
-    pub async fn get_version(&mut self) -> Result<GetWebServiceVersionResponse, tonic::Status> {
-        Ok(self.inner.get_web_service_version(()).await?.into_inner())
-    }
-
-    /// Get the list of views accessible to this connection.
-    pub async fn get_views(&mut self) -> Result<GetViewsResponse, tonic::Status> {
+    pub async fn get_version(&self) -> Result<GetWebServiceVersionResponse, Error> {
         Ok(self
-            .inner
-            .get_views(GetViewsRequest { cci: self.cci })
+            .call(|mut inner| async move { inner.get_web_service_version(()).await })
             .await?
             .into_inner())
     }
 
-    /// Get the datasets for a view.
+    /// Concurrently fetch the views, each view's dataset list, and the
+    /// aggregate catalog into the metadata cache.
+    ///
+    /// Call this right after connecting so the first catalog lookups in an
+    /// interactive session are served locally instead of one round trip each.
+    pub async fn warm_up(&self) -> Result<(), Error> {
+        Ok(request_id::operation(self.warm_up_cache()).await?)
+    }
+
+    async fn warm_up_cache(&self) -> Result<(), tonic::Status> {
+        let (views, aggregates) = tokio::try_join!(
+            self.call_with_cci(|mut inner, cci| async move {
+                inner.get_views(GetViewsRequest { cci }).await
+            }),
+            self.call(|mut inner| async move { inner.get_aggregate_list(()).await }),
+        )?;
+        let views = views.into_inner();
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for view in &views.views {
+            let client = self.clone();
+            let view = view.clone();
+            tasks.spawn(request_id::inherit(async move {
+                let datasets = client
+                    .call_with_cci(|mut inner, cci| {
+                        let request = GetDataSetListRequest {
+                            view: view.clone(),
+                            include_hidden: false,
+                            cci,
+                        };
+                        async move { inner.get_data_set_list(request).await }
+                    })
+                    .await?;
+                Ok::<_, tonic::Status>(((view, false), datasets.into_inner()))
+            }));
+        }
+        while let Some(result) = tasks.join_next().await {
+            let (key, datasets) = result.map_err(|e| tonic::Status::internal(e.to_string()))??;
+            self.cache().dataset_lists.insert(key, datasets);
+        }
+
+        let mut cache = self.cache();
+        cache.views = Some(views);
+        cache.aggregates = Some(aggregates.into_inner());
+        Ok(())
+    }
+
+    /// Drop all cached catalog metadata, so the next lookups hit the server.
+    pub fn clear_cache(&self) {
+        *self.cache() = MetadataCache::default();
+    }
+
+    /// Get the list of views accessible to this connection. Cached.
+    pub async fn get_views(&self) -> Result<GetViewsResponse, Error> {
+        if let Some(views) = &self.cache().views {
+            return Ok(views.clone());
+        }
+        let views = self
+            .call_with_cci(|mut inner, cci| async move {
+                inner.get_views(GetViewsRequest { cci }).await
+            })
+            .await?
+            .into_inner();
+        self.cache().views = Some(views.clone());
+        Ok(views)
+    }
+
+    /// Get the datasets for a view. Cached.
     pub async fn get_dataset_list(
-        &mut self,
+        &self,
         view: impl Into<String>,
         include_hidden: bool,
-    ) -> Result<GetDataSetListResponse, tonic::Status> {
-        Ok(self
-            .inner
-            .get_data_set_list(GetDataSetListRequest {
-                view: view.into(),
-                include_hidden,
-                cci: self.cci,
+    ) -> Result<GetDataSetListResponse, Error> {
+        let key = (view.into(), include_hidden);
+        if let Some(datasets) = self.cache().dataset_lists.get(&key) {
+            return Ok(datasets.clone());
+        }
+        let view = &key.0;
+        let datasets = self
+            .call_with_cci(|mut inner, cci| {
+                let request = GetDataSetListRequest {
+                    view: view.clone(),
+                    include_hidden,
+                    cci,
+                };
+                async move { inner.get_data_set_list(request).await }
             })
             .await?
-            .into_inner())
+            .into_inner();
+        self.cache().dataset_lists.insert(key, datasets.clone());
+        Ok(datasets)
     }
 
-    /// Get dataset info.
+    /// Get dataset info, parsed into a typed [`DatasetInfo`].
     pub async fn get_dataset_info(
-        &mut self,
+        &self,
         view: impl Into<String>,
         dataset_name: impl Into<String>,
-    ) -> Result<GetDatasetInfoResponse, tonic::Status> {
-        Ok(self
-            .inner
-            .get_dataset_info(GetDatasetInfoRequest {
-                view: view.into(),
-                dataset_name: dataset_name.into(),
-                cci: self.cci,
+    ) -> Result<DatasetInfo, Error> {
+        let view = view.into();
+        let dataset_name = dataset_name.into();
+        let resp = self
+            .call_with_cci(|mut inner, cci| {
+                let request = GetDatasetInfoRequest {
+                    view: view.clone(),
+                    dataset_name: dataset_name.clone(),
+                    cci,
+                };
+                async move { inner.get_dataset_info(request).await }
             })
             .await?
-            .into_inner())
+            .into_inner();
+        match resp.extended_status() {
+            get_dataset_info_response::Status::Unspecified => {}
+            get_dataset_info_response::Status::ViewNotFound => {
+                return Err(CanaryError::UnknownView { view: Some(view) }.into());
+            }
+            get_dataset_info_response::Status::AccessDenied => {
+                return Err(tonic::Status::permission_denied("access denied").into());
+            }
+            get_dataset_info_response::Status::ViewsError => {
+                return Err(tonic::Status::internal("views error").into());
+            }
+        }
+        Ok(DatasetInfo::from_props(
+            dataset_name,
+            &resp.prop_name,
+            &resp.prop_value,
+        ))
     }
 
     /// Get the tag list for a dataset.
     pub async fn get_tag_list(
-        &mut self,
+        &self,
         view: impl Into<String>,
         dataset_name: impl Into<String>,
         starting_offset: i32,
         max_count: i32,
-    ) -> Result<GetTagListResponse, tonic::Status> {
+    ) -> Result<GetTagListResponse, Error> {
+        let (view, dataset_name) = (view.into(), dataset_name.into());
         Ok(self
-            .inner
-            .get_tag_list(GetTagListRequest {
-                view: view.into(),
-                dataset_name: dataset_name.into(),
-                starting_offset,
-                max_count,
-                cci: self.cci,
+            .call_with_cci(|mut inner, cci| {
+                let request = GetTagListRequest {
+                    view: view.clone(),
+                    dataset_name: dataset_name.clone(),
+                    starting_offset,
+                    max_count,
+                    cci,
+                };
+                async move { inner.get_tag_list(request).await }
             })
             .await?
             .into_inner())
@@ -254,33 +1013,59 @@ impl ViewsClient {
 
     /// Get tag info for the specified tags.
     pub async fn get_tag_info(
-        &mut self,
+        &self,
         view: impl Into<String>,
         tag_names: Vec<String>,
-    ) -> Result<GetTagInfoResponse, tonic::Status> {
+    ) -> Result<GetTagInfoResponse, Error> {
+        let view = view.into();
         Ok(self
-            .inner
-            .get_tag_info(GetTagInfoRequest {
-                view: view.into(),
-                tag_names,
-                cci: self.cci,
+            .call_with_cci(|mut inner, cci| {
+                let request = GetTagInfoRequest {
+                    view: view.clone(),
+                    tag_names: tag_names.clone(),
+                    cci,
+                };
+                async move { inner.get_tag_info(request).await }
             })
             .await?
             .into_inner())
     }
 
+    /// Get the engineering units of the specified tags, keyed by tag name.
+    ///
+    /// Tags without a units property are omitted.
+    pub async fn get_eng_units(
+        &self,
+        view: impl Into<String>,
+        tag_names: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let resp = self.get_tag_info(view, tag_names).await?;
+        Ok(resp
+            .tag_infos
+            .iter()
+            .map(TagInfo::from)
+            .filter_map(|info| {
+                let units = info.eng_units()?.to_string();
+                Some((info.tag_item_id, units))
+            })
+            .collect())
+    }
+
     /// Get tag data context (temporal bounds) for specified tags.
     pub async fn get_tag_data_context(
-        &mut self,
+        &self,
         view: impl Into<String>,
         tag_names: Vec<String>,
-    ) -> Result<GetTagDataContextResponse, tonic::Status> {
+    ) -> Result<GetTagDataContextResponse, Error> {
+        let view = view.into();
         Ok(self
-            .inner
-            .get_tag_data_context(GetTagDataContextRequest {
-                view: view.into(),
-                tag_names,
-                cci: self.cci,
+            .call_with_cci(|mut inner, cci| {
+                let request = GetTagDataContextRequest {
+                    view: view.clone(),
+                    tag_names: tag_names.clone(),
+                    cci,
+                };
+                async move { inner.get_tag_data_context(request).await }
             })
             .await?
             .into_inner())
@@ -288,79 +1073,293 @@ impl ViewsClient {
 
     /// Get the current value of specified tags.
     pub async fn get_tag_current_value(
-        &mut self,
+        &self,
         request: GetTagCurrentValueRequest,
-    ) -> Result<GetTagCurrentValueResponse, tonic::Status> {
-        Ok(self
-            .inner
-            .get_tag_current_value(GetTagCurrentValueRequest {
-                cci: self.cci,
-                ..request
-            })
-            .await?
-            .into_inner())
+    ) -> Result<GetTagCurrentValueResponse, Error> {
+        let call = SlowCall::new("GetTagCurrentValue", &request.view, request.tag_names.len());
+        let resp = slow_calls::timed(
+            self.slow_calls.as_ref(),
+            call,
+            self.call_with_cci(|mut inner, cci| {
+                let request = GetTagCurrentValueRequest {
+                    cci,
+                    ..request.clone()
+                };
+                async move { inner.get_tag_current_value(request).await }
+            }),
+            |resp| resp.get_ref().tag_values.len(),
+        )
+        .await?;
+        Ok(resp.into_inner())
     }
 
     /// Get raw data for tags within a time range.
+    ///
+    /// Each tag stops at `max_count_per_tag` samples, leaving a continuation
+    /// point for the rest; [`get_raw_data_complete`](Self::get_raw_data_complete)
+    /// follows them.
     pub async fn get_raw_data(
-        &mut self,
+        &self,
+        request: GetRawDataRequest,
+    ) -> Result<GetRawDataResponse, Error> {
+        let (view, max_count) = (request.view.clone(), request.max_count_per_tag);
+        let resp = self.raw_data_page(request).await?;
+        if let Some(check) = &self.truncation {
+            check.check(
+                &view,
+                max_count,
+                resp.raw_data.iter().map(|d| {
+                    (
+                        d.tag_name.as_str(),
+                        d.tvqs.len(),
+                        !d.continuation_point.is_empty(),
+                    )
+                }),
+            )?;
+        }
+        Ok(resp)
+    }
+
+    /// Get raw data like [`get_raw_data`](Self::get_raw_data), following each
+    /// tag's continuation point until its range is exhausted. Each tag's
+    /// pages are merged into one entry, in the order the tags first came
+    /// back, with no continuation point left.
+    pub async fn get_raw_data_complete(
+        &self,
+        mut request: GetRawDataRequest,
+    ) -> Result<GetRawDataResponse, Error> {
+        request_id::operation(async {
+            let mut merged = GetRawDataResponse::default();
+            let mut index: HashMap<String, usize> = HashMap::new();
+            while !request.requests.is_empty() {
+                let page = self.raw_data_page(request.clone()).await?;
+                request.requests = continuations(&request.requests, &page)?;
+                merged.status = page.status;
+                merged.extended_status = page.extended_status;
+                for data in page.raw_data {
+                    match index.get(&data.tag_name) {
+                        Some(&i) => {
+                            let entry = &mut merged.raw_data[i];
+                            entry.tvqs.extend(data.tvqs);
+                            entry.annotations.extend(data.annotations);
+                        }
+                        None => {
+                            index.insert(data.tag_name.clone(), merged.raw_data.len());
+                            merged.raw_data.push(data);
+                        }
+                    }
+                }
+            }
+            for data in &mut merged.raw_data {
+                data.continuation_point.clear();
+            }
+            Ok(merged)
+        })
+        .await
+    }
+
+    /// Get raw data like [`get_raw_data_complete`](Self::get_raw_data_complete),
+    /// but yielding each page as it arrives instead of merging them, for
+    /// ranges too large to hold at once. A background task fetches one page
+    /// ahead of the reader and stops when the stream is dropped. An error
+    /// ends the stream.
+    pub fn raw_data_stream(
+        &self,
+        mut request: GetRawDataRequest,
+    ) -> ResultStream<GetRawDataResponse> {
+        let client = self.handle();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(request_id::inherit(async move {
+            while !request.requests.is_empty() {
+                let page = match client.raw_data_page(request.clone()).await {
+                    Ok(page) => page,
+                    Err(status) => {
+                        let _ = tx.send(Err(status.into())).await;
+                        return;
+                    }
+                };
+                request.requests = match continuations(&request.requests, &page) {
+                    Ok(requests) => requests,
+                    Err(status) => {
+                        let _ = tx.send(Err(status.into())).await;
+                        return;
+                    }
+                };
+                if tx.send(Ok(page)).await.is_err() {
+                    return;
+                }
+            }
+        }));
+        Box::pin(ReceiverStream::new(rx))
+    }
+
+    /// One page of [`get_raw_data`](Self::get_raw_data), unchecked, for
+    /// callers that follow continuation points.
+    async fn raw_data_page(
+        &self,
         request: GetRawDataRequest,
     ) -> Result<GetRawDataResponse, tonic::Status> {
-        Ok(self
-            .inner
-            .get_raw_data(GetRawDataRequest {
-                cci: self.cci,
-                ..request
-            })
-            .await?
-            .into_inner())
+        if request.return_annotations {
+            self.capabilities.require(Capability::Annotations)?;
+        }
+        let call = raw_call(&request);
+        let resp = slow_calls::timed(
+            self.slow_calls.as_ref(),
+            call,
+            self.call_with_cci(|mut inner, cci| {
+                let request = GetRawDataRequest {
+                    cci,
+                    ..request.clone()
+                };
+                async move { inner.get_raw_data(request).await }
+            }),
+            |resp| resp.get_ref().raw_data.iter().map(|d| d.tvqs.len()).sum(),
+        )
+        .await?;
+        Ok(resp.into_inner())
+    }
+
+    /// Get raw data for tags, decoded into compact per-tag columns.
+    ///
+    /// Equivalent to [`get_raw_data`](Self::get_raw_data), but never builds
+    /// the per-sample protobuf messages; prefer it for bulk extracts.
+    pub async fn get_raw_data_columnar(
+        &self,
+        request: GetRawDataRequest,
+    ) -> Result<RawDataColumns, Error> {
+        let (view, max_count) = (request.view.clone(), request.max_count_per_tag);
+        let resp = self.raw_data_columnar_page(request).await?;
+        if let Some(check) = &self.truncation {
+            check.check(
+                &view,
+                max_count,
+                resp.raw_data.iter().map(|d| {
+                    (
+                        d.tag_name.as_str(),
+                        d.len(),
+                        !d.continuation_point.is_empty(),
+                    )
+                }),
+            )?;
+        }
+        Ok(resp)
+    }
+
+    /// One page of [`get_raw_data_columnar`](Self::get_raw_data_columnar),
+    /// unchecked, for callers that follow continuation points.
+    pub(crate) async fn raw_data_columnar_page(
+        &self,
+        request: GetRawDataRequest,
+    ) -> Result<RawDataColumns, tonic::Status> {
+        if request.return_annotations {
+            self.capabilities.require(Capability::Annotations)?;
+        }
+        let call = raw_call(&request);
+        let resp = slow_calls::timed(
+            self.slow_calls.as_ref(),
+            call,
+            self.call_with_cci(|_, cci| {
+                let mut grpc = tonic::client::Grpc::new(self.service.clone());
+                let request = tonic::Request::new(GetRawDataRequest {
+                    cci,
+                    ..request.clone()
+                });
+                async move {
+                    grpc.ready().await.map_err(|e| {
+                        tonic::Status::unknown(format!("Service was not ready: {e}"))
+                    })?;
+                    let codec =
+                        tonic_prost::ProstCodec::<GetRawDataRequest, RawDataColumns>::default();
+                    let path = http::uri::PathAndQuery::from_static(
+                        "/canary.views.grpc.api.CanaryViewsApiService/GetRawData",
+                    );
+                    grpc.unary(request, path, codec).await
+                }
+            }),
+            |resp| resp.get_ref().raw_data.iter().map(|d| d.len()).sum(),
+        )
+        .await?;
+        Ok(resp.into_inner())
     }
 
     /// Get aggregate data for tags.
     pub async fn get_aggregate_data(
-        &mut self,
+        &self,
         request: GetAggregateDataRequest,
-    ) -> Result<GetAggregateDataResponse, tonic::Status> {
-        Ok(self
-            .inner
-            .get_aggregate_data(GetAggregateDataRequest {
-                cci: self.cci,
-                ..request
-            })
-            .await?
-            .into_inner())
+    ) -> Result<GetAggregateDataResponse, Error> {
+        let call = SlowCall::new("GetAggregateData", &request.view, request.requests.len())
+            .range(request.start_time.as_ref(), request.end_time.as_ref());
+        let resp = slow_calls::timed(
+            self.slow_calls.as_ref(),
+            call,
+            self.call_with_cci(|mut inner, cci| {
+                let request = GetAggregateDataRequest {
+                    cci,
+                    ..request.clone()
+                };
+                async move { inner.get_aggregate_data(request).await }
+            }),
+            |resp| {
+                resp.get_ref()
+                    .aggregated_data
+                    .iter()
+                    .map(|d| d.tvqs.len())
+                    .sum()
+            },
+        )
+        .await?;
+        Ok(resp.into_inner())
     }
 
     /// Get tag statistics.
     pub async fn get_tag_statistics(
-        &mut self,
+        &self,
         request: GetTagStatisticsRequest,
-    ) -> Result<GetTagStatisticsResponse, tonic::Status> {
-        Ok(self
-            .inner
-            .get_tag_statistics(GetTagStatisticsRequest {
-                cci: self.cci,
-                ..request
-            })
-            .await?
-            .into_inner())
+    ) -> Result<GetTagStatisticsResponse, Error> {
+        let call = SlowCall::new("GetTagStatistics", &request.view_name, 1)
+            .range(request.start_time.as_ref(), request.end_time.as_ref());
+        let resp = slow_calls::timed(
+            self.slow_calls.as_ref(),
+            call,
+            self.call_with_cci(|mut inner, cci| {
+                let request = GetTagStatisticsRequest {
+                    cci,
+                    ..request.clone()
+                };
+                async move { inner.get_tag_statistics(request).await }
+            }),
+            |resp| resp.get_ref().total_samples.max(0) as usize,
+        )
+        .await?;
+        Ok(resp.into_inner())
     }
 
-    /// Get the list of available aggregates.
-    pub async fn get_aggregate_list(&mut self) -> Result<GetAggregateListResponse, tonic::Status> {
-        Ok(self.inner.get_aggregate_list(()).await?.into_inner())
+    /// Get the list of available aggregates. Cached.
+    pub async fn get_aggregate_list(&self) -> Result<GetAggregateListResponse, Error> {
+        if let Some(aggregates) = &self.cache().aggregates {
+            return Ok(aggregates.clone());
+        }
+        let aggregates = self
+            .call(|mut inner| async move { inner.get_aggregate_list(()).await })
+            .await?
+            .into_inner();
+        self.cache().aggregates = Some(aggregates.clone());
+        Ok(aggregates)
     }
 
     /// Subscribe to live data updates. Returns a streaming response.
     pub async fn subscribe_to_live_data(
-        &mut self,
+        &self,
         request: SubscribeToLiveDataRequest,
-    ) -> Result<tonic::Streaming<SubscribeToLiveDataResponse>, tonic::Status> {
+    ) -> Result<tonic::Streaming<SubscribeToLiveDataResponse>, Error> {
+        self.capabilities.require(Capability::LiveSubscriptions)?;
         Ok(self
-            .inner
-            .subscribe_to_live_data(SubscribeToLiveDataRequest {
-                cci: self.cci,
-                ..request
+            .call_with_cci(|mut inner, cci| {
+                let request = SubscribeToLiveDataRequest {
+                    cci,
+                    ..request.clone()
+                };
+                async move { inner.subscribe_to_live_data(request).await }
             })
             .await?
             .into_inner())
@@ -368,15 +1367,18 @@ impl ViewsClient {
 
     /// Browse the views tree by node ID.
     pub async fn browse(
-        &mut self,
+        &self,
         node_id_path: impl Into<String>,
         force_reload: bool,
-    ) -> Result<BrowseResponse, tonic::Status> {
+    ) -> Result<BrowseResponse, Error> {
+        let node_id_path = node_id_path.into();
         Ok(self
-            .inner
-            .browse(BrowseRequest {
-                node_id_path: node_id_path.into(),
-                force_reload,
+            .call(|mut inner| {
+                let request = BrowseRequest {
+                    node_id_path: node_id_path.clone(),
+                    force_reload,
+                };
+                async move { inner.browse(request).await }
             })
             .await?
             .into_inner())
@@ -384,35 +1386,128 @@ impl ViewsClient {
 
     /// Browse tags at a specified node.
     pub async fn browse_tags(
-        &mut self,
+        &self,
         request: BrowseTagsRequest,
-    ) -> Result<BrowseTagsResponse, tonic::Status> {
-        Ok(self.inner.browse_tags(request).await?.into_inner())
+    ) -> Result<BrowseTagsResponse, Error> {
+        Ok(self
+            .call(|mut inner| {
+                let request = request.clone();
+                async move { inner.browse_tags(request).await }
+            })
+            .await?
+            .into_inner())
     }
 
     /// Search for tags matching criteria.
     pub async fn search_tags(
-        &mut self,
+        &self,
         request: SearchTagsRequest,
-    ) -> Result<SearchTagsResponse, tonic::Status> {
-        Ok(self.inner.search_tags(request).await?.into_inner())
+    ) -> Result<SearchTagsResponse, Error> {
+        Ok(self
+            .call(|mut inner| {
+                let request = request.clone();
+                async move { inner.search_tags(request).await }
+            })
+            .await?
+            .into_inner())
     }
 
     /// Browse by tree path.
-    pub async fn browse_path(
-        &mut self,
-        tree_path: Vec<String>,
-    ) -> Result<BrowsePathResponse, tonic::Status> {
+    pub async fn browse_path(&self, tree_path: Vec<String>) -> Result<BrowsePathResponse, Error> {
         Ok(self
-            .inner
-            .browse_path(BrowsePathRequest { tree_path })
+            .call(|mut inner| {
+                let request = BrowsePathRequest {
+                    tree_path: tree_path.clone(),
+                };
+                async move { inner.browse_path(request).await }
+            })
             .await?
             .into_inner())
     }
 
+    /// Another handle on the same channel and client connection, for issuing
+    /// calls concurrently.
+    pub(crate) fn handle(&self) -> Self {
+        self.clone()
+    }
+
+    /// Like [`handle`](Self::handle), but starting with an empty metadata
+    /// cache, for long-lived handles that only read data.
+    pub(crate) fn data_handle(&self) -> Self {
+        Self {
+            cache: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// A clone of the generated client, for calls made outside
+    /// [`call`](Self::call).
+    pub(crate) fn inner(&self) -> Inner {
+        self.inner.clone()
+    }
+
+    fn cache(&self) -> MutexGuard<'_, MetadataCache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The API key shared by every handle on this connection.
+    pub(crate) fn api_key(&self) -> SharedApiKey {
+        self.api_key.clone()
+    }
+
+    /// The underlying service, for calls outside the Views API.
+    pub(crate) fn service(&self) -> InterceptedService<Channel, ApiKeyInterceptor> {
+        self.service.clone()
+    }
+
+    /// When this handle's CCI last answered a call, for calls made outside
+    /// the typed wrappers.
+    #[cfg(feature = "dynamic")]
+    pub(crate) fn lease_clock(&self) -> &LeaseClock {
+        &self.lease_clock
+    }
+
+    /// The connection's background tasks, stopped on shutdown.
+    pub(crate) fn tasks(&self) -> &Tasks {
+        &self.tasks
+    }
+
+    /// The state of the connection under this handle, shared by every handle
+    /// and CCI on it.
+    pub(crate) fn state_monitor(&self) -> &StateMonitor {
+        self.lease_clock.state()
+    }
+
+    /// The features the connected server supports, from its version at connect time.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
     /// Get the client connection ID.
     pub fn cci(&self) -> i32 {
-        self.cci
+        self.cci.id.load(Ordering::Acquire)
+    }
+
+    /// The user ID the client connected as.
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// The server's connection timeout, as given to
+    /// [`ViewsClientBuilder::cci_lease`].
+    pub fn cci_lease_timeout(&self) -> Option<Duration> {
+        self.lease_timeout
+    }
+
+    /// How long since the client connection ID last answered a call.
+    pub fn idle_for(&self) -> Duration {
+        self.lease_clock.idle()
+    }
+
+    /// Drop this handle's share of the lease watch, for the watch's own
+    /// handle.
+    pub(crate) fn clear_lease_watch(&mut self) {
+        self.lease_watch = None;
     }
 
     /// Get a mutable reference to the underlying tonic client for direct RPC access.
@@ -422,3 +1517,194 @@ impl ViewsClient {
         &mut self.inner
     }
 }
+
+/// The requests for the next page of every tag in `page` that left a
+/// continuation point, keeping each tag's range from `sent`.
+fn continuations(
+    sent: &[RawTagRequest],
+    page: &GetRawDataResponse,
+) -> Result<Vec<RawTagRequest>, tonic::Status> {
+    let mut next = Vec::new();
+    for data in &page.raw_data {
+        let Some(sent) = sent.iter().find(|r| r.tag_name == data.tag_name) else {
+            continue;
+        };
+        let point = &data.continuation_point;
+        check_progress(
+            &data.tag_name,
+            &sent.continuation_point,
+            point,
+            data.tvqs.len(),
+        )?;
+        if !point.is_empty() {
+            next.push(RawTagRequest {
+                continuation_point: point.clone(),
+                ..sent.clone()
+            });
+        }
+    }
+    Ok(next)
+}
+
+/// An error if a page of `tag` asked for with continuation point `sent`
+/// came back with `samples` samples and continuation point `received`, but
+/// following it could page forever: the page was empty, or the point came
+/// back unchanged.
+pub(crate) fn check_progress(
+    tag: &str,
+    sent: &[u8],
+    received: &[u8],
+    samples: usize,
+) -> Result<(), tonic::Status> {
+    if received.is_empty() {
+        Ok(())
+    } else if samples == 0 {
+        Err(tonic::Status::internal(format!(
+            "the server returned an empty page of {tag} with a continuation point"
+        )))
+    } else if received == sent {
+        Err(tonic::Status::internal(format!(
+            "the server returned the same continuation point for {tag} twice"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Describe a raw-data request for the slow-call log, spanning the earliest
+/// start to the latest end of its tags.
+fn raw_call(request: &GetRawDataRequest) -> SlowCall {
+    let key = |ts: &&prost_types::Timestamp| (ts.seconds, ts.nanos);
+    let starts = request
+        .requests
+        .iter()
+        .filter_map(|r| r.start_time.as_ref());
+    let ends = request.requests.iter().filter_map(|r| r.end_time.as_ref());
+    SlowCall::new("GetRawData", &request.view, request.requests.len())
+        .range(starts.min_by_key(key), ends.max_by_key(key))
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use tonic::codegen::tokio_stream::StreamExt;
+
+    use super::*;
+    use crate::quality::Quality;
+    use crate::testing::MockViewsServer;
+    use crate::types::{Tvq, Value};
+
+    fn mock() -> MockViewsServer {
+        let mock = MockViewsServer::new();
+        mock.add_tag("Plant", "Plant", "Plant.Flow")
+            .add_tag("Plant", "Plant", "Plant.Idle")
+            .add_tvqs(
+                "Plant",
+                "Plant.Flow",
+                (0..5).map(|i| Tvq {
+                    timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(i),
+                    value: Value::Float(i as f64),
+                    quality: Quality::GOOD,
+                }),
+            );
+        mock
+    }
+
+    async fn client(mock: &MockViewsServer) -> ViewsClient {
+        mock.client().await.unwrap()
+    }
+
+    fn request(tag: &str) -> GetRawDataRequest {
+        GetRawDataRequest {
+            view: "Plant".into(),
+            requests: vec![RawTagRequest {
+                tag_name: tag.into(),
+                start_time: Some(SystemTime::UNIX_EPOCH.into()),
+                end_time: Some((SystemTime::UNIX_EPOCH + Duration::from_secs(60)).into()),
+                client_data: 0,
+                continuation_point: Vec::new(),
+            }],
+            max_count_per_tag: 2,
+            return_bounds: false,
+            return_annotations: false,
+            cci: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_continuation_points_to_the_end() {
+        let client = client(&mock()).await;
+        let resp = client
+            .get_raw_data_complete(request("Plant.Flow"))
+            .await
+            .unwrap();
+        assert_eq!(resp.raw_data[0].tvqs.len(), 5);
+        let pages: Vec<_> = client
+            .raw_data_stream(request("Plant.Flow"))
+            .collect()
+            .await;
+        assert_eq!(pages.len(), 3);
+        assert!(pages.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn stops_when_a_continuation_point_repeats() {
+        let mock = mock();
+        mock.set_stuck_paging(true);
+        let client = client(&mock).await;
+
+        let error = client
+            .get_raw_data_complete(request("Plant.Flow"))
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("same continuation point"),
+            "{error:?}"
+        );
+        assert_eq!(mock.requests_for("GetRawData").len(), 2);
+
+        let pages: Vec<_> = client
+            .raw_data_stream(request("Plant.Flow"))
+            .collect()
+            .await;
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].is_ok());
+        assert!(
+            pages[1]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("same continuation point")
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_at_an_empty_page_with_a_continuation_point() {
+        let mock = mock();
+        mock.set_stuck_paging(true);
+        let client = client(&mock).await;
+
+        let error = client
+            .get_raw_data_complete(request("Plant.Idle"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("empty page"), "{error:?}");
+
+        let mut stream = client.raw_data_stream(request("Plant.Idle"));
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn bulk_fetches_stop_too() {
+        let mock = mock();
+        mock.set_stuck_paging(true);
+        let client = client(&mock).await;
+        let end = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        let fetch =
+            crate::bulk::BulkFetch::new("Plant", ["Plant.Flow"], SystemTime::UNIX_EPOCH, end)
+                .page_size(2);
+        assert!(fetch.run(&client).await.is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! Threshold and quality watches that notify webhooks.
+//!
+//! A [`WatchRule`] ties a tag to a [`Condition`] such as `> 80 for 60s` or
+//! `quality != good`. [`Watch`] evaluates rules against samples as they
+//! arrive and reports an [`Alert`] when a condition has held for its hold
+//! time and again when it clears.
+//!
+//! [`WatchConfig`] is the YAML form used by `crowsong watch-rules`: a view,
+//! rules, and webhooks. Running it evaluates the rules against live data (or
+//! current values polled every `poll_seconds`) and POSTs each alert to every
+//! webhook as JSON, with timestamps in the config's `timestamp_format`:
+//!
+//! ```yaml
+//! view: Plant
+//! webhooks:
+//!   - url: https://hooks.example.com/plant
+//!     headers: { Authorization: Bearer abc123 }
+//! rules:
+//!   - name: line1-hot
+//!     tag: Plant.Line1.Temperature
+//!     condition: "> 80 for 60s"
+//!   - name: line1-quality
+//!     tag: Plant.Line1.Temperature
+//!     condition: quality != good for 5m
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::canary::views::grpc::api::{GetTagCurrentValueRequest, SubscribeToLiveDataRequest};
+use crate::error::Error;
+use crate::interval::parse_interval;
+use crate::quality::Quality;
+use crate::timestamps::TimestampFormat;
+use crate::transport::Redacted;
+use crate::types::{Tvq, Value};
+use crate::views_client::ViewsClient;
+
+/// How a value is compared with a threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        }
+    }
+}
+
+/// The broad quality classes a quality condition tests for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityClass {
+    Good,
+    Uncertain,
+    Bad,
+}
+
+impl QualityClass {
+    fn contains(self, quality: Quality) -> bool {
+        match self {
+            QualityClass::Good => quality.is_good(),
+            QualityClass::Uncertain => quality.is_uncertain(),
+            QualityClass::Bad => quality.is_bad(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            QualityClass::Good => "good",
+            QualityClass::Uncertain => "uncertain",
+            QualityClass::Bad => "bad",
+        }
+    }
+}
+
+/// What a rule watches for, and how long it must hold before alerting.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Condition {
+    /// The value compared with a threshold, e.g. `> 80 for 60s`. Samples
+    /// without a numeric value never match.
+    Value {
+        comparison: Comparison,
+        threshold: f64,
+        hold: Duration,
+    },
+    /// The quality class, e.g. `quality != good`.
+    Quality {
+        equal: bool,
+        class: QualityClass,
+        hold: Duration,
+    },
+}
+
+impl Condition {
+    /// Whether a sample matches, regardless of how long it has.
+    pub fn matches(&self, tvq: &Tvq) -> bool {
+        match *self {
+            Condition::Value {
+                comparison,
+                threshold,
+                ..
+            } => {
+                let value = match tvq.value {
+                    Value::Null => None,
+                    ref value => value.as_f64(),
+                };
+                value.is_some_and(|v| comparison.holds(v, threshold))
+            }
+            Condition::Quality { equal, class, .. } => class.contains(tvq.quality) == equal,
+        }
+    }
+
+    /// How long the condition must hold before alerting.
+    pub fn hold(&self) -> Duration {
+        match *self {
+            Condition::Value { hold, .. } | Condition::Quality { hold, .. } => hold,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Condition::Value {
+                comparison,
+                threshold,
+                ..
+            } => write!(f, "{} {threshold}", comparison.symbol())?,
+            Condition::Quality { equal, class, .. } => write!(
+                f,
+                "quality {} {}",
+                if equal { "==" } else { "!=" },
+                class.name()
+            )?,
+        }
+        let hold = self.hold();
+        if !hold.is_zero() {
+            write!(f, " for {}s", hold.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when a condition string cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseConditionError(String);
+
+impl fmt::Display for ParseConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid condition {:?}; expected e.g. \"> 80 for 60s\" or \"quality != good\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseConditionError {}
+
+impl FromStr for Condition {
+    type Err = ParseConditionError;
+
+    /// Parse `<op> <number> [for <duration>]` or
+    /// `quality (==|!=) (good|uncertain|bad) [for <duration>]`, where `op`
+    /// is one of `>`, `>=`, `<`, `<=`, `==`, `!=` and a duration is an
+    /// interval such as `30s`, `5 minutes`, or `1h30m` (see
+    /// [`parse_interval`]).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseConditionError(s.to_string());
+        let (test, hold) = match s.split_once(" for ") {
+            Some((test, hold)) => (test, parse_interval(hold).map_err(|_| error())?),
+            None => (s, Duration::ZERO),
+        };
+        let test = test.trim();
+
+        if let Some(rest) = test.strip_prefix("quality") {
+            let rest = rest.trim_start();
+            let (equal, class) = if let Some(class) = rest.strip_prefix("==") {
+                (true, class)
+            } else if let Some(class) = rest.strip_prefix("!=") {
+                (false, class)
+            } else {
+                return Err(error());
+            };
+            let class = match class.trim().to_ascii_lowercase().as_str() {
+                "good" => QualityClass::Good,
+                "uncertain" => QualityClass::Uncertain,
+                "bad" => QualityClass::Bad,
+                _ => return Err(error()),
+            };
+            return Ok(Condition::Quality { equal, class, hold });
+        }
+
+        let split = test
+            .find(|c: char| !matches!(c, '<' | '>' | '=' | '!'))
+            .ok_or_else(error)?;
+        let comparison = match &test[..split] {
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            _ => return Err(error()),
+        };
+        let threshold = test[split..].trim().parse().map_err(|_| error())?;
+        Ok(Condition::Value {
+            comparison,
+            threshold,
+            hold,
+        })
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = ParseConditionError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A named condition on one tag.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchRule {
+    pub name: String,
+    pub tag: String,
+    pub condition: Condition,
+}
+
+impl WatchRule {
+    pub fn new(name: impl Into<String>, tag: impl Into<String>, condition: Condition) -> Self {
+        Self {
+            name: name.into(),
+            tag: tag.into(),
+            condition,
+        }
+    }
+}
+
+/// Whether an alert starts or ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+
+/// A rule's condition starting to hold, or clearing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub rule: String,
+    pub tag: String,
+    pub state: AlertState,
+    pub condition: Condition,
+    /// The sample that fired or resolved the alert.
+    pub tvq: Tvq,
+    /// When the condition started to hold.
+    pub since: SystemTime,
+}
+
+#[derive(Debug, Clone, Default)]
+struct RuleState {
+    since: Option<SystemTime>,
+    firing: bool,
+    last: Option<Tvq>,
+}
+
+/// Evaluates rules against samples as they arrive.
+#[derive(Debug, Clone)]
+pub struct Watch {
+    rules: Vec<WatchRule>,
+    states: Vec<RuleState>,
+}
+
+impl Watch {
+    pub fn new(rules: impl IntoIterator<Item = WatchRule>) -> Self {
+        let rules: Vec<WatchRule> = rules.into_iter().collect();
+        Self {
+            states: vec![RuleState::default(); rules.len()],
+            rules,
+        }
+    }
+
+    pub fn rules(&self) -> &[WatchRule] {
+        &self.rules
+    }
+
+    /// The watched tags, without duplicates.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.rules.iter().map(|r| r.tag.clone()).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Evaluate a tag's new sample. Samples of a tag must arrive in time
+    /// order.
+    pub fn observe(&mut self, tag: &str, tvq: &Tvq) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (rule, state) in self.rules.iter().zip(&mut self.states) {
+            if rule.tag != tag {
+                continue;
+            }
+            state.last = Some(tvq.clone());
+            if !rule.condition.matches(tvq) {
+                if let Some(since) = state.since.take()
+                    && state.firing
+                {
+                    state.firing = false;
+                    alerts.push(alert(rule, AlertState::Resolved, tvq, since));
+                }
+                continue;
+            }
+            let since = *state.since.get_or_insert(tvq.timestamp);
+            if !state.firing && held(since, tvq.timestamp, rule.condition.hold()) {
+                state.firing = true;
+                alerts.push(alert(rule, AlertState::Firing, tvq, since));
+            }
+        }
+        alerts
+    }
+
+    /// Fire rules whose condition has held for its hold time as of `now`,
+    /// for tags that don't report while their value is unchanged.
+    pub fn tick(&mut self, now: SystemTime) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (rule, state) in self.rules.iter().zip(&mut self.states) {
+            if let (Some(since), Some(last), false) = (state.since, &state.last, state.firing)
+                && held(since, now, rule.condition.hold())
+            {
+                state.firing = true;
+                alerts.push(alert(rule, AlertState::Firing, last, since));
+            }
+        }
+        alerts
+    }
+}
+
+fn held(since: SystemTime, now: SystemTime, hold: Duration) -> bool {
+    now.duration_since(since).unwrap_or_default() >= hold
+}
+
+fn alert(rule: &WatchRule, state: AlertState, tvq: &Tvq, since: SystemTime) -> Alert {
+    Alert {
+        rule: rule.name.clone(),
+        tag: rule.tag.clone(),
+        state,
+        condition: rule.condition,
+        tvq: tvq.clone(),
+        since,
+    }
+}
+
+/// An HTTP endpoint that receives alerts as JSON POSTs.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    /// Extra request headers, e.g. `Authorization`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl fmt::Debug for Webhook {
+    /// Header values often carry credentials, so only their names are shown.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: BTreeMap<&str, Redacted> = self
+            .headers
+            .keys()
+            .map(|name| (name.as_str(), Redacted))
+            .collect();
+        f.debug_struct("Webhook")
+            .field("url", &self.url)
+            .field("headers", &headers)
+            .finish()
+    }
+}
+
+/// Rules, the view they watch, and where alerts go, as read from YAML.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchConfig {
+    pub view: String,
+    /// Poll current values this often instead of subscribing to live data.
+    #[serde(default)]
+    pub poll_seconds: Option<f64>,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    pub rules: Vec<WatchRule>,
+    /// How alert timestamps are rendered, e.g. `ms,+02:00`; see
+    /// [`TimestampFormat`]. Defaults to RFC 3339 in UTC.
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+}
+
+impl WatchConfig {
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        serde_yaml::from_str(yaml).map_err(Error::other)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_yaml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Watch until the live subscription ends or a read fails.
+    pub async fn run(&self, client: &ViewsClient) -> Result<(), Error> {
+        self.run_with_alerts(client, |_, _| {}).await
+    }
+
+    /// Like [`run`](Self::run), calling `on_alert` with each alert after
+    /// notifying the webhooks, along with the first webhook error if any.
+    pub async fn run_with_alerts(
+        &self,
+        client: &ViewsClient,
+        mut on_alert: impl FnMut(&Alert, Option<&Error>),
+    ) -> Result<(), Error> {
+        let mut watch = Watch::new(self.rules.iter().cloned());
+        let notifier = Notifier::new(&self.view, &self.webhooks, self.timestamp_format)?;
+        let mut notify = async |alerts: Vec<Alert>| {
+            for alert in alerts {
+                let result = notifier.send(&alert).await;
+                on_alert(&alert, result.as_ref().err());
+            }
+        };
+
+        if let Some(seconds) = self.poll_seconds {
+            let mut ticker =
+                tokio::time::interval(Duration::try_from_secs_f64(seconds).map_err(Error::other)?);
+            loop {
+                ticker.tick().await;
+                let resp = client
+                    .get_tag_current_value(GetTagCurrentValueRequest {
+                        view: self.view.clone(),
+                        tag_names: watch.tags(),
+                        use_time_extension: None,
+                        quality: 0,
+                        cci: 0,
+                    })
+                    .await?;
+                let mut alerts = Vec::new();
+                for current in &resp.tag_values {
+                    let tvq = Tvq {
+                        timestamp: current
+                            .timestamp
+                            .and_then(|ts| SystemTime::try_from(ts).ok())
+                            .unwrap_or_else(SystemTime::now),
+                        value: current.value.as_ref().map(Value::from).unwrap_or_default(),
+                        quality: Quality(current.quality as u32),
+                    };
+                    alerts.extend(watch.observe(&current.tag_item_id, &tvq));
+                }
+                alerts.extend(watch.tick(SystemTime::now()));
+                notify(alerts).await;
+            }
+        }
+
+        let mut live = client
+            .subscribe_to_live_data(SubscribeToLiveDataRequest {
+                tags: watch.tags(),
+                ..Default::default()
+            })
+            .await?;
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            let alerts = tokio::select! {
+                message = live.message() => {
+                    let Some(resp) = message? else {
+                        return Ok(());
+                    };
+                    let mut alerts = Vec::new();
+                    for (tag, data) in &resp.tags_and_data {
+                        for tvq in data.tvqs.iter().map(Tvq::from) {
+                            alerts.extend(watch.observe(tag, &tvq));
+                        }
+                    }
+                    alerts
+                }
+                _ = ticker.tick() => watch.tick(SystemTime::now()),
+            };
+            notify(alerts).await;
+        }
+    }
+}
+
+/// POSTs alerts to webhooks.
+struct Notifier<'a> {
+    view: &'a str,
+    webhooks: &'a [Webhook],
+    timestamps: TimestampFormat,
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+}
+
+impl<'a> Notifier<'a> {
+    fn new(
+        view: &'a str,
+        webhooks: &'a [Webhook],
+        timestamps: TimestampFormat,
+    ) -> Result<Self, Error> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            view,
+            webhooks,
+            timestamps,
+            client: Client::builder(TokioExecutor::new()).build(connector),
+        })
+    }
+
+    /// Send an alert to every webhook, returning the first failure.
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+        let value = match &alert.tvq.value {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => json!(b),
+            Value::String(s) => json!(s),
+            value => value
+                .as_f64()
+                .filter(|f| f.is_finite())
+                .map_or(serde_json::Value::Null, |f| json!(f)),
+        };
+        let body = json!({
+            "rule": alert.rule,
+            "state": alert.state.as_str(),
+            "view": self.view,
+            "tag": alert.tag,
+            "condition": alert.condition.to_string(),
+            "value": value,
+            "quality": alert.tvq.quality.0,
+            "quality_name": alert.tvq.quality.to_string(),
+            "timestamp": self.timestamps.format(alert.tvq.timestamp),
+            "since": self.timestamps.format(alert.since),
+        })
+        .to_string();
+
+        let mut first_error = None;
+        for webhook in self.webhooks {
+            if let Err(e) = self.post(webhook, body.clone()).await {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn post(&self, webhook: &Webhook, body: String) -> Result<(), Error> {
+        let mut request = http::Request::post(&webhook.url)
+            .header(http::header::CONTENT_TYPE, "application/json");
+        for (name, value) in &webhook.headers {
+            request = request.header(name, value);
+        }
+        let request = request
+            .body(Full::new(Bytes::from(body)))
+            .map_err(Error::other)?;
+        let resp = tokio::time::timeout(Duration::from_secs(10), self.client.request(request))
+            .await
+            .map_err(|_| format!("webhook {} timed out", webhook.url))?
+            .map_err(|e| format!("webhook {} failed: {e}", webhook.url))?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = resp
+            .into_body()
+            .collect()
+            .await
+            .map_err(Error::other)?
+            .to_bytes();
+        Err(format!(
+            "webhook {} failed ({status}): {}",
+            webhook.url,
+            String::from_utf8_lossy(&body).trim()
+        )
+        .into())
+    }
+}